@@ -0,0 +1,213 @@
+#ifndef RUST_UML_SM_H
+#define RUST_UML_SM_H
+
+/* Generated by cbindgen from src/ffi.rs, see tests/ffi.rs */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The status of an instance, see `Status`.
+ */
+typedef enum SmStatus {
+  SM_STATUS_IDLE,
+  SM_STATUS_RUNNING,
+  SM_STATUS_TERMINATED,
+} SmStatus;
+
+/**
+ * A model, from which instances are created.
+ */
+typedef struct SmDefinition SmDefinition;
+
+/**
+ * A running copy of a definition.
+ */
+typedef struct SmInstance SmInstance;
+
+/**
+ * Performs a behavior, called with the `user_data` it was registered
+ * with.
+ */
+typedef void (*SmBehaviorFn)(void *user_data);
+
+/**
+ * Evaluates a guard, called with the `user_data` it was registered
+ * with.
+ */
+typedef bool (*SmGuardFn)(void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * The message of the last failure on this thread, empty if there was
+ * none. It lives until the next failure.
+ */
+const char *sm_last_error(void);
+
+/**
+ * Parse and check a model in the textual format.
+ *
+ * # Safety
+ *
+ * `text` is NULL or a NUL-terminated string.
+ */
+struct SmDefinition *sm_definition_load(const char *text);
+
+/**
+ * # Safety
+ *
+ * `def` is NULL or was returned by `sm_definition_load`, and is not
+ * used afterwards.
+ */
+void sm_definition_free(struct SmDefinition *def);
+
+/**
+ * The id of an event type by name, -1 if there is none.
+ *
+ * # Safety
+ *
+ * `def` was returned by `sm_definition_load`, `name` is NULL or a
+ * NUL-terminated string.
+ */
+ptrdiff_t sm_definition_event(const struct SmDefinition *def, const char *name);
+
+/**
+ * The id of an element by path, see `Db::find`, -1 if there is none.
+ *
+ * # Safety
+ *
+ * `def` was returned by `sm_definition_load`, `path` is NULL or a
+ * NUL-terminated string.
+ */
+ptrdiff_t sm_definition_find(const struct SmDefinition *def, const char *path);
+
+/**
+ * The name of an element, NULL if there is no such id.
+ *
+ * # Safety
+ *
+ * `def` was returned by `sm_definition_load`.
+ */
+const char *sm_definition_name(const struct SmDefinition *def, size_t id);
+
+/**
+ * The fullname of an element, such as `door::region_1::closed`, NULL
+ * if there is no such id.
+ *
+ * # Safety
+ *
+ * `def` was returned by `sm_definition_load`.
+ */
+const char *sm_definition_fullname(const struct SmDefinition *def, size_t id);
+
+/**
+ * A new instance of a definition, not started. It does not refer to
+ * the definition, which may be freed first.
+ *
+ * # Safety
+ *
+ * `def` was returned by `sm_definition_load`.
+ */
+struct SmInstance *sm_instance_new(const struct SmDefinition *def);
+
+/**
+ * # Safety
+ *
+ * `sm` is NULL or was returned by `sm_instance_new`, and is not used
+ * afterwards.
+ */
+void sm_instance_free(struct SmInstance *sm);
+
+/**
+ * Register the callback performing the behavior `name`, replacing the
+ * one registered before. NULL unregisters it.
+ *
+ * # Safety
+ *
+ * `sm` was returned by `sm_instance_new`, `name` is NULL or a
+ * NUL-terminated string, `f` can be called with `user_data` as long as
+ * it is registered.
+ */
+int sm_instance_on_behavior(struct SmInstance *sm,
+                            const char *name,
+                            SmBehaviorFn f,
+                            void *user_data);
+
+/**
+ * Register the callback evaluating the guard `name`, replacing the one
+ * registered before. NULL unregisters it.
+ *
+ * # Safety
+ *
+ * `sm` was returned by `sm_instance_new`, `name` is NULL or a
+ * NUL-terminated string, `f` can be called with `user_data` as long as
+ * it is registered.
+ */
+int sm_instance_on_guard(struct SmInstance *sm, const char *name, SmGuardFn f, void *user_data);
+
+/**
+ * Start the instance, 0 on success.
+ *
+ * # Safety
+ *
+ * `sm` was returned by `sm_instance_new`.
+ */
+int sm_instance_start(struct SmInstance *sm);
+
+/**
+ * Stop the instance, 0 on success.
+ *
+ * # Safety
+ *
+ * `sm` was returned by `sm_instance_new`.
+ */
+int sm_instance_stop(struct SmInstance *sm);
+
+/**
+ * Dispatch an event by id and run to completion, 1 if it fired a
+ * transition and 0 if not.
+ *
+ * # Safety
+ *
+ * `sm` was returned by `sm_instance_new`.
+ */
+int sm_instance_dispatch(struct SmInstance *sm, size_t event);
+
+/**
+ * Write the ids of the innermost active states to `ids`, at most
+ * `capacity` of them, and return how many there are.
+ *
+ * # Safety
+ *
+ * `sm` was returned by `sm_instance_new`, `ids` has room for
+ * `capacity` ids or is NULL if `capacity` is 0.
+ */
+size_t sm_instance_active_states(const struct SmInstance *sm, size_t *ids, size_t capacity);
+
+/**
+ * Whether the state or pseudostate `id` is active.
+ *
+ * # Safety
+ *
+ * `sm` was returned by `sm_instance_new`.
+ */
+bool sm_instance_is_active(const struct SmInstance *sm, size_t id);
+
+/**
+ * # Safety
+ *
+ * `sm` was returned by `sm_instance_new`.
+ */
+enum SmStatus sm_instance_status(const struct SmInstance *sm);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RUST_UML_SM_H */
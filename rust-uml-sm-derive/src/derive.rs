@@ -0,0 +1,779 @@
+//! Implementation of `#[derive(StateMachine)]` and `#[behaviors]`.
+//!
+//! The derive reads the states and transitions of a state machine from
+//! attributes, either on a struct:
+//!
+//! ```text
+//! #[derive(StateMachine)]
+//! #[statemachine(constructor = new)]
+//! #[state(idle, initial)]
+//! #[state(busy, entry = start_motor)]
+//! #[transition(idle -> busy, on = go, guard = is_ready)]
+//! #[transition(busy -> idle, on = stop)]
+//! struct Motor { ... }
+//! ```
+//!
+//! or on the variants of an enum, where each variant is a state:
+//!
+//! ```text
+//! #[derive(StateMachine)]
+//! enum Door {
+//!     #[state(initial)]
+//!     #[transition(-> Opened, on = open)]
+//!     Closed,
+//!     #[transition(-> Closed, on = close)]
+//!     Opened,
+//! }
+//! ```
+//!
+//! A struct may take its states from such an enum with
+//! `#[statemachine(states = Door)]`. For a struct a `<Name>StateMachine`
+//! wrapper is generated, running the definition with the struct as its
+//! `Context`. The derive only sees the type, the methods performing the
+//! named behaviors and guards are bound by `#[behaviors]` on their impl
+//! block.
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Fields, Ident, ImplItem, ItemImpl, Token};
+
+fn error<T>(span: Span, message: impl std::fmt::Display) -> syn::Result<T> {
+    Err(syn::Error::new(span, message))
+}
+
+//----------------------------------------------------------------
+// Attribute arguments
+//----------------------------------------------------------------
+
+/// `name`, `name = value`, `source -> target` or `-> target`
+enum Arg {
+    Flag(Ident),
+    Value(Ident, Ident),
+    Arrow(Option<Ident>, Ident),
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Token![->]) {
+            input.parse::<Token![->]>()?;
+            return Ok(Arg::Arrow(None, Ident::parse_any(input)?));
+        }
+        let name = Ident::parse_any(input)?;
+        if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Ok(Arg::Value(name, Ident::parse_any(input)?))
+        } else if input.peek(Token![->]) {
+            input.parse::<Token![->]>()?;
+            Ok(Arg::Arrow(Some(name), Ident::parse_any(input)?))
+        } else {
+            Ok(Arg::Flag(name))
+        }
+    }
+}
+
+fn args(attr: &Attribute) -> syn::Result<Vec<Arg>> {
+    if attr.tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(attr
+        .parse_args_with(Punctuated::<Arg, Token![,]>::parse_terminated)?
+        .into_iter()
+        .collect())
+}
+
+fn is(attr: &Attribute, name: &str) -> bool {
+    attr.path.is_ident(name)
+}
+
+//----------------------------------------------------------------
+// Model
+//----------------------------------------------------------------
+
+#[derive(Default)]
+struct StateSpec {
+    name: Option<Ident>,
+    parent: Option<Ident>,
+    initial: bool,
+    is_final: bool,
+    entry: Option<Ident>,
+    exit: Option<Ident>,
+    do_activity: Option<Ident>,
+    defer: Vec<Ident>,
+}
+
+struct TransitionSpec {
+    source: Ident,
+    target: Option<Ident>,
+    trigger: Option<Ident>,
+    guard: Option<Ident>,
+    effect: Option<Ident>,
+}
+
+fn state_spec(name: Option<Ident>, attr: &Attribute) -> syn::Result<StateSpec> {
+    let mut spec = StateSpec {
+        name,
+        ..StateSpec::default()
+    };
+    for arg in args(attr)? {
+        match arg {
+            Arg::Flag(f) if f == "initial" => spec.initial = true,
+            Arg::Flag(f) if f == "final" => spec.is_final = true,
+            Arg::Flag(f) if spec.name.is_none() => spec.name = Some(f),
+            Arg::Value(k, v) if k == "parent" => spec.parent = Some(v),
+            Arg::Value(k, v) if k == "entry" => spec.entry = Some(v),
+            Arg::Value(k, v) if k == "exit" => spec.exit = Some(v),
+            Arg::Value(k, v) if k == "do" => spec.do_activity = Some(v),
+            Arg::Value(k, v) if k == "defer" => spec.defer.push(v),
+            Arg::Flag(f) | Arg::Value(f, _) | Arg::Arrow(Some(f), _) => {
+                return error(f.span(), format!("unexpected `{}`", f))
+            }
+            Arg::Arrow(None, t) => return error(t.span(), "unexpected `->`"),
+        }
+    }
+    match spec.name {
+        Some(_) => Ok(spec),
+        None => error(Span::call_site(), "expected the name of the state"),
+    }
+}
+
+fn transition_spec(source: Option<Ident>, attr: &Attribute) -> syn::Result<TransitionSpec> {
+    let mut source = source;
+    let mut target = None;
+    let mut trigger = None;
+    let mut guard = None;
+    let mut effect = None;
+    for arg in args(attr)? {
+        match arg {
+            Arg::Arrow(Some(s), t) if source.is_none() => {
+                source = Some(s);
+                target = Some(t);
+            }
+            Arg::Arrow(None, t) if source.is_some() && target.is_none() => target = Some(t),
+            Arg::Flag(s) if source.is_none() => source = Some(s),
+            Arg::Value(k, v) if k == "on" => trigger = Some(v),
+            Arg::Value(k, v) if k == "guard" => guard = Some(v),
+            Arg::Value(k, v) if k == "effect" => effect = Some(v),
+            Arg::Flag(f) | Arg::Value(f, _) | Arg::Arrow(Some(f), _) => {
+                return error(f.span(), format!("unexpected `{}`", f))
+            }
+            Arg::Arrow(None, t) => return error(t.span(), "unexpected `->`"),
+        }
+    }
+    match source {
+        Some(source) => Ok(TransitionSpec {
+            source,
+            target,
+            trigger,
+            guard,
+            effect,
+        }),
+        None => error(
+            Span::call_site(),
+            "expected the source state of the transition",
+        ),
+    }
+}
+
+struct Model {
+    machine: String,
+    states: Vec<StateSpec>,
+    transitions: Vec<TransitionSpec>,
+}
+
+impl Model {
+    fn name(&self, id: usize) -> &Ident {
+        self.states[id].name.as_ref().expect("named")
+    }
+
+    fn find(&self, name: &Ident) -> syn::Result<usize> {
+        match self
+            .states
+            .iter()
+            .position(|s| s.name.as_ref() == Some(name))
+        {
+            Some(id) => Ok(id),
+            None => error(name.span(), format!("unknown state `{}`", name)),
+        }
+    }
+
+    /// Check the names, parents are declared before their substates
+    fn check(&self) -> syn::Result<()> {
+        for (id, s) in self.states.iter().enumerate() {
+            let name = self.name(id);
+            if name == "initial" {
+                return error(
+                    name.span(),
+                    "`initial` is reserved for the initial pseudostate",
+                );
+            }
+            if self.states[..id]
+                .iter()
+                .any(|o| o.name.as_ref() == Some(name))
+            {
+                return error(name.span(), format!("duplicate name `{}`", name));
+            }
+            if let Some(parent) = &s.parent {
+                let p = self.find(parent)?;
+                if p >= id {
+                    return error(
+                        parent.span(),
+                        format!("`{}` must be declared before its substates", parent),
+                    );
+                }
+                if self.states[p].is_final {
+                    return error(parent.span(), format!("`{}` is a final state", parent));
+                }
+            }
+            if s.is_final && (s.entry.is_some() || s.exit.is_some() || s.do_activity.is_some()) {
+                return error(name.span(), "a final state has no behaviors");
+            }
+            if s.initial
+                && self.states[..id]
+                    .iter()
+                    .any(|o| o.initial && o.parent == s.parent)
+            {
+                return error(name.span(), "several initial states in the same region");
+            }
+        }
+        for t in &self.transitions {
+            let source = self.find(&t.source)?;
+            if self.states[source].is_final {
+                return error(t.source.span(), "a final state has no outgoing transitions");
+            }
+            if let Some(target) = &t.target {
+                self.find(target)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn events(&self) -> Vec<String> {
+        let mut events: Vec<String> = Vec::new();
+        let used = self
+            .states
+            .iter()
+            .flat_map(|s| s.defer.iter())
+            .chain(self.transitions.iter().filter_map(|t| t.trigger.as_ref()));
+        for ev in used {
+            let ev = ev.to_string();
+            if !events.contains(&ev) {
+                events.push(ev);
+            }
+        }
+        events
+    }
+
+    fn fullname(&self, id: usize) -> String {
+        let prefix = match &self.states[id].parent {
+            Some(p) => self.fullname(self.find(p).expect("checked")),
+            None => self.machine.clone(),
+        };
+        format!("{}::region_1::{}", prefix, self.name(id))
+    }
+
+    fn definition(&self) -> TokenStream {
+        let machine = &self.machine;
+        let var = |id: usize| format_ident!("s{}", id);
+        let mut stmts = Vec::new();
+        for (id, s) in self.states.iter().enumerate() {
+            let v = var(id);
+            let name = self.name(id).to_string();
+            let parent = match &s.parent {
+                Some(p) => {
+                    let p = var(self.find(p).expect("checked"));
+                    quote! { #p }
+                }
+                None => quote! { 0 },
+            };
+            if s.is_final {
+                stmts.push(quote! {
+                    let r = match sm.get_only_region(#parent) {
+                        Ok(r) => r,
+                        Err(_) => sm.add_region("region_1", #parent).expect(CHECKED),
+                    };
+                    let #v = sm.add_vertex(#name, r, VertexType::FinalState).expect(CHECKED);
+                });
+            } else {
+                stmts.push(quote! { let #v = sm.add_substate(#name, #parent).expect(CHECKED); });
+            }
+            for (set, behavior) in &[
+                (quote! { set_entry }, &s.entry),
+                (quote! { set_exit }, &s.exit),
+                (quote! { set_do }, &s.do_activity),
+            ] {
+                if let Some(b) = behavior {
+                    let b = b.to_string();
+                    stmts.push(quote! { sm.#set(#v, Behavior::named(#b)).expect(CHECKED); });
+                }
+            }
+        }
+        for (id, s) in self.states.iter().enumerate() {
+            if s.initial {
+                let v = var(id);
+                stmts.push(quote! {
+                    let r = sm.owning_region(#v).expect(CHECKED);
+                    sm.initial_transition(r, #v, OptBehavior::None).expect(CHECKED);
+                });
+            }
+        }
+        for ev in self.events() {
+            stmts.push(quote! { sm.add_event_type(#ev).expect(CHECKED); });
+        }
+        for (id, s) in self.states.iter().enumerate() {
+            let v = var(id);
+            for ev in &s.defer {
+                let ev = ev.to_string();
+                stmts.push(quote! {
+                    let ev = sm.event_type(#ev).expect(CHECKED);
+                    sm.add_deferrable_event(#v, ev).expect(CHECKED);
+                });
+            }
+        }
+        for (n, t) in self.transitions.iter().enumerate() {
+            let name = format!("t{}", n + 1);
+            let source = var(self.find(&t.source).expect("checked"));
+            let target = match &t.target {
+                Some(target) => var(self.find(target).expect("checked")),
+                None => source.clone(),
+            };
+            let trigger = match &t.trigger {
+                Some(ev) => {
+                    let ev = ev.to_string();
+                    quote! { sm.event_type(#ev) }
+                }
+                None => quote! { None },
+            };
+            let guard = match &t.guard {
+                Some(g) => {
+                    let g = g.to_string();
+                    quote! { OptGuard::Guard(Guard::named(#g)) }
+                }
+                None => quote! { OptGuard::None },
+            };
+            let effect = match &t.effect {
+                Some(e) => {
+                    let e = e.to_string();
+                    quote! { OptBehavior::Behavior(Behavior::named(#e)) }
+                }
+                None => quote! { OptBehavior::None },
+            };
+            let kind = match t.target {
+                Some(_) => quote! {},
+                None => quote! {
+                    sm.set_transition_kind(t, TransitionKind::Internal).expect(CHECKED);
+                },
+            };
+            stmts.push(quote! {
+                let t = sm
+                    .add_transition(#name, #trigger, #source, #target, #effect, #guard)
+                    .expect(CHECKED);
+                #kind
+            });
+        }
+        quote! {
+            #[allow(unused_imports)]
+            use ::rust_uml_sm::{
+                Behavior, Guard, OptBehavior, OptGuard, StateMachineDef, TransitionKind,
+                VertexType,
+            };
+            const CHECKED: &str = "checked by derive(StateMachine)";
+            let mut sm = StateMachineDef::new(#machine);
+            #(#stmts)*
+            sm
+        }
+    }
+}
+
+//----------------------------------------------------------------
+// derive(StateMachine)
+//----------------------------------------------------------------
+
+pub fn expand(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    if !ast.generics.params.is_empty() {
+        return error(
+            ast.generics.span(),
+            "derive(StateMachine) does not support generic types",
+        );
+    }
+    match &ast.data {
+        Data::Enum(data) => {
+            let mut model = Model {
+                machine: ast.ident.to_string(),
+                states: Vec::new(),
+                transitions: Vec::new(),
+            };
+            if let Some(attr) = ast
+                .attrs
+                .iter()
+                .find(|a| is(a, "state") || is(a, "transition"))
+            {
+                return error(
+                    attr.path
+                        .get_ident()
+                        .map_or(Span::call_site(), |i| i.span()),
+                    "the states of an enum are declared on its variants",
+                );
+            }
+            for variant in &data.variants {
+                if !matches!(variant.fields, Fields::Unit) {
+                    return error(variant.ident.span(), "states are unit variants");
+                }
+                let name = Some(variant.ident.clone());
+                let mut spec = StateSpec {
+                    name: name.clone(),
+                    ..StateSpec::default()
+                };
+                for attr in &variant.attrs {
+                    if is(attr, "state") {
+                        spec = state_spec(name.clone(), attr)?;
+                    } else if is(attr, "transition") {
+                        model.transitions.push(transition_spec(name.clone(), attr)?);
+                    }
+                }
+                model.states.push(spec);
+            }
+            model.check()?;
+            Ok(expand_enum(ast, &model))
+        }
+        Data::Struct(_) => {
+            let mut model = Model {
+                machine: ast.ident.to_string(),
+                states: Vec::new(),
+                transitions: Vec::new(),
+            };
+            let mut constructor = None;
+            let mut states = None;
+            for attr in &ast.attrs {
+                if is(attr, "state") {
+                    model.states.push(state_spec(None, attr)?);
+                } else if is(attr, "transition") {
+                    model.transitions.push(transition_spec(None, attr)?);
+                } else if is(attr, "statemachine") {
+                    for arg in args(attr)? {
+                        match arg {
+                            Arg::Value(k, v) if k == "constructor" => constructor = Some(v),
+                            Arg::Value(k, v) if k == "states" => states = Some(v),
+                            Arg::Flag(f) | Arg::Value(f, _) | Arg::Arrow(Some(f), _) => {
+                                return error(f.span(), format!("unexpected `{}`", f))
+                            }
+                            Arg::Arrow(None, t) => return error(t.span(), "unexpected `->`"),
+                        }
+                    }
+                }
+            }
+            model.check()?;
+            let definition = match &states {
+                Some(states) if !model.states.is_empty() || !model.transitions.is_empty() => {
+                    return error(
+                        states.span(),
+                        format!("the states are declared by `{}`", states),
+                    )
+                }
+                Some(states) => quote! { #states::definition() },
+                None => model.definition(),
+            };
+            Ok(expand_struct(ast, definition, constructor))
+        }
+        Data::Union(_) => error(
+            ast.ident.span(),
+            "derive(StateMachine) is for structs and enums",
+        ),
+    }
+}
+
+fn expand_enum(ast: &DeriveInput, model: &Model) -> TokenStream {
+    let name = &ast.ident;
+    let definition = model.definition();
+    let variants: Vec<&Ident> = (0..model.states.len()).map(|id| model.name(id)).collect();
+    let names: Vec<String> = variants.iter().map(|v| v.to_string()).collect();
+    let fullnames: Vec<String> = (0..model.states.len())
+        .map(|id| model.fullname(id))
+        .collect();
+    let n = variants.len();
+    quote! {
+        impl #name {
+            pub const ALL: [#name; #n] = [#(#name::#variants,)*];
+
+            /// Build the definition of the state machine.
+            pub fn definition() -> ::rust_uml_sm::StateMachineDef {
+                #definition
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #(#name::#variants => #names,)*
+                }
+            }
+
+            /// The fully qualified name, as returned by `StateMachineDef::fullname`
+            pub fn fullname(&self) -> &'static str {
+                match self {
+                    #(#name::#variants => #fullnames,)*
+                }
+            }
+
+            pub fn from_fullname(fullname: &str) -> Option<#name> {
+                #name::ALL.iter().find(|s| s.fullname() == fullname).map(|s| match s {
+                    #(#name::#variants => #name::#variants,)*
+                })
+            }
+        }
+    }
+}
+
+fn expand_struct(
+    ast: &DeriveInput,
+    definition: TokenStream,
+    constructor: Option<Ident>,
+) -> TokenStream {
+    let name = &ast.ident;
+    let vis = &ast.vis;
+    let sm = format_ident!("{}StateMachine", name);
+    let new_statemachine = constructor.map(|ctor| {
+        quote! {
+            pub fn new_statemachine() -> #sm {
+                #sm::new(#name::#ctor())
+            }
+        }
+    });
+    quote! {
+        impl #name {
+            /// Build the definition of the state machine.
+            pub fn definition() -> ::rust_uml_sm::StateMachineDef {
+                #definition
+            }
+
+            pub fn into_statemachine(self) -> #sm {
+                #sm::new(self)
+            }
+
+            #new_statemachine
+        }
+
+        /// Runs the state machine of the wrapped state, which performs
+        /// its behaviors and evaluates its guards.
+        #vis struct #sm<S = #name> {
+            pub state: S,
+            pub define: ::rust_uml_sm::StateMachineDef,
+        }
+
+        impl #sm<#name> {
+            pub fn new(state: #name) -> #sm {
+                #sm {
+                    state,
+                    define: #name::definition(),
+                }
+            }
+        }
+
+        impl<S: ::rust_uml_sm::Context> #sm<S> {
+            pub fn start(&mut self) -> ::rust_uml_sm::StateMachineResult<()> {
+                self.define.start_with(&mut self.state)
+            }
+
+            /// Dispatch the event with the given name, return true if it
+            /// fired a transition.
+            pub fn send(&mut self, event: &str) -> ::rust_uml_sm::StateMachineResult<bool> {
+                match self.define.event_type(event) {
+                    Some(ev) => self.define.dispatch_with(ev, &mut self.state),
+                    None => Err(::rust_uml_sm::StateMachineError::UnknownEvent(
+                        ::rust_uml_sm::alloc::string::ToString::to_string(event),
+                    )),
+                }
+            }
+
+            pub fn stop(&mut self) -> ::rust_uml_sm::StateMachineResult<()> {
+                self.define.stop_with(&mut self.state)
+            }
+        }
+
+        impl<S> #sm<S> {
+            /// The fully qualified names of the innermost active states
+            pub fn active_states(&self) -> ::rust_uml_sm::alloc::vec::Vec<&str> {
+                self.define
+                    .active_states()
+                    .into_iter()
+                    .filter_map(|s| self.define.fullname(s).ok())
+                    .map(|s| s.as_str())
+                    .collect()
+            }
+
+            /// Return true if a state with the given name is active
+            pub fn is_in(&self, state: &str) -> bool {
+                self.define.active_states().into_iter().any(|leaf| {
+                    let mut v = leaf;
+                    while v != 0 {
+                        if self.define.name(v).map_or(false, |n| n == state) {
+                            return true;
+                        }
+                        v = self
+                            .define
+                            .parent(v)
+                            .and_then(|r| self.define.parent(r))
+                            .unwrap_or(0);
+                    }
+                    false
+                })
+            }
+        }
+    }
+}
+
+//----------------------------------------------------------------
+// #[behaviors]
+//----------------------------------------------------------------
+
+/// Names given to a method by its attributes
+#[derive(Default)]
+struct Binding {
+    on_entry: Vec<Ident>,
+    on_exit: Vec<Ident>,
+    guard: Option<String>,
+    behavior: Option<String>,
+}
+
+fn names(attr: &Attribute) -> syn::Result<Vec<Ident>> {
+    let mut names = Vec::new();
+    for arg in args(attr)? {
+        match arg {
+            Arg::Flag(name) => names.push(name),
+            Arg::Value(f, _) | Arg::Arrow(Some(f), _) => {
+                return error(f.span(), format!("unexpected `{}`", f))
+            }
+            Arg::Arrow(None, t) => return error(t.span(), "unexpected `->`"),
+        }
+    }
+    Ok(names)
+}
+
+/// The name of a guard or behavior, the method name unless given
+fn bound_name(attr: &Attribute, method: &Ident) -> syn::Result<String> {
+    let names = names(attr)?;
+    match names.len() {
+        0 => Ok(method.to_string()),
+        1 => Ok(names[0].to_string()),
+        _ => error(names[1].span(), "expected a single name"),
+    }
+}
+
+pub fn behaviors(mut item: ItemImpl) -> syn::Result<TokenStream> {
+    let mut entries = Vec::new();
+    let mut exits = Vec::new();
+    let mut guards = Vec::new();
+    let mut behaviors = Vec::new();
+    for impl_item in item.items.iter_mut() {
+        let method = match impl_item {
+            ImplItem::Method(method) => method,
+            _ => continue,
+        };
+        let ident = method.sig.ident.clone();
+        let mut binding = Binding::default();
+        let mut bound = false;
+        for attr in &method.attrs {
+            if is(attr, "on_entry") {
+                binding.on_entry.extend(names(attr)?);
+            } else if is(attr, "on_exit") {
+                binding.on_exit.extend(names(attr)?);
+            } else if is(attr, "guard") {
+                binding.guard = Some(bound_name(attr, &ident)?);
+            } else if is(attr, "behavior") {
+                binding.behavior = Some(bound_name(attr, &ident)?);
+            } else {
+                continue;
+            }
+            bound = true;
+        }
+        if !bound {
+            continue;
+        }
+        method.attrs.retain(|a| {
+            !(is(a, "on_entry") || is(a, "on_exit") || is(a, "guard") || is(a, "behavior"))
+        });
+        if method.sig.receiver().is_none() || method.sig.inputs.len() != 1 {
+            return error(
+                ident.span(),
+                "behaviors and guards are methods taking only `self`",
+            );
+        }
+        for state in binding.on_entry {
+            entries.push((state.to_string(), ident.clone()));
+        }
+        for state in binding.on_exit {
+            exits.push((state.to_string(), ident.clone()));
+        }
+        if let Some(name) = binding.guard {
+            guards.push((name, ident.clone()));
+        }
+        if let Some(name) = binding.behavior {
+            behaviors.push((name, ident.clone()));
+        }
+    }
+    if let Some((name, _)) = guards
+        .iter()
+        .enumerate()
+        .find(|(i, (n, _))| guards[..*i].iter().any(|(o, _)| o == n))
+        .map(|(_, g)| g)
+    {
+        return error(Span::call_site(), format!("guard `{}` bound twice", name));
+    }
+    let dispatch = |bound: &[(String, Ident)]| {
+        let mut keys: Vec<&String> = Vec::new();
+        for (key, _) in bound {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        let arms = keys.iter().map(|key| {
+            let calls = bound
+                .iter()
+                .filter(|(k, _)| k == *key)
+                .map(|(_, method)| quote! { self.#method(); });
+            quote! { #key => { #(#calls)* } }
+        });
+        quote! {
+            match name {
+                #(#arms)*
+                _ => {}
+            }
+        }
+    };
+    let entry = dispatch(&entries);
+    let exit = dispatch(&exits);
+    let behavior = dispatch(&behaviors);
+    let guard_names = guards.iter().map(|(n, _)| n);
+    let guard_methods = guards.iter().map(|(_, m)| m);
+    let self_ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    Ok(quote! {
+        #item
+
+        impl #impl_generics ::rust_uml_sm::Context for #self_ty #where_clause {
+            #[allow(unused_variables)]
+            fn behavior(&mut self, name: &str) {
+                #behavior
+            }
+
+            #[allow(unused_variables)]
+            fn guard(&mut self, name: &str) -> bool {
+                match name {
+                    #(#guard_names => self.#guard_methods(),)*
+                    _ => true,
+                }
+            }
+
+            #[allow(unused_variables)]
+            fn on_entry(&mut self, name: &str) {
+                #entry
+            }
+
+            #[allow(unused_variables)]
+            fn on_exit(&mut self, name: &str) {
+                #exit
+            }
+        }
+    })
+}
@@ -1,56 +1,75 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use quote::quote;
-use quote::format_ident;
-use syn;
+use syn::parse_macro_input;
+
+mod derive;
+mod machine;
 
 /// Usage:
-/// [derive(StateMachine)]
-/// struct Foo{...};
-/// This will add an implementation of the trait StateMachine to our
-/// struct, enabling us to call Foo::new_statemachine()
+/// ```text
+/// statemachine! {
+///     statemachine door {
+///         initial -> closed;
+///         state closed { on open [unlocked] -> opened / creak; }
+///         state opened { on close -> closed; }
+///     }
+/// }
+/// ```
+/// The notation is the one read by rust_uml_sm::dsl::parse, without
+/// time and change events. Each state machine becomes a module (here `door`) with a `definition()` function
+/// returning the StateMachineDef and `State` and `Event` enums.
+/// Unknown or duplicate names are compile errors.
+#[proc_macro]
+pub fn statemachine(input: TokenStream) -> TokenStream {
+    let machines = parse_macro_input!(input as machine::Machines);
+    machine::expand(machines)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
 
-#[proc_macro_derive(StateMachine)]
+/// Usage:
+/// ```text
+/// #[derive(StateMachine)]
+/// #[statemachine(constructor = new)]
+/// #[state(idle, initial)]
+/// #[state(busy, entry = start_motor)]
+/// #[transition(idle -> busy, on = go, guard = is_ready)]
+/// struct Motor {...}
+/// ```
+/// Builds `Motor::definition()` from the `state` and `transition`
+/// attributes and a `MotorStateMachine` wrapper with `start()`,
+/// `send(event)` and `stop()`, running the definition with the struct
+/// as its Context. On an enum the variants are the states.
+/// See the derive module for all attributes.
+#[proc_macro_derive(StateMachine, attributes(statemachine, state, transition))]
 pub fn statemachine_derive(input: TokenStream) -> TokenStream {
-    // Construct a representation of Rust code as a syntax tree
-    // that we can manipulate
-    let ast = syn::parse(input).unwrap();
-
-    // Build the trait implementation
-    impl_statemachine_macro(&ast)
+    let ast = parse_macro_input!(input as syn::DeriveInput);
+    derive::expand(&ast)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
 }
 
-fn impl_statemachine_macro(ast: &syn::DeriveInput) -> TokenStream {
-    let name = &ast.ident;
-    let sm = format_ident!("{}StateMachine", name);
-    let sm_trait = format_ident!("{}StateMachineTrait", name);
-    //let sm_new = format_ident!("{}::new", sm);
-
-    let gen = quote! {
-        impl #name {
-            fn new_statemachine() -> #sm {
-                println!("Constructing, StateMachine {}!", stringify!(#sm));
-                let state = #name::new();
-                let state_def = StateMachineDef::new(stringify!(#sm));
-                #sm {
-                    state: state,
-                    define: state_def,
-                }
-            }
-        }
-        struct #sm {
-            state: #name,
-            define: StateMachineDef,
-        }
-        /*
-        impl #sm {
-            fn new() {
-                println!("Creating {}!", stringify!(#sm));
-            }
-        }
-        */
-    };
-    gen.into()
+/// Usage:
+/// ```text
+/// #[behaviors]
+/// impl Motor {
+///     #[on_entry(busy)]
+///     fn start_motor(&mut self) {...}
+///     #[guard]
+///     fn is_ready(&self) -> bool {...}
+///     #[behavior(beep)]
+///     fn make_noise(&mut self) {...}
+/// }
+/// ```
+/// Implements rust_uml_sm::Context for the type, calling the methods
+/// when the named states are entered or exited and for the named
+/// guards and behaviors. Guards and behaviors are named after their
+/// method unless a name is given.
+#[proc_macro_attribute]
+pub fn behaviors(_args: TokenStream, input: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(input as syn::ItemImpl);
+    derive::behaviors(item)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
 }
-
@@ -0,0 +1,873 @@
+//! Implementation of the `statemachine!` macro.
+//!
+//! The macro accepts the notation of `rust_uml_sm::dsl`, written as
+//! Rust tokens, for one or more state machines: vertices, regions,
+//! entry, exit and do behaviors, deferred events and transitions
+//! triggered by events or on completion. The tokens are read by the
+//! parser of `rust_uml_sm_dsl`, as the text of `dsl::parse` is. Time and
+//! change events, `on after(..)`, `on at(..)` and `on when(..)`, are not
+//! supported and reported as errors. Names and transition targets are
+//! checked while expanding so that mistakes are reported at compile
+//! time, pointing at the offending tokens. Each state machine expands to
+//! a module with a `definition()` function building the
+//! `StateMachineDef`, and `State` and `Event` enums.
+
+use proc_macro2::{Delimiter, Spacing, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote};
+use rust_uml_sm_dsl::{
+    camel, parse_all, symbol, Location, Pseudostate, Tok, Token, Trigger, VertexKeyword,
+};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream, Parser};
+
+//----------------------------------------------------------------
+// Parsing
+//----------------------------------------------------------------
+
+/// The span of a Rust token, as a location of the parser.
+#[derive(Clone, Copy)]
+struct At(Span);
+
+impl Location for At {
+    fn to(self, other: At) -> At {
+        At(self.0.join(other.0).unwrap_or(self.0))
+    }
+}
+
+type Ident = rust_uml_sm_dsl::Ident<At>;
+type Item = rust_uml_sm_dsl::Item<At>;
+type MachineAst = rust_uml_sm_dsl::Machine<At>;
+type TransitionDecl = rust_uml_sm_dsl::TransitionDecl<At>;
+
+pub struct Machines {
+    machines: Vec<MachineAst>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+    State,
+    Final,
+    Initial,
+    Pseudostate(Pseudostate),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BehaviorKind {
+    Entry,
+    Exit,
+    Do,
+}
+
+impl Parse for Machines {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut tokens = Vec::new();
+        lex(input.parse()?, &mut tokens)?;
+        tokens.push(Token {
+            tok: Tok::Eof,
+            span: At(Span::call_site()),
+        });
+        match parse_all(&tokens) {
+            Ok(machines) => Ok(Machines { machines }),
+            Err(e) => error(e.span.0, e.message),
+        }
+    }
+}
+
+/// The Rust tokens as tokens of the notation. Literals such as `30s`
+/// are kept as names, `check` reports those which are used as one.
+fn lex(stream: TokenStream, out: &mut Vec<Token<At>>) -> syn::Result<()> {
+    let mut trees = stream.into_iter().peekable();
+    while let Some(tree) = trees.next() {
+        let (tok, span) = match tree {
+            TokenTree::Ident(ident) => (Tok::Ident(ident.to_string()), ident.span()),
+            TokenTree::Literal(lit) => (Tok::Ident(lit.to_string()), lit.span()),
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => {
+                        lex(group.stream(), out)?;
+                        continue;
+                    }
+                };
+                out.push(Token {
+                    tok: Tok::Sym(open),
+                    span: At(group.span_open()),
+                });
+                lex(group.stream(), out)?;
+                (Tok::Sym(close), group.span_close())
+            }
+            TokenTree::Punct(punct) => {
+                let mut text = punct.as_char().to_string();
+                let mut span = punct.span();
+                // `::` and `->` arrive as two joint puncts
+                if let (Spacing::Joint, Some(TokenTree::Punct(next))) =
+                    (punct.spacing(), trees.peek())
+                {
+                    let pair = format!("{}{}", text, next.as_char());
+                    if symbol(&pair).is_some() {
+                        span = span.join(next.span()).unwrap_or(span);
+                        text = pair;
+                        trees.next();
+                    }
+                }
+                match symbol(&text) {
+                    Some(sym) => (Tok::Sym(sym), span),
+                    None => return error(span, format!("unexpected character `{}`", text)),
+                }
+            }
+        };
+        out.push(Token {
+            tok,
+            span: At(span),
+        });
+    }
+    Ok(())
+}
+
+/// Names become Rust identifiers, and time and change events are left
+/// to `rust_uml_sm::dsl::parse`.
+fn check(items: &[Item]) -> syn::Result<()> {
+    for item in items {
+        match item {
+            Item::Region(name, body) | Item::Vertex(_, name, body) => {
+                check_name(name)?;
+                check(body)?;
+            }
+            Item::Initial(decl) | Item::Transition(decl) => {
+                if let Some(name) = &decl.name {
+                    check_name(name)?;
+                }
+                match &decl.trigger {
+                    Some(Trigger::Event(event)) => check_name(event)?,
+                    Some(Trigger::Time(word, ..)) | Some(Trigger::Change(word, ..)) => {
+                        return error(
+                            word.span.0,
+                            format!(
+                                "`{}(..)` is not supported by `statemachine!`, use `rust_uml_sm::dsl::parse`",
+                                word.name
+                            ),
+                        )
+                    }
+                    None => {}
+                }
+                let names = decl.guard.iter().chain(decl.target.iter().flatten());
+                for name in names.chain(&decl.effect) {
+                    check_name(name)?;
+                }
+            }
+            Item::Events(list) | Item::Defer(list) => {
+                for name in list {
+                    check_name(name)?;
+                }
+            }
+            Item::Entry(b) | Item::Exit(b) | Item::Do(b) => check_name(b)?,
+        }
+    }
+    Ok(())
+}
+
+fn check_name(name: &Ident) -> syn::Result<()> {
+    match syn::Ident::parse_any.parse_str(&name.name) {
+        Ok(_) => Ok(()),
+        Err(_) => error(
+            name.span.0,
+            format!("expected identifier, found `{}`", name.name),
+        ),
+    }
+}
+
+/// The identifier for a checked name.
+fn rust_ident(name: &Ident) -> syn::Ident {
+    match name.name.strip_prefix("r#") {
+        Some(raw) => syn::Ident::new_raw(raw, name.span.0),
+        None => syn::Ident::new(&name.name, name.span.0),
+    }
+}
+
+//----------------------------------------------------------------
+// Checking, mirrors the builder in `rust_uml_sm::dsl`
+//----------------------------------------------------------------
+
+#[derive(Clone, Copy, PartialEq)]
+enum NodeKind {
+    Machine,
+    Region,
+    Vertex(VertexKind),
+}
+
+struct Node {
+    kind: NodeKind,
+    name: String,
+    parent: usize,
+    children: Vec<usize>,
+}
+
+/// The calls made to build the definition, in order.
+enum Op {
+    /// add_sm_region, which renames the default region when it is empty
+    SmRegion(usize),
+    Region(usize),
+    Vertex(usize),
+    Behavior(usize, BehaviorKind, String),
+    Event(String),
+    Defer(usize, String),
+    Transition(Plan),
+}
+
+struct Plan {
+    name: String,
+    trigger: Option<String>,
+    source: usize,
+    target: usize,
+    guard: Option<String>,
+    effect: Option<String>,
+    internal: bool,
+}
+
+struct Pending {
+    source: usize,
+    decl: TransitionDecl,
+    initial: bool,
+}
+
+struct Model<'m> {
+    machines: &'m [MachineAst],
+    index: usize,
+    nodes: Vec<Node>,
+    /// false until a vertex has been added, while the state machine is
+    /// empty the first region declared replaces the default region
+    populated: bool,
+    ops: Vec<Op>,
+    pending: Vec<Pending>,
+    defers: Vec<(usize, Vec<Ident>)>,
+    events: Vec<String>,
+    transitions: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scope {
+    Machine,
+    State,
+    Region,
+    Pseudostate,
+}
+
+fn error<T>(span: Span, message: String) -> syn::Result<T> {
+    Err(syn::Error::new(span, message))
+}
+
+impl<'m> Model<'m> {
+    fn new(machines: &'m [MachineAst], index: usize) -> Model<'m> {
+        let name = machines[index].name.name.clone();
+        let mut model = Model {
+            machines,
+            index,
+            nodes: Vec::new(),
+            populated: false,
+            ops: Vec::new(),
+            pending: Vec::new(),
+            defers: Vec::new(),
+            events: Vec::new(),
+            transitions: 0,
+        };
+        model.node(NodeKind::Machine, &name, 0);
+        model.node(NodeKind::Region, "region_1", 0);
+        model
+    }
+
+    fn node(&mut self, kind: NodeKind, name: &str, parent: usize) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            kind,
+            name: name.to_string(),
+            parent,
+            children: Vec::new(),
+        });
+        if id != 0 {
+            self.nodes[parent].children.push(id);
+        }
+        id
+    }
+
+    fn fullname(&self, id: usize) -> String {
+        if id == 0 {
+            self.nodes[0].name.clone()
+        } else {
+            format!(
+                "{}::{}",
+                self.fullname(self.nodes[id].parent),
+                self.nodes[id].name
+            )
+        }
+    }
+
+    fn check_unique(&self, parent: usize, name: &str, span: Span) -> syn::Result<()> {
+        if self.nodes[parent]
+            .children
+            .iter()
+            .any(|c| name == self.nodes[*c].name)
+        {
+            return error(span, format!("duplicate name `{}`", name));
+        }
+        Ok(())
+    }
+
+    fn region_of(
+        &mut self,
+        container: usize,
+        scope: Scope,
+        span: Span,
+        name: &str,
+    ) -> syn::Result<usize> {
+        let regions = self.nodes[container].children.clone();
+        match (scope, regions.len()) {
+            (Scope::Region, _) => Ok(container),
+            (Scope::State, 0) => {
+                let region = self.node(NodeKind::Region, "region_1", container);
+                self.ops.push(Op::Region(region));
+                Ok(region)
+            }
+            (_, 1) => Ok(regions[0]),
+            _ => error(
+                span,
+                format!(
+                    "`{}` has several regions, declare `{}` inside one of them",
+                    self.nodes[container].name, name
+                ),
+            ),
+        }
+    }
+
+    fn body(&mut self, container: usize, scope: Scope, items: &[Item]) -> syn::Result<()> {
+        for item in items {
+            match item {
+                Item::Region(name, body) => {
+                    let region = match scope {
+                        Scope::Machine => {
+                            let default = self.nodes[0].children[0];
+                            if !self.populated && self.nodes[default].name == "region_1" {
+                                self.nodes[default].name = name.name.clone();
+                                default
+                            } else {
+                                self.check_unique(container, &name.name, name.span.0)?;
+                                self.node(NodeKind::Region, &name.name, container)
+                            }
+                        }
+                        Scope::State => {
+                            self.check_unique(container, &name.name, name.span.0)?;
+                            self.node(NodeKind::Region, &name.name, container)
+                        }
+                        _ => {
+                            return error(
+                                name.span.0,
+                                "a region may only be declared in a state".into(),
+                            )
+                        }
+                    };
+                    self.ops.push(match scope {
+                        Scope::Machine => Op::SmRegion(region),
+                        _ => Op::Region(region),
+                    });
+                    self.body(region, Scope::Region, body)?;
+                }
+                Item::Vertex(keyword, name, body) => {
+                    if scope == Scope::Pseudostate {
+                        return error(
+                            name.span.0,
+                            format!("`{}` cannot be declared in a pseudostate", name.name),
+                        );
+                    }
+                    let kind = match keyword {
+                        VertexKeyword::State => VertexKind::State,
+                        VertexKeyword::Final => VertexKind::Final,
+                        VertexKeyword::Pseudostate(kind) => VertexKind::Pseudostate(*kind),
+                    };
+                    let region = self.region_of(container, scope, name.span.0, &name.name)?;
+                    self.check_unique(region, &name.name, name.span.0)?;
+                    let vertex = self.node(NodeKind::Vertex(kind), &name.name, region);
+                    self.populated = true;
+                    self.ops.push(Op::Vertex(vertex));
+                    let inner = match kind {
+                        VertexKind::State => Scope::State,
+                        _ => Scope::Pseudostate,
+                    };
+                    self.body(vertex, inner, body)?;
+                }
+                Item::Initial(decl) => {
+                    if scope == Scope::Pseudostate {
+                        return error(
+                            decl.span.0,
+                            "an initial transition must be in a region or state".into(),
+                        );
+                    }
+                    if decl.trigger.is_some() || decl.target.is_none() {
+                        return error(
+                            decl.span.0,
+                            "an initial transition has a target and no trigger".into(),
+                        );
+                    }
+                    let region = self.region_of(container, scope, decl.span.0, "initial")?;
+                    let existing = self.nodes[region]
+                        .children
+                        .iter()
+                        .copied()
+                        .find(|c| self.nodes[*c].kind == NodeKind::Vertex(VertexKind::Initial));
+                    if let Some(initial) = existing {
+                        return error(
+                            decl.span.0,
+                            format!(
+                                "region `{}` already has an initial pseudostate `{}`",
+                                self.fullname(region),
+                                self.nodes[initial].name
+                            ),
+                        );
+                    }
+                    self.check_unique(region, "initial", decl.span.0)?;
+                    let initial =
+                        self.node(NodeKind::Vertex(VertexKind::Initial), "initial", region);
+                    self.populated = true;
+                    self.ops.push(Op::Vertex(initial));
+                    self.pending.push(Pending {
+                        source: initial,
+                        decl: decl.clone(),
+                        initial: true,
+                    });
+                }
+                Item::Events(list) => {
+                    if scope != Scope::Machine {
+                        return error(
+                            list[0].span.0,
+                            "events are declared in the state machine".into(),
+                        );
+                    }
+                    for ident in list {
+                        if self.events.contains(&ident.name) {
+                            return error(
+                                ident.span.0,
+                                format!("event `{}` declared twice", ident.name),
+                            );
+                        }
+                        self.events.push(ident.name.clone());
+                    }
+                }
+                Item::Entry(b) | Item::Exit(b) | Item::Do(b) => {
+                    if scope != Scope::State {
+                        return error(
+                            b.span.0,
+                            format!("behavior `{}` must be declared in a state", b.name),
+                        );
+                    }
+                    let kind = match item {
+                        Item::Entry(_) => BehaviorKind::Entry,
+                        Item::Exit(_) => BehaviorKind::Exit,
+                        _ => BehaviorKind::Do,
+                    };
+                    self.ops.push(Op::Behavior(container, kind, b.name.clone()));
+                }
+                Item::Defer(list) => {
+                    if scope != Scope::State {
+                        return error(
+                            list[0].span.0,
+                            "deferred events must be declared in a state".into(),
+                        );
+                    }
+                    self.defers.push((container, list.clone()));
+                }
+                Item::Transition(decl) => {
+                    if scope != Scope::State && scope != Scope::Pseudostate {
+                        return error(
+                            decl.span.0,
+                            "a transition must be declared in its source state or pseudostate"
+                                .into(),
+                        );
+                    }
+                    self.pending.push(Pending {
+                        source: container,
+                        decl: decl.clone(),
+                        initial: false,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, name: &str) {
+        if !self.events.iter().any(|e| e == name) {
+            self.events.push(name.to_string());
+            self.ops.push(Op::Event(name.to_string()));
+        }
+    }
+
+    fn finish(&mut self) -> syn::Result<()> {
+        for event in self.events.clone() {
+            self.ops.push(Op::Event(event));
+        }
+        for (state, list) in std::mem::take(&mut self.defers) {
+            for ident in &list {
+                self.event(&ident.name);
+                self.ops.push(Op::Defer(state, ident.name.clone()));
+            }
+        }
+        let mut names: Vec<(usize, usize, String)> = Vec::new();
+        for pending in std::mem::take(&mut self.pending) {
+            let decl = &pending.decl;
+            let trigger = match &decl.trigger {
+                Some(Trigger::Event(event)) => Some(event.name.clone()),
+                _ => None,
+            };
+            if let Some(trigger) = &trigger {
+                self.event(trigger);
+            }
+            let target = match &decl.target {
+                Some(path) => self.resolve(pending.source, path)?,
+                None => pending.source,
+            };
+            self.transitions += 1;
+            let name = match (&decl.name, pending.initial) {
+                (Some(ident), _) => ident.name.clone(),
+                (None, true) => "initial".to_string(),
+                (None, false) => format!("t{}", self.transitions),
+            };
+            // transition names are unique among the outgoing transitions
+            // of the source and the incoming transitions of the target
+            if names
+                .iter()
+                .any(|(s, t, n)| *n == name && (*s == pending.source || *t == target))
+            {
+                let span = decl.name.as_ref().map(|n| n.span).unwrap_or(decl.span).0;
+                return error(span, format!("duplicate name `{}`", name));
+            }
+            names.push((pending.source, target, name.clone()));
+            self.ops.push(Op::Transition(Plan {
+                name,
+                trigger,
+                source: pending.source,
+                target,
+                guard: decl.guard.as_ref().map(|g| g.name.clone()),
+                effect: decl.effect.as_ref().map(|e| e.name.clone()),
+                internal: decl.target.is_none(),
+            }));
+        }
+        Ok(())
+    }
+
+    /// Look for the first element of a path among the children of the
+    /// source and then of each of its ancestors in turn.
+    fn resolve(&self, source: usize, path: &[Ident]) -> syn::Result<usize> {
+        let mut scope = source;
+        loop {
+            if let Some(id) = self.resolve_from(scope, path) {
+                return Ok(id);
+            }
+            if scope == 0 {
+                break;
+            }
+            scope = self.nodes[scope].parent;
+        }
+        let first = &path[0].name;
+        if path.len() > 1 && *first == self.nodes[0].name {
+            if let Some(id) = self.resolve_from(0, &path[1..]) {
+                return Ok(id);
+            }
+        }
+        let name = path
+            .iter()
+            .map(|i| i.name.as_str())
+            .collect::<Vec<_>>()
+            .join("::");
+        let other = self
+            .machines
+            .iter()
+            .enumerate()
+            .find(|(i, m)| *i != self.index && m.name.name == *first);
+        match other {
+            Some((_, m)) if path.len() > 1 => error(
+                path[0].span.0,
+                format!(
+                    "`{}` belongs to state machine `{}`, a transition cannot leave `{}`",
+                    name, m.name.name, self.nodes[0].name
+                ),
+            ),
+            _ => error(path[0].span.0, format!("unknown state `{}`", name)),
+        }
+    }
+
+    fn resolve_from(&self, scope: usize, path: &[Ident]) -> Option<usize> {
+        let mut current = scope;
+        for ident in path {
+            current = self.child(current, &ident.name)?;
+        }
+        match self.nodes[current].kind {
+            NodeKind::Vertex(_) => Some(current),
+            _ => None,
+        }
+    }
+
+    fn child(&self, parent: usize, name: &str) -> Option<usize> {
+        let children = &self.nodes[parent].children;
+        match self.nodes[parent].kind {
+            NodeKind::Region => children
+                .iter()
+                .copied()
+                .find(|c| self.nodes[*c].name == name),
+            NodeKind::Machine | NodeKind::Vertex(VertexKind::State) => children
+                .iter()
+                .copied()
+                .find(|r| self.nodes[*r].name == name)
+                .or_else(|| children.iter().find_map(|r| self.child(*r, name))),
+            _ => None,
+        }
+    }
+
+    //----------------------------------------------------------------
+    // Code generation
+    //----------------------------------------------------------------
+
+    fn var(&self, id: usize) -> TokenStream {
+        if id == 0 {
+            quote! { 0 }
+        } else {
+            let v = format_ident!("n{}", id);
+            quote! { #v }
+        }
+    }
+
+    fn statements(&self) -> Vec<TokenStream> {
+        let mut stmts = Vec::new();
+        // the default region of the state machine
+        stmts.push(quote! { let n1 = sm.sm_regions()[0]; });
+        for op in &self.ops {
+            stmts.push(match op {
+                Op::SmRegion(r) => {
+                    let v = self.var(*r);
+                    let name = &self.nodes[*r].name;
+                    quote! { let #v = sm.add_sm_region(#name).expect(CHECKED); }
+                }
+                Op::Region(r) => {
+                    let v = self.var(*r);
+                    let p = self.var(self.nodes[*r].parent);
+                    let name = &self.nodes[*r].name;
+                    quote! { let #v = sm.add_region(#name, #p).expect(CHECKED); }
+                }
+                Op::Vertex(id) => {
+                    let v = self.var(*id);
+                    let r = self.var(self.nodes[*id].parent);
+                    let name = &self.nodes[*id].name;
+                    let vertex_type = match self.nodes[*id].kind {
+                        NodeKind::Vertex(VertexKind::State) => quote! { VertexType::State },
+                        NodeKind::Vertex(VertexKind::Final) => quote! { VertexType::FinalState },
+                        NodeKind::Vertex(VertexKind::Initial) => quote! { VertexType::InitialState },
+                        NodeKind::Vertex(VertexKind::Pseudostate(kind)) => {
+                            let kind = format_ident!("{}", format!("{:?}", kind));
+                            quote! { VertexType::Pseudostate(PseudostateKind::#kind) }
+                        }
+                        _ => unreachable!(),
+                    };
+                    quote! { let #v = sm.add_vertex(#name, #r, #vertex_type).expect(CHECKED); }
+                }
+                Op::Behavior(state, kind, name) => {
+                    let s = self.var(*state);
+                    let set = match kind {
+                        BehaviorKind::Entry => quote! { set_entry },
+                        BehaviorKind::Exit => quote! { set_exit },
+                        BehaviorKind::Do => quote! { set_do },
+                    };
+                    quote! { sm.#set(#s, Behavior::named(#name)).expect(CHECKED); }
+                }
+                Op::Event(name) => quote! { sm.add_event_type(#name).expect(CHECKED); },
+                Op::Defer(state, event) => {
+                    let s = self.var(*state);
+                    quote! {
+                        let ev = sm.event_type(#event).expect(CHECKED);
+                        sm.add_deferrable_event(#s, ev).expect(CHECKED);
+                    }
+                }
+                Op::Transition(plan) => {
+                    let name = &plan.name;
+                    let source = self.var(plan.source);
+                    let target = self.var(plan.target);
+                    let trigger = match &plan.trigger {
+                        Some(ev) => quote! { sm.event_type(#ev) },
+                        None => quote! { None },
+                    };
+                    let guard = match &plan.guard {
+                        Some(g) => quote! { OptGuard::Guard(Guard::named(#g)) },
+                        None => quote! { OptGuard::None },
+                    };
+                    let effect = match &plan.effect {
+                        Some(e) => quote! { OptBehavior::Behavior(Behavior::named(#e)) },
+                        None => quote! { OptBehavior::None },
+                    };
+                    let kind = if plan.internal {
+                        quote! { sm.set_transition_kind(t, TransitionKind::Internal).expect(CHECKED); }
+                    } else {
+                        quote! {}
+                    };
+                    quote! {
+                        let t = sm
+                            .add_transition(#name, #trigger, #source, #target, #effect, #guard)
+                            .expect(CHECKED);
+                        #kind
+                    }
+                }
+            });
+        }
+        stmts
+    }
+
+    fn expand(&self) -> TokenStream {
+        let name = &self.machines[self.index].name;
+        let machine = rust_ident(name);
+        let name = &name.name;
+        let stmts = self.statements();
+        let states = self.typed_states();
+        let events = self.typed_events();
+        quote! {
+            #[allow(dead_code)]
+            pub mod #machine {
+                #[allow(unused_imports)]
+                use ::rust_uml_sm::{
+                    Behavior, Guard, OptBehavior, OptGuard, PseudostateKind, StateMachineDef,
+                    TransitionKind, VertexType,
+                };
+
+                const CHECKED: &str = "checked by statemachine!";
+
+                /// Build the definition of the state machine.
+                #[allow(unused_variables)]
+                pub fn definition() -> StateMachineDef {
+                    let mut sm = StateMachineDef::new(#name);
+                    #(#stmts)*
+                    sm
+                }
+
+                #states
+                #events
+            }
+        }
+    }
+
+    /// A variant for every state and final state, named after the state
+    /// or, if the name is not unique, after the enclosing states too.
+    fn typed_states(&self) -> TokenStream {
+        let ids: Vec<usize> = (0..self.nodes.len())
+            .filter(|id| {
+                matches!(
+                    self.nodes[*id].kind,
+                    NodeKind::Vertex(VertexKind::State) | NodeKind::Vertex(VertexKind::Final)
+                )
+            })
+            .collect();
+        let variant = |id: usize| {
+            let short = camel(&self.nodes[id].name);
+            let unique = ids
+                .iter()
+                .filter(|o| camel(&self.nodes[**o].name) == short)
+                .count()
+                == 1;
+            if unique {
+                return format_ident!("{}", short);
+            }
+            let mut parts = Vec::new();
+            let mut v = id;
+            while v != 0 {
+                if let NodeKind::Vertex(_) = self.nodes[v].kind {
+                    parts.push(camel(&self.nodes[v].name));
+                }
+                v = self.nodes[v].parent;
+            }
+            parts.reverse();
+            format_ident!("{}", parts.join(""))
+        };
+        let variants: Vec<syn::Ident> = ids.iter().map(|id| variant(*id)).collect();
+        let names: Vec<&String> = ids.iter().map(|id| &self.nodes[*id].name).collect();
+        let fullnames: Vec<String> = ids.iter().map(|id| self.fullname(*id)).collect();
+        let n = ids.len();
+        quote! {
+            /// The states of the state machine.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum State {
+                #(#variants,)*
+            }
+
+            impl State {
+                pub const ALL: [State; #n] = [#(State::#variants,)*];
+
+                pub fn name(self) -> &'static str {
+                    match self {
+                        #(State::#variants => #names,)*
+                    }
+                }
+
+                /// The fully qualified name, as returned by `StateMachineDef::fullname`
+                pub fn fullname(self) -> &'static str {
+                    match self {
+                        #(State::#variants => #fullnames,)*
+                    }
+                }
+
+                pub fn from_fullname(fullname: &str) -> Option<State> {
+                    State::ALL.iter().copied().find(|s| s.fullname() == fullname)
+                }
+            }
+        }
+    }
+
+    fn typed_events(&self) -> TokenStream {
+        let variants: Vec<syn::Ident> = self
+            .events
+            .iter()
+            .map(|e| format_ident!("{}", camel(e)))
+            .collect();
+        let names = &self.events;
+        let n = names.len();
+        quote! {
+            /// The event types of the state machine.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum Event {
+                #(#variants,)*
+            }
+
+            impl Event {
+                pub const ALL: [Event; #n] = [#(Event::#variants,)*];
+
+                pub fn name(self) -> &'static str {
+                    match self {
+                        #(Event::#variants => #names,)*
+                    }
+                }
+
+                pub fn from_name(name: &str) -> Option<Event> {
+                    Event::ALL.iter().copied().find(|e| e.name() == name)
+                }
+            }
+        }
+    }
+}
+
+pub fn expand(machines: Machines) -> syn::Result<TokenStream> {
+    for machine in &machines.machines {
+        check_name(&machine.name)?;
+        check(&machine.body)?;
+    }
+    let mut out = TokenStream::new();
+    for (index, machine) in machines.machines.iter().enumerate() {
+        if machines.machines[..index]
+            .iter()
+            .any(|m| m.name.name == machine.name.name)
+        {
+            return error(
+                machine.name.span.0,
+                format!("duplicate name `{}`", machine.name.name),
+            );
+        }
+        let mut model = Model::new(&machines.machines, index);
+        model.body(0, Scope::Machine, &machine.body)?;
+        model.finish()?;
+        out.extend(model.expand());
+    }
+    Ok(out)
+}
@@ -0,0 +1,547 @@
+//! The notation of `rust_uml_sm::dsl`, read into declarations.
+//!
+//! Both `rust_uml_sm::dsl::parse` and the `statemachine!` macro read
+//! state machines with this parser: the first from text split by
+//! [`lex`], the second from the Rust tokens it is given. The parser
+//! works on [`Token`]s with any [`Location`], the [`Span`] of the text
+//! or the span of a Rust token, so errors point where they are in
+//! either. What the declarations mean is left to the callers.
+
+#![no_std]
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+/// A location in the source text.
+/// `start` and `end` are byte offsets, `line` and `column` are 1 based.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Where a token is, see `Span`.
+pub trait Location: Copy {
+    /// From the start of self to the end of other, or self when the
+    /// locations cannot be joined.
+    fn to(self, other: Self) -> Self;
+}
+
+impl Location for Span {
+    fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+/// An error found while reading a definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError<S = Span> {
+    pub message: String,
+    pub span: S,
+}
+
+impl<S> ParseError<S> {
+    pub fn new(message: String, span: S) -> ParseError<S> {
+        ParseError { message, span }
+    }
+}
+
+impl ParseError {
+    /// Render the error along with the offending source line and a
+    /// marker under the span.
+    pub fn render(&self, src: &str) -> String {
+        let line = src.lines().nth(self.span.line - 1).unwrap_or("");
+        let width = (self.span.end - self.span.start).max(1);
+        let width = width.min(line.len().saturating_sub(self.span.column - 1).max(1));
+        format!(
+            "error: {}\n --> {}:{}\n  |\n  | {}\n  | {}{}\n",
+            self.message,
+            self.span.line,
+            self.span.column,
+            line,
+            " ".repeat(self.span.column - 1),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+fn err<T, S>(message: String, span: S) -> Result<T, ParseError<S>> {
+    Err(ParseError::new(message, span))
+}
+
+/// `door_open` becomes `DoorOpen`
+pub fn camel(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+//----------------------------------------------------------------
+// Lexer
+//----------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tok {
+    /// Names, keywords and durations such as `30s`
+    Ident(String),
+    Sym(&'static str),
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token<S = Span> {
+    pub tok: Tok,
+    pub span: S,
+}
+
+const SYMBOLS: [&str; 12] = ["::", "->", "{", "}", "[", "]", "(", ")", ";", ",", ":", "/"];
+
+/// The symbol of the notation written as text, if it is one.
+pub fn symbol(text: &str) -> Option<&'static str> {
+    SYMBOLS.iter().copied().find(|sym| *sym == text)
+}
+
+/// Split text into tokens, ending with `Tok::Eof`.
+pub fn lex(src: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut line_start = 0;
+    let mut pos = 0;
+    let bytes = src.as_bytes();
+    let span = |start: usize, end: usize, line: usize, line_start: usize| Span {
+        start,
+        end,
+        line,
+        column: start - line_start + 1,
+    };
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        if c == b'\n' {
+            pos += 1;
+            line += 1;
+            line_start = pos;
+        } else if c.is_ascii_whitespace() {
+            pos += 1;
+        } else if src[pos..].starts_with("//") {
+            while pos < bytes.len() && bytes[pos] != b'\n' {
+                pos += 1;
+            }
+        } else if c.is_ascii_alphanumeric() || c == b'_' {
+            let start = pos;
+            while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_') {
+                pos += 1;
+            }
+            tokens.push(Token {
+                tok: Tok::Ident(src[start..pos].to_string()),
+                span: span(start, pos, line, line_start),
+            });
+        } else if let Some(sym) = SYMBOLS.iter().find(|sym| src[pos..].starts_with(*sym)) {
+            tokens.push(Token {
+                tok: Tok::Sym(sym),
+                span: span(pos, pos + sym.len(), line, line_start),
+            });
+            pos += sym.len();
+        } else {
+            let ch = src[pos..].chars().next().unwrap_or('?');
+            return err(
+                format!("unexpected character `{}`", ch),
+                span(pos, pos + ch.len_utf8(), line, line_start),
+            );
+        }
+    }
+    tokens.push(Token {
+        tok: Tok::Eof,
+        span: span(pos, pos, line, line_start),
+    });
+    Ok(tokens)
+}
+
+//----------------------------------------------------------------
+// Declarations
+//----------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+pub struct Ident<S = Span> {
+    pub name: String,
+    pub span: S,
+}
+
+/// The pseudostates, named as the variants of
+/// `rust_uml_sm::PseudostateKind`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pseudostate {
+    Choice,
+    Junction,
+    Fork,
+    Join,
+    ShallowHistory,
+    DeepHistory,
+    EntryPoint,
+    ExitPoint,
+    Terminate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VertexKeyword {
+    State,
+    Final,
+    Pseudostate(Pseudostate),
+}
+
+#[derive(Debug, Clone)]
+pub enum Trigger<S = Span> {
+    Event(Ident<S>),
+    /// `after(30s)` or `at(30s)`: the keyword, the duration and the
+    /// span of both.
+    Time(Ident<S>, Duration, S),
+    /// `when(condition)`: the keyword, the condition and the span of both.
+    Change(Ident<S>, Ident<S>, S),
+}
+
+#[derive(Debug, Clone)]
+pub struct TransitionDecl<S = Span> {
+    pub name: Option<Ident<S>>,
+    pub trigger: Option<Trigger<S>>,
+    pub guard: Option<Ident<S>>,
+    pub target: Option<Vec<Ident<S>>>,
+    pub effect: Option<Ident<S>>,
+    pub span: S,
+}
+
+#[derive(Debug, Clone)]
+pub enum Item<S = Span> {
+    Region(Ident<S>, Vec<Item<S>>),
+    Vertex(VertexKeyword, Ident<S>, Vec<Item<S>>),
+    Initial(TransitionDecl<S>),
+    Events(Vec<Ident<S>>),
+    Entry(Ident<S>),
+    Exit(Ident<S>),
+    Do(Ident<S>),
+    Defer(Vec<Ident<S>>),
+    Transition(TransitionDecl<S>),
+}
+
+#[derive(Debug)]
+pub struct Machine<S = Span> {
+    pub name: Ident<S>,
+    pub body: Vec<Item<S>>,
+}
+
+/// Parse the tokens of a single state machine.
+pub fn parse<S: Location>(tokens: &[Token<S>]) -> Result<Machine<S>, ParseError<S>> {
+    let mut parser = Parser { tokens, pos: 0 };
+    let machine = parser.machine()?;
+    if parser.peek().tok != Tok::Eof {
+        return parser.unexpected("end of input");
+    }
+    Ok(machine)
+}
+
+/// Parse the tokens of any number of state machines.
+pub fn parse_all<S: Location>(tokens: &[Token<S>]) -> Result<Vec<Machine<S>>, ParseError<S>> {
+    let mut parser = Parser { tokens, pos: 0 };
+    let mut machines = Vec::new();
+    while parser.peek().tok != Tok::Eof {
+        machines.push(parser.machine()?);
+    }
+    Ok(machines)
+}
+
+//----------------------------------------------------------------
+// Parser
+//----------------------------------------------------------------
+
+struct Parser<'t, S> {
+    tokens: &'t [Token<S>],
+    pos: usize,
+}
+
+fn vertex_keyword(word: &str) -> Option<VertexKeyword> {
+    let kind = match word {
+        "state" => return Some(VertexKeyword::State),
+        "final" => return Some(VertexKeyword::Final),
+        "choice" => Pseudostate::Choice,
+        "junction" => Pseudostate::Junction,
+        "fork" => Pseudostate::Fork,
+        "join" => Pseudostate::Join,
+        "history" => Pseudostate::ShallowHistory,
+        "deephistory" => Pseudostate::DeepHistory,
+        "entrypoint" => Pseudostate::EntryPoint,
+        "exitpoint" => Pseudostate::ExitPoint,
+        "terminate" => Pseudostate::Terminate,
+        _ => return None,
+    };
+    Some(VertexKeyword::Pseudostate(kind))
+}
+
+fn describe(tok: &Tok) -> String {
+    match tok {
+        Tok::Ident(name) => format!("`{}`", name),
+        Tok::Sym(sym) => format!("`{}`", sym),
+        Tok::Eof => "end of input".to_string(),
+    }
+}
+
+impl<'t, S: Location> Parser<'t, S> {
+    fn peek(&self) -> &Token<S> {
+        &self.tokens[self.pos]
+    }
+
+    fn peek_at(&self, n: usize) -> &Token<S> {
+        &self.tokens[(self.pos + n).min(self.tokens.len() - 1)]
+    }
+
+    fn next(&mut self) -> Token<S> {
+        let t = self.tokens[self.pos].clone();
+        if t.tok != Tok::Eof {
+            self.pos += 1;
+        }
+        t
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, ParseError<S>> {
+        let t = self.peek();
+        err(
+            format!("expected {}, found {}", expected, describe(&t.tok)),
+            t.span,
+        )
+    }
+
+    fn is_sym(&self, sym: &str) -> bool {
+        matches!(self.peek().tok, Tok::Sym(s) if s == sym)
+    }
+
+    fn eat_sym(&mut self, sym: &str) -> bool {
+        if self.is_sym(sym) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_sym(&mut self, sym: &str) -> Result<S, ParseError<S>> {
+        if self.is_sym(sym) {
+            Ok(self.next().span)
+        } else {
+            self.unexpected(&format!("`{}`", sym))
+        }
+    }
+
+    fn is_keyword(&self, word: &str) -> bool {
+        matches!(&self.peek().tok, Tok::Ident(name) if name == word)
+    }
+
+    fn ident(&mut self, what: &str) -> Result<Ident<S>, ParseError<S>> {
+        match self.peek().tok.clone() {
+            Tok::Ident(name) => {
+                let span = self.next().span;
+                Ok(Ident { name, span })
+            }
+            _ => self.unexpected(what),
+        }
+    }
+
+    fn ident_list(&mut self, what: &str) -> Result<Vec<Ident<S>>, ParseError<S>> {
+        let mut list = vec![self.ident(what)?];
+        while self.eat_sym(",") {
+            list.push(self.ident(what)?);
+        }
+        Ok(list)
+    }
+
+    fn machine(&mut self) -> Result<Machine<S>, ParseError<S>> {
+        if !self.is_keyword("statemachine") {
+            return self.unexpected("`statemachine`");
+        }
+        self.next();
+        let name = self.ident("a state machine name")?;
+        let body = self.block()?;
+        Ok(Machine { name, body })
+    }
+
+    fn block(&mut self) -> Result<Vec<Item<S>>, ParseError<S>> {
+        self.expect_sym("{")?;
+        let mut items = Vec::new();
+        while !self.eat_sym("}") {
+            if self.peek().tok == Tok::Eof {
+                return self.unexpected("`}`");
+            }
+            items.push(self.item()?);
+        }
+        Ok(items)
+    }
+
+    fn item(&mut self) -> Result<Item<S>, ParseError<S>> {
+        let word = match &self.peek().tok {
+            Tok::Ident(word) => word.clone(),
+            Tok::Sym("[") | Tok::Sym("->") | Tok::Sym("/") => {
+                return Ok(Item::Transition(self.transition(None)?))
+            }
+            _ => return self.unexpected("a declaration"),
+        };
+        // `name: on ev -> target;` is a named transition
+        if self.peek_at(1).tok == Tok::Sym(":") {
+            let name = self.ident("a transition name")?;
+            self.next();
+            return Ok(Item::Transition(self.transition(Some(name))?));
+        }
+        if let Some(keyword) = vertex_keyword(&word) {
+            self.next();
+            let name = self.ident("a name")?;
+            let body = if keyword != VertexKeyword::Final && self.is_sym("{") {
+                self.block()?
+            } else {
+                self.expect_sym(";")?;
+                Vec::new()
+            };
+            return Ok(Item::Vertex(keyword, name, body));
+        }
+        match word.as_str() {
+            "region" => {
+                self.next();
+                let name = self.ident("a region name")?;
+                Ok(Item::Region(name, self.block()?))
+            }
+            "initial" => {
+                self.next();
+                let decl = self.transition(None)?;
+                Ok(Item::Initial(decl))
+            }
+            "event" | "defer" => {
+                self.next();
+                let list = self.ident_list("an event name")?;
+                self.expect_sym(";")?;
+                if word == "event" {
+                    Ok(Item::Events(list))
+                } else {
+                    Ok(Item::Defer(list))
+                }
+            }
+            "entry" | "exit" | "do" => {
+                self.next();
+                let behavior = self.ident("a behavior name")?;
+                self.expect_sym(";")?;
+                Ok(match word.as_str() {
+                    "entry" => Item::Entry(behavior),
+                    "exit" => Item::Exit(behavior),
+                    _ => Item::Do(behavior),
+                })
+            }
+            "on" => Ok(Item::Transition(self.transition(None)?)),
+            _ => self.unexpected("a declaration"),
+        }
+    }
+
+    fn transition(&mut self, name: Option<Ident<S>>) -> Result<TransitionDecl<S>, ParseError<S>> {
+        let start = self.peek().span;
+        let mut decl = TransitionDecl {
+            name,
+            trigger: None,
+            guard: None,
+            target: None,
+            effect: None,
+            span: start,
+        };
+        if self.is_keyword("on") {
+            self.next();
+            let event = self.ident("an event name")?;
+            decl.trigger = Some(match event.name.as_str() {
+                "after" | "at" if self.eat_sym("(") => {
+                    let duration = self.duration()?;
+                    let span = event.span.to(self.expect_sym(")")?);
+                    Trigger::Time(event, duration, span)
+                }
+                "when" if self.eat_sym("(") => {
+                    let condition = self.ident("a condition name")?;
+                    let span = event.span.to(self.expect_sym(")")?);
+                    Trigger::Change(event, condition, span)
+                }
+                _ => Trigger::Event(event),
+            });
+        }
+        if self.eat_sym("[") {
+            decl.guard = Some(self.ident("a guard name")?);
+            self.expect_sym("]")?;
+        }
+        if self.eat_sym("->") {
+            let mut path = vec![self.ident("a target")?];
+            while self.eat_sym("::") {
+                path.push(self.ident("a target")?);
+            }
+            decl.target = Some(path);
+        }
+        if self.eat_sym("/") {
+            decl.effect = Some(self.ident("an effect name")?);
+        }
+        let end = self.expect_sym(";")?;
+        decl.span = decl.span.to(end);
+        if decl.trigger.is_none() && decl.target.is_none() {
+            return err(
+                "a transition needs a trigger (`on event`) or a target (`-> state`)".to_string(),
+                decl.span,
+            );
+        }
+        Ok(decl)
+    }
+
+    /// The duration of `after(...)` or `at(...)`.
+    fn duration(&mut self) -> Result<Duration, ParseError<S>> {
+        let duration = self.ident("a duration")?;
+        match parse_duration(&duration.name) {
+            Some(d) => Ok(d),
+            None => err(
+                format!(
+                    "expected a duration such as `30s`, found `{}`",
+                    duration.name
+                ),
+                duration.span,
+            ),
+        }
+    }
+}
+
+/// Read a duration such as `30s` or `1500ms`.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let digits = text.bytes().take_while(|b| b.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    let n: u64 = text[..digits].parse().ok()?;
+    Some(match &text[digits..] {
+        "ns" => Duration::from_nanos(n),
+        "us" => Duration::from_micros(n),
+        "ms" => Duration::from_millis(n),
+        "s" => Duration::from_secs(n),
+        "min" => Duration::from_secs(n.checked_mul(60)?),
+        "h" => Duration::from_secs(n.checked_mul(3600)?),
+        _ => return None,
+    })
+}
@@ -0,0 +1,231 @@
+//! Do-activities as futures, with the `async` feature.
+//!
+//! A UML do-activity runs while its state is active: it starts once the
+//! state is entered, is aborted if the state is exited first, and the
+//! state completes when it is over. An [`AsyncInstance`] asks its
+//! context, an [`Activities`], for the future of each named do-activity
+//! when its state is entered, and keeps it until it is over or the
+//! state is exited. Dropping the future aborts the activity, before the
+//! exit behavior of the state is performed. A do-activity without a
+//! future is performed at entry like any behavior.
+//!
+//! The instance polls the futures itself, so it runs on any executor:
+//! [`AsyncInstance::next_done`] resolves once a do-activity is over and
+//! the completion transitions it enabled were taken, and is meant to be
+//! awaited alongside the events, for example with a `select!`.
+//!
+//! ```text
+//! loop {
+//!     select! {
+//!         event = events.recv() => sm.dispatch(event)?,
+//!         state = sm.next_done() => println!("{} done", state?),
+//!     }
+//! }
+//! ```
+//!
+//! The futures are not part of a snapshot, a restored instance has no
+//! do-activity going on.
+
+use crate::trace::{Divergence, Trace};
+use crate::*;
+use core::future::{self, Future};
+use core::pin::Pin;
+use core::task::{self, Poll};
+
+/// The future of a do-activity going on in the background.
+pub type Activity = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Resolves named behaviors and guards, and creates the futures of the
+/// named do-activities.
+pub trait Activities: Context {
+    /// The future performing the named do-activity, or None if it is a
+    /// behavior performed at entry.
+    fn activity(&mut self, name: &str) -> Option<Activity>;
+}
+
+/// A definition running with its do-activities as futures.
+pub struct AsyncInstance<C> {
+    def: StateMachineDef,
+    ctx: C,
+    running: Vec<(DbId, Activity)>,
+}
+
+/// The context of a step, keeps the futures of the do-activities
+/// started and drops those aborted.
+struct Spawner<'a, C> {
+    ctx: &'a mut C,
+    running: &'a mut Vec<(DbId, Activity)>,
+}
+
+impl<C: Activities> Context for Spawner<'_, C> {
+    fn behavior(&mut self, name: &str) {
+        self.ctx.behavior(name)
+    }
+
+    fn guard(&mut self, name: &str) -> bool {
+        self.ctx.guard(name)
+    }
+
+    fn on_entry(&mut self, state: &str) {
+        self.ctx.on_entry(state)
+    }
+
+    fn on_exit(&mut self, state: &str) {
+        self.ctx.on_exit(state)
+    }
+
+    fn start_activity(&mut self, state: DbId, name: &str) -> bool {
+        match self.ctx.activity(name) {
+            Some(activity) => {
+                self.running.push((state, activity));
+                true
+            }
+            None => {
+                self.ctx.behavior(name);
+                false
+            }
+        }
+    }
+
+    fn abort_activity(&mut self, state: DbId) {
+        self.running.retain(|(s, _)| *s != state);
+    }
+
+    fn condition(&mut self, name: &str) -> bool {
+        self.ctx.condition(name)
+    }
+}
+
+/// The context of a replay, the do-activities with a future go on in
+/// the background but their future is not polled, the trace tells when
+/// they are over.
+struct Replayer<'a, C>(&'a mut C);
+
+impl<C: Activities> Context for Replayer<'_, C> {
+    fn behavior(&mut self, name: &str) {
+        self.0.behavior(name)
+    }
+
+    fn guard(&mut self, name: &str) -> bool {
+        self.0.guard(name)
+    }
+
+    fn on_entry(&mut self, state: &str) {
+        self.0.on_entry(state)
+    }
+
+    fn on_exit(&mut self, state: &str) {
+        self.0.on_exit(state)
+    }
+
+    fn start_activity(&mut self, _state: DbId, name: &str) -> bool {
+        match self.0.activity(name) {
+            Some(_) => true,
+            None => {
+                self.0.behavior(name);
+                false
+            }
+        }
+    }
+
+    fn condition(&mut self, name: &str) -> bool {
+        self.0.condition(name)
+    }
+}
+
+impl<C: Activities> AsyncInstance<C> {
+    pub fn new(def: StateMachineDef, ctx: C) -> AsyncInstance<C> {
+        AsyncInstance {
+            def,
+            ctx,
+            running: Vec::new(),
+        }
+    }
+
+    pub fn def(&self) -> &StateMachineDef {
+        &self.def
+    }
+
+    pub fn ctx(&self) -> &C {
+        &self.ctx
+    }
+
+    pub fn ctx_mut(&mut self) -> &mut C {
+        &mut self.ctx
+    }
+
+    pub fn into_inner(self) -> (StateMachineDef, C) {
+        (self.def, self.ctx)
+    }
+
+    /// Run f with the context keeping the futures.
+    fn with_spawner<T>(
+        &mut self,
+        f: impl FnOnce(&mut StateMachineDef, &mut dyn Context) -> StateMachineResult<T>,
+    ) -> StateMachineResult<T> {
+        let mut spawner = Spawner {
+            ctx: &mut self.ctx,
+            running: &mut self.running,
+        };
+        f(&mut self.def, &mut spawner)
+    }
+
+    /// Enter the initial configuration, see `Db::start`.
+    pub fn start(&mut self) -> StateMachineResult<()> {
+        self.running.clear();
+        self.with_spawner(|def, ctx| def.start_with(ctx))
+    }
+
+    /// Process an event to completion, see `Db::dispatch`.
+    pub fn dispatch(&mut self, event: DbId) -> StateMachineResult<bool> {
+        self.with_spawner(|def, ctx| def.dispatch_with(event, ctx))
+    }
+
+    /// Exit all active states, aborting the do-activities.
+    pub fn stop(&mut self) -> StateMachineResult<()> {
+        self.with_spawner(|def, ctx| def.stop_with(ctx))
+    }
+
+    /// Evaluate the conditions of the change events again, see
+    /// `Db::notify_changed`.
+    pub fn notify_changed(&mut self) -> StateMachineResult<usize> {
+        self.with_spawner(|def, ctx| def.notify_changed_with(ctx))
+    }
+
+    /// The states whose do-activity goes on.
+    pub fn running(&self) -> impl Iterator<Item = DbId> + '_ {
+        self.running.iter().map(|(state, _)| *state)
+    }
+
+    /// Poll the do-activities going on. Once one is over its state
+    /// completes, see `Db::activity_done`, and the state is returned.
+    /// Pending while no do-activity is going on.
+    pub fn poll_done(&mut self, cx: &mut task::Context<'_>) -> Poll<StateMachineResult<DbId>> {
+        let done = self
+            .running
+            .iter_mut()
+            .position(|(_, activity)| activity.as_mut().poll(cx).is_ready());
+        match done {
+            Some(i) => {
+                let (state, _) = self.running.remove(i);
+                let result = self.with_spawner(|def, ctx| def.activity_done_with(state, ctx));
+                Poll::Ready(result.map(|_| state))
+            }
+            None => Poll::Pending,
+        }
+    }
+
+    /// Resolves once a do-activity is over and its state completed, see
+    /// `poll_done`.
+    pub fn next_done(&mut self) -> impl Future<Output = StateMachineResult<DbId>> + '_ {
+        future::poll_fn(move |cx| self.poll_done(cx))
+    }
+
+    /// Replay a trace recorded by an instance with the same
+    /// do-activities, see `Db::replay_with`. Their futures are created
+    /// but not polled, the trace tells when they are over.
+    pub fn replay(&mut self, trace: &Trace) -> StateMachineResult<Option<Divergence>> {
+        self.running.clear();
+        self.def.replay_with(trace, &mut Replayer(&mut self.ctx))
+    }
+}
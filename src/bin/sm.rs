@@ -1,9 +1,3 @@
-// use std::collections::HashMap;
-// use std::fmt;
-// use std::process;
-// use std::io::{self, Write};
-//use std::string::ToString;
-
 /*
 Simple State: a state without internal Vertices or Transitions.
 Composite State: a state with at least one Region.
@@ -36,62 +30,599 @@ Similarly, a submachine Statemachine can be exited as a result of:reaching it
 */
 
 extern crate rust_uml_sm;
-use rust_uml_sm::StateMachineDef;
-use rust_uml_sm::StateMachine;
+use rust_uml_sm::render::{self, json_string};
+use rust_uml_sm::repl::Repl;
+use rust_uml_sm::trace::Trace;
+use rust_uml_sm::{codegen, dsl, Context, StateMachineDef, StateMachineError};
+use std::io::{Read, Write};
+use std::process;
+
+const USAGE: &str = "usage: sm <command> <model.sm> [options]
+
+commands:
+    validate <model> [--json]                  check a model, exit 1 on problems
+    render <model> --format <format> [-o out]  plantuml, mermaid, dot, svg, html or
+           [--trace file]                      plantuml-svg, html plays the trace back
+    simulate <model> [events] [--guard name=false]... [--json]
+                                               dispatch the events, one per line
+                                               of the file or of stdin
+    stats <model> [--json]                     count the elements of a model
+    convert <model> --to <format> [-o out]     sm, json or rust
+    repl <model>                               an interactive session, see help in it
+    diff <old> <new>                           the changes between two models
+
+Exit codes: 0 ok, 1 the model has problems or the models differ,
+2 usage or input/output errors. With --json a failure is written to
+stderr as a JSON object, like the problems found by validate.
+Models are read in the sm notation or as the JSON written by convert.
+";
+
+/// A failure of a command, its exit code and what went wrong.
+struct Failure {
+    exit: i32,
+    file: Option<String>,
+    diagnostic: Diagnostic,
+}
+
+impl Failure {
+    fn new(exit: i32, code: &'static str, message: impl ToString) -> Failure {
+        Failure {
+            exit,
+            file: None,
+            diagnostic: Diagnostic {
+                code,
+                message: message.to_string(),
+                line: None,
+            },
+        }
+    }
+
+    /// A failure about a file.
+    fn in_file(exit: i32, code: &'static str, file: &str, message: impl ToString) -> Failure {
+        Failure {
+            file: Some(file.to_string()),
+            ..Failure::new(exit, code, message)
+        }
+    }
+
+    fn at(mut self, line: usize, column: usize) -> Failure {
+        self.diagnostic.line = Some((line, column));
+        self
+    }
+
+    /// The failure for people, `sm:` aside.
+    fn text(&self) -> String {
+        let d = &self.diagnostic;
+        let text = match (&self.file, d.line) {
+            (Some(file), Some((line, column))) => {
+                format!("{}:{}:{}: {}", file, line, column, d.message)
+            }
+            (Some(file), None) => format!("{}: {}", file, d.message),
+            (None, Some((line, _))) => format!("line {}: {}", line, d.message),
+            (None, None) => d.message.clone(),
+        };
+        match d.code {
+            "usage" => format!("{}\n\n{}", text, USAGE),
+            _ => text,
+        }
+    }
+}
+
+type CommandResult = Result<i32, Failure>;
+
+fn usage(message: &str) -> Failure {
+    Failure::new(2, "usage", message)
+}
+
+/// Arguments left once the options are taken out.
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+    json: bool,
+}
+
+impl Args {
+    /// Split arguments, the options in `valued` take a value.
+    fn parse(args: &[String], valued: &[&str]) -> Result<Args, Failure> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            options: Vec::new(),
+            json: false,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--json" {
+                parsed.json = true;
+            } else if valued.contains(&arg.as_str()) {
+                match args.next() {
+                    Some(value) => parsed.options.push((arg.clone(), value.clone())),
+                    None => return Err(usage(&format!("{} takes a value", arg))),
+                }
+            } else if arg.starts_with('-') && arg != "-" {
+                return Err(usage(&format!("unknown option {}", arg)));
+            } else {
+                parsed.positional.push(arg.clone());
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The model path and at most `extra` more positional arguments.
+    fn model(&self, extra: usize) -> Result<&str, Failure> {
+        match self.positional.len() {
+            0 => Err(usage("missing model file")),
+            n if n > extra + 1 => Err(usage(&format!(
+                "unexpected argument {}",
+                self.positional[extra + 1]
+            ))),
+            _ => Ok(&self.positional[0]),
+        }
+    }
+}
+
+fn read(path: &str) -> Result<String, Failure> {
+    let mut text = String::new();
+    let result = match path {
+        "-" => std::io::stdin().read_to_string(&mut text).map(|_| ()),
+        _ => std::fs::read_to_string(path).map(|t| text = t),
+    };
+    result.map_err(|e| Failure::in_file(2, "io", path, e))?;
+    Ok(text)
+}
+
+/// Read and parse a model, parse errors are reported as problems.
+fn load(path: &str) -> Result<StateMachineDef, Failure> {
+    parse(path, &read(path)?)
+}
+
+/// Parse the text of a model read from path.
+fn parse(path: &str, text: &str) -> Result<StateMachineDef, Failure> {
+    read_model(path, text).map_err(|e| match e {
+        StateMachineError::ParseError(e) => {
+            Failure::in_file(1, "parse", path, &e.message).at(e.span.line, e.span.column)
+        }
+        e => Failure::in_file(1, "definition", path, e),
+    })
+}
+
+fn is_json(path: &str, text: &str) -> bool {
+    path.ends_with(".json") || text.trim_start().starts_with('{')
+}
+
+/// A model is in the sm notation or in the JSON written by
+/// `convert --to json`.
+fn read_model(path: &str, text: &str) -> Result<StateMachineDef, StateMachineError> {
+    match is_json(path, text) {
+        true => render::from_json(text),
+        false => dsl::parse(text),
+    }
+}
+
+fn write_output(out: Option<&str>, text: &str) -> Result<(), Failure> {
+    match out {
+        None | Some("-") => {
+            print!("{}", text);
+            Ok(())
+        }
+        Some(path) => std::fs::write(path, text).map_err(|e| Failure::in_file(2, "io", path, e)),
+    }
+}
+
+/// The path of an element from the state machine, the only region of
+/// a state is left out as `find` does not need it.
+fn path(def: &StateMachineDef, dbid: usize) -> String {
+    let mut names = Vec::new();
+    let mut current = dbid;
+    while current != 0 {
+        let parent = match def.parent(current) {
+            Ok(parent) => parent,
+            Err(_) => break,
+        };
+        let only_region = def.regions(parent).is_ok_and(|r| r.len() == 1);
+        if current == dbid || !only_region {
+            names.push(def.name(current).unwrap_or("?"));
+        }
+        current = parent;
+    }
+    if names.is_empty() {
+        return def.name(dbid).unwrap_or("?").to_string();
+    }
+    names.reverse();
+    names.join("::")
+}
+
+//----------------------------------------------------------------
+// validate
+//----------------------------------------------------------------
+
+/// A problem of a model, for people and as a JSON object.
+struct Diagnostic {
+    code: &'static str,
+    message: String,
+    line: Option<(usize, usize)>,
+}
+
+impl Diagnostic {
+    fn json(&self, file: Option<&str>) -> String {
+        let mut text = format!(
+            "{{\"file\": {}, \"severity\": \"error\", \"code\": {}, \"message\": {}",
+            file.map_or(String::from("null"), json_string),
+            json_string(self.code),
+            json_string(&self.message)
+        );
+        if let Some((line, column)) = self.line {
+            text.push_str(&format!(", \"line\": {}, \"column\": {}", line, column));
+        }
+        text.push('}');
+        text
+    }
+}
+
+fn diagnostics(file: &str, text: &str) -> Vec<Diagnostic> {
+    let def = match read_model(file, text) {
+        Ok(def) => def,
+        Err(StateMachineError::ParseError(e)) => {
+            return vec![Diagnostic {
+                code: "parse",
+                message: e.message.clone(),
+                line: Some((e.span.line, e.span.column)),
+            }]
+        }
+        Err(e) => {
+            return vec![Diagnostic {
+                code: "definition",
+                message: e.to_string(),
+                line: None,
+            }]
+        }
+    };
+    def.problems()
+        .into_iter()
+        .map(|problem| match problem {
+            StateMachineError::NoInitialState(region) => Diagnostic {
+                code: "no-initial",
+                message: format!("region {} has no initial pseudostate", path(&def, region)),
+                line: None,
+            },
+            StateMachineError::NoEnabledTransition(vertex) => Diagnostic {
+                code: "no-outgoing",
+                message: format!("{} has no outgoing transition", path(&def, vertex)),
+                line: None,
+            },
+            e => Diagnostic {
+                code: "definition",
+                message: e.to_string(),
+                line: None,
+            },
+        })
+        .collect()
+}
+
+fn validate(args: &[String]) -> CommandResult {
+    let args = Args::parse(args, &[])?;
+    let file = args.model(0)?;
+    let found = diagnostics(file, &read(file)?);
+    if args.json {
+        let items: Vec<String> = found
+            .iter()
+            .map(|d| format!("  {}", d.json(Some(file))))
+            .collect();
+        match items.is_empty() {
+            true => println!("[]"),
+            false => println!("[\n{}\n]", items.join(",\n")),
+        }
+    } else {
+        for d in &found {
+            match d.line {
+                Some((line, column)) => {
+                    eprintln!("{}:{}:{}: error: {}", file, line, column, d.message)
+                }
+                None => eprintln!("{}: error: {}", file, d.message),
+            }
+        }
+        if found.is_empty() {
+            println!("{}: ok", file);
+        }
+    }
+    Ok(if found.is_empty() { 0 } else { 1 })
+}
+
+//----------------------------------------------------------------
+// render and convert
+//----------------------------------------------------------------
+
+/// Pipe PlantUML through `plantuml -tsvg -pipe`.
+fn plantuml_svg(plantuml: &str) -> Result<String, Failure> {
+    let mut child = process::Command::new("plantuml")
+        .args(["-tsvg", "-pipe"])
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            Failure::new(
+                2,
+                "io",
+                format!("plantuml-svg needs plantuml on the PATH: {}", e),
+            )
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(plantuml.as_bytes())
+            .map_err(|e| Failure::new(2, "io", format!("plantuml: {}", e)))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| Failure::new(2, "io", format!("plantuml: {}", e)))?;
+    if !output.status.success() {
+        return Err(Failure::new(
+            2,
+            "io",
+            format!("plantuml failed: {}", output.status),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
 
-#[derive(PartialEq, Clone, Debug)]
-enum Emotion {
-    Happy,
-    Sad,
-    Angry,
+fn render(args: &[String]) -> CommandResult {
+    let args = Args::parse(args, &["--format", "-f", "-o", "--trace"])?;
+    let mut def = load(args.model(0)?)?;
+    let format = args.option("--format").or(args.option("-f"));
+    let text = match format {
+        Some("plantuml") => render::plantuml(&def),
+        Some("mermaid") => render::mermaid(&def),
+        Some("dot") => render::dot(&def),
+        Some("svg") => render::svg(&def),
+        Some("plantuml-svg") => plantuml_svg(&render::plantuml(&def))?,
+        Some("html") => {
+            let trace = match args.option("--trace") {
+                Some(path) => read(path)?
+                    .parse::<Trace>()
+                    .map_err(|e| Failure::in_file(1, "trace", path, e))?,
+                // the initial configuration
+                None => {
+                    def.record();
+                    def.start().map_err(|e| Failure::new(1, "runtime", e))?;
+                    def.take_trace().unwrap_or_default()
+                }
+            };
+            render::html(&def, &trace)
+        }
+        Some(other) => return Err(usage(&format!("unknown format {}", other))),
+        None => return Err(usage("missing --format")),
+    };
+    write_output(args.option("-o"), &text)?;
+    Ok(0)
 }
 
-#[derive(StateMachine)]
-struct Foo {
-    emotion: Emotion,
+fn convert(args: &[String]) -> CommandResult {
+    let args = Args::parse(args, &["--to", "-o"])?;
+    let file = args.model(0)?;
+    let mut model = read(file)?;
+    let def = parse(file, &model)?;
+    // the generated module embeds the model in the sm notation
+    if is_json(file, &model) {
+        model = dsl::write(&def);
+    }
+    let text = match args.option("--to") {
+        Some("sm") => dsl::write(&def),
+        Some("json") => render::json(&def),
+        Some("rust") => {
+            codegen::module(&model).map_err(|e| Failure::in_file(1, "definition", file, e))?
+        }
+        Some(other) => return Err(usage(&format!("unknown format {}", other))),
+        None => return Err(usage("missing --to")),
+    };
+    write_output(args.option("-o"), &text)?;
+    Ok(0)
 }
-impl Foo {
-    fn new() -> Foo {
-        Foo {
-            emotion: Emotion::Happy,
+
+//----------------------------------------------------------------
+// simulate
+//----------------------------------------------------------------
+
+/// Records the behaviors performed, guards are true unless set false
+/// on the command line.
+struct Recorder {
+    false_guards: Vec<String>,
+    behaviors: Vec<String>,
+}
+
+impl Context for Recorder {
+    fn behavior(&mut self, name: &str) {
+        self.behaviors.push(name.to_string());
+    }
+
+    fn guard(&mut self, name: &str) -> bool {
+        !self.false_guards.iter().any(|g| g == name)
+    }
+}
+
+fn simulate(args: &[String]) -> CommandResult {
+    let args = Args::parse(args, &["--guard"])?;
+    let mut def = load(args.model(1)?)?;
+    let events = read(args.positional.get(1).map_or("-", String::as_str))?;
+    let mut ctx = Recorder {
+        false_guards: Vec::new(),
+        behaviors: Vec::new(),
+    };
+    for (name, value) in &args.options {
+        match value.split_once('=') {
+            Some((guard, "false")) => ctx.false_guards.push(guard.to_string()),
+            Some((_, "true")) => (),
+            _ => return Err(usage(&format!("{} {} is not name=true|false", name, value))),
         }
     }
-    pub fn is_happy(&self) -> bool {
-        if self.emotion == Emotion::Happy {
-            println!("Yes! I'm happy");
-            true
+    let report = |def: &StateMachineDef, ctx: &mut Recorder, event: Option<&str>, fired: bool| {
+        let active: Vec<String> = def
+            .active_states()
+            .into_iter()
+            .map(|s| path(def, s))
+            .collect();
+        let behaviors = std::mem::take(&mut ctx.behaviors);
+        if args.json {
+            let quoted = |names: &[String]| {
+                names
+                    .iter()
+                    .map(|n| json_string(n))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            println!(
+                "{{\"event\": {}, \"fired\": {}, \"behaviors\": [{}], \"active\": [{}], \"status\": \"{:?}\"}}",
+                event.map_or(String::from("null"), json_string),
+                fired,
+                quoted(&behaviors),
+                quoted(&active),
+                def.status()
+            );
         } else {
-            println!("No! I'm not happy");
-            false
+            let step = match (event, fired) {
+                (None, _) => String::from("[start]"),
+                (Some(event), true) => event.to_string(),
+                (Some(event), false) => format!("{} (not fired)", event),
+            };
+            match behaviors.is_empty() {
+                true => println!("{}: {}", step, active.join(", ")),
+                false => println!(
+                    "{}: {}  [{}]",
+                    step,
+                    active.join(", "),
+                    behaviors.join(", ")
+                ),
+            }
+        }
+    };
+    def.start_with(&mut ctx)
+        .map_err(|e| Failure::new(1, "runtime", e))?;
+    report(&def, &mut ctx, None, true);
+    for (n, line) in events.lines().enumerate() {
+        let event = line.split('#').next().unwrap_or("").trim();
+        if event.is_empty() {
+            continue;
         }
+        let dbid = def.event_type(event).ok_or_else(|| {
+            Failure::new(1, "unknown-event", format!("unknown event {}", event)).at(n + 1, 1)
+        })?;
+        let fired = def
+            .dispatch_with(dbid, &mut ctx)
+            .map_err(|e| Failure::new(1, "runtime", e).at(n + 1, 1))?;
+        report(&def, &mut ctx, Some(event), fired);
+    }
+    Ok(0)
+}
+
+//----------------------------------------------------------------
+// stats
+//----------------------------------------------------------------
 
+fn stats(args: &[String]) -> CommandResult {
+    let args = Args::parse(args, &[])?;
+    let def = load(args.model(0)?)?;
+    let depth = def
+        .iter_vertices()
+        .map(|v| def.fullname(v).map_or(0, |f| f.split("::").count() / 2))
+        .max()
+        .unwrap_or(0);
+    let counts = [
+        ("states", def.iter_states().count()),
+        (
+            "pseudostates",
+            def.iter_vertices().count() - def.iter_states().count(),
+        ),
+        ("regions", def.iter_regions().count()),
+        ("transitions", def.iter_transitions().count()),
+        ("events", def.iter_event_types().count()),
+        ("depth", depth),
+    ];
+    if args.json {
+        let fields: Vec<String> = counts
+            .iter()
+            .map(|(k, v)| format!("\"{}\": {}", k, v))
+            .collect();
+        println!(
+            "{{\"name\": {}, {}}}",
+            json_string(&path(&def, 0)),
+            fields.join(", ")
+        );
+    } else {
+        println!("{}", path(&def, 0));
+        for (k, v) in counts {
+            println!("    {:<12} {}", k, v);
+        }
     }
+    Ok(0)
+}
+
+//----------------------------------------------------------------
+// repl
+//----------------------------------------------------------------
+
+fn repl(args: &[String]) -> CommandResult {
+    let args = Args::parse(args, &[])?;
+    let file = args.model(0)?;
+    let mut repl =
+        Repl::new(&read(file)?).map_err(|e| Failure::in_file(1, "definition", file, e))?;
+    println!("{}, help for the commands", file);
+    repl.run(std::io::stdin().lock(), std::io::stdout().lock())
+        .map_err(|e| Failure::new(2, "io", e))?;
+    Ok(0)
 }
 
+//----------------------------------------------------------------
+// diff
+//----------------------------------------------------------------
+
+/// `sm diff <old.sm> <new.sm>` prints the changes from the first
+/// definition to the second. Like diff(1) it exits with 1 if there are
+/// changes and with 2 on errors.
+fn diff(args: &[String]) -> CommandResult {
+    let (old, new) = match args {
+        [old, new] => (old, new),
+        _ => return Err(usage("sm diff takes two models")),
+    };
+    // a model which does not parse is an error here, not a change
+    let read = |path: &str| load(path).map_err(|f| Failure { exit: 2, ..f });
+    let diff = rust_uml_sm::diff::diff(&read(old)?, &read(new)?);
+    print!("{}", diff);
+    Ok(if diff.is_empty() { 0 } else { 1 })
+}
 
 fn main() {
-    let mut sm = StateMachineDef::new("sm1");
-    println!("Created {:#?}", sm);
-    let s1 = sm.add_state("s1").expect("Failed to add state");
-    println!("Added S1 {:#?}", sm);
-    let _s2 = sm.add_substate("s2", s1).expect("Failed to add state");
-    println!("Added S2 {:#?}", sm);
-
-    let foo = Foo::new_statemachine();
-    foo.state.is_happy();
-
-    /*
-    let mut s = StateMachine::new("sm1");
-    static S1: &State = &State::new("s1");
-    static S2: &State = &State::new("s2");
-    if let Err(e) = s.add_vertex(S1) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
-    }
-    if let Err(e) = s.add_vertex(S2) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
-    }
-    println!("{}", &s);
-    */
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.first().map(String::as_str);
+    let rest = args.get(1..).unwrap_or(&[]);
+    let json = rest.iter().any(|arg| arg == "--json");
+    let result = match command {
+        Some("validate") => validate(rest),
+        Some("render") => render(rest),
+        Some("simulate") => simulate(rest),
+        Some("stats") => stats(rest),
+        Some("convert") => convert(rest),
+        Some("repl") => repl(rest),
+        Some("diff") => diff(rest),
+        Some("help" | "--help" | "-h") => {
+            print!("{}", USAGE);
+            Ok(0)
+        }
+        Some(other) => Err(usage(&format!("unknown command {}", other))),
+        None => Err(usage("missing command")),
+    };
+    match result {
+        Ok(code) => process::exit(code),
+        Err(failure) => {
+            match json {
+                true => eprintln!("{}", failure.diagnostic.json(failure.file.as_deref())),
+                false => eprintln!("sm: {}", failure.text()),
+            }
+            process::exit(failure.exit);
+        }
+    }
 }
@@ -0,0 +1,496 @@
+//! A fluent layer over the definition API, which names elements by
+//! path instead of dbid.
+//!
+//! ```text
+//! let mut sm = Builder::new("door");
+//! sm.initial("closed");
+//! sm.state("closed").defer("push");
+//! sm.state("opened").entry("light_on").initial("ajar");
+//! sm.state("opened::ajar");
+//! sm.state("opened").region("region_1").state("wide");
+//! sm.transition("closed", "opened").on("open").guard("unlocked");
+//! sm.transition("opened::ajar", "opened::wide").on("push");
+//! let door = sm.build()?;
+//! ```
+//!
+//! A path is a list of names separated by `::` looked up from the state
+//! machine, or from the state or region it is given to. A state is
+//! found among the vertices of its regions, so region names may be left
+//! out: `s2::s4` and `s2::r3::s4` name the same state when `s2` has the
+//! single region `r3`. `state` and `region` look up the element at the
+//! path and create what is missing, the states on the way included.
+//!
+//! Transitions are declared by the paths of their source and target and
+//! added by `build`, once all the states are known, in the order they
+//! were declared. Unnamed transitions are named `t1`, `t2`... as in the
+//! textual definitions. Errors are kept until `build`, which returns the
+//! first one, or the definition once it passed `Db::validate`.
+
+use crate::*;
+
+/// Builds a definition, see the module documentation.
+pub struct Builder {
+    db: StateMachineDef,
+    transitions: Vec<PendingTransition>,
+    error: Option<StateMachineError>,
+}
+
+/// A transition added by `build`.
+struct PendingTransition {
+    /// The paths are looked up from there.
+    scope: DbId,
+    source: Name,
+    target: Name,
+    name: Option<Name>,
+    trigger: Option<Name>,
+    guard: OptGuard,
+    effect: OptBehavior,
+    kind: TransitionKind,
+    initial: bool,
+}
+
+/// A vertex being built, or nothing if it could not be created.
+pub struct StateBuilder<'b> {
+    builder: &'b mut Builder,
+    dbid: Option<DbId>,
+}
+
+/// A region being built, or nothing if it could not be created.
+pub struct RegionBuilder<'b> {
+    builder: &'b mut Builder,
+    dbid: Option<DbId>,
+}
+
+/// A transition being declared.
+pub struct TransitionBuilder<'b> {
+    builder: &'b mut Builder,
+    idx: usize,
+}
+
+impl Builder {
+    pub fn new(name: Name) -> Builder {
+        Builder {
+            db: StateMachineDef::new(name),
+            transitions: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Keep the first error, further calls do nothing once there is
+    /// one.
+    fn check<T>(&mut self, result: StateMachineResult<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.error.get_or_insert(err);
+                None
+            }
+        }
+    }
+
+    /// The region vertices are added to in a region, a state or the
+    /// state machine. A state without a region is given one.
+    fn region_of(&mut self, scope: DbId) -> StateMachineResult<RegionDbId> {
+        match self.db.element(scope)?.element_type {
+            ElementType::Region => Ok(scope),
+            ElementType::Vertex(VertexType::State) => match self.db.regions(scope)?.len() {
+                0 => self.db.add_region("region_1", scope),
+                _ => self.db.get_only_region(scope),
+            },
+            ElementType::StateMachine => self.db.get_only_region(scope),
+            _ => Err(StateMachineError::CannotAddState(scope)),
+        }
+    }
+
+    /// Look up the vertex at a path from scope, creating what is
+    /// missing: states on the way, and a vertex of the given type at
+    /// the end.
+    fn vertex_at(
+        &mut self,
+        scope: Option<DbId>,
+        path: Name,
+        vertex_type: VertexType,
+    ) -> Option<DbId> {
+        let scope = scope.filter(|_| self.error.is_none())?;
+        let result = self.try_vertex_at(scope, path, vertex_type);
+        self.check(result)
+    }
+
+    fn try_vertex_at(
+        &mut self,
+        scope: DbId,
+        path: Name,
+        vertex_type: VertexType,
+    ) -> StateMachineResult<DbId> {
+        let mut names = path.split("::").peekable();
+        let mut current = scope;
+        while let Some(name) = names.next() {
+            let last = names.peek().is_none();
+            current = match self.db.child(current, name) {
+                Some(dbid) if last => match self.db.elements[dbid].element_type {
+                    ElementType::Vertex(t) if t == vertex_type => dbid,
+                    _ => return Err(StateMachineError::Duplicate(name)),
+                },
+                Some(dbid) => dbid,
+                None => {
+                    let region = self.region_of(current)?;
+                    let t = if last { vertex_type } else { VertexType::State };
+                    self.db.add_vertex(name, region, t)?
+                }
+            };
+        }
+        Ok(current)
+    }
+
+    /// Look up the region of a state or of the state machine by name,
+    /// or add it.
+    fn region_at(&mut self, owner: Option<DbId>, name: Name) -> Option<DbId> {
+        let owner = owner.filter(|_| self.error.is_none())?;
+        let result = self.db.regions(owner).and_then(|regions| {
+            match regions.into_iter().find(|r| self.db.names[*r] == name) {
+                Some(dbid) => Ok(dbid),
+                None if owner == 0 => self.db.add_sm_region(name),
+                None => self.db.add_region(name, owner),
+            }
+        });
+        self.check(result)
+    }
+
+    /// Add the initial pseudostate of the region of scope and declare
+    /// its transition to target, looked up from the region.
+    fn initial_at(&mut self, scope: Option<DbId>, target: Name) {
+        let Some(scope) = scope.filter(|_| self.error.is_none()) else {
+            return;
+        };
+        let result = self.region_of(scope).and_then(|region| {
+            if let Ok(Some(_)) = self.db.initial_state(region) {
+                return Err(StateMachineError::Duplicate("initial"));
+            }
+            self.db
+                .add_vertex("initial", region, VertexType::InitialState)?;
+            Ok(region)
+        });
+        if let Some(region) = self.check(result) {
+            self.declare(region, "initial", target, true);
+        }
+    }
+
+    fn declare(&mut self, scope: DbId, source: Name, target: Name, initial: bool) -> usize {
+        self.transitions.push(PendingTransition {
+            scope,
+            source,
+            target,
+            name: None,
+            trigger: None,
+            guard: OptGuard::None,
+            effect: OptBehavior::None,
+            kind: TransitionKind::External,
+            initial,
+        });
+        self.transitions.len() - 1
+    }
+
+    fn event_type(&mut self, name: Name) -> StateMachineResult<DbId> {
+        match self.db.event_type(name) {
+            Some(dbid) => Ok(dbid),
+            None => self.db.add_event_type(name),
+        }
+    }
+
+    /// Declare an event type, events which trigger transitions or are
+    /// deferred are declared as well.
+    pub fn event(&mut self, name: Name) -> &mut Builder {
+        if self.error.is_none() {
+            let result = self.event_type(name);
+            self.check(result);
+        }
+        self
+    }
+
+    /// The region of the state machine with the given name. The first
+    /// region added renames the default one.
+    pub fn region(&mut self, name: Name) -> RegionBuilder<'_> {
+        let dbid = self.region_at(Some(0), name);
+        RegionBuilder {
+            builder: self,
+            dbid,
+        }
+    }
+
+    /// The state at the path, see the module documentation.
+    pub fn state(&mut self, path: Name) -> StateBuilder<'_> {
+        self.vertex(path, VertexType::State)
+    }
+
+    /// The vertex of the given type at the path, a pseudostate or a
+    /// final state.
+    pub fn vertex(&mut self, path: Name, vertex_type: VertexType) -> StateBuilder<'_> {
+        let dbid = self.vertex_at(Some(0), path, vertex_type);
+        StateBuilder {
+            builder: self,
+            dbid,
+        }
+    }
+
+    /// Declare the initial transition of the state machine, which must
+    /// have a single region.
+    pub fn initial(&mut self, target: Name) -> &mut Builder {
+        self.initial_at(Some(0), target);
+        self
+    }
+
+    /// Declare a transition between the vertices at the paths.
+    pub fn transition(&mut self, source: Name, target: Name) -> TransitionBuilder<'_> {
+        let idx = self.declare(0, source, target, false);
+        TransitionBuilder { builder: self, idx }
+    }
+
+    /// The element at a path from the state machine, or with the given
+    /// fullname.
+    pub fn find(&self, path: &str) -> Option<DbId> {
+        self.db.find(path)
+    }
+
+    /// Add the transitions declared and validate the definition.
+    pub fn build(mut self) -> StateMachineResult<StateMachineDef> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        for pending in core::mem::take(&mut self.transitions) {
+            self.add_transition(&pending)?;
+        }
+        self.db.validate()?;
+        Ok(self.db)
+    }
+
+    fn add_transition(&mut self, pending: &PendingTransition) -> StateMachineResult<DbId> {
+        let vertex = |path: Name| {
+            self.db
+                .lookup(pending.scope, path)
+                .filter(|d| matches!(self.db.elements[*d].element_type, ElementType::Vertex(_)))
+                .ok_or_else(|| StateMachineError::UnknownPath(path.to_string()))
+        };
+        let source = vertex(pending.source)?;
+        let target = vertex(pending.target)?;
+        let trigger = match pending.trigger {
+            Some(event) => Some(self.event_type(event)?),
+            None => None,
+        };
+        let name = match (pending.name, pending.initial) {
+            (Some(name), _) => name,
+            (None, true) => "initial",
+            (None, false) => self
+                .db
+                .intern(&format!("t{}", self.db.transitions.len() + 1)),
+        };
+        let dbid =
+            self.db
+                .add_transition(name, trigger, source, target, pending.effect, pending.guard)?;
+        if pending.kind != TransitionKind::External {
+            self.db.set_transition_kind(dbid, pending.kind)?;
+        }
+        Ok(dbid)
+    }
+}
+
+impl<'b> StateBuilder<'b> {
+    /// The dbid of the vertex, none after an error.
+    pub fn dbid(&self) -> Option<DbId> {
+        self.dbid
+    }
+
+    /// The state at the path from this one.
+    pub fn state(self, path: Name) -> StateBuilder<'b> {
+        self.vertex(path, VertexType::State)
+    }
+
+    /// The vertex of the given type at the path from this state.
+    pub fn vertex(self, path: Name, vertex_type: VertexType) -> StateBuilder<'b> {
+        let dbid = self.builder.vertex_at(self.dbid, path, vertex_type);
+        StateBuilder {
+            builder: self.builder,
+            dbid,
+        }
+    }
+
+    /// The region of this state with the given name.
+    pub fn region(self, name: Name) -> RegionBuilder<'b> {
+        let dbid = self.builder.region_at(self.dbid, name);
+        RegionBuilder {
+            builder: self.builder,
+            dbid,
+        }
+    }
+
+    /// Declare the initial transition of the single region of this
+    /// state, target is looked up from the region.
+    pub fn initial(self, target: Name) -> StateBuilder<'b> {
+        self.builder.initial_at(self.dbid, target);
+        self
+    }
+
+    fn set(self, f: impl FnOnce(&mut Builder, DbId) -> StateMachineResult<()>) -> Self {
+        if let Some(dbid) = self.dbid.filter(|_| self.builder.error.is_none()) {
+            let result = f(self.builder, dbid);
+            self.builder.check(result);
+        }
+        self
+    }
+
+    pub fn entry(self, behavior: impl Into<Behavior>) -> StateBuilder<'b> {
+        let behavior = behavior.into();
+        self.set(|b, dbid| b.db.set_entry(dbid, behavior))
+    }
+
+    pub fn exit(self, behavior: impl Into<Behavior>) -> StateBuilder<'b> {
+        let behavior = behavior.into();
+        self.set(|b, dbid| b.db.set_exit(dbid, behavior))
+    }
+
+    /// Set the doActivity behavior of the state.
+    pub fn do_activity(self, behavior: impl Into<Behavior>) -> StateBuilder<'b> {
+        let behavior = behavior.into();
+        self.set(|b, dbid| b.db.set_do(dbid, behavior))
+    }
+
+    /// Defer the event while the state is active.
+    pub fn defer(self, event: Name) -> StateBuilder<'b> {
+        self.set(|b, dbid| {
+            let event = b.event_type(event)?;
+            b.db.add_deferrable_event(dbid, event)
+        })
+    }
+}
+
+impl<'b> RegionBuilder<'b> {
+    /// The dbid of the region, none after an error.
+    pub fn dbid(&self) -> Option<DbId> {
+        self.dbid
+    }
+
+    /// The state at the path from this region.
+    pub fn state(self, path: Name) -> StateBuilder<'b> {
+        self.vertex(path, VertexType::State)
+    }
+
+    /// The vertex of the given type at the path from this region.
+    pub fn vertex(self, path: Name, vertex_type: VertexType) -> StateBuilder<'b> {
+        let dbid = self.builder.vertex_at(self.dbid, path, vertex_type);
+        StateBuilder {
+            builder: self.builder,
+            dbid,
+        }
+    }
+
+    /// Declare the initial transition of the region, target is looked
+    /// up from the region.
+    pub fn initial(self, target: Name) -> RegionBuilder<'b> {
+        self.builder.initial_at(self.dbid, target);
+        self
+    }
+}
+
+impl TransitionBuilder<'_> {
+    fn pending(&mut self) -> &mut PendingTransition {
+        &mut self.builder.transitions[self.idx]
+    }
+
+    /// The event triggering the transition.
+    pub fn on(mut self, event: Name) -> Self {
+        self.pending().trigger = Some(event);
+        self
+    }
+
+    pub fn guard(mut self, guard: impl Into<Guard>) -> Self {
+        self.pending().guard = OptGuard::Guard(guard.into());
+        self
+    }
+
+    pub fn effect(mut self, effect: impl Into<Behavior>) -> Self {
+        self.pending().effect = OptBehavior::Behavior(effect.into());
+        self
+    }
+
+    /// Name the transition instead of numbering it.
+    pub fn named(mut self, name: Name) -> Self {
+        self.pending().name = Some(name);
+        self
+    }
+
+    /// Transitions are external unless set otherwise.
+    pub fn kind(mut self, kind: TransitionKind) -> Self {
+        self.pending().kind = kind;
+        self
+    }
+}
+
+impl Db {
+    /// A child of a region is one of its vertices. A child of a state
+    /// or of the state machine is one of its regions, or a vertex in one
+    /// of its regions.
+    pub(crate) fn child(&self, parent: DbId, name: &str) -> Option<DbId> {
+        match self.elements[parent].element_type {
+            ElementType::Region => self
+                .subvertices(parent)
+                .ok()?
+                .into_iter()
+                .find(|v| self.names[*v] == name),
+            ElementType::Vertex(VertexType::State) | ElementType::StateMachine => {
+                let regions = self.regions(parent).ok()?;
+                regions
+                    .iter()
+                    .copied()
+                    .find(|r| self.names[*r] == name)
+                    .or_else(|| regions.iter().find_map(|r| self.child(*r, name)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The element at a path of names from scope, or with the path as
+    /// fullname.
+    pub(crate) fn lookup(&self, scope: DbId, path: &str) -> Option<DbId> {
+        path.split("::")
+            .try_fold(scope, |current, name| self.child(current, name))
+            .or_else(|| self.dbid_named(path))
+    }
+
+    /// Check the definition may be run, see `problems`, and return the
+    /// first problem found.
+    pub fn validate(&self) -> StateMachineResult<()> {
+        match self.problems().into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// What keeps the definition from being run: a region of the state
+    /// machine with vertices but no initial pseudostate, and initial
+    /// pseudostates, choices, junctions, forks and joins without an
+    /// outgoing transition.
+    pub fn problems(&self) -> Vec<StateMachineError> {
+        let mut problems = Vec::new();
+        for r in &self.state_machine.regions {
+            let region = &self.regions[self.elements[*r].idx];
+            if !region.subvertex.is_empty() && region.initial_state(self).is_err() {
+                problems.push(StateMachineError::NoInitialState(*r));
+            }
+        }
+        for v in &self.vertices {
+            let passed_through = matches!(
+                v.vertex_type,
+                VertexType::InitialState
+                    | VertexType::Pseudostate(
+                        PseudostateKind::Choice
+                            | PseudostateKind::Junction
+                            | PseudostateKind::Fork
+                            | PseudostateKind::Join
+                    )
+            );
+            if passed_through && v.outgoing.is_empty() {
+                problems.push(StateMachineError::NoEnabledTransition(v.dbid));
+            }
+        }
+        problems
+    }
+}
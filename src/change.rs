@@ -0,0 +1,124 @@
+//! Change events, triggered by a condition becoming true.
+//!
+//! A transition triggered by `when(buffer_full)` fires once the named
+//! condition goes from false to true, without an event being sent:
+//!
+//! ```text
+//! state filling {
+//!     on push / store;
+//!     on when(buffer_full) -> flushing;
+//! }
+//! ```
+//!
+//! The conditions are evaluated by the [`Context`], see
+//! [`Context::condition`], once the state machine is started and after
+//! every event processed to completion. They are false before the start,
+//! and each change from false to true dispatches the change event once,
+//! to completion, like any other event: it fires the transitions it
+//! triggers from the active states and is discarded otherwise. When the
+//! context changes on its own, [`Db::notify_changed`] evaluates them
+//! again.
+//!
+//! Change events belong to the state machine and are named after their
+//! condition, `sm::when(buffer_full)`. They are not looked up by name
+//! with `event_type`. The dispatches they cause are recorded in a trace
+//! like the others, a replay evaluates the conditions without
+//! dispatching the change events, the trace has them. The
+//! `compiled` and `frozen` engines have no change events, `freeze` and
+//! `codegen::static_module` reject definitions with them.
+
+use crate::runtime::MAX_COMPLETIONS;
+use crate::*;
+
+impl Db {
+    /// Register a change event triggered when a condition becomes true.
+    /// The event is shared by the transitions with the same condition.
+    pub fn add_change_event(&mut self, condition: Name) -> StateMachineResult<DbId> {
+        let existing = self
+            .event_types
+            .iter()
+            .find(|ev| ev.condition == Some(condition));
+        if let Some(ev) = existing {
+            return Ok(ev.dbid);
+        }
+        let e_idx = self.event_types.len();
+        let name = self.intern(&format!("when({})", condition));
+        let dbid = self.new_element(name, 0, e_idx, ElementType::EventType);
+        self.event_types.push(EventType {
+            condition: Some(condition),
+            ..EventType::new(name, dbid)
+        });
+        Ok(dbid)
+    }
+
+    /// The condition of an event type, None if it is not a change event.
+    pub fn change_event(&self, event: DbId) -> StateMachineResult<Option<Name>> {
+        match self.element(event)?.element_type {
+            ElementType::EventType => Ok(self.event_types[self.elements[event].idx].condition),
+            _ => Err(StateMachineError::InvalidEventType(event)),
+        }
+    }
+
+    /// Evaluate the conditions again, the context changed. Return how
+    /// many change events were dispatched.
+    pub fn notify_changed(&mut self) -> StateMachineResult<usize> {
+        self.notify_changed_with(&mut ())
+    }
+
+    /// Evaluate the conditions again with ctx, which also resolves the
+    /// named behaviors and guards.
+    pub fn notify_changed_with(&mut self, ctx: &mut dyn Context) -> StateMachineResult<usize> {
+        if self.status != Status::Running {
+            return Err(StateMachineError::NotRunning);
+        }
+        self.changed(ctx)
+    }
+
+    /// Dispatch the change events whose condition became true, until
+    /// the conditions settle. While replaying the trace has the change
+    /// events, the conditions are only brought up to date.
+    pub(crate) fn changed(&mut self, ctx: &mut dyn Context) -> StateMachineResult<usize> {
+        let changes: Vec<(DbId, Name)> = self
+            .event_types
+            .iter()
+            .filter_map(|ev| Some((ev.dbid, ev.condition?)))
+            .collect();
+        if self.replaying() {
+            self.conditions = changes
+                .into_iter()
+                .filter(|(_, condition)| ctx.condition(condition))
+                .map(|(event, _)| event)
+                .collect();
+            return Ok(0);
+        }
+        let mut fired = 0;
+        while self.status == Status::Running {
+            let mut rising = Vec::new();
+            for (event, condition) in &changes {
+                let was = self.conditions.contains(event);
+                match (was, ctx.condition(condition)) {
+                    (false, true) => {
+                        self.conditions.push(*event);
+                        rising.push(*event);
+                    }
+                    (true, false) => self.conditions.retain(|e| e != event),
+                    _ => (),
+                }
+            }
+            if rising.is_empty() {
+                break;
+            }
+            for event in rising {
+                if self.status != Status::Running {
+                    break;
+                }
+                fired += 1;
+                if fired > MAX_COMPLETIONS {
+                    return Err(StateMachineError::CompletionLoop(event));
+                }
+                self.dispatch_event(event, ctx)?;
+            }
+        }
+        Ok(fired)
+    }
+}
@@ -0,0 +1,789 @@
+//! Generation of Rust source from a definition, meant for build scripts.
+//!
+//! ```text
+//! // build.rs
+//! fn main() {
+//!     rust_uml_sm::codegen::generate("door.sm").unwrap();
+//! }
+//!
+//! // src/main.rs
+//! include!(concat!(env!("OUT_DIR"), "/door.rs"));
+//! ```
+//!
+//! The model file is read with `dsl::parse` and becomes a module named
+//! after the state machine, holding the model text, a `definition()`
+//! function and typed `State` and `Event` declarations:
+//!
+//! * a region becomes an enum with a variant for each of its states and
+//!   final states
+//! * the variant of a composite state wraps the enum of its region, the
+//!   variant of an orthogonal state wraps a struct with a field for each
+//!   of its regions
+//! * `State` is the enum of the only region of the state machine, or a
+//!   struct if it has several
+//!
+//! These convert to and from the dbids of the definition returned by
+//! `definition()`: `State::from_dbids(&def.active_states())` and
+//! `state.leaves()` for the innermost states, `dbid()` for the state of
+//! a variant, `Event::dbid` and `Event::from_dbid` for event types.
+//! Time and change events, see `timer` and `change`, have no variant.
+//!
+//! Names are used as identifiers as they are, or camel cased for types
+//! and variants. Keywords are escaped as raw identifiers, names which
+//! cannot be identifiers at all, like `1st` or `self` (or `Self` once
+//! camel cased), fail with `InvalidIdentifier`.
+//!
+//! `generate_static` instead generates a module for `compiled`: the
+//! definition as constant tables, `match`es selecting the transitions
+//! an event fires from the active vertices, the events they defer and
+//! the regions transitions exit and enter, the `Event` enum and a
+//! `machine()` constructor. It needs neither the definition nor the
+//! heap at runtime. The model may be in the notation of `dsl` or the
+//! JSON of `render::json`.
+//!
+//! ```text
+//! // build.rs
+//! rust_uml_sm::codegen::generate_static("door.sm", 8).unwrap();
+//!
+//! // src/main.rs
+//! include!(concat!(env!("OUT_DIR"), "/door_static.rs"));
+//!
+//! static mut DOOR: door::Machine = door::machine();
+//! ```
+
+use crate::{
+    DbId, ElementType, OptBehavior, OptGuard, RegionDbId, StateMachineDef, StateMachineError,
+    StateMachineResult, VertexType,
+};
+use rust_uml_sm_dsl::camel;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// The keywords which may be used as raw identifiers.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// `name` as an identifier, `r#match` for a keyword.
+fn ident(name: &str) -> StateMachineResult<String> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(c) => {
+            (c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
+        }
+        None => false,
+    };
+    if !valid || ["_", "self", "Self", "super", "crate"].contains(&name) {
+        return Err(StateMachineError::InvalidIdentifier(name.to_string()));
+    }
+    match KEYWORDS.contains(&name) {
+        true => Ok(format!("r#{}", name)),
+        false => Ok(name.to_string()),
+    }
+}
+
+/// Check the names the generated types are made of.
+fn check_names(def: &StateMachineDef) -> StateMachineResult<()> {
+    ident(def.name)?;
+    for r in &def.regions {
+        ident(r.name)?;
+        ident(&camel(r.name))
+            .map_err(|_| StateMachineError::InvalidIdentifier(r.name.to_string()))?;
+    }
+    let states = def
+        .vertices
+        .iter()
+        .filter(|v| matches!(v.vertex_type, VertexType::State | VertexType::FinalState));
+    let events = def.event_types.iter().filter(|e| e.is_signal());
+    for name in states.map(|v| v.name).chain(events.map(|e| e.name)) {
+        ident(&camel(name)).map_err(|_| StateMachineError::InvalidIdentifier(name.to_string()))?;
+    }
+    Ok(())
+}
+
+const DERIVE: &str = "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]";
+
+struct Gen<'d> {
+    def: &'d StateMachineDef,
+    out: String,
+}
+
+impl<'d> Gen<'d> {
+    /// The states and final states of a region
+    fn states(&self, region: RegionDbId) -> Vec<DbId> {
+        self.def.regions[self.def.elements[region].idx]
+            .subvertex
+            .iter()
+            .copied()
+            .filter(|v| {
+                matches!(
+                    self.def.elements[*v].element_type,
+                    ElementType::Vertex(VertexType::State)
+                        | ElementType::Vertex(VertexType::FinalState)
+                )
+            })
+            .collect()
+    }
+
+    /// The regions of a state which contain states
+    fn regions(&self, state: DbId) -> Vec<RegionDbId> {
+        match self.def.elements[state].element_type {
+            ElementType::Vertex(VertexType::State) => self
+                .def
+                ._state(state)
+                .regions
+                .iter()
+                .copied()
+                .filter(|r| !self.states(*r).is_empty())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The type of a composite state, prefix is the type name of the
+    /// enclosing region
+    fn state_type(&self, prefix: &str, state: DbId) -> String {
+        format!("{}{}", prefix, camel(self.def.names[state]))
+    }
+
+    /// The prefix of the types of the states within a region: the type
+    /// of its state, followed by the region name if it has siblings.
+    fn region_prefix(&self, owner_type: &str, owner_regions: usize, region: RegionDbId) -> String {
+        if owner_regions == 1 {
+            owner_type.to_string()
+        } else {
+            format!("{}{}", owner_type, camel(self.def.names[region]))
+        }
+    }
+
+    /// Declare the type name for a state or the state machine, with the
+    /// given regions
+    fn owner(&mut self, name: &str, regions: &[RegionDbId], prefix: &str) {
+        if regions.len() == 1 {
+            self.region(name, regions[0], prefix);
+            return;
+        }
+        let fields: Vec<(String, String)> = regions
+            .iter()
+            .map(|r| {
+                (
+                    ident(self.def.names[*r]).expect("checked by check_names"),
+                    self.region_prefix(prefix, regions.len(), *r),
+                )
+            })
+            .collect();
+        writeln!(self.out, "{}\npub struct {} {{", DERIVE, name).unwrap();
+        for (field, ty) in &fields {
+            writeln!(self.out, "    pub {}: {},", field, ty).unwrap();
+        }
+        writeln!(self.out, "}}\n\nimpl {} {{", name).unwrap();
+        writeln!(self.out, "    /// The dbids of the innermost states").unwrap();
+        writeln!(self.out, "    pub fn leaves(&self) -> Vec<usize> {{").unwrap();
+        writeln!(self.out, "        let mut leaves = Vec::new();").unwrap();
+        for (field, _) in &fields {
+            writeln!(self.out, "        leaves.extend(self.{}.leaves());", field).unwrap();
+        }
+        writeln!(self.out, "        leaves\n    }}\n").unwrap();
+        writeln!(
+            self.out,
+            "    pub fn from_dbids(active: &[usize]) -> Option<{}> {{",
+            name
+        )
+        .unwrap();
+        writeln!(self.out, "        Some({} {{", name).unwrap();
+        for (field, ty) in &fields {
+            writeln!(
+                self.out,
+                "            {}: {}::from_dbids(active)?,",
+                field, ty
+            )
+            .unwrap();
+        }
+        writeln!(self.out, "        }})\n    }}\n}}\n").unwrap();
+        for r in regions {
+            let ty = self.region_prefix(prefix, regions.len(), *r);
+            self.region(&ty, *r, &ty);
+        }
+    }
+
+    /// Declare the enum of a region and the types of its composite states
+    fn region(&mut self, name: &str, region: RegionDbId, prefix: &str) {
+        let states = self.states(region);
+        let composite: Vec<(DbId, String)> = states
+            .iter()
+            .filter(|s| !self.regions(**s).is_empty())
+            .map(|s| (*s, self.state_type(prefix, *s)))
+            .collect();
+        let inner = |s: DbId| composite.iter().find(|(c, _)| *c == s).map(|(_, t)| t);
+
+        writeln!(self.out, "{}\npub enum {} {{", DERIVE, name).unwrap();
+        for s in &states {
+            let variant = camel(self.def.names[*s]);
+            match inner(*s) {
+                Some(ty) => writeln!(self.out, "    {}({}),", variant, ty).unwrap(),
+                None => writeln!(self.out, "    {},", variant).unwrap(),
+            }
+        }
+        writeln!(self.out, "}}\n\nimpl {} {{", name).unwrap();
+
+        writeln!(self.out, "    /// The dbid of the state").unwrap();
+        writeln!(
+            self.out,
+            "    pub fn dbid(&self) -> usize {{\n        match self {{"
+        )
+        .unwrap();
+        for s in &states {
+            let variant = camel(self.def.names[*s]);
+            let pattern = if inner(*s).is_some() { "(_)" } else { "" };
+            writeln!(
+                self.out,
+                "            {}::{}{} => {},",
+                name, variant, pattern, s
+            )
+            .unwrap();
+        }
+        writeln!(self.out, "        }}\n    }}\n").unwrap();
+
+        writeln!(self.out, "    /// The dbids of the innermost states").unwrap();
+        writeln!(
+            self.out,
+            "    pub fn leaves(&self) -> Vec<usize> {{\n        match self {{"
+        )
+        .unwrap();
+        for s in &states {
+            let variant = camel(self.def.names[*s]);
+            match inner(*s) {
+                Some(_) => writeln!(
+                    self.out,
+                    "            {}::{}(s) => s.leaves(),",
+                    name, variant
+                ),
+                None => writeln!(
+                    self.out,
+                    "            {}::{} => vec![{}],",
+                    name, variant, s
+                ),
+            }
+            .unwrap();
+        }
+        writeln!(self.out, "        }}\n    }}\n").unwrap();
+
+        writeln!(
+            self.out,
+            "    pub fn from_dbids(active: &[usize]) -> Option<{}> {{",
+            name
+        )
+        .unwrap();
+        for s in &states {
+            let variant = camel(self.def.names[*s]);
+            match inner(*s) {
+                Some(ty) => writeln!(
+                    self.out,
+                    "        if let Some(s) = {}::from_dbids(active) {{\n            return Some({}::{}(s));\n        }}",
+                    ty, name, variant
+                ),
+                None => writeln!(
+                    self.out,
+                    "        if active.contains(&{}) {{\n            return Some({}::{});\n        }}",
+                    s, name, variant
+                ),
+            }
+            .unwrap();
+        }
+        writeln!(self.out, "        None\n    }}\n}}\n").unwrap();
+
+        for (s, ty) in &composite {
+            let regions = self.regions(*s);
+            self.owner(ty, &regions, ty);
+        }
+    }
+
+    fn events(&mut self) {
+        let events: Vec<(String, &str, DbId)> = self
+            .def
+            .event_types
+            .iter()
+            .filter(|e| e.is_signal())
+            .map(|e| (camel(e.name), e.name, e.dbid))
+            .collect();
+        writeln!(self.out, "/// The event types of the state machine.").unwrap();
+        writeln!(self.out, "{}\npub enum Event {{", DERIVE).unwrap();
+        for (variant, _, _) in &events {
+            writeln!(self.out, "    {},", variant).unwrap();
+        }
+        writeln!(self.out, "}}\n\nimpl Event {{").unwrap();
+        let all: Vec<String> = events
+            .iter()
+            .map(|(v, _, _)| format!("Event::{}", v))
+            .collect();
+        writeln!(
+            self.out,
+            "    pub const ALL: [Event; {}] = [{}];\n",
+            events.len(),
+            all.join(", ")
+        )
+        .unwrap();
+        writeln!(
+            self.out,
+            "    pub fn name(self) -> &'static str {{\n        match self {{"
+        )
+        .unwrap();
+        for (variant, name, _) in &events {
+            writeln!(self.out, "            Event::{} => {:?},", variant, name).unwrap();
+        }
+        writeln!(self.out, "        }}\n    }}\n").unwrap();
+        writeln!(self.out, "    /// The dbid of the event type").unwrap();
+        writeln!(
+            self.out,
+            "    pub fn dbid(self) -> usize {{\n        match self {{"
+        )
+        .unwrap();
+        for (variant, _, dbid) in &events {
+            writeln!(self.out, "            Event::{} => {},", variant, dbid).unwrap();
+        }
+        writeln!(self.out, "        }}\n    }}\n").unwrap();
+        writeln!(
+            self.out,
+            "    pub fn from_dbid(dbid: usize) -> Option<Event> {{"
+        )
+        .unwrap();
+        writeln!(
+            self.out,
+            "        Event::ALL.iter().copied().find(|e| e.dbid() == dbid)\n    }}\n}}\n"
+        )
+        .unwrap();
+        writeln!(
+            self.out,
+            "impl From<Event> for usize {{\n    fn from(event: Event) -> usize {{\n        event.dbid()\n    }}\n}}\n"
+        )
+        .unwrap();
+        writeln!(
+            self.out,
+            "impl ::core::convert::TryFrom<usize> for Event {{\n    type Error = usize;\n\n    fn try_from(dbid: usize) -> Result<Event, usize> {{\n        Event::from_dbid(dbid).ok_or(dbid)\n    }}\n}}"
+        )
+        .unwrap();
+    }
+}
+
+/// Return the declarations of the typed `State` and `Event` of a
+/// definition.
+pub fn types(def: &StateMachineDef) -> StateMachineResult<String> {
+    check_names(def)?;
+    let mut gen = Gen {
+        def,
+        out: String::new(),
+    };
+    let regions: Vec<RegionDbId> = def
+        .sm_regions()
+        .into_iter()
+        .filter(|r| !gen.states(*r).is_empty())
+        .collect();
+    writeln!(
+        gen.out,
+        "/// The active state configuration of the state machine."
+    )
+    .unwrap();
+    gen.owner("State", &regions, "");
+    writeln!(
+        gen.out,
+        "impl State {{\n    pub fn current(def: &::rust_uml_sm::StateMachineDef) -> Option<State> {{\n        State::from_dbids(&def.active_states())\n    }}\n}}\n"
+    )
+    .unwrap();
+    gen.events();
+    Ok(gen.out)
+}
+
+/// Return a module named after the state machine defined by the model
+/// text, with the model, `definition()` and the typed `State` and
+/// `Event`.
+pub fn module(model: &str) -> StateMachineResult<String> {
+    source(&crate::dsl::parse(model)?, model)
+}
+
+fn source(def: &StateMachineDef, model: &str) -> StateMachineResult<String> {
+    let types = types(def)?;
+    let mut out = String::new();
+    writeln!(out, "// Generated by rust_uml_sm::codegen, do not edit.").unwrap();
+    writeln!(out, "#[allow(dead_code)]\npub mod {} {{", ident(def.name)?).unwrap();
+    writeln!(out, "pub const MODEL: &str = {:?};\n", model).unwrap();
+    writeln!(
+        out,
+        "/// Build the definition, the dbids below refer to it.\npub fn definition() -> ::rust_uml_sm::StateMachineDef {{\n    ::rust_uml_sm::dsl::parse(MODEL).expect(\"checked by codegen\")\n}}\n"
+    )
+    .unwrap();
+    out.push_str(&types);
+    writeln!(out, "}}").unwrap();
+    Ok(out)
+}
+
+fn behavior(name: &str, behavior: Option<crate::Behavior>) -> String {
+    match behavior {
+        Some(b) => format!(
+            ", {}: Some(::rust_uml_sm::Behavior::named({:?}))",
+            name, b.name
+        ),
+        None => String::new(),
+    }
+}
+
+fn ids(ids: &[DbId]) -> String {
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    format!("&[{}]", ids.join(", "))
+}
+
+/// The constant describing an element for `compiled::Tables`
+fn element(def: &StateMachineDef, dbid: DbId) -> String {
+    let mut fields = String::new();
+    let kind = match def.elements[dbid].element_type {
+        ElementType::StateMachine => {
+            write!(fields, ", regions: {}", ids(&def.sm_regions())).unwrap();
+            "StateMachine".to_string()
+        }
+        ElementType::Region => {
+            let r = &def.regions[def.elements[dbid].idx];
+            if def.elements[dbid].idx != 0 {
+                write!(fields, ", index: {}", def.elements[dbid].idx).unwrap();
+            }
+            if let Some(initial) = r.subvertex.iter().find(|v| {
+                def.elements[**v].element_type == ElementType::Vertex(VertexType::InitialState)
+            }) {
+                write!(fields, ", initial: {}", initial).unwrap();
+            }
+            "Region".to_string()
+        }
+        ElementType::Vertex(vertex_type) => {
+            if vertex_type == VertexType::State {
+                let s = def._state(dbid);
+                if !s.regions.is_empty() {
+                    write!(fields, ", regions: {}", ids(&s.regions)).unwrap();
+                }
+                fields.push_str(&behavior("entry", s.entry));
+                fields.push_str(&behavior("exit", s.exit));
+                fields.push_str(&behavior("activity", s.do_while));
+                if !s.deferrable.is_empty() {
+                    write!(fields, ", defer: {}", ids(&s.deferrable)).unwrap();
+                }
+            }
+            let v = &def.vertices[def.elements[dbid].idx];
+            if !v.outgoing.is_empty() {
+                write!(fields, ", outgoing: {}", ids(&v.outgoing)).unwrap();
+            }
+            if !v.incoming.is_empty() {
+                write!(fields, ", incoming: {}", ids(&v.incoming)).unwrap();
+            }
+            match vertex_type {
+                VertexType::Pseudostate(kind) => {
+                    format!("Pseudostate(::rust_uml_sm::PseudostateKind::{:?})", kind)
+                }
+                other => format!("{:?}", other),
+            }
+        }
+        ElementType::Transition => {
+            let t = def._transition(dbid);
+            if def.elements[dbid].idx != 0 {
+                write!(fields, ", index: {}", def.elements[dbid].idx).unwrap();
+            }
+            write!(fields, ", source: {}, target: {}", t.source, t.target).unwrap();
+            if let Some(trigger) = t.trigger {
+                write!(fields, ", trigger: {}", trigger).unwrap();
+            }
+            if let OptGuard::Guard(guard) = t.guard {
+                write!(
+                    fields,
+                    ", guard: Some(::rust_uml_sm::Guard::named({:?}))",
+                    guard.name
+                )
+                .unwrap();
+            }
+            if let OptBehavior::Behavior(effect) = t.effect {
+                fields.push_str(&behavior("effect", Some(effect)));
+            }
+            if t.kind != crate::TransitionKind::External {
+                write!(
+                    fields,
+                    ", transition: ::rust_uml_sm::TransitionKind::{:?}",
+                    t.kind
+                )
+                .unwrap();
+            }
+            "Transition".to_string()
+        }
+        ElementType::EventType => "EventType".to_string(),
+    };
+    format!(
+        "    Element {{ kind: Kind::{}, parent: {}, name: {:?}{}, ..Element::NONE }},",
+        kind, def.parents[dbid], def.names[dbid], fields
+    )
+}
+
+/// Parse a model in the notation of `dsl` or, when it starts with `{`,
+/// in the JSON written by `render::json`.
+fn read(model: &str) -> StateMachineResult<StateMachineDef> {
+    match model.trim_start().starts_with('{') {
+        true => crate::render::from_json(model),
+        false => crate::dsl::parse(model),
+    }
+}
+
+/// Return a module named after the state machine defined by the model
+/// text, in the notation of `dsl` or as JSON, with the tables of
+/// `compiled` and a `Machine` able to keep `deferred` deferred events.
+/// Fails with `Unsupported` on time and change events.
+pub fn static_module(model: &str, deferred: usize) -> StateMachineResult<String> {
+    static_source(&read(model)?, deferred)
+}
+
+fn static_source(def: &StateMachineDef, deferred: usize) -> StateMachineResult<String> {
+    let n = def.elements.len();
+    if n > crate::compiled::Id::MAX as usize {
+        return Err(StateMachineError::CapacityExceeded("element ids"));
+    }
+    def.check_compilable()?;
+    check_names(def)?;
+    let mut out = String::new();
+    writeln!(out, "// Generated by rust_uml_sm::codegen, do not edit.").unwrap();
+    writeln!(out, "#[allow(dead_code)]\npub mod {} {{", ident(def.name)?).unwrap();
+    writeln!(
+        out,
+        "use ::rust_uml_sm::compiled::{{Element, Id, Kind, Tables}};\n"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub type Machine = ::rust_uml_sm::compiled::Machine<'static, Tables, {}, {}, {}, {}>;\n",
+        def.vertices.len(),
+        def.regions.len(),
+        def.transitions.len(),
+        deferred
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub const fn machine() -> Machine {{\n    Machine::new(&TABLES)\n}}\n"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub static TABLES: Tables = Tables {{\n    name: {:?},\n    elements: &ELEMENTS,\n    triggered,\n    candidates,\n    scope,\n    defers,\n}};\n",
+        def.name
+    )
+    .unwrap();
+    writeln!(out, "static ELEMENTS: [Element; {}] = [", n).unwrap();
+    for dbid in 0..n {
+        writeln!(out, "{}", element(def, dbid)).unwrap();
+    }
+    writeln!(out, "];\n").unwrap();
+
+    writeln!(
+        out,
+        "/// The outgoing transitions of a vertex for an event, 0 for completion.\nfn triggered(vertex: Id, event: Id) -> &'static [Id] {{\n    match (vertex, event) {{"
+    )
+    .unwrap();
+    for v in &def.vertices {
+        let mut triggers: Vec<usize> = Vec::new();
+        for t in &v.outgoing {
+            let trigger = def._transition(*t).trigger.unwrap_or(0);
+            if !triggers.contains(&trigger) {
+                triggers.push(trigger);
+            }
+        }
+        for trigger in triggers {
+            let transitions: Vec<DbId> = v
+                .outgoing
+                .iter()
+                .copied()
+                .filter(|t| def._transition(*t).trigger.unwrap_or(0) == trigger)
+                .collect();
+            writeln!(
+                out,
+                "        ({}, {}) => {},",
+                v.dbid,
+                trigger,
+                ids(&transitions)
+            )
+            .unwrap();
+        }
+    }
+    writeln!(out, "        _ => &[],\n    }}\n}}\n").unwrap();
+    dispatch_source(def, &mut out);
+
+    let mut gen = Gen {
+        def,
+        out: String::new(),
+    };
+    gen.events();
+    out.push_str(&gen.out);
+    writeln!(
+        out,
+        "\nimpl From<Event> for Id {{\n    fn from(event: Event) -> Id {{\n        event.dbid() as Id\n    }}\n}}"
+    )
+    .unwrap();
+    writeln!(out, "}}").unwrap();
+    Ok(out)
+}
+
+/// The vertex followed by the states containing it, innermost first.
+fn enclosing(def: &StateMachineDef, vertex: DbId) -> Vec<DbId> {
+    let mut chain = vec![vertex];
+    let mut v = def.parents[def.parents[vertex]];
+    while v != 0 {
+        chain.push(v);
+        v = def.parents[def.parents[v]];
+    }
+    chain
+}
+
+/// The region a transition exits and enters, as `compiled` works it
+/// out when the definition does not tell.
+fn scope(def: &StateMachineDef, transition: DbId) -> DbId {
+    let t = def._transition(transition);
+    let within = |mut d: DbId, ancestor: DbId| loop {
+        if d == ancestor {
+            return true;
+        }
+        if d == 0 {
+            return false;
+        }
+        d = def.parents[d];
+    };
+    if t.kind == crate::TransitionKind::Local && t.source != t.target && within(t.target, t.source)
+    {
+        let mut d = t.target;
+        while def.parents[d] != t.source {
+            d = def.parents[d];
+        }
+        return d;
+    }
+    let mut r = def.parents[t.source];
+    while r != 0 {
+        if def.elements[r].element_type == ElementType::Region && within(t.target, r) {
+            return r;
+        }
+        r = def.parents[r];
+    }
+    0
+}
+
+/// The `match`es dispatching an event from the active vertices: the
+/// candidate transitions, the deferred events and the scopes.
+fn dispatch_source(def: &StateMachineDef, out: &mut String) {
+    writeln!(
+        out,
+        "/// The transitions an event may fire from a vertex and the states containing it.\nfn candidates(vertex: Id, event: Id) -> &'static [Id] {{\n    match (vertex, event) {{"
+    )
+    .unwrap();
+    let mut deferred = Vec::new();
+    for v in &def.vertices {
+        let chain = enclosing(def, v.dbid);
+        for event in &def.event_types {
+            let transitions: Vec<DbId> = chain
+                .iter()
+                .flat_map(|u| def.vertices[def.elements[*u].idx].outgoing.iter().copied())
+                .filter(|t| def._transition(*t).trigger == Some(event.dbid))
+                .collect();
+            if !transitions.is_empty() {
+                writeln!(
+                    out,
+                    "        ({}, {}) => {},",
+                    v.dbid,
+                    event.dbid,
+                    ids(&transitions)
+                )
+                .unwrap();
+            }
+            if chain.iter().any(|u| {
+                def.elements[*u].element_type == ElementType::Vertex(VertexType::State)
+                    && def._state(*u).deferrable.contains(&event.dbid)
+            }) {
+                deferred.push(format!("({}, {})", v.dbid, event.dbid));
+            }
+        }
+    }
+    writeln!(out, "        _ => &[],\n    }}\n}}\n").unwrap();
+
+    writeln!(
+        out,
+        "/// Whether a vertex or a state containing it defers an event."
+    )
+    .unwrap();
+    match deferred.is_empty() {
+        true => writeln!(
+            out,
+            "fn defers(_vertex: Id, _event: Id) -> bool {{\n    false\n}}\n"
+        ),
+        false => writeln!(
+            out,
+            "fn defers(vertex: Id, event: Id) -> bool {{\n    matches!((vertex, event), {})\n}}\n",
+            deferred.join(" | ")
+        ),
+    }
+    .unwrap();
+
+    writeln!(
+        out,
+        "/// The region a transition exits and enters, 0 for the state machine.\nfn scope(transition: Id) -> Id {{\n    match transition {{"
+    )
+    .unwrap();
+    for t in &def.transitions {
+        let region = scope(def, t.dbid);
+        if region != 0 {
+            writeln!(out, "        {} => {},", t.dbid, region).unwrap();
+        }
+    }
+    writeln!(out, "        _ => 0,\n    }}\n}}\n").unwrap();
+}
+
+/// Write the module generated from a model file into `dir`, in a file
+/// named after the state machine. Return the path of that file.
+pub fn generate_to(model: impl AsRef<Path>, dir: impl AsRef<Path>) -> StateMachineResult<PathBuf> {
+    let text = std::fs::read_to_string(model.as_ref())?;
+    let def = crate::dsl::parse(&text)?;
+    let path = dir.as_ref().join(format!("{}.rs", def.name));
+    std::fs::write(&path, source(&def, &text)?)?;
+    Ok(path)
+}
+
+/// Build script helper: write the module generated from a model file
+/// into `OUT_DIR` and have cargo rerun the build script when the model
+/// changes.
+pub fn generate(model: impl AsRef<Path>) -> StateMachineResult<PathBuf> {
+    let dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+        StateMachineError::IOError(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "OUT_DIR is not set, generate is meant to be called from a build script",
+        ))
+    })?;
+    println!("cargo:rerun-if-changed={}", model.as_ref().display());
+    generate_to(model, dir)
+}
+
+/// Write the module of `static_module` generated from a model file into
+/// `dir`, in a file named after the state machine with a `_static`
+/// suffix. Return the path of that file.
+pub fn generate_static_to(
+    model: impl AsRef<Path>,
+    dir: impl AsRef<Path>,
+    deferred: usize,
+) -> StateMachineResult<PathBuf> {
+    let text = std::fs::read_to_string(model.as_ref())?;
+    let def = read(&text)?;
+    let path = dir.as_ref().join(format!("{}_static.rs", def.name));
+    std::fs::write(&path, static_source(&def, deferred)?)?;
+    Ok(path)
+}
+
+/// Build script helper: as `generate` for the module of `static_module`.
+pub fn generate_static(model: impl AsRef<Path>, deferred: usize) -> StateMachineResult<PathBuf> {
+    let dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+        StateMachineError::IOError(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "OUT_DIR is not set, generate_static is meant to be called from a build script",
+        ))
+    })?;
+    println!("cargo:rerun-if-changed={}", model.as_ref().display());
+    generate_static_to(model, dir, deferred)
+}
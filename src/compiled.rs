@@ -0,0 +1,953 @@
+//! Execution of a state machine from fixed size tables.
+//!
+//! A [`Machine`] runs a [`Definition`] with the semantics of the
+//! `runtime` module, event for event and behavior for behavior, but
+//! keeps its configuration in arrays, so it never allocates and may be
+//! placed in a static. Definitions are either:
+//!
+//! * [`Tables`], generated by `codegen::static_module` as constants: an
+//!   [`Element`] for every dbid, and `match`es returning the transitions
+//!   an event may fire from a vertex, whether it is deferred there and
+//!   the region each transition exits and enters, so dispatching needs
+//!   no walk up the states
+//! * `frozen::Frozen`, copied from a `StateMachineDef` by `freeze`
+//!
+//! The capacities are const parameters: `S` vertices, `R` regions, `T`
+//! transitions and `Q` deferred events. The first three are those of
+//! the definition, `start` checks they are large enough. Deferring more
+//! than `Q` events, or completing more than `S` states in a single step,
+//! is reported as `StateMachineError::CapacityExceeded`.
+//!
+//! Do-activities the context keeps going in the background, see
+//! `Context::start_activity`, are over once `Machine::activity_done`
+//! is called. There are no time or change events.
+
+use crate::{
+    Behavior, Context, DbId, Guard, PseudostateKind, StateMachineError, StateMachineResult, Status,
+    TransitionKind,
+};
+
+/// The dbid of an element, narrowed to keep tables small.
+pub type Id = u16;
+
+/// See `runtime`
+const MAX_COMPLETIONS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    StateMachine,
+    Region,
+    State,
+    FinalState,
+    InitialState,
+    Pseudostate(PseudostateKind),
+    Transition,
+    EventType,
+}
+
+/// What the engine needs to know about an element. Fields which do not
+/// apply to the kind of element keep their value from `Element::NONE`.
+#[derive(Debug, Clone, Copy)]
+pub struct Element<'a> {
+    pub kind: Kind,
+    pub parent: Id,
+    pub name: &'static str,
+    /// regions and transitions, their position among the elements of
+    /// the same kind
+    pub index: Id,
+    /// state machine and states
+    pub regions: &'a [Id],
+    /// states
+    pub entry: Option<Behavior>,
+    pub exit: Option<Behavior>,
+    pub activity: Option<Behavior>,
+    pub defer: &'a [Id],
+    /// regions, their initial pseudostate or 0
+    pub initial: Id,
+    /// vertices
+    pub outgoing: &'a [Id],
+    pub incoming: &'a [Id],
+    /// transitions, trigger is 0 for completion transitions
+    pub source: Id,
+    pub target: Id,
+    pub trigger: Id,
+    pub guard: Option<Guard>,
+    pub effect: Option<Behavior>,
+    pub transition: TransitionKind,
+}
+
+impl Element<'static> {
+    pub const NONE: Element<'static> = Element {
+        kind: Kind::StateMachine,
+        parent: 0,
+        name: "",
+        index: 0,
+        regions: &[],
+        entry: None,
+        exit: None,
+        activity: None,
+        defer: &[],
+        initial: 0,
+        outgoing: &[],
+        incoming: &[],
+        source: 0,
+        target: 0,
+        trigger: 0,
+        guard: None,
+        effect: None,
+        transition: TransitionKind::External,
+    };
+}
+
+/// The elements of a state machine, by id.
+pub trait Definition {
+    fn element(&self, id: Id) -> Option<Element<'_>>;
+
+    /// The outgoing transitions of a vertex with the given trigger, 0
+    /// for completion transitions, in declaration order.
+    fn triggered(&self, vertex: Id, event: Id) -> &[Id];
+
+    /// The transitions an event may fire when a vertex is active: those
+    /// of the vertex, then those of the states containing it, innermost
+    /// first. None to have the machine walk up the states.
+    fn candidates(&self, _vertex: Id, _event: Id) -> Option<&[Id]> {
+        None
+    }
+
+    /// The region a transition exits and enters, 0 for the state
+    /// machine. None to have the machine work it out.
+    fn scope(&self, _transition: Id) -> Option<Id> {
+        None
+    }
+
+    /// Whether an event is deferred when a vertex is active, by the
+    /// vertex or a state containing it. None to have the machine walk up
+    /// the states.
+    fn defers(&self, _vertex: Id, _event: Id) -> Option<bool> {
+        None
+    }
+}
+
+/// The tables generated by `codegen::static_module`, the functions are
+/// a `match` on their arguments.
+pub struct Tables {
+    pub name: &'static str,
+    pub elements: &'static [Element<'static>],
+    pub triggered: fn(Id, Id) -> &'static [Id],
+    pub candidates: fn(Id, Id) -> &'static [Id],
+    pub scope: fn(Id) -> Id,
+    pub defers: fn(Id, Id) -> bool,
+}
+
+impl Definition for Tables {
+    fn element(&self, id: Id) -> Option<Element<'_>> {
+        self.elements.get(id as usize).copied()
+    }
+
+    fn triggered(&self, vertex: Id, event: Id) -> &[Id] {
+        (self.triggered)(vertex, event)
+    }
+
+    fn candidates(&self, vertex: Id, event: Id) -> Option<&[Id]> {
+        Some((self.candidates)(vertex, event))
+    }
+
+    fn scope(&self, transition: Id) -> Option<Id> {
+        Some((self.scope)(transition))
+    }
+
+    fn defers(&self, vertex: Id, event: Id) -> Option<bool> {
+        Some((self.defers)(vertex, event))
+    }
+}
+
+/// A list of at most N ids.
+#[derive(Debug, Clone, Copy)]
+pub struct IdList<const N: usize> {
+    ids: [Id; N],
+    len: usize,
+}
+
+impl<const N: usize> IdList<N> {
+    pub const fn new() -> Self {
+        IdList {
+            ids: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Return false if the list is full.
+    pub fn push(&mut self, id: Id) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.ids[self.len] = id;
+        self.len += 1;
+        true
+    }
+
+    fn pop_front(&mut self) -> Option<Id> {
+        if self.len == 0 {
+            return None;
+        }
+        let id = self.ids[0];
+        self.ids.copy_within(1..self.len, 0);
+        self.len -= 1;
+        Some(id)
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.ids.copy_within(i + 1..self.len, i);
+        self.len -= 1;
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn as_slice(&self) -> &[Id] {
+        &self.ids[..self.len]
+    }
+}
+
+impl<const N: usize> Default for IdList<N> {
+    fn default() -> Self {
+        IdList::new()
+    }
+}
+
+impl<const N: usize> core::ops::Deref for IdList<N> {
+    type Target = [Id];
+    fn deref(&self) -> &[Id] {
+        self.as_slice()
+    }
+}
+
+/// The execution state of a definition, see the module documentation
+/// for the capacities.
+pub struct Machine<'d, M, const S: usize, const R: usize, const T: usize, const Q: usize> {
+    def: &'d M,
+    /// indexed by the index of the region
+    active: [Id; R],
+    history: [Id; R],
+    completions: IdList<S>,
+    deferred: IdList<Q>,
+    /// the states whose do-activity goes on in the background
+    activities: IdList<S>,
+    status: Status,
+}
+
+impl<'d, M: Definition, const S: usize, const R: usize, const T: usize, const Q: usize>
+    Machine<'d, M, S, R, T, Q>
+{
+    pub const fn new(def: &'d M) -> Self {
+        Machine {
+            def,
+            active: [0; R],
+            history: [0; R],
+            completions: IdList::new(),
+            deferred: IdList::new(),
+            activities: IdList::new(),
+            status: Status::Idle,
+        }
+    }
+
+    pub fn definition(&self) -> &'d M {
+        self.def
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status == Status::Running
+    }
+
+    pub fn deferred_events(&self) -> &[Id] {
+        &self.deferred
+    }
+
+    /// The states whose do-activity goes on in the background.
+    pub fn running_activities(&self) -> &[Id] {
+        &self.activities
+    }
+
+    /// Enter the initial configuration of the state machine.
+    pub fn start(&mut self, ctx: &mut dyn Context) -> StateMachineResult<()> {
+        if self.status == Status::Running {
+            return Err(StateMachineError::AlreadyRunning);
+        }
+        self.check_capacities()?;
+        self.active = [0; R];
+        self.history = [0; R];
+        self.completions.clear();
+        self.deferred.clear();
+        self.activities.clear();
+        self.status = Status::Running;
+        self.enter_regions(ctx, 0, &[], false)?;
+        self.complete(ctx)
+    }
+
+    /// Process an event to completion.
+    /// Return true if it fired a transition and false if it was
+    /// deferred or discarded.
+    pub fn dispatch(&mut self, event: Id, ctx: &mut dyn Context) -> StateMachineResult<bool> {
+        match self.el(event).map(|e| e.kind) {
+            Some(Kind::EventType) => (),
+            _ => return Err(StateMachineError::InvalidEventType(event as usize)),
+        }
+        if self.status != Status::Running {
+            return Err(StateMachineError::NotRunning);
+        }
+        let consumed = self.step(ctx, event)?;
+        if consumed {
+            self.recall(ctx)?;
+        }
+        Ok(consumed)
+    }
+
+    /// Exit all active states, innermost first.
+    pub fn stop(&mut self, ctx: &mut dyn Context) -> StateMachineResult<()> {
+        if self.status == Status::Running {
+            for r in self.e(0).regions.iter().rev() {
+                self.exit_region(ctx, *r);
+            }
+        }
+        self.status = Status::Idle;
+        self.completions.clear();
+        self.deferred.clear();
+        self.activities.clear();
+        Ok(())
+    }
+
+    /// The do-activity of a state, going on in the background, is over.
+    /// Return false if it was not going on, see `Db::activity_done_with`.
+    pub fn activity_done(&mut self, state: Id, ctx: &mut dyn Context) -> StateMachineResult<bool> {
+        if self.status != Status::Running {
+            return Err(StateMachineError::NotRunning);
+        }
+        let i = match self.activities.iter().position(|s| *s == state) {
+            Some(i) => i,
+            None => return Ok(false),
+        };
+        self.activities.remove(i);
+        let completed = self.e(state).regions.iter().all(|r| {
+            let v = self.active[self.slot(*r)];
+            v != 0 && self.e(v).kind == Kind::FinalState
+        });
+        if completed {
+            if !self.completions.push(state) {
+                return Err(StateMachineError::CapacityExceeded("completion events"));
+            }
+            self.complete(ctx)?;
+            self.recall(ctx)?;
+        }
+        Ok(true)
+    }
+
+    /// Return the innermost active vertices, in the order of their
+    /// regions.
+    pub fn active_states(&self) -> IdList<S> {
+        let mut active = IdList::new();
+        if self.status != Status::Idle {
+            for r in self.e(0).regions {
+                self.collect_active(*r, &mut active);
+            }
+        }
+        active
+    }
+
+    fn collect_active(&self, region: Id, active: &mut IdList<S>) {
+        let v = self.active[self.slot(region)];
+        if v == 0 {
+            return;
+        }
+        let e = self.e(v);
+        if e.kind == Kind::State && !e.regions.is_empty() {
+            for r in e.regions {
+                self.collect_active(*r, active);
+            }
+        } else {
+            active.push(v);
+        }
+    }
+
+    /// The definition must fit the capacities, the arrays are indexed
+    /// by the ids and indices of its elements.
+    fn check_capacities(&self) -> StateMachineResult<()> {
+        let mut vertices = 0;
+        let mut id: Id = 0;
+        while let Some(e) = self.el(id) {
+            let fits = match e.kind {
+                Kind::Region => (e.index as usize) < R,
+                Kind::Transition => (e.index as usize) < T,
+                Kind::StateMachine | Kind::EventType => true,
+                _ => {
+                    vertices += 1;
+                    vertices <= S
+                }
+            };
+            if !fits {
+                return Err(StateMachineError::CapacityExceeded(match e.kind {
+                    Kind::Region => "regions",
+                    Kind::Transition => "transitions",
+                    _ => "states",
+                }));
+            }
+            if id == Id::MAX {
+                break;
+            }
+            id += 1;
+        }
+        Ok(())
+    }
+
+    /// Return true if the vertex or region is part of the active state
+    /// configuration.
+    pub fn is_active(&self, id: Id) -> bool {
+        if self.status == Status::Idle {
+            return false;
+        }
+        if id == 0 {
+            return true;
+        }
+        match self.el(id).map(|e| e.kind) {
+            Some(Kind::Region) => self.is_active(self.e(id).parent),
+            Some(Kind::Transition) | Some(Kind::EventType) | Some(Kind::StateMachine) | None => {
+                false
+            }
+            Some(_) => {
+                let region = self.e(id).parent;
+                self.active[self.slot(region)] == id && self.is_active(self.e(region).parent)
+            }
+        }
+    }
+
+    fn el(&self, id: Id) -> Option<Element<'d>> {
+        self.def.element(id)
+    }
+
+    fn e(&self, id: Id) -> Element<'d> {
+        self.def.element(id).expect("an id of the definition")
+    }
+
+    /// The index of a region in the active and history arrays
+    fn slot(&self, region: Id) -> usize {
+        self.e(region).index as usize
+    }
+
+    fn within(&self, id: Id, ancestor: Id) -> bool {
+        let mut d = id;
+        loop {
+            if d == ancestor {
+                return true;
+            }
+            if d == 0 {
+                return false;
+            }
+            d = self.e(d).parent;
+        }
+    }
+
+    fn child_of(&self, ancestor: Id, id: Id) -> Option<Id> {
+        let mut d = id;
+        while d != 0 {
+            if self.e(d).parent == ancestor {
+                return Some(d);
+            }
+            d = self.e(d).parent;
+        }
+        None
+    }
+
+    fn depth(&self, id: Id) -> usize {
+        let mut n = 0;
+        let mut d = id;
+        while d != 0 {
+            n += 1;
+            d = self.e(d).parent;
+        }
+        n
+    }
+
+    fn scope(&self, transition: Id) -> Id {
+        if let Some(scope) = self.def.scope(transition) {
+            return scope;
+        }
+        let t = self.e(transition);
+        if t.transition == TransitionKind::Local
+            && t.source != t.target
+            && self.within(t.target, t.source)
+        {
+            if let Some(r) = self.child_of(t.source, t.target) {
+                return r;
+            }
+        }
+        let mut r = self.e(t.source).parent;
+        while r != 0 {
+            if self.e(r).kind == Kind::Region && self.within(t.target, r) {
+                return r;
+            }
+            r = self.e(r).parent;
+        }
+        0
+    }
+
+    fn exit_root(&self, transition: Id) -> Option<Id> {
+        let t = self.e(transition);
+        match t.transition {
+            TransitionKind::Internal => None,
+            _ => match self.scope(transition) {
+                0 => Some(0),
+                r => self.child_of(r, t.source),
+            },
+        }
+    }
+
+    fn conflict(&self, t1: Id, t2: Id) -> bool {
+        match (self.exit_root(t1), self.exit_root(t2)) {
+            (Some(a), Some(b)) => self.within(a, b) || self.within(b, a),
+            _ => false,
+        }
+    }
+
+    fn perform(&self, ctx: &mut dyn Context, behavior: Option<Behavior>) {
+        if let Some(behavior) = behavior {
+            match behavior.func {
+                Some(func) => func(),
+                None => ctx.behavior(behavior.name),
+            }
+        }
+    }
+
+    /// Start the do-activity of a state, return true if it goes on in
+    /// the background.
+    fn start_activity(&mut self, ctx: &mut dyn Context, state: Id) -> StateMachineResult<bool> {
+        let running = match self.e(state).activity {
+            Some(Behavior {
+                func: Some(func), ..
+            }) => {
+                func();
+                false
+            }
+            Some(behavior) => ctx.start_activity(state as DbId, behavior.name),
+            None => false,
+        };
+        if running && !self.activities.push(state) {
+            return Err(StateMachineError::CapacityExceeded("do-activities"));
+        }
+        Ok(running)
+    }
+
+    fn test(&self, ctx: &mut dyn Context, transition: Id) -> bool {
+        match self.e(transition).guard {
+            None => true,
+            Some(guard) => match guard.func {
+                Some(func) => func(),
+                None => ctx.guard(guard.name),
+            },
+        }
+    }
+
+    fn join_ready(&self, transition: Id) -> bool {
+        let target = self.e(self.e(transition).target);
+        match target.kind {
+            Kind::Pseudostate(PseudostateKind::Join) => target
+                .incoming
+                .iter()
+                .all(|t| self.is_active(self.e(*t).source)),
+            _ => true,
+        }
+    }
+
+    fn enabled(
+        &self,
+        ctx: &mut dyn Context,
+        vertex: Id,
+        trigger: Id,
+        tested: &mut [u8; T],
+    ) -> Option<Id> {
+        self.first_enabled(ctx, self.def.triggered(vertex, trigger), tested)
+    }
+
+    /// tested holds 0 for guards not evaluated yet, 1 if false, 2 if true
+    fn first_enabled(
+        &self,
+        ctx: &mut dyn Context,
+        transitions: &[Id],
+        tested: &mut [u8; T],
+    ) -> Option<Id> {
+        for t in transitions {
+            if !self.join_ready(*t) {
+                continue;
+            }
+            let i = self.e(*t).index as usize;
+            if tested[i] == 0 {
+                tested[i] = if self.test(ctx, *t) { 2 } else { 1 };
+            }
+            if tested[i] == 2 {
+                return Some(*t);
+            }
+        }
+        None
+    }
+
+    fn branch(&self, ctx: &mut dyn Context, vertex: Id) -> Option<Id> {
+        let mut otherwise = None;
+        for t in self.e(vertex).outgoing {
+            match self.e(*t).guard {
+                Some(guard) if guard.func.is_none() && guard.name == "else" => {
+                    otherwise = otherwise.or(Some(*t))
+                }
+                _ => {
+                    if self.test(ctx, *t) {
+                        return Some(*t);
+                    }
+                }
+            }
+        }
+        otherwise
+    }
+
+    /// The first transition enabled from the vertex or the states
+    /// containing it.
+    fn enabled_within(
+        &self,
+        ctx: &mut dyn Context,
+        vertex: Id,
+        event: Id,
+        tested: &mut [u8; T],
+    ) -> Option<Id> {
+        let mut v = vertex;
+        while v != 0 {
+            if let Some(t) = self.enabled(ctx, v, event, tested) {
+                return Some(t);
+            }
+            v = self.e(self.e(v).parent).parent;
+        }
+        None
+    }
+
+    fn select(&self, ctx: &mut dyn Context, event: Id) -> IdList<T> {
+        let mut tested = [0u8; T];
+        let mut candidates: IdList<T> = IdList::new();
+        for leaf in self.active_states().iter() {
+            let enabled = match self.def.candidates(*leaf, event) {
+                Some(transitions) => self.first_enabled(ctx, transitions, &mut tested),
+                None => self.enabled_within(ctx, *leaf, event, &mut tested),
+            };
+            if let Some(t) = enabled {
+                if !candidates.contains(&t) {
+                    candidates.push(t);
+                }
+            }
+        }
+        // innermost sources first, keeping the order of equal depths
+        let ids = &mut candidates.ids[..candidates.len];
+        for i in 1..ids.len() {
+            let mut j = i;
+            while j > 0 && self.depth(self.e(ids[j - 1]).source) < self.depth(self.e(ids[j]).source)
+            {
+                ids.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        let mut selected: IdList<T> = IdList::new();
+        for t in candidates.iter() {
+            if !selected.iter().any(|s| self.conflict(*s, *t)) {
+                selected.push(*t);
+            }
+        }
+        selected
+    }
+
+    fn defers(&self, event: Id) -> bool {
+        self.active_states().iter().any(|leaf| {
+            if let Some(deferred) = self.def.defers(*leaf, event) {
+                return deferred;
+            }
+            let mut v = *leaf;
+            while v != 0 {
+                let e = self.e(v);
+                if e.kind == Kind::State && e.defer.contains(&event) {
+                    return true;
+                }
+                v = self.e(e.parent).parent;
+            }
+            false
+        })
+    }
+
+    fn step(&mut self, ctx: &mut dyn Context, event: Id) -> StateMachineResult<bool> {
+        let selected = self.select(ctx, event);
+        if selected.is_empty() {
+            if self.defers(event) && !self.deferred.push(event) {
+                return Err(StateMachineError::CapacityExceeded("deferred events"));
+            }
+            return Ok(false);
+        }
+        for t in selected.iter() {
+            if self.status != Status::Running {
+                break;
+            }
+            if self.is_active(self.e(*t).source) {
+                self.fire(ctx, *t)?;
+            }
+        }
+        self.complete(ctx)?;
+        Ok(true)
+    }
+
+    fn recall(&mut self, ctx: &mut dyn Context) -> StateMachineResult<()> {
+        while !self.deferred.is_empty() && self.status == Status::Running {
+            let pending = self.deferred;
+            self.deferred.clear();
+            let mut consumed = false;
+            for (i, event) in pending.iter().enumerate() {
+                if self.status != Status::Running {
+                    for rest in &pending[i..] {
+                        self.deferred.push(*rest);
+                    }
+                    break;
+                }
+                consumed |= self.step(ctx, *event)?;
+            }
+            if !consumed {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn complete(&mut self, ctx: &mut dyn Context) -> StateMachineResult<()> {
+        let mut fired = 0;
+        while let Some(state) = self.completions.pop_front() {
+            if self.status != Status::Running {
+                self.completions.clear();
+                break;
+            }
+            if !self.is_active(state) {
+                continue;
+            }
+            if let Some(t) = self.enabled(ctx, state, 0, &mut [0; T]) {
+                fired += 1;
+                if fired > MAX_COMPLETIONS {
+                    return Err(StateMachineError::CompletionLoop(state as usize));
+                }
+                self.fire(ctx, t)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fire(&mut self, ctx: &mut dyn Context, transition: Id) -> StateMachineResult<()> {
+        let t = self.e(transition);
+        if t.transition == TransitionKind::Internal {
+            self.perform(ctx, t.effect);
+            return Ok(());
+        }
+        let scope = self.scope(transition);
+        self.exit_scope(ctx, scope);
+        self.perform(ctx, t.effect);
+        self.enter_scope(ctx, scope, &[t.target])
+    }
+
+    fn exit_scope(&mut self, ctx: &mut dyn Context, scope: Id) {
+        if scope == 0 {
+            for r in self.e(0).regions.iter().rev() {
+                self.exit_region(ctx, *r);
+            }
+            return;
+        }
+        let v = self.active[self.slot(scope)];
+        if v != 0 {
+            self.exit_vertex(ctx, v);
+            self.active[self.slot(scope)] = 0;
+        }
+    }
+
+    fn enter_scope(
+        &mut self,
+        ctx: &mut dyn Context,
+        scope: Id,
+        targets: &[Id],
+    ) -> StateMachineResult<()> {
+        match scope {
+            0 => self.enter_regions(ctx, 0, targets, false),
+            r => self.enter_region(ctx, r, targets, false),
+        }
+    }
+
+    fn exit_region(&mut self, ctx: &mut dyn Context, region: Id) {
+        let v = self.active[self.slot(region)];
+        if v != 0 {
+            self.exit_vertex(ctx, v);
+            self.history[self.slot(region)] = v;
+            self.active[self.slot(region)] = 0;
+        }
+    }
+
+    fn exit_vertex(&mut self, ctx: &mut dyn Context, vertex: Id) {
+        let e = self.e(vertex);
+        if e.kind != Kind::State {
+            return;
+        }
+        for r in e.regions.iter().rev() {
+            self.exit_region(ctx, *r);
+        }
+        if let Some(i) = self.activities.iter().position(|s| *s == vertex) {
+            self.activities.remove(i);
+            ctx.abort_activity(vertex as DbId);
+        }
+        ctx.on_exit(e.name);
+        self.perform(ctx, e.exit);
+    }
+
+    fn enter_regions(
+        &mut self,
+        ctx: &mut dyn Context,
+        owner: Id,
+        targets: &[Id],
+        deep: bool,
+    ) -> StateMachineResult<()> {
+        for r in self.e(owner).regions {
+            let mut explicit: IdList<T> = IdList::new();
+            for t in targets {
+                if *t != owner && self.within(*t, *r) {
+                    explicit.push(*t);
+                }
+            }
+            self.enter_region(ctx, *r, &explicit, deep)?;
+        }
+        Ok(())
+    }
+
+    fn enter_region(
+        &mut self,
+        ctx: &mut dyn Context,
+        region: Id,
+        targets: &[Id],
+        deep: bool,
+    ) -> StateMachineResult<()> {
+        if let Some(target) = targets.first() {
+            let child = self
+                .child_of(region, *target)
+                .ok_or(StateMachineError::InvalidVertex(*target as usize))?;
+            return self.enter_vertex(ctx, child, targets, false);
+        }
+        let history = self.history[self.slot(region)];
+        if deep && history != 0 && self.e(history).kind != Kind::FinalState {
+            return self.enter_vertex(ctx, history, &[], true);
+        }
+        match self.e(region).initial {
+            0 => Ok(()),
+            initial => {
+                let t = *self
+                    .e(initial)
+                    .outgoing
+                    .first()
+                    .ok_or(StateMachineError::NoEnabledTransition(initial as usize))?;
+                self.active[self.slot(region)] = initial;
+                self.fire(ctx, t)
+            }
+        }
+    }
+
+    fn enter_vertex(
+        &mut self,
+        ctx: &mut dyn Context,
+        vertex: Id,
+        targets: &[Id],
+        deep: bool,
+    ) -> StateMachineResult<()> {
+        let e = self.e(vertex);
+        let region = e.parent;
+        self.active[self.slot(region)] = vertex;
+        match e.kind {
+            Kind::State => {
+                self.perform(ctx, e.entry);
+                ctx.on_entry(e.name);
+                let running = self.start_activity(ctx, vertex)?;
+                if e.regions.is_empty() && !running && !self.completions.push(vertex) {
+                    return Err(StateMachineError::CapacityExceeded("completion events"));
+                }
+                self.enter_regions(ctx, vertex, targets, deep)
+            }
+            Kind::FinalState => self.final_reached(region),
+            Kind::InitialState => self.enter_region(ctx, region, &[], false),
+            Kind::Pseudostate(kind) => self.enter_pseudostate(ctx, vertex, kind),
+            _ => Err(StateMachineError::InvalidVertex(vertex as usize)),
+        }
+    }
+
+    fn enter_pseudostate(
+        &mut self,
+        ctx: &mut dyn Context,
+        vertex: Id,
+        kind: PseudostateKind,
+    ) -> StateMachineResult<()> {
+        let e = self.e(vertex);
+        let region = e.parent;
+        match kind {
+            PseudostateKind::Terminate => {
+                self.status = Status::Terminated;
+                Ok(())
+            }
+            PseudostateKind::Fork => {
+                let first = *e
+                    .outgoing
+                    .first()
+                    .ok_or(StateMachineError::NoEnabledTransition(vertex as usize))?;
+                let scope = self.scope(first);
+                self.exit_scope(ctx, scope);
+                let mut targets: IdList<T> = IdList::new();
+                for t in e.outgoing {
+                    self.perform(ctx, self.e(*t).effect);
+                    targets.push(self.e(*t).target);
+                }
+                self.enter_scope(ctx, scope, &targets)
+            }
+            PseudostateKind::ShallowHistory | PseudostateKind::DeepHistory => {
+                let history = self.history[self.slot(region)];
+                if history != 0 && self.e(history).kind != Kind::FinalState {
+                    let deep = kind == PseudostateKind::DeepHistory;
+                    return self.enter_vertex(ctx, history, &[], deep);
+                }
+                match e.outgoing.first() {
+                    Some(t) => self.fire(ctx, *t),
+                    None => {
+                        self.active[self.slot(region)] = 0;
+                        self.enter_region(ctx, region, &[], false)
+                    }
+                }
+            }
+            _ => {
+                let t = self
+                    .branch(ctx, vertex)
+                    .ok_or(StateMachineError::NoEnabledTransition(vertex as usize))?;
+                self.fire(ctx, t)
+            }
+        }
+    }
+
+    fn final_reached(&mut self, region: Id) -> StateMachineResult<()> {
+        let owner = self.e(region).parent;
+        let done = self.e(owner).regions.iter().all(|r| {
+            let v = self.active[self.slot(*r)];
+            v != 0 && self.e(v).kind == Kind::FinalState
+        });
+        if done {
+            match owner {
+                0 => self.status = Status::Terminated,
+                _ if self.activities.contains(&owner) => (),
+                _ => {
+                    if !self.completions.push(owner) {
+                        return Err(StateMachineError::CapacityExceeded("completion events"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+//! Structural comparison of two definitions.
+//!
+//! [`diff`] matches the elements of two definitions by fullname and
+//! lists what changed from the first to the second:
+//!
+//! * vertices, regions, transitions and event types added or removed,
+//!   a vertex or region removed from one place and added with the same
+//!   name and kind elsewhere is reported as moved
+//! * for the elements in both, a changed kind, the changed entry, exit
+//!   and do behaviors and deferred events of states, and the changed
+//!   source, target, trigger, guard, effect and kind of transitions
+//!
+//! Transitions without a name are named after their position, `t3`,
+//! those which are only renumbered are not reported. The names of the
+//! state machines may differ. The [`Diff`] displays as
+//! a report, removed and moved elements first, then added ones and
+//! changes, one per line:
+//!
+//! ```text
+//! - door::region_1::ajar (state)
+//! > door::region_1::opened::region_1::wide -> door::region_1::wide (state)
+//! + door::region_1::locked (state)
+//! ~ door::region_1::t3 target: region_1::closed -> region_1::locked
+//! ```
+
+use crate::*;
+use alloc::vec;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added {
+        fullname: String,
+        kind: &'static str,
+    },
+    Removed {
+        fullname: String,
+        kind: &'static str,
+    },
+    Moved {
+        from: String,
+        to: String,
+        kind: &'static str,
+    },
+    /// A property of an element in both definitions.
+    Changed {
+        fullname: String,
+        property: &'static str,
+        old: String,
+        new: String,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    /// True if the definitions have the same structure and behaviors.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { fullname, kind } => write!(f, "+ {} ({})", fullname, kind),
+            Change::Removed { fullname, kind } => write!(f, "- {} ({})", fullname, kind),
+            Change::Moved { from, to, kind } => write!(f, "> {} -> {} ({})", from, to, kind),
+            Change::Changed {
+                fullname,
+                property,
+                old,
+                new,
+            } => write!(f, "~ {} {}: {} -> {}", fullname, property, old, new),
+        }
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// The keyword of the element kind in the textual definitions.
+pub(crate) fn kind(element_type: ElementType) -> &'static str {
+    match element_type {
+        ElementType::StateMachine => "statemachine",
+        ElementType::Region => "region",
+        ElementType::Transition => "transition",
+        ElementType::EventType => "event",
+        ElementType::Vertex(VertexType::State) => "state",
+        ElementType::Vertex(VertexType::InitialState) => "initial",
+        ElementType::Vertex(VertexType::FinalState) => "final",
+        ElementType::Vertex(VertexType::Pseudostate(kind)) => match kind {
+            PseudostateKind::EntryPoint => "entrypoint",
+            PseudostateKind::ExitPoint => "exitpoint",
+            PseudostateKind::Initial => "initial",
+            PseudostateKind::DeepHistory => "deephistory",
+            PseudostateKind::ShallowHistory => "history",
+            PseudostateKind::Join => "join",
+            PseudostateKind::Fork => "fork",
+            PseudostateKind::Junction => "junction",
+            PseudostateKind::Terminate => "terminate",
+            PseudostateKind::Choice => "choice",
+        },
+    }
+}
+
+/// The fullname of an element without the name of the state machine,
+/// so that elements of state machines with different names compare.
+fn relative(db: &Db, dbid: DbId) -> &str {
+    db.fullnames[dbid][db.fullnames[0].len()..].trim_start_matches("::")
+}
+
+/// The properties compared of an element, by name, elements they
+/// refer to are named relative to the state machine.
+fn properties(db: &Db, dbid: DbId) -> Vec<(&'static str, String)> {
+    let behavior = |b: Option<Behavior>| b.map_or(String::new(), |b| b.name.to_string());
+    match db.elements[dbid].element_type {
+        ElementType::Vertex(VertexType::State) => {
+            let s = db._state(dbid);
+            let defer: Vec<&str> = s.deferrable.iter().map(|e| relative(db, *e)).collect();
+            vec![
+                ("entry", behavior(s.entry)),
+                ("exit", behavior(s.exit)),
+                ("do", behavior(s.do_while)),
+                ("defer", defer.join(", ")),
+            ]
+        }
+        ElementType::Transition => {
+            let t = db._transition(dbid);
+            vec![
+                ("source", relative(db, t.source).to_string()),
+                ("target", relative(db, t.target).to_string()),
+                (
+                    "trigger",
+                    t.trigger
+                        .map_or(String::new(), |e| relative(db, e).to_string()),
+                ),
+                (
+                    "guard",
+                    match t.guard {
+                        OptGuard::Guard(g) => g.name.to_string(),
+                        OptGuard::None => String::new(),
+                    },
+                ),
+                (
+                    "effect",
+                    match t.effect {
+                        OptBehavior::Behavior(b) => b.name.to_string(),
+                        OptBehavior::None => String::new(),
+                    },
+                ),
+                ("kind", format!("{:?}", t.kind).to_lowercase()),
+            ]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The element of b with the fullname of an element of a, the names of
+/// the state machines aside. An initial transition has the fullname of
+/// its initial pseudostate, an element of the same kind is preferred.
+fn counterpart(a: &Db, dbid: DbId, b: &Db) -> Option<DbId> {
+    let name = relative(a, dbid);
+    let mut same = (1..b.fullnames.len()).filter(|d| relative(b, *d) == name);
+    let first = same.next()?;
+    let k = kind(a.elements[dbid].element_type);
+    Some(
+        core::iter::once(first)
+            .chain(same)
+            .find(|d| kind(b.elements[*d].element_type) == k)
+            .unwrap_or(first),
+    )
+}
+
+fn last_name(fullname: &str) -> &str {
+    fullname.rsplit("::").next().unwrap_or(fullname)
+}
+
+/// Compare two definitions, see the module documentation.
+pub fn diff(a: &Db, b: &Db) -> Diff {
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    if a.fullnames[0] != b.fullnames[0] {
+        changed.push(Change::Changed {
+            fullname: a.fullnames[0].clone(),
+            property: "name",
+            old: a.fullnames[0].clone(),
+            new: b.fullnames[0].clone(),
+        });
+    }
+    for (dbid, fullname) in a.fullnames.iter().enumerate().skip(1) {
+        match counterpart(a, dbid, b) {
+            None => removed.push(dbid),
+            Some(other) => {
+                let (ka, kb) = (
+                    kind(a.elements[dbid].element_type),
+                    kind(b.elements[other].element_type),
+                );
+                if ka != kb {
+                    changed.push(Change::Changed {
+                        fullname: fullname.clone(),
+                        property: "kind",
+                        old: ka.to_string(),
+                        new: kb.to_string(),
+                    });
+                    continue;
+                }
+                for ((property, old), (_, new)) in
+                    properties(a, dbid).into_iter().zip(properties(b, other))
+                {
+                    if old != new {
+                        changed.push(Change::Changed {
+                            fullname: fullname.clone(),
+                            property,
+                            old,
+                            new,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    for dbid in 1..b.fullnames.len() {
+        if counterpart(b, dbid, a).is_none() {
+            added.push(dbid);
+        }
+    }
+
+    // transitions without a name are numbered, a transition removed and
+    // added with all the same properties was only renumbered
+    let transition = |db: &Db, d: &DbId| db.elements[*d].element_type == ElementType::Transition;
+    let mut renumbered: Vec<(DbId, DbId)> = Vec::new();
+    for r in removed.iter().copied().filter(|r| transition(a, r)) {
+        let same = added.iter().copied().find(|d| {
+            transition(b, d)
+                && !renumbered.iter().any(|(_, n)| n == d)
+                && properties(a, r) == properties(b, *d)
+        });
+        if let Some(d) = same {
+            renumbered.push((r, d));
+        }
+    }
+    removed.retain(|r| !renumbered.iter().any(|(o, _)| o == r));
+    added.retain(|d| !renumbered.iter().any(|(_, n)| n == d));
+
+    // a vertex or region removed and added once elsewhere with the
+    // same name and kind was moved
+    let movable = |element_type: ElementType| {
+        matches!(element_type, ElementType::Vertex(_) | ElementType::Region)
+    };
+    let mut changes = Vec::new();
+    let mut moved_to = Vec::new();
+    for r in removed {
+        let (name, k) = (last_name(&a.fullnames[r]), kind(a.elements[r].element_type));
+        let same = |d: &DbId| {
+            last_name(&b.fullnames[*d]) == name && kind(b.elements[*d].element_type) == k
+        };
+        let candidates: Vec<DbId> = added.iter().copied().filter(same).collect();
+        let sources = (1..a.fullnames.len())
+            .filter(|d| {
+                last_name(&a.fullnames[*d]) == name
+                    && kind(a.elements[*d].element_type) == k
+                    && counterpart(a, *d, b).is_none()
+            })
+            .count();
+        if movable(a.elements[r].element_type) && candidates.len() == 1 && sources == 1 {
+            moved_to.push(candidates[0]);
+            changes.push(Change::Moved {
+                from: a.fullnames[r].clone(),
+                to: b.fullnames[candidates[0]].clone(),
+                kind: k,
+            });
+        } else {
+            changes.push(Change::Removed {
+                fullname: a.fullnames[r].clone(),
+                kind: k,
+            });
+        }
+    }
+    for d in added {
+        if !moved_to.contains(&d) {
+            changes.push(Change::Added {
+                fullname: b.fullnames[d].clone(),
+                kind: kind(b.elements[d].element_type),
+            });
+        }
+    }
+    changes.extend(changed);
+    Diff { changes }
+}
@@ -0,0 +1,658 @@
+//! A compact, human-writable text format for defining state machines.
+//!
+//! ```text
+//! statemachine door {
+//!     event open, close, lock;
+//!     initial -> closed;
+//!     state closed {
+//!         on open [unlocked] -> opened / creak;
+//!         on lock / click;            // internal transition, no target
+//!     }
+//!     state opened {
+//!         entry light_on;
+//!         exit light_off;
+//!         on close -> closed;
+//!     }
+//! }
+//! ```
+//!
+//! A state (or the state machine) either contains its substates
+//! directly, in which case they are placed in its only region, or
+//! contains one or more `region name { ... }` blocks.
+//!
+//! Vertices:  `state`, `final`, `choice`, `junction`, `fork`, `join`,
+//!            `history`, `deephistory`, `entrypoint`, `exitpoint`, `terminate`
+//! In a state: `entry b;` `exit b;` `do b;` `defer ev1, ev2;`
+//! Transitions: `[name:] [on event] [[guard]] [-> target] [/ effect];`
+//! Time events: `on after(30s)` and `on at(3600s)`, see `timer`
+//! Change events: `on when(condition)`, see `change`
+//!
+//! A transition without a target is an internal transition. A transition
+//! without a trigger is a completion transition. Targets are paths such
+//! as `s2` or `s2::r3::s4` and are looked up from the source outwards,
+//! the nearest enclosing declaration wins. Guards and behaviors are only
+//! names, see `Guard::named` and `Behavior::named`.
+//!
+//! Comments start with `//` and run to the end of the line.
+//!
+//! The text is lexed and parsed by the `rust_uml_sm_dsl` crate, which
+//! `statemachine!` shares, and built into the definition here.
+
+use crate::{
+    Behavior, DbId, ElementType, Guard, Interned, OptBehavior, OptGuard, PseudostateKind,
+    StateMachineDef, StateMachineError, StateMachineResult, TimeEvent, TransitionKind, VertexType,
+};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use rust_uml_sm_dsl::{
+    lex, Ident, Item, Location, Machine, Pseudostate, TransitionDecl, Trigger, VertexKeyword,
+};
+pub use rust_uml_sm_dsl::{ParseError, Span};
+
+impl From<ParseError> for StateMachineError {
+    fn from(err: ParseError) -> StateMachineError {
+        StateMachineError::ParseError(err)
+    }
+}
+
+/// Parse a textual definition into a `StateMachineDef`.
+pub fn parse(src: &str) -> StateMachineResult<StateMachineDef> {
+    let tokens = lex(src)?;
+    let machine = rust_uml_sm_dsl::parse(&tokens)?;
+    Ok(Builder::build(machine)?)
+}
+
+fn pseudostate_kind(kind: Pseudostate) -> PseudostateKind {
+    match kind {
+        Pseudostate::Choice => PseudostateKind::Choice,
+        Pseudostate::Junction => PseudostateKind::Junction,
+        Pseudostate::Fork => PseudostateKind::Fork,
+        Pseudostate::Join => PseudostateKind::Join,
+        Pseudostate::ShallowHistory => PseudostateKind::ShallowHistory,
+        Pseudostate::DeepHistory => PseudostateKind::DeepHistory,
+        Pseudostate::EntryPoint => PseudostateKind::EntryPoint,
+        Pseudostate::ExitPoint => PseudostateKind::ExitPoint,
+        Pseudostate::Terminate => PseudostateKind::Terminate,
+    }
+}
+
+//----------------------------------------------------------------
+// Builder
+//----------------------------------------------------------------
+
+/// Transitions are added once every vertex exists so that targets
+/// may be declared after the transitions which refer to them.
+struct Pending {
+    source: DbId,
+    decl: TransitionDecl,
+    initial: bool,
+}
+
+/// Deferred events and state behaviors also wait for the second pass
+/// since registering an event type adds an element to the definition.
+struct PendingDefer {
+    state: DbId,
+    events: Vec<Ident>,
+}
+
+struct Builder {
+    db: StateMachineDef,
+    transitions: Vec<Pending>,
+    defers: Vec<PendingDefer>,
+    events: Vec<Ident>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scope {
+    Machine,
+    State,
+    Region,
+    Pseudostate,
+}
+
+fn err<T>(message: String, span: Span) -> Result<T, ParseError> {
+    Err(ParseError::new(message, span))
+}
+
+impl Builder {
+    fn build(machine: Machine) -> Result<StateMachineDef, ParseError> {
+        let mut interned = Interned::default();
+        let mut db = StateMachineDef::new(interned.intern(&machine.name.name));
+        db.interned = interned;
+        let mut builder = Builder {
+            db,
+            transitions: Vec::new(),
+            defers: Vec::new(),
+            events: Vec::new(),
+        };
+        builder.body(0, Scope::Machine, &machine.body)?;
+        builder.finish()?;
+        Ok(builder.db)
+    }
+
+    fn map_err<T>(&self, result: StateMachineResult<T>, ident: &Ident) -> Result<T, ParseError> {
+        result.or_else(|e| match e {
+            StateMachineError::Duplicate(name) => {
+                err(format!("duplicate name `{}`", name), ident.span)
+            }
+            StateMachineError::ContainsMultipleRegions(dbid) => err(
+                format!(
+                    "`{}` has several regions, declare `{}` inside one of them",
+                    self.db._name(dbid),
+                    ident.name
+                ),
+                ident.span,
+            ),
+            e => err(e.to_string(), ident.span),
+        })
+    }
+
+    /// The region vertices are added to when declared directly in a
+    /// state or in the state machine.
+    fn region_of(
+        &mut self,
+        container: DbId,
+        scope: Scope,
+        ident: &Ident,
+    ) -> Result<DbId, ParseError> {
+        match scope {
+            Scope::Region => Ok(container),
+            Scope::Machine => {
+                let r = self.db.get_only_region(container);
+                self.map_err(r, ident)
+            }
+            _ => match self.db.regions(container).map(|r| r.len()) {
+                Ok(0) => {
+                    let r = self.db.add_region("region_1", container);
+                    self.map_err(r, ident)
+                }
+                _ => {
+                    let r = self.db.get_only_region(container);
+                    self.map_err(r, ident)
+                }
+            },
+        }
+    }
+
+    fn body(&mut self, container: DbId, scope: Scope, items: &[Item]) -> Result<(), ParseError> {
+        for item in items {
+            match item {
+                Item::Region(name, body) => {
+                    let interned = self.db.intern(&name.name);
+                    let r = match scope {
+                        Scope::Machine => self.db.add_sm_region(interned),
+                        Scope::State => self.db.add_region(interned, container),
+                        _ => {
+                            return err(
+                                "a region may only be declared in a state".into(),
+                                name.span,
+                            )
+                        }
+                    };
+                    let region = self.map_err(r, name)?;
+                    self.body(region, Scope::Region, body)?;
+                }
+                Item::Vertex(keyword, name, body) => {
+                    if scope == Scope::Pseudostate {
+                        return err(
+                            format!("`{}` cannot be declared in a pseudostate", name.name),
+                            name.span,
+                        );
+                    }
+                    let region = self.region_of(container, scope, name)?;
+                    let vertex_type = match keyword {
+                        VertexKeyword::State => VertexType::State,
+                        VertexKeyword::Final => VertexType::FinalState,
+                        VertexKeyword::Pseudostate(kind) => {
+                            VertexType::Pseudostate(pseudostate_kind(*kind))
+                        }
+                    };
+                    let interned = self.db.intern(&name.name);
+                    let v = self.db.add_vertex(interned, region, vertex_type);
+                    let vertex = self.map_err(v, name)?;
+                    let inner = match keyword {
+                        VertexKeyword::State => Scope::State,
+                        _ => Scope::Pseudostate,
+                    };
+                    self.body(vertex, inner, body)?;
+                }
+                Item::Initial(decl) => {
+                    if scope == Scope::Pseudostate {
+                        return err(
+                            "an initial transition must be in a region or state".into(),
+                            decl.span,
+                        );
+                    }
+                    if decl.trigger.is_some() || decl.target.is_none() {
+                        return err(
+                            "an initial transition has a target and no trigger".into(),
+                            decl.span,
+                        );
+                    }
+                    let ident = Ident {
+                        name: "initial".to_string(),
+                        span: decl.span,
+                    };
+                    let region = self.region_of(container, scope, &ident)?;
+                    let initial = match self.db.initial_state(region) {
+                        Ok(Some(dbid)) => {
+                            return err(
+                                format!(
+                                    "region `{}` already has an initial pseudostate `{}`",
+                                    self.db._fullname(region),
+                                    self.db._name(dbid)
+                                ),
+                                decl.span,
+                            )
+                        }
+                        _ => {
+                            let v = self
+                                .db
+                                .add_vertex("initial", region, VertexType::InitialState);
+                            self.map_err(v, &ident)?
+                        }
+                    };
+                    self.transitions.push(Pending {
+                        source: initial,
+                        decl: decl.clone(),
+                        initial: true,
+                    });
+                }
+                Item::Events(list) => {
+                    if scope != Scope::Machine {
+                        return err(
+                            "events are declared in the state machine".into(),
+                            list[0].span,
+                        );
+                    }
+                    self.events.extend(list.iter().cloned());
+                }
+                Item::Entry(b) | Item::Exit(b) | Item::Do(b) => {
+                    if scope != Scope::State {
+                        return err(
+                            format!("behavior `{}` must be declared in a state", b.name),
+                            b.span,
+                        );
+                    }
+                    let behavior = Behavior::named(self.db.intern(&b.name));
+                    let r = match item {
+                        Item::Entry(_) => self.db.set_entry(container, behavior),
+                        Item::Exit(_) => self.db.set_exit(container, behavior),
+                        _ => self.db.set_do(container, behavior),
+                    };
+                    self.map_err(r, b)?;
+                }
+                Item::Defer(list) => {
+                    if scope != Scope::State {
+                        return err(
+                            "deferred events must be declared in a state".into(),
+                            list[0].span,
+                        );
+                    }
+                    self.defers.push(PendingDefer {
+                        state: container,
+                        events: list.clone(),
+                    });
+                }
+                Item::Transition(decl) => {
+                    if scope != Scope::State && scope != Scope::Pseudostate {
+                        return err(
+                            "a transition must be declared in its source state or pseudostate"
+                                .into(),
+                            decl.span,
+                        );
+                    }
+                    self.transitions.push(Pending {
+                        source: container,
+                        decl: decl.clone(),
+                        initial: false,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, ident: &Ident) -> Result<DbId, ParseError> {
+        match self.db.event_type(&ident.name) {
+            Some(dbid) => Ok(dbid),
+            None => {
+                let interned = self.db.intern(&ident.name);
+                let r = self.db.add_event_type(interned);
+                self.map_err(r, ident)
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), ParseError> {
+        let events = core::mem::take(&mut self.events);
+        for ident in &events {
+            if self.db.event_type(&ident.name).is_some() {
+                return err(format!("event `{}` declared twice", ident.name), ident.span);
+            }
+            self.event(ident)?;
+        }
+        let defers = core::mem::take(&mut self.defers);
+        for defer in &defers {
+            for ident in &defer.events {
+                let ev = self.event(ident)?;
+                let r = self.db.add_deferrable_event(defer.state, ev);
+                self.map_err(r, ident)?;
+            }
+        }
+        let transitions = core::mem::take(&mut self.transitions);
+        for pending in &transitions {
+            self.transition(pending)?;
+        }
+        Ok(())
+    }
+
+    fn transition(&mut self, pending: &Pending) -> Result<(), ParseError> {
+        let decl = &pending.decl;
+        let trigger = match &decl.trigger {
+            Some(Trigger::Event(ident)) => Some(self.event(ident)?),
+            Some(Trigger::Time(word, duration, span)) => {
+                let when = match word.name.as_str() {
+                    "after" => TimeEvent::After(*duration),
+                    _ => TimeEvent::At(*duration),
+                };
+                match self.db.add_time_event(pending.source, when) {
+                    Ok(dbid) => Some(dbid),
+                    Err(_) => {
+                        return err(
+                            format!("`{}` may only trigger transitions from a state", when),
+                            *span,
+                        )
+                    }
+                }
+            }
+            Some(Trigger::Change(_, condition, span)) => {
+                let interned = self.db.intern(&condition.name);
+                let r = self.db.add_change_event(interned);
+                let ident = Ident {
+                    name: format!("when({})", condition.name),
+                    span: *span,
+                };
+                Some(self.map_err(r, &ident)?)
+            }
+            None => None,
+        };
+        let target = match &decl.target {
+            Some(path) => self.resolve(pending.source, path)?,
+            None => pending.source,
+        };
+        let guard = match &decl.guard {
+            Some(ident) => OptGuard::Guard(Guard::named(self.db.intern(&ident.name))),
+            None => OptGuard::None,
+        };
+        let effect = match &decl.effect {
+            Some(ident) => OptBehavior::Behavior(Behavior::named(self.db.intern(&ident.name))),
+            None => OptBehavior::None,
+        };
+        let name = match (&decl.name, pending.initial) {
+            (Some(ident), _) => self.db.intern(&ident.name),
+            (None, true) => "initial",
+            (None, false) => self
+                .db
+                .intern(&format!("t{}", self.db.transitions.len() + 1)),
+        };
+        let ident = decl.name.clone().unwrap_or(Ident {
+            name: name.to_string(),
+            span: decl.span,
+        });
+        let r = self
+            .db
+            .add_transition(name, trigger, pending.source, target, effect, guard);
+        let dbid = self.map_err(r, &ident)?;
+        if decl.target.is_none() {
+            let r = self.db.set_transition_kind(dbid, TransitionKind::Internal);
+            self.map_err(r, &ident)?;
+        }
+        Ok(())
+    }
+
+    /// The vertex at the target path of a transition, see `resolve`.
+    fn resolve(&self, source: DbId, path: &[Ident]) -> Result<DbId, ParseError> {
+        let names: Vec<&str> = path.iter().map(|i| i.name.as_str()).collect();
+        if let Some(dbid) = resolve(&self.db, source, &names) {
+            return Ok(dbid);
+        }
+        let span = path[0].span.to(path[path.len() - 1].span);
+        err(format!("unknown state `{}`", names.join("::")), span)
+    }
+}
+
+/// Look for the first element of a path among the children of the
+/// source and then of each of its ancestors in turn, or from the state
+/// machine if the path starts with its name.
+fn resolve(db: &StateMachineDef, source: DbId, path: &[&str]) -> Option<DbId> {
+    let mut scope = source;
+    loop {
+        if let Some(dbid) = resolve_from(db, scope, path) {
+            return Some(dbid);
+        }
+        if scope == 0 {
+            break;
+        }
+        scope = db.parents[scope];
+    }
+    // an absolute path starting with the state machine name
+    if path[0] == db._name(0) && path.len() > 1 {
+        return resolve_from(db, 0, &path[1..]);
+    }
+    None
+}
+
+fn resolve_from(db: &StateMachineDef, scope: DbId, path: &[&str]) -> Option<DbId> {
+    let mut current = scope;
+    for name in path {
+        current = db.child(current, name)?;
+    }
+    match db.elements[current].element_type {
+        ElementType::Vertex(_) => Some(current),
+        _ => None,
+    }
+}
+
+//----------------------------------------------------------------
+// Writer
+//----------------------------------------------------------------
+
+/// Write a definition in the textual format, which `parse` reads back
+/// into an equivalent definition. Transitions named `t1`, `t2`... are
+/// written without a name and numbered again when read. Local
+/// transitions are written as external ones, the format has no syntax
+/// for them.
+pub fn write(def: &StateMachineDef) -> String {
+    let mut writer = Writer {
+        def,
+        out: String::new(),
+    };
+    writer.line(0, &format!("statemachine {} {{", def._name(0)));
+    let events: Vec<&str> = def
+        .event_types
+        .iter()
+        .filter(|e| e.is_signal())
+        .map(|e| e.name)
+        .collect();
+    if !events.is_empty() {
+        writer.line(1, &format!("event {};", events.join(", ")));
+    }
+    writer.regions(0, 1);
+    writer.line(0, "}");
+    writer.out
+}
+
+fn pseudostate_keyword(kind: PseudostateKind) -> Option<&'static str> {
+    Some(match kind {
+        PseudostateKind::Choice => "choice",
+        PseudostateKind::Junction => "junction",
+        PseudostateKind::Fork => "fork",
+        PseudostateKind::Join => "join",
+        PseudostateKind::ShallowHistory => "history",
+        PseudostateKind::DeepHistory => "deephistory",
+        PseudostateKind::EntryPoint => "entrypoint",
+        PseudostateKind::ExitPoint => "exitpoint",
+        PseudostateKind::Terminate => "terminate",
+        PseudostateKind::Initial => return None,
+    })
+}
+
+/// Names given to unnamed transitions when read.
+fn is_numbered(name: &str) -> bool {
+    name.len() > 1 && name.starts_with('t') && name[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+struct Writer<'d> {
+    def: &'d StateMachineDef,
+    out: String,
+}
+
+impl Writer<'_> {
+    fn line(&mut self, depth: usize, text: &str) {
+        for _ in 0..depth {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// A single region named `region_1` is left implicit.
+    fn regions(&mut self, owner: DbId, depth: usize) {
+        let regions = self.def.regions(owner).unwrap_or_default();
+        match regions[..] {
+            [r] if self.def._name(r) == "region_1" => self.region(r, depth),
+            _ => {
+                for r in regions {
+                    self.line(depth, &format!("region {} {{", self.def._name(r)));
+                    self.region(r, depth + 1);
+                    self.line(depth, "}");
+                }
+            }
+        }
+    }
+
+    fn region(&mut self, region: DbId, depth: usize) {
+        let def = self.def;
+        for v in def.subvertices(region).unwrap_or_default() {
+            let name = def._name(v);
+            let outgoing = def.outgoing(v).unwrap_or_default();
+            let keyword = match def.elements[v].element_type {
+                ElementType::Vertex(VertexType::InitialState) => {
+                    for t in outgoing {
+                        let text = format!("initial {}", self.transition(t, false));
+                        self.line(depth, &text);
+                    }
+                    continue;
+                }
+                ElementType::Vertex(VertexType::FinalState) => {
+                    self.line(depth, &format!("final {};", name));
+                    continue;
+                }
+                ElementType::Vertex(VertexType::State) => "state",
+                ElementType::Vertex(VertexType::Pseudostate(kind)) => {
+                    match pseudostate_keyword(kind) {
+                        Some(keyword) => keyword,
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+            let mut body = Vec::new();
+            if keyword == "state" {
+                let state = def._state(v);
+                for (word, behavior) in [
+                    ("entry", state.entry),
+                    ("exit", state.exit),
+                    ("do", state.do_while),
+                ] {
+                    if let Some(b) = behavior {
+                        body.push(format!("{} {};", word, b.name));
+                    }
+                }
+                if !state.deferrable.is_empty() {
+                    let events: Vec<&str> =
+                        state.deferrable.iter().map(|e| def._name(*e)).collect();
+                    body.push(format!("defer {};", events.join(", ")));
+                }
+            }
+            let composite = keyword == "state"
+                && def._state(v).regions.iter().any(|r| {
+                    def._name(*r) != "region_1"
+                        || !def.subvertices(*r).unwrap_or_default().is_empty()
+                });
+            if body.is_empty() && outgoing.is_empty() && !composite {
+                self.line(depth, &format!("{} {};", keyword, name));
+                continue;
+            }
+            self.line(depth, &format!("{} {} {{", keyword, name));
+            for line in body {
+                self.line(depth + 1, &line);
+            }
+            if composite {
+                self.regions(v, depth + 1);
+            }
+            for t in outgoing {
+                let text = self.transition(t, true);
+                self.line(depth + 1, &text);
+            }
+            self.line(depth, "}");
+        }
+    }
+
+    fn transition(&self, t: DbId, named: bool) -> String {
+        let tr = self.def._transition(t);
+        let mut text = String::new();
+        if named && !is_numbered(tr.name) {
+            text.push_str(&format!("{}: ", tr.name));
+        }
+        if let Some(event) = tr.trigger {
+            text.push_str(&format!("on {} ", self.def._name(event)));
+        }
+        if let OptGuard::Guard(guard) = tr.guard {
+            text.push_str(&format!("[{}] ", guard.name));
+        }
+        if tr.kind != TransitionKind::Internal {
+            text.push_str(&format!("-> {} ", self.target_path(tr.source, tr.target)));
+        }
+        if let OptBehavior::Behavior(effect) = tr.effect {
+            text.push_str(&format!("/ {} ", effect.name));
+        }
+        format!("{};", text.trim_end())
+    }
+
+    /// The shortest path which `resolve` takes from the source to the
+    /// target, region names are left out where they may be.
+    fn target_path(&self, source: DbId, target: DbId) -> String {
+        let def = self.def;
+        let mut scope = source;
+        loop {
+            let mut below = Vec::new();
+            let mut d = target;
+            while d != scope && d != 0 {
+                below.push(d);
+                d = def.parents[d];
+            }
+            if d == scope && !below.is_empty() {
+                below.reverse();
+                let all: Vec<&str> = below.iter().map(|d| def._name(*d)).collect();
+                let vertices: Vec<&str> = below
+                    .iter()
+                    .filter(|d| def.elements[**d].element_type != ElementType::Region)
+                    .map(|d| def._name(*d))
+                    .collect();
+                for path in [vertices, all] {
+                    if resolve(def, source, &path) == Some(target) {
+                        return path.join("::");
+                    }
+                }
+            }
+            if scope == 0 {
+                break;
+            }
+            scope = def.parents[scope];
+        }
+        def._fullname(target).clone()
+    }
+}
@@ -0,0 +1,275 @@
+//! Editing a definition: removing, moving, renaming and retargeting.
+//!
+//! Each edit keeps the definition consistent: the parents and fullnames
+//! of the elements, the incoming and outgoing transitions of the
+//! vertices, the vertices of the regions and the regions of the states.
+//! A definition is not edited while it runs.
+//!
+//! Removing an element removes what it contains and the transitions
+//! from or to the vertices removed, then compacts the arena: elements
+//! created after a removed one move down, so their dbids change and
+//! should be looked up again by name.
+
+use crate::*;
+use alloc::vec;
+
+impl Db {
+    fn editable(&self) -> StateMachineResult<()> {
+        match self.status {
+            Status::Running => Err(StateMachineError::AlreadyRunning),
+            _ => Ok(()),
+        }
+    }
+
+    /// True if ancestor is dbid or one of the elements containing it.
+    fn within_element(&self, dbid: DbId, ancestor: DbId) -> bool {
+        let mut d = dbid;
+        loop {
+            if d == ancestor {
+                return true;
+            }
+            if d == 0 {
+                return false;
+            }
+            d = self.parents[d];
+        }
+    }
+
+    /// Compute the fullnames of all elements again, after a rename or
+    /// a move.
+    fn refresh_fullnames(&mut self) {
+        for dbid in 0..self.elements.len() {
+            let mut path = vec![self.names[dbid]];
+            let mut d = dbid;
+            while d != 0 {
+                d = self.parents[d];
+                path.push(self.names[d]);
+            }
+            path.reverse();
+            self.fullnames[dbid] = path.join("::");
+        }
+    }
+
+    /// Rename an element, the fullnames of the elements it contains
+    /// change with it. Names are unique among the vertices of a
+    /// region, the regions of a state, the transitions from or to a
+    /// vertex and the event types.
+    pub fn rename_element(&mut self, dbid: DbId, name: Name) -> StateMachineResult<()> {
+        self.is_valid_dbid(dbid)?;
+        self.editable()?;
+        let ele = self.elements[dbid];
+        let siblings: Vec<DbId> = match ele.element_type {
+            ElementType::StateMachine => Vec::new(),
+            ElementType::Vertex(_) => self._region(self.parents[dbid])?.subvertex.clone(),
+            ElementType::Region => self.regions(self.parents[dbid])?,
+            ElementType::Transition => {
+                let t = &self.transitions[ele.idx];
+                let mut transitions = self.vertices[self.vertex(t.source)?].outgoing.clone();
+                transitions.extend(&self.vertices[self.vertex(t.target)?].incoming);
+                transitions
+            }
+            ElementType::EventType => self.event_types.iter().map(|e| e.dbid).collect(),
+        };
+        let others: Vec<DbId> = siblings.into_iter().filter(|d| *d != dbid).collect();
+        self.is_duplicate(name, &others)?;
+        match ele.element_type {
+            ElementType::StateMachine => {
+                self.name = name;
+                self.state_machine.name = name;
+            }
+            ElementType::Vertex(_) => self.vertices[ele.idx].name = name,
+            ElementType::Region => self.regions[ele.idx].name = name,
+            ElementType::Transition => self.transitions[ele.idx].name = name,
+            ElementType::EventType => self.event_types[ele.idx].name = name,
+        }
+        self.names[dbid] = name;
+        self.refresh_fullnames();
+        Ok(())
+    }
+
+    /// Move a vertex, with the regions and vertices it contains, into
+    /// another region. Its outgoing transitions move with it, they
+    /// belong to the region of their source, while its incoming
+    /// transitions stay in the region of theirs.
+    pub fn move_vertex(
+        &mut self,
+        vertex: VertexDbId,
+        region: RegionDbId,
+    ) -> StateMachineResult<()> {
+        let v_idx = self.vertex(vertex)?;
+        let r_idx = self.region(region)?;
+        self.editable()?;
+        if self.within_element(region, vertex) {
+            return Err(StateMachineError::InvalidRegion(region));
+        }
+        let old = self.parents[vertex];
+        if old == region {
+            return Ok(());
+        }
+        self.is_duplicate(self.names[vertex], &self.regions[r_idx].subvertex)?;
+
+        let old_idx = self.elements[old].idx;
+        let from = &mut self.regions[old_idx];
+        from.subvertex.retain(|v| *v != vertex);
+        if from.initial_state == vertex {
+            from.initial_state = from.subvertex.first().copied().unwrap_or(0);
+        }
+        if from.active_state == vertex {
+            from.active_state = from.initial_state;
+        }
+        if from.history == vertex {
+            from.history = 0;
+        }
+        let to = &mut self.regions[r_idx];
+        if to.subvertex.is_empty() {
+            to.initial_state = vertex;
+            to.active_state = vertex;
+        }
+        to.subvertex.push(vertex);
+        self.vertices[v_idx].container = region;
+        self.parents[vertex] = region;
+        for t in self.vertices[v_idx].outgoing.clone() {
+            self.parents[t] = region;
+        }
+        self.refresh_fullnames();
+        Ok(())
+    }
+
+    /// Make a transition end in another vertex. An internal transition
+    /// must keep its source as target, see `set_transition_kind`.
+    pub fn retarget_transition(
+        &mut self,
+        transition: DbId,
+        target: VertexDbId,
+    ) -> StateMachineResult<()> {
+        let t_idx = self.transition(transition)?;
+        let new_idx = self.vertex(target)?;
+        self.editable()?;
+        let old_idx = self.vertex(self.transitions[t_idx].target)?;
+        if old_idx == new_idx {
+            return Ok(());
+        }
+        if self.transitions[t_idx].kind == TransitionKind::Internal {
+            return Err(StateMachineError::InvalidTransitionKind(transition));
+        }
+        self.is_duplicate(self.names[transition], &self.vertices[new_idx].incoming)?;
+        self.vertices[old_idx].incoming.retain(|t| *t != transition);
+        self.vertices[new_idx].incoming.push(transition);
+        self.transitions[t_idx].target = target;
+        Ok(())
+    }
+
+    /// Remove an element and what it contains: the regions of a state
+    /// and their vertices, the vertices of a region, the transitions
+    /// from and to the vertices removed, and for an event type the
+    /// transitions it triggers. The state machine itself is not
+    /// removed. The arena is compacted, see the module documentation.
+    pub fn remove_element(&mut self, dbid: DbId) -> StateMachineResult<()> {
+        self.is_valid_dbid(dbid)?;
+        self.editable()?;
+        if dbid == 0 {
+            return Err(StateMachineError::InvalidDbId(dbid));
+        }
+        let n = self.elements.len();
+        let mut removed: Vec<bool> = (0..n).map(|d| self.within_element(d, dbid)).collect();
+        for t in &self.transitions {
+            if removed[t.source] || removed[t.target] || t.trigger.is_some_and(|e| removed[e]) {
+                removed[t.dbid] = true;
+            }
+        }
+        self.compact(&removed);
+        Ok(())
+    }
+
+    /// Drop the removed elements and renumber the others, in order.
+    fn compact(&mut self, removed: &[bool]) {
+        let mut map: Vec<Option<DbId>> = Vec::with_capacity(removed.len());
+        let mut next = 0;
+        for gone in removed {
+            map.push(match gone {
+                true => None,
+                false => {
+                    next += 1;
+                    Some(next - 1)
+                }
+            });
+        }
+        // references to removed vertices in the regions become 0
+        let one = |d: DbId| map[d].unwrap_or(0);
+        let all = |list: &[DbId]| -> Vec<DbId> { list.iter().filter_map(|d| map[*d]).collect() };
+        let kept = |d: &DbId| !removed[*d];
+
+        let mut keep = removed.iter().map(|gone| !gone);
+        self.elements.retain(|_| keep.next().unwrap());
+        let mut keep = removed.iter().map(|gone| !gone);
+        self.parents.retain(|_| keep.next().unwrap());
+        let mut keep = removed.iter().map(|gone| !gone);
+        self.names.retain(|_| keep.next().unwrap());
+        let mut keep = removed.iter().map(|gone| !gone);
+        self.fullnames.retain(|_| keep.next().unwrap());
+        for p in self.parents.iter_mut() {
+            *p = one(*p);
+        }
+
+        self.states.retain(|s| kept(&s.dbid));
+        self.vertices.retain(|v| kept(&v.dbid));
+        self.regions.retain(|r| kept(&r.dbid));
+        self.transitions.retain(|t| kept(&t.dbid));
+        self.event_types.retain(|e| kept(&e.dbid));
+
+        self.state_machine.regions = all(&self.state_machine.regions);
+        for s in self.states.iter_mut() {
+            s.dbid = one(s.dbid);
+            s.regions = all(&s.regions);
+            s.region = s.region.and_then(|r| map[r]);
+            s.deferrable = all(&s.deferrable);
+        }
+        for (i, v) in self.vertices.iter_mut().enumerate() {
+            v.dbid = one(v.dbid);
+            v.container = one(v.container);
+            v.incoming = all(&v.incoming);
+            v.outgoing = all(&v.outgoing);
+            self.elements[v.dbid].idx = i;
+        }
+        for (i, r) in self.regions.iter_mut().enumerate() {
+            r.dbid = one(r.dbid);
+            r.container = match r.container {
+                Container::State(d) => Container::State(one(d)),
+                Container::StateMachine(d) => Container::StateMachine(one(d)),
+            };
+            r.initial_state = one(r.initial_state);
+            r.active_state = one(r.active_state);
+            r.history = one(r.history);
+            r.subvertex = all(&r.subvertex);
+            r.transition = all(&r.transition);
+            self.elements[r.dbid].idx = i;
+        }
+        for (i, t) in self.transitions.iter_mut().enumerate() {
+            t.dbid = one(t.dbid);
+            t.source = one(t.source);
+            t.target = one(t.target);
+            t.trigger = t.trigger.map(one);
+            self.elements[t.dbid].idx = i;
+        }
+        for (i, e) in self.event_types.iter_mut().enumerate() {
+            e.dbid = one(e.dbid);
+            self.elements[e.dbid].idx = i;
+        }
+        for (i, s) in self.states.iter().enumerate() {
+            let v_idx = self.elements[s.dbid].idx;
+            self.vertices[v_idx].idx = i;
+        }
+        for (dbid, ele) in self.elements.iter_mut().enumerate() {
+            ele.dbid = dbid;
+        }
+        self.completions.clear();
+        self.deferred = all(&self.deferred);
+        self.activities = all(&self.activities);
+        self.conditions = all(&self.conditions);
+        self.timers.retain(|t| kept(&t.state) && kept(&t.event));
+        for t in self.timers.iter_mut() {
+            t.state = one(t.state);
+            t.event = one(t.event);
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! The C API, with the `ffi` feature, declared in `include/rust_uml_sm.h`.
+//!
+//! ```text
+//! SmDefinition *door = sm_definition_load(text);
+//! if (!door) { fprintf(stderr, "%s\n", sm_last_error()); ... }
+//! SmInstance *sm = sm_instance_new(door);
+//! sm_instance_on_behavior(sm, "creak", creak, &motor);
+//! sm_instance_on_guard(sm, "unlocked", unlocked, &lock);
+//! sm_instance_start(sm);
+//! sm_instance_dispatch(sm, sm_definition_event(door, "open"));
+//! size_t active[8];
+//! size_t n = sm_instance_active_states(sm, active, 8);
+//! puts(sm_definition_fullname(door, active[0]));
+//! sm_instance_free(sm);
+//! sm_definition_free(door);
+//! ```
+//!
+//! A definition is loaded from the textual format and checked with
+//! `validate`, an instance is created from it and runs on its own. Ids
+//! are dbids, the same in a definition and its instances. Behaviors and
+//! guards are named in the model, a callback registered for a name is
+//! called with its `user_data`; without one a behavior does nothing and
+//! a guard is true.
+//!
+//! Functions returning a pointer return NULL on failure, those
+//! returning an `int` return a negative value, and `sm_last_error` gives
+//! the message of the failure. Strings are NUL-terminated UTF-8, those
+//! returned by a definition live as long as the definition.
+
+use crate::*;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_error(message: impl ToString) {
+    let message =
+        CString::new(message.to_string().replace('\0', " ")).expect("internal_error:906101");
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+}
+
+/// The string at `s`, NULL and invalid UTF-8 are errors.
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        set_error("null string");
+        return None;
+    }
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Some(s),
+        Err(err) => {
+            set_error(err);
+            None
+        }
+    }
+}
+
+/// A model, from which instances are created.
+pub struct SmDefinition {
+    model: String,
+    def: StateMachineDef,
+    names: Vec<CString>,
+    fullnames: Vec<CString>,
+}
+
+/// Performs a behavior, called with the `user_data` it was registered
+/// with.
+pub type SmBehaviorFn = Option<extern "C" fn(user_data: *mut c_void)>;
+
+/// Evaluates a guard, called with the `user_data` it was registered
+/// with.
+pub type SmGuardFn = Option<extern "C" fn(user_data: *mut c_void) -> bool>;
+
+/// The callbacks of an instance, by name.
+struct Callbacks {
+    behaviors: Vec<(String, SmBehaviorFn, *mut c_void)>,
+    guards: Vec<(String, SmGuardFn, *mut c_void)>,
+}
+
+impl Context for Callbacks {
+    fn behavior(&mut self, name: &str) {
+        if let Some((_, Some(f), user_data)) = self.behaviors.iter().find(|(n, ..)| n == name) {
+            f(*user_data)
+        }
+    }
+
+    fn guard(&mut self, name: &str) -> bool {
+        match self.guards.iter().find(|(n, ..)| n == name) {
+            Some((_, Some(f), user_data)) => f(*user_data),
+            _ => true,
+        }
+    }
+}
+
+/// A running copy of a definition.
+pub struct SmInstance {
+    def: StateMachineDef,
+    callbacks: Callbacks,
+}
+
+/// The status of an instance, see `Status`.
+#[repr(C)]
+pub enum SmStatus {
+    Idle,
+    Running,
+    Terminated,
+}
+
+/// The message of the last failure on this thread, empty if there was
+/// none. It lives until the next failure.
+#[no_mangle]
+pub extern "C" fn sm_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+/// Parse and check a model in the textual format.
+///
+/// # Safety
+///
+/// `text` is NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sm_definition_load(text: *const c_char) -> *mut SmDefinition {
+    let model = match str_arg(text) {
+        Some(model) => model,
+        None => return ptr::null_mut(),
+    };
+    let def = match dsl::parse(model).and_then(|def| def.validate().map(|_| def)) {
+        Ok(def) => def,
+        Err(err) => {
+            set_error(err);
+            return ptr::null_mut();
+        }
+    };
+    let c_string = |s: &str| CString::new(s).expect("internal_error:906102");
+    let names = def.names.iter().map(|n| c_string(n)).collect();
+    let fullnames = def.fullnames.iter().map(|n| c_string(n)).collect();
+    Box::into_raw(Box::new(SmDefinition {
+        model: model.to_string(),
+        def,
+        names,
+        fullnames,
+    }))
+}
+
+/// # Safety
+///
+/// `def` is NULL or was returned by `sm_definition_load`, and is not
+/// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn sm_definition_free(def: *mut SmDefinition) {
+    if !def.is_null() {
+        drop(Box::from_raw(def));
+    }
+}
+
+/// The id of an event type by name, -1 if there is none.
+///
+/// # Safety
+///
+/// `def` was returned by `sm_definition_load`, `name` is NULL or a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sm_definition_event(
+    def: *const SmDefinition,
+    name: *const c_char,
+) -> isize {
+    let def = &(*def).def;
+    match str_arg(name).map(|name| def.event_type(name)) {
+        Some(Some(event)) => event as isize,
+        Some(None) => {
+            set_error(StateMachineError::UnknownEvent(
+                CStr::from_ptr(name).to_string_lossy().into_owned(),
+            ));
+            -1
+        }
+        None => -1,
+    }
+}
+
+/// The id of an element by path, see `Db::find`, -1 if there is none.
+///
+/// # Safety
+///
+/// `def` was returned by `sm_definition_load`, `path` is NULL or a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sm_definition_find(
+    def: *const SmDefinition,
+    path: *const c_char,
+) -> isize {
+    let def = &(*def).def;
+    match str_arg(path).map(|path| def.find(path)) {
+        Some(Some(dbid)) => dbid as isize,
+        Some(None) => {
+            set_error(StateMachineError::UnknownPath(
+                CStr::from_ptr(path).to_string_lossy().into_owned(),
+            ));
+            -1
+        }
+        None => -1,
+    }
+}
+
+/// The name of an element, NULL if there is no such id.
+///
+/// # Safety
+///
+/// `def` was returned by `sm_definition_load`.
+#[no_mangle]
+pub unsafe extern "C" fn sm_definition_name(def: *const SmDefinition, id: usize) -> *const c_char {
+    let def = &*def;
+    match def.names.get(id) {
+        Some(name) => name.as_ptr(),
+        None => {
+            set_error(StateMachineError::InvalidDbId(id));
+            ptr::null()
+        }
+    }
+}
+
+/// The fullname of an element, such as `door::region_1::closed`, NULL
+/// if there is no such id.
+///
+/// # Safety
+///
+/// `def` was returned by `sm_definition_load`.
+#[no_mangle]
+pub unsafe extern "C" fn sm_definition_fullname(
+    def: *const SmDefinition,
+    id: usize,
+) -> *const c_char {
+    let def = &*def;
+    match def.fullnames.get(id) {
+        Some(name) => name.as_ptr(),
+        None => {
+            set_error(StateMachineError::InvalidDbId(id));
+            ptr::null()
+        }
+    }
+}
+
+/// A new instance of a definition, not started. It does not refer to
+/// the definition, which may be freed first.
+///
+/// # Safety
+///
+/// `def` was returned by `sm_definition_load`.
+#[no_mangle]
+pub unsafe extern "C" fn sm_instance_new(def: *const SmDefinition) -> *mut SmInstance {
+    Box::into_raw(Box::new(SmInstance {
+        def: dsl::parse(&(*def).model).expect("internal_error:906103"),
+        callbacks: Callbacks {
+            behaviors: Vec::new(),
+            guards: Vec::new(),
+        },
+    }))
+}
+
+/// # Safety
+///
+/// `sm` is NULL or was returned by `sm_instance_new`, and is not used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn sm_instance_free(sm: *mut SmInstance) {
+    if !sm.is_null() {
+        drop(Box::from_raw(sm));
+    }
+}
+
+/// Register the callback performing the behavior `name`, replacing the
+/// one registered before. NULL unregisters it.
+///
+/// # Safety
+///
+/// `sm` was returned by `sm_instance_new`, `name` is NULL or a
+/// NUL-terminated string, `f` can be called with `user_data` as long as
+/// it is registered.
+#[no_mangle]
+pub unsafe extern "C" fn sm_instance_on_behavior(
+    sm: *mut SmInstance,
+    name: *const c_char,
+    f: SmBehaviorFn,
+    user_data: *mut c_void,
+) -> c_int {
+    let name = match str_arg(name) {
+        Some(name) => name,
+        None => return -1,
+    };
+    let behaviors = &mut (*sm).callbacks.behaviors;
+    behaviors.retain(|(n, ..)| n != name);
+    if f.is_some() {
+        behaviors.push((name.to_string(), f, user_data));
+    }
+    0
+}
+
+/// Register the callback evaluating the guard `name`, replacing the one
+/// registered before. NULL unregisters it.
+///
+/// # Safety
+///
+/// `sm` was returned by `sm_instance_new`, `name` is NULL or a
+/// NUL-terminated string, `f` can be called with `user_data` as long as
+/// it is registered.
+#[no_mangle]
+pub unsafe extern "C" fn sm_instance_on_guard(
+    sm: *mut SmInstance,
+    name: *const c_char,
+    f: SmGuardFn,
+    user_data: *mut c_void,
+) -> c_int {
+    let name = match str_arg(name) {
+        Some(name) => name,
+        None => return -1,
+    };
+    let guards = &mut (*sm).callbacks.guards;
+    guards.retain(|(n, ..)| n != name);
+    if f.is_some() {
+        guards.push((name.to_string(), f, user_data));
+    }
+    0
+}
+
+fn result(result: StateMachineResult<bool>) -> c_int {
+    match result {
+        Ok(fired) => fired as c_int,
+        Err(err) => {
+            set_error(err);
+            -1
+        }
+    }
+}
+
+/// Start the instance, 0 on success.
+///
+/// # Safety
+///
+/// `sm` was returned by `sm_instance_new`.
+#[no_mangle]
+pub unsafe extern "C" fn sm_instance_start(sm: *mut SmInstance) -> c_int {
+    let sm = &mut *sm;
+    result(sm.def.start_with(&mut sm.callbacks).map(|_| false))
+}
+
+/// Stop the instance, 0 on success.
+///
+/// # Safety
+///
+/// `sm` was returned by `sm_instance_new`.
+#[no_mangle]
+pub unsafe extern "C" fn sm_instance_stop(sm: *mut SmInstance) -> c_int {
+    let sm = &mut *sm;
+    result(sm.def.stop_with(&mut sm.callbacks).map(|_| false))
+}
+
+/// Dispatch an event by id and run to completion, 1 if it fired a
+/// transition and 0 if not.
+///
+/// # Safety
+///
+/// `sm` was returned by `sm_instance_new`.
+#[no_mangle]
+pub unsafe extern "C" fn sm_instance_dispatch(sm: *mut SmInstance, event: usize) -> c_int {
+    let sm = &mut *sm;
+    result(sm.def.dispatch_with(event, &mut sm.callbacks))
+}
+
+/// Write the ids of the innermost active states to `ids`, at most
+/// `capacity` of them, and return how many there are.
+///
+/// # Safety
+///
+/// `sm` was returned by `sm_instance_new`, `ids` has room for
+/// `capacity` ids or is NULL if `capacity` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn sm_instance_active_states(
+    sm: *const SmInstance,
+    ids: *mut usize,
+    capacity: usize,
+) -> usize {
+    let active = (*sm).def.active_states();
+    for (i, dbid) in active.iter().take(capacity).enumerate() {
+        *ids.add(i) = *dbid;
+    }
+    active.len()
+}
+
+/// Whether the state or pseudostate `id` is active.
+///
+/// # Safety
+///
+/// `sm` was returned by `sm_instance_new`.
+#[no_mangle]
+pub unsafe extern "C" fn sm_instance_is_active(sm: *const SmInstance, id: usize) -> bool {
+    let def = &(*sm).def;
+    id < def.elements.len() && def.is_active(id)
+}
+
+/// # Safety
+///
+/// `sm` was returned by `sm_instance_new`.
+#[no_mangle]
+pub unsafe extern "C" fn sm_instance_status(sm: *const SmInstance) -> SmStatus {
+    match (*sm).def.status() {
+        Status::Idle => SmStatus::Idle,
+        Status::Running => SmStatus::Running,
+        Status::Terminated => SmStatus::Terminated,
+    }
+}
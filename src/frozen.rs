@@ -0,0 +1,417 @@
+//! A definition frozen into arrays of fixed size.
+//!
+//! ```text
+//! let def = dsl::parse(MODEL)?;
+//! // at most 8 vertices, 2 regions, 12 transitions and 4 event types
+//! let frozen: Frozen<8, 2, 12, 4> = def.freeze()?;
+//! // at most 4 deferred events
+//! let mut machine = frozen.machine::<4>();
+//! machine.start(&mut ctx)?;
+//! machine.dispatch(frozen.event("open").unwrap(), &mut ctx)?;
+//! ```
+//!
+//! `freeze` copies the definition and fails with
+//! `StateMachineError::CapacityExceeded` if it does not fit, and with
+//! `StateMachineError::Unsupported` if it has time or change events. The
+//! deferrable triggers of the states share a capacity of `T` of their
+//! own. Ids are the dbids of the definition, so the states and events
+//! may still be looked up in it. The `Machine` returned by `machine`
+//! runs with the semantics of the runtime and never allocates.
+
+use crate::compiled::{Definition, Element, Id, Kind, Machine};
+use crate::*;
+
+/// A range of one of the id arrays of `Frozen`
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: u16,
+    len: u16,
+}
+
+impl Span {
+    const EMPTY: Span = Span { start: 0, len: 0 };
+
+    fn of(self, ids: &[Id]) -> &[Id] {
+        &ids[self.start as usize..(self.start + self.len) as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FrozenVertex {
+    dbid: Id,
+    kind: Kind,
+    parent: Id,
+    name: Name,
+    entry: Option<Behavior>,
+    exit: Option<Behavior>,
+    activity: Option<Behavior>,
+    regions: Span,
+    outgoing: Span,
+    incoming: Span,
+    defer: Span,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FrozenRegion {
+    dbid: Id,
+    parent: Id,
+    name: Name,
+    initial: Id,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FrozenTransition {
+    dbid: Id,
+    parent: Id,
+    name: Name,
+    source: Id,
+    target: Id,
+    trigger: Id,
+    guard: Option<Guard>,
+    effect: Option<Behavior>,
+    kind: TransitionKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FrozenEvent {
+    dbid: Id,
+    name: Name,
+}
+
+/// A definition with at most S vertices, R regions, T transitions and E
+/// event types.
+#[derive(Debug, Clone)]
+pub struct Frozen<const S: usize, const R: usize, const T: usize, const E: usize> {
+    name: Name,
+    sm_regions: Span,
+    vertices: [FrozenVertex; S],
+    vertex_count: usize,
+    regions: [FrozenRegion; R],
+    region_count: usize,
+    transitions: [FrozenTransition; T],
+    transition_count: usize,
+    events: [FrozenEvent; E],
+    event_count: usize,
+    /// the regions of the state machine, then of each state
+    region_ids: [Id; R],
+    /// the outgoing transitions of each vertex, grouped by trigger
+    outgoing: [Id; T],
+    outgoing_triggers: [Id; T],
+    incoming: [Id; T],
+    defer: [Id; T],
+}
+
+/// Append ids to one of the arrays of `Frozen`
+fn append<const N: usize>(
+    array: &mut [Id; N],
+    len: &mut usize,
+    ids: impl IntoIterator<Item = DbId>,
+    what: &'static str,
+) -> StateMachineResult<Span> {
+    let start = *len;
+    for id in ids {
+        if *len == N {
+            return Err(StateMachineError::CapacityExceeded(what));
+        }
+        array[*len] = id as Id;
+        *len += 1;
+    }
+    Ok(Span {
+        start: start as u16,
+        len: (*len - start) as u16,
+    })
+}
+
+impl Db {
+    /// Fail on the first element the compiled engines cannot run.
+    pub(crate) fn check_compilable(&self) -> StateMachineResult<()> {
+        match self.event_types.iter().find(|ev| !ev.is_signal()) {
+            Some(ev) => Err(StateMachineError::Unsupported(ev.dbid)),
+            None => Ok(()),
+        }
+    }
+
+    /// Copy the definition into arrays of fixed size, see `frozen`.
+    pub fn freeze<const S: usize, const R: usize, const T: usize, const E: usize>(
+        &self,
+    ) -> StateMachineResult<Frozen<S, R, T, E>> {
+        if self.elements.len() > Id::MAX as usize {
+            return Err(StateMachineError::CapacityExceeded("ids"));
+        }
+        self.check_compilable()?;
+        let capacities = [
+            (self.vertices.len(), S, "states"),
+            (self.regions.len(), R, "regions"),
+            (self.transitions.len(), T, "transitions"),
+            (self.event_types.len(), E, "event types"),
+        ];
+        for (len, capacity, what) in capacities.iter() {
+            if len > capacity {
+                return Err(StateMachineError::CapacityExceeded(what));
+            }
+        }
+        let mut frozen = Frozen {
+            name: self.name,
+            sm_regions: Span::EMPTY,
+            vertices: [FrozenVertex {
+                dbid: 0,
+                kind: Kind::State,
+                parent: 0,
+                name: "",
+                entry: None,
+                exit: None,
+                activity: None,
+                regions: Span::EMPTY,
+                outgoing: Span::EMPTY,
+                incoming: Span::EMPTY,
+                defer: Span::EMPTY,
+            }; S],
+            vertex_count: 0,
+            regions: [FrozenRegion {
+                dbid: 0,
+                parent: 0,
+                name: "",
+                initial: 0,
+            }; R],
+            region_count: 0,
+            transitions: [FrozenTransition {
+                dbid: 0,
+                parent: 0,
+                name: "",
+                source: 0,
+                target: 0,
+                trigger: 0,
+                guard: None,
+                effect: None,
+                kind: TransitionKind::External,
+            }; T],
+            transition_count: 0,
+            events: [FrozenEvent { dbid: 0, name: "" }; E],
+            event_count: 0,
+            region_ids: [0; R],
+            outgoing: [0; T],
+            outgoing_triggers: [0; T],
+            incoming: [0; T],
+            defer: [0; T],
+        };
+        let (mut regions, mut outgoing, mut incoming, mut defer) = (0, 0, 0, 0);
+        frozen.sm_regions = append(
+            &mut frozen.region_ids,
+            &mut regions,
+            self.sm_regions(),
+            "regions",
+        )?;
+        for v in &self.vertices {
+            let kind = match v.vertex_type {
+                VertexType::State => Kind::State,
+                VertexType::FinalState => Kind::FinalState,
+                VertexType::InitialState => Kind::InitialState,
+                VertexType::Pseudostate(kind) => Kind::Pseudostate(kind),
+            };
+            let trigger = |t: &DbId| self._transition(*t).trigger.unwrap_or(0);
+            let mut triggers: Vec<DbId> = Vec::new();
+            for t in &v.outgoing {
+                if !triggers.contains(&trigger(t)) {
+                    triggers.push(trigger(t));
+                }
+            }
+            let grouped = triggers.iter().flat_map(|tr| {
+                v.outgoing
+                    .iter()
+                    .copied()
+                    .filter(move |t| trigger(t) == *tr)
+            });
+            let start = outgoing;
+            let out = append(&mut frozen.outgoing, &mut outgoing, grouped, "transitions")?;
+            for i in start..outgoing {
+                frozen.outgoing_triggers[i] = trigger(&(frozen.outgoing[i] as DbId)) as Id;
+            }
+            let mut vertex = FrozenVertex {
+                dbid: v.dbid as Id,
+                kind,
+                parent: self.parents[v.dbid] as Id,
+                name: self.names[v.dbid],
+                entry: None,
+                exit: None,
+                activity: None,
+                regions: Span::EMPTY,
+                outgoing: out,
+                incoming: append(
+                    &mut frozen.incoming,
+                    &mut incoming,
+                    v.incoming.iter().copied(),
+                    "transitions",
+                )?,
+                defer: Span::EMPTY,
+            };
+            if kind == Kind::State {
+                let s = self._state(v.dbid);
+                vertex.entry = s.entry;
+                vertex.exit = s.exit;
+                vertex.activity = s.do_while;
+                vertex.regions = append(
+                    &mut frozen.region_ids,
+                    &mut regions,
+                    s.regions.iter().copied(),
+                    "regions",
+                )?;
+                vertex.defer = append(
+                    &mut frozen.defer,
+                    &mut defer,
+                    s.deferrable.iter().copied(),
+                    "deferrable triggers",
+                )?;
+            }
+            frozen.vertices[frozen.vertex_count] = vertex;
+            frozen.vertex_count += 1;
+        }
+        for r in &self.regions {
+            let initial = r.subvertex.iter().copied().find(|v| {
+                self.elements[*v].element_type == ElementType::Vertex(VertexType::InitialState)
+            });
+            frozen.regions[frozen.region_count] = FrozenRegion {
+                dbid: r.dbid as Id,
+                parent: self.parents[r.dbid] as Id,
+                name: self.names[r.dbid],
+                initial: initial.unwrap_or(0) as Id,
+            };
+            frozen.region_count += 1;
+        }
+        for t in &self.transitions {
+            frozen.transitions[frozen.transition_count] = FrozenTransition {
+                dbid: t.dbid as Id,
+                parent: self.parents[t.dbid] as Id,
+                name: t.name,
+                source: t.source as Id,
+                target: t.target as Id,
+                trigger: t.trigger.unwrap_or(0) as Id,
+                guard: match t.guard {
+                    OptGuard::Guard(guard) => Some(guard),
+                    OptGuard::None => None,
+                },
+                effect: match t.effect {
+                    OptBehavior::Behavior(effect) => Some(effect),
+                    OptBehavior::None => None,
+                },
+                kind: t.kind,
+            };
+            frozen.transition_count += 1;
+        }
+        for ev in &self.event_types {
+            frozen.events[frozen.event_count] = FrozenEvent {
+                dbid: ev.dbid as Id,
+                name: ev.name,
+            };
+            frozen.event_count += 1;
+        }
+        Ok(frozen)
+    }
+}
+
+impl<const S: usize, const R: usize, const T: usize, const E: usize> Frozen<S, R, T, E> {
+    pub fn name(&self) -> Name {
+        self.name
+    }
+
+    /// Return a machine running this definition which keeps at most Q
+    /// deferred events.
+    pub fn machine<const Q: usize>(&self) -> Machine<'_, Self, S, R, T, Q> {
+        Machine::new(self)
+    }
+
+    /// Return the id of the event type with the given name.
+    pub fn event(&self, name: &str) -> Option<Id> {
+        self.events[..self.event_count]
+            .iter()
+            .find(|ev| ev.name == name)
+            .map(|ev| ev.dbid)
+    }
+}
+
+impl<const S: usize, const R: usize, const T: usize, const E: usize> Definition
+    for Frozen<S, R, T, E>
+{
+    fn element(&self, id: Id) -> Option<Element<'_>> {
+        if id == 0 {
+            return Some(Element {
+                kind: Kind::StateMachine,
+                name: self.name,
+                regions: self.sm_regions.of(&self.region_ids),
+                ..Element::NONE
+            });
+        }
+        let vertices = &self.vertices[..self.vertex_count];
+        if let Ok(i) = vertices.binary_search_by_key(&id, |v| v.dbid) {
+            let v = &vertices[i];
+            return Some(Element {
+                kind: v.kind,
+                parent: v.parent,
+                name: v.name,
+                regions: v.regions.of(&self.region_ids),
+                entry: v.entry,
+                exit: v.exit,
+                activity: v.activity,
+                defer: v.defer.of(&self.defer),
+                outgoing: v.outgoing.of(&self.outgoing),
+                incoming: v.incoming.of(&self.incoming),
+                ..Element::NONE
+            });
+        }
+        let regions = &self.regions[..self.region_count];
+        if let Ok(i) = regions.binary_search_by_key(&id, |r| r.dbid) {
+            let r = &regions[i];
+            return Some(Element {
+                kind: Kind::Region,
+                parent: r.parent,
+                name: r.name,
+                index: i as Id,
+                initial: r.initial,
+                ..Element::NONE
+            });
+        }
+        let transitions = &self.transitions[..self.transition_count];
+        if let Ok(i) = transitions.binary_search_by_key(&id, |t| t.dbid) {
+            let t = &transitions[i];
+            return Some(Element {
+                kind: Kind::Transition,
+                parent: t.parent,
+                name: t.name,
+                index: i as Id,
+                source: t.source,
+                target: t.target,
+                trigger: t.trigger,
+                guard: t.guard,
+                effect: t.effect,
+                transition: t.kind,
+                ..Element::NONE
+            });
+        }
+        let events = &self.events[..self.event_count];
+        if let Ok(i) = events.binary_search_by_key(&id, |ev| ev.dbid) {
+            return Some(Element {
+                kind: Kind::EventType,
+                name: events[i].name,
+                ..Element::NONE
+            });
+        }
+        None
+    }
+
+    fn triggered(&self, vertex: Id, event: Id) -> &[Id] {
+        let vertices = &self.vertices[..self.vertex_count];
+        let out = match vertices.binary_search_by_key(&vertex, |v| v.dbid) {
+            Ok(i) => vertices[i].outgoing,
+            Err(_) => return &[],
+        };
+        let triggers = out.of(&self.outgoing_triggers);
+        let start = match triggers.iter().position(|t| *t == event) {
+            Some(start) => start,
+            None => return &[],
+        };
+        let len = triggers[start..]
+            .iter()
+            .take_while(|t| **t == event)
+            .count();
+        &out.of(&self.outgoing)[start..start + len]
+    }
+}
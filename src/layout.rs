@@ -0,0 +1,443 @@
+//! Placement of the vertices and regions of a definition on a plane,
+//! for the diagrams drawn without external tools.
+//!
+//! The state machine and the composite states are boxes with their name
+//! and behaviors on top and their regions below, one under the other.
+//! The vertices of a region are placed on a grid as square as possible,
+//! in the order they were added, with room between them for the
+//! transitions and their labels. Sizes are estimated from the number of
+//! characters of the texts, coordinates are whole pixels.
+//!
+//! A transition is an [`Edge`], a straight line between the borders of
+//! its source and target, bent into a curve when it would cross another
+//! vertex or when other transitions join the same vertices. Its label
+//! is put along the edge where it meets no other label nor vertex.
+//! Transitions from a vertex to itself are loops on its right side.
+
+use crate::*;
+use alloc::vec;
+
+/// Width of a character of the text, estimated.
+pub(crate) const CHAR_WIDTH: i32 = 7;
+/// Height of a line of text.
+pub(crate) const LINE_HEIGHT: i32 = 16;
+const PADDING: i32 = 12;
+/// Room between the vertices of a region.
+const GAP: i32 = 60;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn right(&self) -> i32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    /// Whether other lies within, borders included.
+    pub fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.right() <= self.right()
+            && other.bottom() <= self.bottom()
+    }
+
+    /// Whether the insides of both rectangles meet.
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+}
+
+/// The path of a transition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    /// Where the transition leaves its source.
+    pub start: (i32, i32),
+    /// Where the transition enters its target.
+    pub end: (i32, i32),
+    /// The control points of the Bézier curve from start to end, none
+    /// for a straight line.
+    pub controls: Vec<(i32, i32)>,
+    /// The place of the label, empty without label.
+    pub label: Rect,
+}
+
+impl Edge {
+    /// A point of the edge, at `t` sixteenths of the way.
+    pub fn at(&self, t: i32) -> (i32, i32) {
+        let u = 16 - t;
+        match self.controls[..] {
+            [] => (
+                (self.start.0 * u + self.end.0 * t) / 16,
+                (self.start.1 * u + self.end.1 * t) / 16,
+            ),
+            [c] => (
+                (self.start.0 * u * u + 2 * c.0 * u * t + self.end.0 * t * t) / 256,
+                (self.start.1 * u * u + 2 * c.1 * u * t + self.end.1 * t * t) / 256,
+            ),
+            [c1, c2, ..] => (
+                (self.start.0 * u * u * u
+                    + 3 * c1.0 * u * u * t
+                    + 3 * c2.0 * u * t * t
+                    + self.end.0 * t * t * t)
+                    / 4096,
+                (self.start.1 * u * u * u
+                    + 3 * c1.1 * u * u * t
+                    + 3 * c2.1 * u * t * t
+                    + self.end.1 * t * t * t)
+                    / 4096,
+            ),
+        }
+    }
+}
+
+/// The rectangles of the state machine, its regions and vertices, and
+/// the edges of the transitions.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    rects: Vec<Option<Rect>>,
+    edges: Vec<Option<Edge>>,
+}
+
+impl Layout {
+    pub fn new(def: &StateMachineDef) -> Layout {
+        let mut sizes = vec![(0, 0); def.elements.len()];
+        size_of(def, 0, &mut sizes);
+        let mut layout = Layout {
+            rects: vec![None; def.elements.len()],
+            edges: vec![None; def.elements.len()],
+        };
+        layout.place(def, 0, 0, 0, &sizes);
+        layout.route(def);
+        layout
+    }
+
+    /// The edge of a transition, internal transitions have none.
+    pub fn edge(&self, transition: DbId) -> Option<&Edge> {
+        self.edges.get(transition).and_then(Option::as_ref)
+    }
+
+    /// The rectangle of a vertex, region or of the state machine, dbid
+    /// 0, which encloses all the others.
+    pub fn rect(&self, dbid: DbId) -> Option<Rect> {
+        self.rects.get(dbid).copied().flatten()
+    }
+
+    pub fn width(&self) -> i32 {
+        self.rects[0].map_or(0, |r| r.width)
+    }
+
+    pub fn height(&self) -> i32 {
+        self.rects[0].map_or(0, |r| r.height)
+    }
+
+    /// Place a vertex, or the state machine, and what it contains.
+    fn place(&mut self, def: &StateMachineDef, v: DbId, x: i32, y: i32, sizes: &[(i32, i32)]) {
+        let (width, height) = sizes[v];
+        self.rects[v] = Some(Rect {
+            x,
+            y,
+            width,
+            height,
+        });
+        let regions = def.regions(v).unwrap_or_default();
+        if regions.is_empty() {
+            return;
+        }
+        let mut y = y + title_height(def, v);
+        for r in regions {
+            self.rects[r] = Some(Rect {
+                x,
+                y,
+                width,
+                height: sizes[r].1,
+            });
+            let (columns, rows) = grid(def, r, sizes);
+            let mut top = y + PADDING;
+            for (row, row_height) in rows.iter().enumerate() {
+                let mut left = x + PADDING + (width - sizes[r].0) / 2;
+                for (column, column_width) in columns.iter().enumerate() {
+                    if let Some(v) = cell(def, r, columns.len(), row, column) {
+                        let (w, h) = sizes[v];
+                        self.place(
+                            def,
+                            v,
+                            left + (column_width - w) / 2,
+                            top + (row_height - h) / 2,
+                            sizes,
+                        );
+                    }
+                    left += column_width + GAP;
+                }
+                top += row_height + GAP;
+            }
+            y += sizes[r].1;
+        }
+    }
+}
+
+/// The lines of text on top of a state: its name, behaviors, deferred
+/// events and internal transitions.
+pub(crate) fn title(def: &StateMachineDef, v: DbId) -> Vec<String> {
+    let mut lines = vec![def._name(v).to_string()];
+    if def.elements[v].element_type == ElementType::Vertex(VertexType::State) {
+        lines.extend(render::state_lines(def, v));
+    }
+    lines
+}
+
+fn title_height(def: &StateMachineDef, v: DbId) -> i32 {
+    PADDING + LINE_HEIGHT * title(def, v).len() as i32
+}
+
+/// The vertex in a cell of the grid of a region.
+fn cell(def: &StateMachineDef, r: DbId, columns: usize, row: usize, column: usize) -> Option<DbId> {
+    def.subvertices(r)
+        .unwrap_or_default()
+        .get(row * columns + column)
+        .copied()
+}
+
+/// The widths of the columns and the heights of the rows of a region.
+fn grid(def: &StateMachineDef, r: DbId, sizes: &[(i32, i32)]) -> (Vec<i32>, Vec<i32>) {
+    let vertices = def.subvertices(r).unwrap_or_default();
+    let mut columns = 1;
+    while columns * columns < vertices.len() {
+        columns += 1;
+    }
+    let mut widths = vec![0; columns.min(vertices.len())];
+    let mut heights = vec![0; vertices.len().div_ceil(columns)];
+    for (i, v) in vertices.iter().enumerate() {
+        widths[i % columns] = widths[i % columns].max(sizes[*v].0);
+        heights[i / columns] = heights[i / columns].max(sizes[*v].1);
+    }
+    (widths, heights)
+}
+
+fn span(lengths: &[i32]) -> i32 {
+    lengths.iter().sum::<i32>() + GAP * (lengths.len() as i32 - 1).max(0)
+}
+
+/// Compute the sizes of a vertex and of what it contains.
+fn size_of(def: &StateMachineDef, v: DbId, sizes: &mut [(i32, i32)]) -> (i32, i32) {
+    let size = match def.elements[v].element_type {
+        ElementType::Vertex(VertexType::InitialState) => (16, 16),
+        ElementType::Vertex(VertexType::FinalState) => (22, 22),
+        ElementType::Vertex(VertexType::Pseudostate(kind)) => match kind {
+            PseudostateKind::Fork | PseudostateKind::Join => (60, 8),
+            PseudostateKind::Choice | PseudostateKind::Junction => (24, 24),
+            _ => (22, 22),
+        },
+        _ => {
+            let title = title(def, v);
+            let text = title.iter().map(|l| l.chars().count()).max().unwrap_or(0) as i32;
+            let mut width = (text * CHAR_WIDTH + 2 * PADDING).max(60);
+            let mut height = title_height(def, v).max(36);
+            let regions = def.regions(v).unwrap_or_default();
+            if !regions.is_empty() {
+                height = title_height(def, v);
+            }
+            for r in regions {
+                for sub in def.subvertices(r).unwrap_or_default() {
+                    size_of(def, sub, sizes);
+                }
+                let (columns, rows) = grid(def, r, sizes);
+                let region = (span(&columns) + 2 * PADDING, span(&rows) + 2 * PADDING);
+                sizes[r] = region;
+                width = width.max(region.0);
+                height += region.1;
+            }
+            (width, height)
+        }
+    };
+    sizes[v] = size;
+    size
+}
+
+/// The point where the segment from the center of a rectangle to a
+/// point leaves the rectangle.
+pub(crate) fn border(rect: Rect, toward: (i32, i32)) -> (i32, i32) {
+    let (cx, cy) = rect.center();
+    let (dx, dy) = (toward.0 - cx, toward.1 - cy);
+    let (hw, hh) = (rect.width / 2, rect.height / 2);
+    if dx == 0 && dy == 0 {
+        return (cx, cy);
+    }
+    let x = if dx < 0 { rect.x } else { rect.right() };
+    let y = if dy < 0 { rect.y } else { rect.bottom() };
+    if dx.abs() * hh >= dy.abs() * hw {
+        (x, cy + dy * hw / dx.abs())
+    } else {
+        (cx + dx * hh / dy.abs(), y)
+    }
+}
+
+fn isqrt(n: i32) -> i32 {
+    let mut x = 0;
+    while (x + 1) * (x + 1) <= n {
+        x += 1;
+    }
+    x
+}
+
+/// Offsets from the straight line tried for a curve, in pixels at its
+/// middle.
+const BENDS: [i32; 9] = [0, 40, -40, 80, -80, 120, -120, 160, -160];
+/// Places along an edge tried for its label, in sixteenths.
+const LABEL_PLACES: [i32; 5] = [8, 6, 10, 4, 12];
+
+impl Layout {
+    /// The vertices an edge from source to target must not cross: all
+    /// but them and the states enclosing them.
+    fn obstacles(&self, def: &StateMachineDef, source: DbId, target: DbId) -> Vec<Rect> {
+        def.iter_vertices()
+            .filter(|v| !def.ancestor(source, *v) && !def.ancestor(target, *v))
+            .filter_map(|v| self.rect(v))
+            .collect()
+    }
+
+    /// An edge from source to target bent by `bend` pixels to its left.
+    fn bent(&self, source: Rect, target: Rect, bend: i32) -> Edge {
+        let (s, t) = (source.center(), target.center());
+        if source.contains(&target) || target.contains(&source) {
+            // in or out of an enclosing state, through its bottom
+            let (inner, outer) = match source.contains(&target) {
+                true => (target, source),
+                false => (source, target),
+            };
+            let x = inner.center().0 + bend / 4;
+            let (a, b) = ((x, inner.bottom()), (x, outer.bottom()));
+            let (start, end) = if inner == source { (a, b) } else { (b, a) };
+            return Edge {
+                start,
+                end,
+                controls: Vec::new(),
+                label: Rect::default(),
+            };
+        }
+        if bend == 0 {
+            return Edge {
+                start: border(source, t),
+                end: border(target, s),
+                controls: Vec::new(),
+                label: Rect::default(),
+            };
+        }
+        let (dx, dy) = (t.0 - s.0, t.1 - s.1);
+        let length = isqrt(dx * dx + dy * dy).max(1);
+        // the middle of a quadratic curve is half way to its control
+        let control = (
+            (s.0 + t.0) / 2 + dy * 2 * bend / length,
+            (s.1 + t.1) / 2 - dx * 2 * bend / length,
+        );
+        Edge {
+            start: border(source, control),
+            end: border(target, control),
+            controls: vec![control],
+            label: Rect::default(),
+        }
+    }
+
+    fn crosses(edge: &Edge, obstacles: &[Rect]) -> bool {
+        (1..16).any(|t| {
+            let (x, y) = edge.at(t);
+            let point = Rect {
+                x,
+                y,
+                width: 1,
+                height: 1,
+            };
+            obstacles.iter().any(|r| r.overlaps(&point))
+        })
+    }
+
+    /// Route the transitions and place their labels.
+    fn route(&mut self, def: &StateMachineDef) {
+        let mut labels: Vec<Rect> = Vec::new();
+        let mut loops = vec![0; def.elements.len()];
+        for tr in &def.transitions {
+            let (Some(source), Some(target)) = (self.rect(tr.source), self.rect(tr.target)) else {
+                continue;
+            };
+            if tr.kind == TransitionKind::Internal {
+                continue;
+            }
+            let mut edge = if tr.source == tr.target {
+                // loops grow as there are more on the vertex
+                let size = 30 + 20 * loops[tr.source];
+                loops[tr.source] += 1;
+                let (x, y) = (source.right(), source.center().1);
+                Edge {
+                    start: (x, y - 8),
+                    end: (x, y + 8),
+                    controls: vec![(x + size, y - size), (x + size, y + size)],
+                    label: Rect::default(),
+                }
+            } else {
+                // the transitions between the same vertices are spread
+                // on both sides of the straight line
+                let (low, high) = (tr.source.min(tr.target), tr.source.max(tr.target));
+                let siblings: Vec<DbId> = def
+                    .transitions
+                    .iter()
+                    .filter(|o| o.kind != TransitionKind::Internal)
+                    .filter(|o| (o.source.min(o.target), o.source.max(o.target)) == (low, high))
+                    .map(|o| o.dbid)
+                    .collect();
+                let index = siblings.iter().position(|t| *t == tr.dbid).unwrap_or(0) as i32;
+                let mut spread = (2 * index - (siblings.len() as i32 - 1)) * 20;
+                if tr.source != low {
+                    spread = -spread;
+                }
+                let obstacles = self.obstacles(def, tr.source, tr.target);
+                BENDS
+                    .iter()
+                    .map(|bend| self.bent(source, target, spread + bend))
+                    .find(|edge| !Self::crosses(edge, &obstacles))
+                    .unwrap_or_else(|| self.bent(source, target, spread))
+            };
+            let text = render::label(def, tr.dbid);
+            if !text.is_empty() {
+                let obstacles = self.obstacles(def, tr.source, tr.target);
+                let (width, height) = (text.chars().count() as i32 * CHAR_WIDTH, LINE_HEIGHT);
+                // above the edge, or below
+                let place = |(t, below): (i32, bool)| {
+                    let (x, y) = edge.at(t);
+                    Rect {
+                        x: x - width / 2,
+                        y: if below { y + 2 } else { y - height - 2 },
+                        width,
+                        height,
+                    }
+                };
+                let free = |r: &Rect| {
+                    !labels.iter().any(|l| l.overlaps(r))
+                        && !obstacles.iter().any(|o| o.overlaps(r))
+                };
+                edge.label = LABEL_PLACES
+                    .iter()
+                    .flat_map(|t| [(*t, false), (*t, true)])
+                    .map(place)
+                    .find(free)
+                    .unwrap_or_else(|| place((8, false)));
+                labels.push(edge.label);
+            }
+            self.edges[tr.dbid] = Some(edge);
+        }
+    }
+}
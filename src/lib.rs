@@ -1,6 +1,49 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 // use std::collections::HashMap;
-use std::fmt;
+#[doc(hidden)]
+pub extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+pub use rust_uml_sm_derive::behaviors;
+pub use rust_uml_sm_derive::statemachine;
 pub use rust_uml_sm_derive::StateMachine;
+
+#[cfg(feature = "async")]
+pub mod activity;
+pub mod builder;
+pub mod change;
+#[cfg(feature = "std")]
+pub mod codegen;
+pub mod compiled;
+pub mod diff;
+pub mod dsl;
+pub mod edit;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod frozen;
+pub mod layout;
+pub mod migrate;
+pub mod observer;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod query;
+pub mod render;
+#[cfg(feature = "std")]
+pub mod repl;
+pub mod runtime;
+pub mod snapshot;
+pub mod timer;
+pub mod trace;
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
+pub use observer::{Observation, Observer};
+pub use runtime::{Context, Status};
+pub use timer::{Clock, TimeEvent};
 // use std::process;
 // use std::io::{self, Write};
 // use std::string::ToString;
@@ -25,16 +68,50 @@ pub enum StateMachineError {
     ContainsNoRegions(DbId),
     ContainsMultipleRegions(DbId),
     CannotAddState(DbId),
+    InvalidEventType(DbId),
+    InvalidTransitionKind(DbId),
+    /// Represents a syntax or name resolution error in a textual definition.
+    ParseError(dsl::ParseError),
+    /// An event was sent to a state machine which is not running.
+    NotRunning,
+    AlreadyRunning,
+    /// A choice, junction or other pseudostate was reached but none of
+    /// its outgoing transitions is enabled.
+    NoEnabledTransition(DbId),
+    /// Completion transitions, or change events, kept firing without
+    /// reaching a stable configuration.
+    CompletionLoop(DbId),
+    UnknownEvent(String),
+    /// A fixed capacity of a compiled state machine was exceeded.
+    CapacityExceeded(&'static str),
+    /// A definition uses something the compiled and frozen engines do
+    /// not run.
+    Unsupported(DbId),
+    /// A line of a textual trace could not be read.
+    InvalidTrace(usize),
+    /// A snapshot names an element the definition does not have, or an
+    /// active configuration it cannot be in.
+    InvalidSnapshot(String),
+    /// A snapshot was taken with a definition with another fingerprint,
+    /// it may be migrated.
+    FingerprintMismatch(u64),
+    /// No element is found at a path of names.
+    UnknownPath(String),
+    /// A name cannot be turned into a Rust identifier, see `codegen`.
+    InvalidIdentifier(String),
 
     /// Represents a failure to read from input.
+    #[cfg(feature = "std")]
     ReadError {
         source: std::io::Error,
     },
 
     /// Represents all other cases of `std::io::Error`.
+    #[cfg(feature = "std")]
     IOError(std::io::Error),
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for StateMachineError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
@@ -46,19 +123,92 @@ impl std::error::Error for StateMachineError {
     }
 }
 
-impl std::fmt::Display for StateMachineError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl fmt::Display for StateMachineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             StateMachineError::StateAlreadyExists(name) => {
                 write!(f, "State {} already defined", name)
             }
+            StateMachineError::Duplicate(name) => write!(f, "Duplicate name {}", name),
+            StateMachineError::VertexAlreadyAdded(name) => {
+                write!(f, "Vertex {} already added", name)
+            }
+            StateMachineError::VertexAlreadyInDifferentRegion(name) => {
+                write!(f, "Vertex {} already in a different region", name)
+            }
+            StateMachineError::ElementNotFound(dbid) => write!(f, "Element {} not found", dbid),
+            StateMachineError::InvalidState(dbid) => write!(f, "Element {} is not a state", dbid),
+            StateMachineError::InvalidVertex(dbid) => {
+                write!(f, "Element {} is not a vertex", dbid)
+            }
+            StateMachineError::InvalidRegion(dbid) => {
+                write!(f, "Element {} is not a region", dbid)
+            }
+            StateMachineError::NoInitialState(dbid) => {
+                write!(f, "Region {} has no initial state", dbid)
+            }
+            StateMachineError::InvalidDbId(dbid) => write!(f, "Invalid dbid {}", dbid),
+            StateMachineError::NoCommonAncestor(a, b) => {
+                write!(f, "Elements {} and {} have no common ancestor", a, b)
+            }
+            StateMachineError::RegionAlreadyExists(name) => {
+                write!(f, "Region {} already defined", name)
+            }
+            StateMachineError::ContainsNoRegions(dbid) => {
+                write!(f, "Element {} contains no regions", dbid)
+            }
+            StateMachineError::ContainsMultipleRegions(dbid) => {
+                write!(f, "Element {} contains several regions", dbid)
+            }
+            StateMachineError::CannotAddState(dbid) => {
+                write!(f, "Cannot add a state to element {}", dbid)
+            }
+            StateMachineError::InvalidEventType(dbid) => {
+                write!(f, "Element {} is not an event type", dbid)
+            }
+            StateMachineError::InvalidTransitionKind(dbid) => {
+                write!(f, "Invalid kind for transition {}", dbid)
+            }
+            StateMachineError::NoEnabledTransition(dbid) => {
+                write!(f, "No enabled transition from vertex {}", dbid)
+            }
+            StateMachineError::CompletionLoop(dbid) => {
+                write!(f, "Completion loop at element {}", dbid)
+            }
+            StateMachineError::ParseError(ref err) => err.fmt(f),
+            StateMachineError::NotRunning => write!(f, "State machine is not running"),
+            StateMachineError::AlreadyRunning => write!(f, "State machine is already running"),
+            StateMachineError::UnknownEvent(ref name) => write!(f, "Unknown event {}", name),
+            StateMachineError::CapacityExceeded(what) => {
+                write!(f, "Capacity exceeded for {}", what)
+            }
+            StateMachineError::Unsupported(dbid) => {
+                write!(
+                    f,
+                    "Element {} is not supported by the compiled engines",
+                    dbid
+                )
+            }
+            StateMachineError::InvalidTrace(line) => write!(f, "Invalid trace at line {}", line),
+            StateMachineError::InvalidSnapshot(ref name) => write!(f, "Invalid snapshot: {}", name),
+            StateMachineError::FingerprintMismatch(fingerprint) => write!(
+                f,
+                "Snapshot of another definition, fingerprint {:016x}",
+                fingerprint
+            ),
+            StateMachineError::UnknownPath(ref path) => write!(f, "Unknown path {}", path),
+            StateMachineError::InvalidIdentifier(ref name) => {
+                write!(f, "Name {} is not a valid Rust identifier", name)
+            }
+            #[cfg(feature = "std")]
             StateMachineError::ReadError { .. } => write!(f, "Read error"),
+            #[cfg(feature = "std")]
             StateMachineError::IOError(ref err) => err.fmt(f),
-            _ => write!(f, "Unhandled error"),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for StateMachineError {
     fn from(err: std::io::Error) -> StateMachineError {
         StateMachineError::IOError(err)
@@ -66,6 +216,38 @@ impl From<std::io::Error> for StateMachineError {
 }
 
 type Name = &'static str;
+
+#[cfg(feature = "std")]
+static INTERNED: std::sync::Mutex<alloc::collections::BTreeSet<Name>> =
+    std::sync::Mutex::new(alloc::collections::BTreeSet::new());
+
+/// Names are `&'static str` throughout the definition, names which are
+/// only known at runtime (e.g. read from a file) are leaked to obtain one,
+/// once per distinct name. With std the names are shared by all the
+/// definitions, without it each definition keeps its own, no lock is
+/// needed then.
+#[derive(Debug, Default)]
+pub(crate) struct Interned(#[cfg(not(feature = "std"))] alloc::collections::BTreeSet<Name>);
+
+impl Interned {
+    pub(crate) fn intern(&mut self, name: &str) -> Name {
+        #[cfg(feature = "std")]
+        let mut names = INTERNED
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        #[cfg(not(feature = "std"))]
+        let names = &mut self.0;
+        match names.get(name) {
+            Some(interned) => interned,
+            None => {
+                let leaked: Name = Box::leak(name.to_string().into_boxed_str());
+                names.insert(leaked);
+                leaked
+            }
+        }
+    }
+}
+
 pub type StateMachineResult<T> = Result<T, StateMachineError>;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -73,11 +255,13 @@ pub enum VertexType {
     State,
     InitialState,
     FinalState,
+    /// Any pseudostate other than the initial pseudostate
+    Pseudostate(PseudostateKind),
 }
 
 /// Spec has lowercase for some of these Enums
-#[derive(Debug, Clone, Copy)]
-enum PseudostateKind {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PseudostateKind {
     EntryPoint,
     ExitPoint,
     Initial,
@@ -90,6 +274,16 @@ enum PseudostateKind {
     Choice,
 }
 
+/// External transitions exit their source state, internal transitions
+/// leave the active state configuration untouched and local transitions
+/// do not exit the containing source state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransitionKind {
+    External,
+    Internal,
+    Local,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 enum ElementType {
     Vertex(VertexType),
@@ -120,18 +314,21 @@ impl Element {
 type RegionIdx = usize;
 type RegionDbId = usize;
 /// A DbId is an index into elements
-type DbId = usize;
+pub type DbId = usize;
 /// An Idx is an index into states|regions|vertices|triggers|...
 /// For example, elements[dbid] -> idx1 -> vertices[idx1].idx2 -> states[idx2]
+#[allow(dead_code)]
 type Idx = usize;
 type StateIdx = usize;
 type StateDbId = usize;
 type VertexIdx = usize;
 type VertexDbId = usize;
 type TriggerDbId = usize;
+#[allow(dead_code)]
 type TriggerIdx = usize;
 type TransitionIdx = usize;
 
+#[allow(dead_code)]
 #[derive(Debug)]
 /// elements:
 /// parents:  Since this is the same size as elements,
@@ -151,10 +348,25 @@ pub struct Db {
     fullnames: Vec<String>,
     vertices: Vec<VertexDef>,
     regions: Vec<Region>,
+    // execution state, see runtime
+    status: Status,
+    completions: VecDeque<DbId>,
+    deferred: Vec<DbId>,
+    /// States whose do-activity goes on in the background.
+    activities: Vec<DbId>,
+    /// Time events of the active states waiting for their deadline.
+    timers: Vec<timer::Timer>,
+    /// Change events whose condition was true when last evaluated.
+    conditions: Vec<DbId>,
+    clock: timer::Slot,
+    observer: observer::Slot,
+    tracer: trace::Tracer,
+    interned: Interned,
 }
 
 pub type StateMachineDef = Db;
 
+#[allow(dead_code)]
 trait NewStateMachine {
     fn new_statemachine();
 }
@@ -186,6 +398,16 @@ impl Db {
             fullnames: Vec::new(),
             state_machine: StateMachineRoot::new(name, 0),
             regions: Vec::new(),
+            status: Status::Idle,
+            completions: VecDeque::new(),
+            deferred: Vec::new(),
+            activities: Vec::new(),
+            timers: Vec::new(),
+            conditions: Vec::new(),
+            clock: timer::Slot::default(),
+            observer: observer::Slot::default(),
+            tracer: trace::Tracer::default(),
+            interned: Interned::default(),
         };
         let dbid = db.new_element(name, 0, 0, ElementType::StateMachine);
         db.add_region("region_1", dbid)
@@ -211,9 +433,18 @@ impl Db {
             self.fullnames
                 .push(self.fullnames[parent].clone() + "::" + name);
         }
+        self.notify(|db| Observation::ElementAdded {
+            element: dbid,
+            fullname: &db.fullnames[dbid],
+        });
         dbid
     }
 
+    /// A name only known at runtime, see `Interned`.
+    pub(crate) fn intern(&mut self, name: &str) -> Name {
+        self.interned.intern(name)
+    }
+
     /// Used to rename the default region name
     fn rename(&mut self, dbid: DbId, name: Name) {
         self.names[dbid] = name;
@@ -327,6 +558,7 @@ impl Db {
     }
 
     /// Pretty print an element (region/state/...)
+    #[cfg(feature = "std")]
     pub fn print(&self, dbid: DbId) -> StateMachineResult<()> {
         println!("{}", self.to_string(dbid)?);
         Ok(())
@@ -335,7 +567,6 @@ impl Db {
     /// Return true if the given state does not have any regions
     pub fn is_simple(&self, dbid: DbId) -> StateMachineResult<bool> {
         let s_idx = self.state(dbid)?;
-        println!("{:#?}", self.states[s_idx]);
         Ok(self.states[s_idx].is_simple())
     }
 
@@ -366,12 +597,13 @@ impl Db {
         Ok(self.names[dbid])
     }
 
-    fn is_duplicate(&self, name: Name, vec: &Vec<DbId>) -> StateMachineResult<()> {
+    fn is_duplicate(&self, name: Name, vec: &[DbId]) -> StateMachineResult<()> {
         match vec.iter().any(|&i| match self.elements[i].element_type {
             // ElementType::Transition => self.transitions[self.elements[i].idx].name == name,
             ElementType::Vertex(_) => self.vertices[self.elements[i].idx].name == name,
             ElementType::Region => self.regions[self.elements[i].idx].name == name,
             ElementType::Transition => self.transitions[self.elements[i].idx].name == name,
+            ElementType::EventType => self.event_types[self.elements[i].idx].name == name,
             _ => true,
         }) {
             true => Err(StateMachineError::Duplicate(name)),
@@ -379,35 +611,50 @@ impl Db {
         }
     }
 
-    /// Add a transtion between two vertices to the state machine.
-    /// The parent is defaulted to the owning region of the source vertex.
-    /// The parent matters if the transition line connecting the vertices
-    /// goes outside of the lca region - we would be saying the parent is
-    /// the real lcx region in that case.
+    /// Register a type of event which may trigger transitions.
+    /// Event type names are unique within the state machine, time and
+    /// change events aside, see `add_time_event` and `add_change_event`.
     pub fn add_event_type(&mut self, name: Name) -> StateMachineResult<DbId> {
         let e_idx = self.event_types.len();
         let parent = 0;
-        let x = self.event_types.iter().map(|ev| ev.dbid).collect();
+        let x: Vec<DbId> = self
+            .event_types
+            .iter()
+            .filter(|ev| ev.is_signal())
+            .map(|ev| ev.dbid)
+            .collect();
         self.is_duplicate(name, &x)?;
-        let dbid = self.new_element(name, parent, e_idx, ElementType::Transition);
+        let dbid = self.new_element(name, parent, e_idx, ElementType::EventType);
         self.event_types.push(EventType::new(name, dbid));
         Ok(dbid)
     }
 
+    /// Return the dbid of the event type with the given name, time and
+    /// change events are not looked up.
+    pub fn event_type(&self, name: &str) -> Option<DbId> {
+        self.event_types
+            .iter()
+            .find(|ev| ev.is_signal() && ev.name == name)
+            .map(|ev| ev.dbid)
+    }
+
     pub fn check_transition(&mut self, transition_dbid: TransitionIdx) -> StateMachineResult<bool> {
         Ok(self.transitions[self.transition(transition_dbid)?].check())
     }
 
     pub fn perform_entry(&mut self, state_dbid: StateIdx) -> StateMachineResult<()> {
-        Ok(self.states[self.state(state_dbid)?].perform_entry())
+        self.states[self.state(state_dbid)?].perform_entry();
+        Ok(())
     }
 
     pub fn perform_exit(&mut self, state_dbid: StateIdx) -> StateMachineResult<()> {
-        Ok(self.states[self.state(state_dbid)?].perform_exit())
+        self.states[self.state(state_dbid)?].perform_exit();
+        Ok(())
     }
 
     pub fn perform_do(&mut self, state_dbid: StateIdx) -> StateMachineResult<()> {
-        Ok(self.states[self.state(state_dbid)?].perform_do())
+        self.states[self.state(state_dbid)?].perform_do();
+        Ok(())
     }
 
     /// Add a transtion between two vertices to the state machine.
@@ -456,10 +703,21 @@ impl Db {
         &self.vertices
     }
 
+    #[cfg(feature = "std")]
     pub fn print_active_states(&self) -> StateMachineResult<Vec<VertexDbId>> {
+        self.write_active_states(&mut std::io::stdout())
+    }
+
+    /// Write the active states to out, one `[active_state]` line each,
+    /// and return them.
+    #[cfg(feature = "std")]
+    pub fn write_active_states(
+        &self,
+        out: &mut dyn std::io::Write,
+    ) -> StateMachineResult<Vec<VertexDbId>> {
         let active_states = self._active_states(0)?;
         for dbid in &active_states {
-            println!("[active_state] {}", self._fullname(*dbid))
+            writeln!(out, "[active_state] {}", self._fullname(*dbid))?;
         }
         Ok(active_states)
     }
@@ -479,7 +737,7 @@ impl Db {
                 .filter(|t| self.parents[t.target] == dbid)
                 .map(|t| t.dbid)
                 .collect::<Vec<_>>()),
-            ElementType::Vertex(VertexType::State) => Ok(self.vertices[ele.idx].incoming.clone()),
+            ElementType::Vertex(_) => Ok(self.vertices[ele.idx].incoming.clone()),
             ElementType::StateMachine => {
                 Ok(self.transitions.iter().map(|t| t.dbid).collect::<Vec<_>>())
             }
@@ -489,7 +747,6 @@ impl Db {
 
     /// Return all transitions enabled for this vertex recursively.
     fn _active_states(&self, dbid: DbId) -> StateMachineResult<Vec<VertexDbId>> {
-        println!(">active_state] {}", self._fullname(dbid));
         let ele = self.element(dbid)?;
         let mut active_states: Vec<TransitionIdx> = Vec::new();
         match ele.element_type {
@@ -498,17 +755,14 @@ impl Db {
                 if ele.element_type == ElementType::Vertex(VertexType::State) {
                     let s = &self.states[v.idx];
                     if s.is_simple() {
-                        println!("<active_state] {}", self._fullname(dbid));
                         active_states.push(dbid);
                     } else {
-                        println!("|active_state] {}", self._fullname(dbid));
                         for r in &s.regions {
                             active_states.extend(self._active_states(*r)?);
                         }
                     }
                 } else {
                     // Normally, this would not be possible, for pseudostates
-                    println!("+active_state] {}", self._fullname(dbid));
                     active_states.push(dbid);
                 }
             }
@@ -517,10 +771,7 @@ impl Db {
                 let r = &self.regions[r_idx];
                 let active_state = r.active_state;
                 if active_state != 0 {
-                    println!("^active_state]: Region {} ", self._fullname(dbid));
                     active_states.extend(self._active_states(r.active_state)?);
-                } else {
-                    println!("error: Region {} has 0 active state", self._fullname(dbid));
                 }
             }
             ElementType::StateMachine => {
@@ -571,15 +822,16 @@ impl Db {
                     })
                     .collect::<Vec<String>>()
                     .join("\n");
-                if c == "" {
+                if c.is_empty() {
                     Ok(r)
-                } else if r == "" {
+                } else if r.is_empty() {
                     Ok(c)
                 } else {
                     Ok(r + "\n" + &c)
                 }
             }
-            ElementType::Vertex(VertexType::State) => Ok(self.states[self.elements[dbid].idx]
+            ElementType::Vertex(VertexType::State) => Ok(self
+                ._state(dbid)
                 .regions
                 .iter()
                 .map(|r| {
@@ -698,6 +950,48 @@ impl Db {
         Ok(())
     }
 
+    /// Set the doActivity behavior of a state, it is performed once
+    /// the entry behavior of the state has completed.
+    pub fn set_do(&mut self, state: DbId, do_while: Behavior) -> StateMachineResult<()> {
+        let s_idx = self.state(state)?;
+        self.states[s_idx].do_while = Some(do_while);
+        Ok(())
+    }
+
+    /// Mark an event type as deferred while the given state is active.
+    pub fn add_deferrable_event(
+        &mut self,
+        state: DbId,
+        event_type: DbId,
+    ) -> StateMachineResult<()> {
+        let s_idx = self.state(state)?;
+        match self.element(event_type)?.element_type {
+            ElementType::EventType => (),
+            _ => return Err(StateMachineError::InvalidEventType(event_type)),
+        }
+        if !self.states[s_idx].deferrable.contains(&event_type) {
+            self.states[s_idx].deferrable.push(event_type);
+        }
+        Ok(())
+    }
+
+    /// Change the kind of a transition, transitions are external
+    /// unless set otherwise.
+    /// An internal transition must have the same source and target.
+    pub fn set_transition_kind(
+        &mut self,
+        transition: DbId,
+        kind: TransitionKind,
+    ) -> StateMachineResult<()> {
+        let t_idx = self.transition(transition)?;
+        let t = &self.transitions[t_idx];
+        if kind == TransitionKind::Internal && t.source != t.target {
+            return Err(StateMachineError::InvalidTransitionKind(transition));
+        }
+        self.transitions[t_idx].kind = kind;
+        Ok(())
+    }
+
     /// Add a state to the state machine.
     /// If you try to add a state when there is more than one region
     /// already defined for the state machine you will get an error.
@@ -720,12 +1014,9 @@ impl Db {
     /// region instead.
     pub fn add_substate(&mut self, name: Name, parent: DbId) -> StateMachineResult<DbId> {
         let p_ele = self.element(parent)?;
-        println!("{:#?}", self);
-        println!("Adding Substate to: {:#?}", p_ele);
         let r_dbid = match p_ele.element_type {
             ElementType::Vertex(VertexType::State) => {
                 let p_state_idx = self.get_state_by_ele(p_ele)?;
-                println!("Adding Substate to: s_idx:{} ", p_state_idx);
                 match self.states[p_state_idx].get_only_region()? {
                     Some(r_dbid) => r_dbid,
                     None => self.add_region("region_1", p_ele.dbid)?,
@@ -786,12 +1077,11 @@ impl Db {
         };
         self.vertices
             .push(VertexDef::new(name, dbid, idx, region, vertex_type));
-        if self.regions[r_idx].subvertex.len() == 0 {
+        if self.regions[r_idx].subvertex.is_empty() {
             self.regions[r_idx].initial_state = dbid;
             self.regions[r_idx].active_state = dbid;
         }
         self.regions[r_idx].subvertex.push(dbid);
-        println!("Vertices:{:#?}", self.vertices);
         Ok(dbid)
     }
 
@@ -805,16 +1095,8 @@ impl Db {
         // and then we call add_sm_region then presumably
         // we want to use that region name instead.
         if dbid == 2 && self.regions[0].name == "region_1" {
-            println!(
-                "Updating default region from {}",
-                self.regions[self.elements[self.state_machine.regions[0]].idx].name
-            );
             self.rename(1, name);
             self.regions[self.elements[self.state_machine.regions[0]].idx].name = name;
-            println!(
-                "Updating default region to {}/{}",
-                self.regions[self.elements[self.state_machine.regions[0]].idx].name, name
-            );
             return Ok(1);
         }
         self.add_region(name, self.state_machine.dbid)
@@ -903,7 +1185,7 @@ impl Db {
     pub fn regions(&self, dbid: DbId) -> StateMachineResult<Vec<RegionDbId>> {
         let ele = self.element(dbid)?;
         match ele.element_type {
-            ElementType::Vertex(VertexType::State) => Ok(self.states[ele.idx].regions.clone()),
+            ElementType::Vertex(VertexType::State) => Ok(self._state(dbid).regions.clone()),
             ElementType::StateMachine => Ok(self.state_machine.regions.clone()),
             _ => Err(StateMachineError::InvalidState(dbid)),
         }
@@ -941,6 +1223,25 @@ impl Db {
             .collect()
     }
 
+    /// Return a list of dbids of all vertices (states and pseudostates)
+    /// directly contained in a region
+    pub fn subvertices(&self, dbid: DbId) -> StateMachineResult<Vec<VertexDbId>> {
+        let r_idx = self.region(dbid)?;
+        Ok(self.regions[r_idx].subvertex.clone())
+    }
+
+    /// Return the dbid of the element directly containing an element.
+    /// The state machine is its own parent.
+    pub fn parent(&self, dbid: DbId) -> StateMachineResult<DbId> {
+        self.is_valid_dbid(dbid)?;
+        Ok(self.parents[dbid])
+    }
+
+    /// Return the type of a vertex
+    pub fn vertex_type(&self, dbid: DbId) -> StateMachineResult<VertexType> {
+        Ok(self.vertex_def(dbid)?.vertex_type)
+    }
+
     /// Return a list of dbids of regions of a state
     pub fn states(&self, dbid: DbId) -> StateMachineResult<Vec<StateDbId>> {
         let ele = self.element(dbid)?;
@@ -989,6 +1290,7 @@ impl Db {
     }
 
     /// Print out one of a set of canned reports about the state machine
+    #[cfg(feature = "std")]
     pub fn report(&self, report: ReportType) {
         match report {
             ReportType::Full => {
@@ -1037,12 +1339,12 @@ impl Db {
             }
             _ => return Err(StateMachineError::InvalidState(parent)),
         };
-        // TODO: need to check if a region of the same name already exists
+        let existing_regions = self.regions(parent)?;
+        self.is_duplicate(name, &existing_regions)?;
         let idx = self.regions.len();
         self.regions.push(Region::new(name, dbid, c));
         let dbid = self.new_element(name, parent, idx, ElementType::Region);
         self.add_region_to_container(c, dbid);
-        println!("Added region {}:{}", name, dbid);
         Ok(dbid)
     }
 
@@ -1058,37 +1360,30 @@ impl Db {
 
     fn element(&self, dbid: DbId) -> StateMachineResult<Element> {
         if dbid < self.elements.len() {
-            return Ok(self.elements[dbid]);
+            Ok(self.elements[dbid])
         } else {
-            return Err(StateMachineError::InvalidDbId(dbid));
+            Err(StateMachineError::InvalidDbId(dbid))
         }
     }
 
     fn _region(&self, dbid: DbId) -> StateMachineResult<&Region> {
-        println!("get_region_by_dbid: {:?}", dbid);
         let ele = self.element(dbid)?;
-        println!("got_region_by_dbid: {:?}", ele);
         Ok(&self.regions[self.get_region_by_ele(ele)?])
     }
 
     fn region(&self, dbid: DbId) -> StateMachineResult<RegionIdx> {
-        println!("get_region_by_dbid: {:?}", dbid);
         let ele = self.element(dbid)?;
-        println!("got_region_by_dbid: {:?}", ele);
         self.get_region_by_ele(ele)
     }
 
     fn get_region_by_ele(&self, ele: Element) -> StateMachineResult<RegionIdx> {
         match ele.element_type {
-            ElementType::Region => {
-                let r_idx = ele.idx;
-                println!("got_region_by_ele: r_idx:{:?}", r_idx);
-                return Ok(r_idx);
-            }
-            _ => return Err(StateMachineError::InvalidRegion(ele.dbid)),
+            ElementType::Region => Ok(ele.idx),
+            _ => Err(StateMachineError::InvalidRegion(ele.dbid)),
         }
     }
 
+    #[allow(dead_code)]
     fn valid_transition(&self, dbid: DbId) -> StateMachineResult<()> {
         self.transition(dbid)?;
         Ok(())
@@ -1101,12 +1396,13 @@ impl Db {
 
     fn get_transition_by_ele(&self, ele: Element) -> StateMachineResult<VertexIdx> {
         match ele.element_type {
-            ElementType::Transition => return Ok(ele.idx),
-            _ => return Err(StateMachineError::InvalidVertex(ele.dbid)),
+            ElementType::Transition => Ok(ele.idx),
+            _ => Err(StateMachineError::InvalidVertex(ele.dbid)),
         }
     }
 
     /// Verify that a dbid references a State
+    #[allow(dead_code)]
     fn valid_state(&self, dbid: DbId) -> StateMachineResult<()> {
         self.state(dbid)?;
         Ok(())
@@ -1128,12 +1424,13 @@ impl Db {
         match ele.element_type {
             ElementType::Vertex(VertexType::State) => {
                 let s_idx = self.vertices[ele.idx].idx;
-                return Ok(s_idx);
+                Ok(s_idx)
             }
-            _ => return Err(StateMachineError::InvalidState(ele.dbid)),
+            _ => Err(StateMachineError::InvalidState(ele.dbid)),
         }
     }
 
+    #[allow(dead_code)]
     fn valid_vertex(&self, dbid: DbId) -> StateMachineResult<()> {
         self.vertex(dbid)?;
         Ok(())
@@ -1141,16 +1438,14 @@ impl Db {
 
     /// Convert dbid to vertex index
     fn vertex(&self, dbid: DbId) -> StateMachineResult<VertexIdx> {
-        println!("vertex: dbid:{:?}", dbid);
         let ele = self.element(dbid)?;
-        println!("got_vertex_by_dbid: ele:{:?}", ele);
         self.get_vertex_by_ele(ele)
     }
 
     fn get_vertex_by_ele(&self, ele: Element) -> StateMachineResult<VertexIdx> {
         match ele.element_type {
-            ElementType::Vertex(_) => return Ok(ele.idx),
-            _ => return Err(StateMachineError::InvalidVertex(ele.dbid)),
+            ElementType::Vertex(_) => Ok(ele.idx),
+            _ => Err(StateMachineError::InvalidVertex(ele.dbid)),
         }
     }
 
@@ -1174,12 +1469,10 @@ impl Db {
 
     fn _is_contained_in(&self, child: DbId, parent: DbId) -> bool {
         if child == 0 {
-            println!("Not contained in {} {}", child, parent);
             false
         } else if self.parents[child] == parent {
             true
         } else {
-            println!("no contained in {} {}", child, parent);
             self._is_contained_in(self.parents[child], parent)
         }
         /*
@@ -1223,7 +1516,7 @@ impl Db {
 }
 
 /*
-impl std::ops::Deref for State {
+impl core::ops::Deref for State {
     type Target = Vertex;
     fn deref(&self) -> &Self::Target {
         &self.vertex
@@ -1261,8 +1554,9 @@ impl VertexDef {
     }
 }
 
-trait Vertex: std::fmt::Debug {
-    fn def<'db>(&self, db: &'db Db) -> StateMachineResult<VertexIdx>;
+#[allow(dead_code)]
+trait Vertex: fmt::Debug {
+    fn def(&self, db: &Db) -> StateMachineResult<VertexIdx>;
 
     fn name(&self, db: &Db) -> StateMachineResult<Name> {
         Ok(db.vertices[self.def(db)?].name)
@@ -1289,6 +1583,7 @@ trait Vertex: std::fmt::Debug {
 }
 
 // This is an instance of an event
+#[allow(dead_code)]
 #[derive(Debug, PartialEq)]
 struct Event {
     name: Name,
@@ -1299,13 +1594,28 @@ struct Event {
 struct EventType {
     name: Name,
     dbid: DbId,
+    /// When a time event occurs, None for other events.
+    when: Option<TimeEvent>,
+    /// The condition of a change event, None for other events.
+    condition: Option<Name>,
 }
 impl EventType {
     pub fn new(name: Name, dbid: DbId) -> EventType {
-        EventType { name, dbid }
+        EventType {
+            name,
+            dbid,
+            when: None,
+            condition: None,
+        }
+    }
+
+    /// An event dispatched by name, neither a time nor a change event.
+    fn is_signal(&self) -> bool {
+        self.when.is_none() && self.condition.is_none()
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug, PartialEq)]
 struct SubmachineState {
     name: Name,
@@ -1333,45 +1643,53 @@ where
         "foo"
     }
 }
-impl Clone for Behavior {
-    fn clone(&self) -> Self {
-        Behavior {
-            func: self.func,
-            name: self.name.clone(),
-        }
-    }
-}
 
-#[derive(Copy)]
+/// A Guard either wraps a function or is only a name. A named guard
+/// (for example one read from a textual definition) has no function
+/// bound to it and is evaluated by name.
+#[derive(Copy, Clone)]
 pub struct Guard {
-    func: GuardFunc,
+    func: Option<GuardFunc>,
     name: &'static str,
 }
 
-impl Clone for Guard {
-    fn clone(&self) -> Self {
-        Guard {
-            func: self.func,
-            name: self.name.clone(),
-        }
-    }
-}
-
 type GuardFunc = fn() -> bool;
 
 impl Guard {
     pub fn new(func: GuardFunc) -> Guard {
-        Guard { func, name: "ggg" }
+        Guard {
+            func: Some(func),
+            name: "ggg",
+        }
     }
     pub fn some(func: GuardFunc) -> OptGuard {
         OptGuard::Guard(Guard::new(func))
     }
+    /// Create a guard which is only known by name.
+    pub const fn named(name: Name) -> Guard {
+        Guard { func: None, name }
+    }
+    pub fn name(&self) -> Name {
+        self.name
+    }
+    /// Evaluate the guard. A guard with no function bound is true.
+    pub fn eval(&self) -> bool {
+        match self.func {
+            Some(func) => func(),
+            None => true,
+        }
+    }
 }
 impl fmt::Debug for Guard {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Guard<{}>", self.name)
     }
 }
+impl From<Name> for Guard {
+    fn from(name: Name) -> Guard {
+        Guard::named(name)
+    }
+}
 
 #[derive(Debug, Copy, Clone)]
 pub enum OptGuard {
@@ -1385,27 +1703,50 @@ pub enum OptBehavior {
     None,
 }
 
-#[derive(Copy)]
+/// A Behavior either wraps a function or is only a name. A named
+/// behavior has no function bound to it and is performed by name.
+#[derive(Copy, Clone)]
 pub struct Behavior {
-    func: BehaviorFunc,
+    func: Option<BehaviorFunc>,
     name: &'static str,
 }
 
-type BehaviorFunc = fn() -> ();
+type BehaviorFunc = fn();
 
 impl Behavior {
     pub fn new(func: BehaviorFunc) -> Behavior {
-        Behavior { func, name: "xxx" }
+        Behavior {
+            func: Some(func),
+            name: "xxx",
+        }
     }
     pub fn some(func: BehaviorFunc) -> OptBehavior {
         OptBehavior::Behavior(Behavior::new(func))
     }
+    /// Create a behavior which is only known by name.
+    pub const fn named(name: Name) -> Behavior {
+        Behavior { func: None, name }
+    }
+    pub fn name(&self) -> Name {
+        self.name
+    }
+    /// Run the behavior. A behavior with no function bound does nothing.
+    pub fn perform(&self) {
+        if let Some(func) = self.func {
+            func()
+        }
+    }
 }
 impl fmt::Debug for Behavior {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Behavior<{}>", self.name)
     }
 }
+impl From<Name> for Behavior {
+    fn from(name: Name) -> Behavior {
+        Behavior::named(name)
+    }
+}
 
 /*
 impl Copy for Behavior {}
@@ -1479,7 +1820,7 @@ impl<T: ?Sized> fmt::Debug for Debuggable<T> {
 
 // This makes Debuggable have most methods of the thing it wraps.
 // It also lets you call it when T is a function.
-impl<T: ?Sized> ::std::ops::Deref for Debuggable<T> {
+impl<T: ?Sized> core::ops::Deref for Debuggable<T> {
     type Target = T;
     fn deref(&self) -> &T {
         &self.value
@@ -1501,6 +1842,7 @@ fn main() {
 }
 */
 
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct Transition {
     name: Name,
@@ -1510,6 +1852,7 @@ pub struct Transition {
     target: VertexDbId,
     effect: OptEffect,
     guard: OptGuard,
+    kind: TransitionKind,
 }
 
 impl Transition {
@@ -1530,16 +1873,18 @@ impl Transition {
             target,
             effect,
             guard,
+            kind: TransitionKind::External,
         }
     }
     pub fn check(&self) -> bool {
         match &self.guard {
             OptGuard::None => true,
-            OptGuard::Guard(guard) => (guard.func)(),
+            OptGuard::Guard(guard) => guard.eval(),
         }
     }
 }
 
+#[allow(dead_code)]
 enum Visibility {
     Public,
     Private,
@@ -1547,6 +1892,7 @@ enum Visibility {
     Package,
 }
 
+#[allow(dead_code)]
 trait NamedElement {
     fn name(&self) -> Name;
     fn qualified_name(&self) -> Name;
@@ -1593,8 +1939,8 @@ impl StateMachineRoot {
     /// within that statemachine.
     fn get_only_region(&self) -> StateMachineResult<Option<RegionIdx>> {
         match self.regions.len() {
-            n if n == 1 => Ok(Some(self.regions[0])),
-            n if n == 0 => Ok(None),
+            1 => Ok(Some(self.regions[0])),
+            0 => Ok(None),
             _ => Err(StateMachineError::ContainsMultipleRegions(self.dbid)),
         }
     }
@@ -1620,12 +1966,14 @@ impl StateMachineRoot {
     */
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Copy, Clone)]
 enum Container {
     State(DbId),
     StateMachine(DbId),
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 /// during a transition the active_state of a region may be a vertex
 /// but it must always end up as a State (or FinalState)
@@ -1635,6 +1983,8 @@ struct Region {
     container: Container,
     initial_state: DbId,
     active_state: DbId,
+    /// the active vertex when the region was last exited
+    history: DbId,
     subvertex: Vec<DbId>,
     transition: Vec<DbId>,
 }
@@ -1647,21 +1997,22 @@ impl Region {
             container,
             initial_state: 0,
             active_state: 0,
+            history: 0,
             subvertex: Vec::new(),
             transition: Vec::new(),
         }
     }
     fn initial_state(&self, sm: &StateMachineDef) -> StateMachineResult<Option<StateDbId>> {
         for v in &self.subvertex {
-            match sm.elements[*v].element_type {
-                ElementType::Vertex(VertexType::InitialState) => return Ok(Some(*v)),
-                _ => (),
+            if let ElementType::Vertex(VertexType::InitialState) = sm.elements[*v].element_type {
+                return Ok(Some(*v));
             }
         }
         Err(StateMachineError::NoInitialState(self.dbid))
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 /// Note: per spec this is a subclass of State but that it not neccessary
 /// at the moment
@@ -1679,6 +2030,7 @@ struct State {
     entry: Option<Entry>,
     exit: Option<Exit>,
     do_while: Option<Behavior>,
+    deferrable: Vec<DbId>,
 }
 
 impl State {
@@ -1691,17 +2043,18 @@ impl State {
             entry: None,
             exit: None,
             do_while: None,
+            deferrable: Vec::new(),
         }
     }
 
     #[inline]
     fn is_simple(&self) -> bool {
-        self.regions.len() == 0
+        self.regions.is_empty()
     }
 
     #[inline]
     fn is_composite(&self) -> bool {
-        self.regions.len() > 0
+        !self.regions.is_empty()
     }
 
     #[inline]
@@ -1720,30 +2073,27 @@ impl State {
     /// within that state
     fn get_only_region(&self) -> StateMachineResult<Option<RegionIdx>> {
         match self.regions.len() {
-            n if n == 1 => Ok(Some(self.regions[0])),
-            n if n == 0 => Ok(None),
+            1 => Ok(Some(self.regions[0])),
+            0 => Ok(None),
             _ => Err(StateMachineError::ContainsMultipleRegions(self.dbid)),
         }
     }
 
     pub fn perform_entry(&self) {
-        match &self.entry {
-            Some(behavior) => (behavior.func)(),
-            None => (),
+        if let Some(behavior) = &self.entry {
+            behavior.perform()
         }
     }
 
     pub fn perform_exit(&self) {
-        match &self.exit {
-            Some(behavior) => (behavior.func)(),
-            None => (),
+        if let Some(behavior) = &self.exit {
+            behavior.perform()
         }
     }
 
     pub fn perform_do(&self) {
-        match &self.do_while {
-            Some(behavior) => (behavior.func)(),
-            None => (),
+        if let Some(behavior) = &self.do_while {
+            behavior.perform()
         }
     }
 
@@ -1772,7 +2122,7 @@ impl State {
 }
 
 impl Vertex for State {
-    fn def<'db>(&self, db: &'db Db) -> StateMachineResult<VertexIdx> {
+    fn def(&self, db: &Db) -> StateMachineResult<VertexIdx> {
         db.vertex(self.dbid)
     }
 }
@@ -0,0 +1,212 @@
+//! Fingerprints of definitions and migration of snapshots between them.
+//!
+//! [`Db::fingerprint`] hashes everything the execution depends on: the
+//! fullname and kind of each element, the behaviors of the states, the
+//! events they defer and the source, target, trigger, guard, effect and
+//! kind of the transitions. It is embedded in the snapshots, which
+//! [`Db::restore`] only accepts from a definition with the same one.
+//!
+//! When a new version of a definition renames, moves or removes states,
+//! a [`Migration`] maps the fullnames of the old version to those of the
+//! new one. Renaming a vertex renames everything it contains as well.
+//! [`Db::migrate`] converts snapshots taken with the old version, and
+//! reports those which cannot be converted: naming an element without
+//! a counterpart, or leaving the new definition in a configuration it
+//! cannot be in.
+//!
+//! ```text
+//! let mut migration = Migration::new();
+//! migration.rename("door::region_1::opened", "door::region_1::open");
+//! let report = door_v2.migrate(&snapshots, &migration);
+//! ```
+
+use crate::snapshot::Snapshot;
+use crate::*;
+use core::fmt::Write;
+
+/// Maps the fullnames of an old version of a definition to the new
+/// ones.
+#[derive(Debug, Clone, Default)]
+pub struct Migration {
+    renames: Vec<(String, String)>,
+}
+
+impl Migration {
+    pub fn new() -> Migration {
+        Migration::default()
+    }
+
+    /// Map an old fullname, and the fullnames below it, to a new one.
+    pub fn rename(&mut self, old: &str, new: &str) -> &mut Migration {
+        self.renames.push((old.to_string(), new.to_string()));
+        self
+    }
+
+    /// The new fullname of an old one, by the longest rename which
+    /// applies. Names nothing renames are kept.
+    pub fn apply(&self, fullname: &str) -> String {
+        let mut best: Option<&(String, String)> = None;
+        for rename in &self.renames {
+            let (old, _) = rename;
+            let applies = fullname == old
+                || (fullname.starts_with(old.as_str()) && fullname[old.len()..].starts_with("::"));
+            if applies && best.is_none_or(|(b, _)| old.len() > b.len()) {
+                best = Some(rename);
+            }
+        }
+        match best {
+            Some((old, new)) => format!("{}{}", new, &fullname[old.len()..]),
+            None => fullname.to_string(),
+        }
+    }
+}
+
+/// A snapshot which could not be migrated.
+#[derive(Debug)]
+pub struct Unmigrated {
+    /// Index of the snapshot among those migrated.
+    pub index: usize,
+    /// The migrated names the new definition does not have.
+    pub missing: Vec<String>,
+    /// Why the snapshot cannot be restored otherwise.
+    pub error: Option<StateMachineError>,
+}
+
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    /// The snapshots migrated, by index.
+    pub migrated: Vec<(usize, Snapshot)>,
+    pub unmigrated: Vec<Unmigrated>,
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} migrated, {} not migrated",
+            self.migrated.len(),
+            self.unmigrated.len()
+        )?;
+        for u in &self.unmigrated {
+            write!(f, "snapshot {}:", u.index)?;
+            for name in &u.missing {
+                write!(f, " no {}", name)?;
+            }
+            if let Some(err) = &u.error {
+                write!(f, " {}", err)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// 64 bit FNV-1a, stable across platforms and releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+fn behavior_name(behavior: Option<Behavior>) -> &'static str {
+    behavior.map_or("", |b| b.name)
+}
+
+impl Db {
+    /// A hash of the structure and behaviors of the definition, which
+    /// changes whenever a snapshot of it might not mean the same.
+    pub fn fingerprint(&self) -> u64 {
+        let mut text = String::new();
+        for (dbid, element) in self.elements.iter().enumerate() {
+            let _ = write!(text, "{} {:?}", self.fullnames[dbid], element.element_type);
+            match element.element_type {
+                ElementType::Vertex(VertexType::State) => {
+                    let s = self._state(dbid);
+                    let _ = write!(
+                        text,
+                        " {} {} {}",
+                        behavior_name(s.entry),
+                        behavior_name(s.exit),
+                        behavior_name(s.do_while)
+                    );
+                    for event in &s.deferrable {
+                        let _ = write!(text, " {}", self.fullnames[*event]);
+                    }
+                }
+                ElementType::Transition => {
+                    let t = self._transition(dbid);
+                    let _ = write!(
+                        text,
+                        " {} {} {} [{}] {} {:?}",
+                        self.fullnames[t.source],
+                        self.fullnames[t.target],
+                        t.trigger.map_or("", |e| self.fullnames[e].as_str()),
+                        match t.guard {
+                            OptGuard::Guard(g) => g.name,
+                            OptGuard::None => "",
+                        },
+                        match t.effect {
+                            OptBehavior::Behavior(b) => b.name,
+                            OptBehavior::None => "",
+                        },
+                        t.kind
+                    );
+                }
+                _ => (),
+            }
+            text.push('\n');
+        }
+        fnv1a(text.as_bytes())
+    }
+
+    /// Convert snapshots of an old version of the definition with the
+    /// renames of the migration. Snapshots with the fingerprint of this
+    /// definition are kept as they are.
+    pub fn migrate(&self, snapshots: &[Snapshot], migration: &Migration) -> MigrationReport {
+        let fingerprint = self.fingerprint();
+        let mut report = MigrationReport::default();
+        for (index, snapshot) in snapshots.iter().enumerate() {
+            if snapshot.fingerprint == Some(fingerprint) {
+                report.migrated.push((index, snapshot.clone()));
+                continue;
+            }
+            let rename = |names: &[String]| -> Vec<String> {
+                names.iter().map(|name| migration.apply(name)).collect()
+            };
+            let migrated = Snapshot {
+                status: snapshot.status,
+                fingerprint: Some(fingerprint),
+                active: rename(&snapshot.active),
+                history: rename(&snapshot.history),
+                deferred: rename(&snapshot.deferred),
+                conditions: rename(&snapshot.conditions),
+            };
+            let missing: Vec<String> = migrated
+                .active
+                .iter()
+                .chain(&migrated.history)
+                .chain(&migrated.deferred)
+                .chain(&migrated.conditions)
+                .filter(|name| self.dbid_named(name).is_none())
+                .cloned()
+                .collect();
+            let error = match missing.is_empty() {
+                true => self.check_snapshot(&migrated).err(),
+                false => None,
+            };
+            if missing.is_empty() && error.is_none() {
+                report.migrated.push((index, migrated));
+            } else {
+                report.unmigrated.push(Unmigrated {
+                    index,
+                    missing,
+                    error,
+                });
+            }
+        }
+        report
+    }
+}
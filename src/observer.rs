@@ -0,0 +1,308 @@
+//! Observation of a state machine definition as it is built and run.
+//!
+//! An [`Observer`] set with [`Db::set_observer`] is told about every
+//! element added to the definition and, while it runs, about the events
+//! dispatched, the transitions selected, the states exited and entered,
+//! the guards evaluated, the effects run, the events deferred or
+//! discarded and the do-activities going on in the background.
+//! Elements and events are identified by dbid and fullname.
+//!
+//! With the `log` feature [`LogObserver`] writes the observations to the
+//! `log` crate, with the `tracing` feature [`TracingObserver`] emits them
+//! as `tracing` events, both at debug level with the target
+//! `rust_uml_sm`. Besides the message they carry the fields `kind`, the
+//! observation in snake case, and those which apply of `state`, `event`,
+//! `transition`, `element`, `fullname`, `guard`, `behavior` and
+//! `result`, as `log` key-values or `tracing` fields.
+//!
+//! The `compiled` engine does not report observations.
+
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Observation<'a> {
+    /// A state, region, transition, event type... was added.
+    ElementAdded { element: DbId, fullname: &'a str },
+    /// An event is processed, deferred events dispatched again included.
+    EventDispatched { event: DbId, fullname: &'a str },
+    /// The transition fires. Transitions taken from pseudostates and
+    /// completion transitions are selected too.
+    TransitionSelected { transition: DbId, fullname: &'a str },
+    /// The state is exited, before its exit behavior is performed.
+    StateExited { state: DbId, fullname: &'a str },
+    /// The state was entered, its entry behavior was performed.
+    StateEntered { state: DbId, fullname: &'a str },
+    /// The guard of the transition was evaluated.
+    GuardEvaluated {
+        transition: DbId,
+        guard: &'a str,
+        result: bool,
+    },
+    /// The effect of the transition is performed.
+    EffectRun { transition: DbId, behavior: &'a str },
+    /// The event enabled no transition and an active state defers it.
+    EventDeferred { event: DbId, fullname: &'a str },
+    /// The event enabled no transition and nothing defers it.
+    EventDiscarded { event: DbId, fullname: &'a str },
+    /// The do-activity of the state goes on in the background.
+    ActivityStarted { state: DbId, fullname: &'a str },
+    /// The do-activity of the state is aborted, the state is exited.
+    ActivityAborted { state: DbId, fullname: &'a str },
+    /// The do-activity of the state is over.
+    ActivityDone { state: DbId, fullname: &'a str },
+}
+
+impl fmt::Display for Observation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Observation::ElementAdded { fullname, .. } => write!(f, "added {}", fullname),
+            Observation::EventDispatched { fullname, .. } => {
+                write!(f, "dispatched {}", fullname)
+            }
+            Observation::TransitionSelected { fullname, .. } => {
+                write!(f, "selected {}", fullname)
+            }
+            Observation::StateExited { fullname, .. } => write!(f, "exited {}", fullname),
+            Observation::StateEntered { fullname, .. } => write!(f, "entered {}", fullname),
+            Observation::GuardEvaluated { guard, result, .. } => {
+                write!(f, "guard [{}] is {}", guard, result)
+            }
+            Observation::EffectRun { behavior, .. } => write!(f, "effect {}", behavior),
+            Observation::EventDeferred { fullname, .. } => write!(f, "deferred {}", fullname),
+            Observation::EventDiscarded { fullname, .. } => {
+                write!(f, "discarded {}", fullname)
+            }
+            Observation::ActivityStarted { fullname, .. } => {
+                write!(f, "activity of {} started", fullname)
+            }
+            Observation::ActivityAborted { fullname, .. } => {
+                write!(f, "activity of {} aborted", fullname)
+            }
+            Observation::ActivityDone { fullname, .. } => {
+                write!(f, "activity of {} done", fullname)
+            }
+        }
+    }
+}
+
+/// Receives the observations of the definition it is set on.
+pub trait Observer: Send {
+    fn observe(&mut self, observation: &Observation<'_>);
+}
+
+/// Observations are kept by a closure.
+impl<F: FnMut(&Observation<'_>) + Send> Observer for F {
+    fn observe(&mut self, observation: &Observation<'_>) {
+        self(observation)
+    }
+}
+
+/// Holds the observer of a definition, if any.
+#[derive(Default)]
+pub(crate) struct Slot(Option<Box<dyn Observer>>);
+
+impl fmt::Debug for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(_) => f.write_str("Some(Observer)"),
+            None => f.write_str("None"),
+        }
+    }
+}
+
+/// The fields of an observation given to `log` and `tracing`, None
+/// where they do not apply.
+#[cfg(any(feature = "log", feature = "tracing"))]
+#[derive(Default)]
+struct Fields<'a> {
+    kind: &'static str,
+    state: Option<DbId>,
+    event: Option<DbId>,
+    transition: Option<DbId>,
+    element: Option<DbId>,
+    fullname: Option<&'a str>,
+    guard: Option<&'a str>,
+    behavior: Option<&'a str>,
+    result: Option<bool>,
+}
+
+#[cfg(any(feature = "log", feature = "tracing"))]
+impl<'a> Fields<'a> {
+    fn of(observation: &Observation<'a>) -> Fields<'a> {
+        let state = |kind, state, fullname| Fields {
+            kind,
+            state: Some(state),
+            fullname: Some(fullname),
+            ..Fields::default()
+        };
+        let event = |kind, event, fullname| Fields {
+            kind,
+            event: Some(event),
+            fullname: Some(fullname),
+            ..Fields::default()
+        };
+        match *observation {
+            Observation::ElementAdded { element, fullname } => Fields {
+                kind: "element_added",
+                element: Some(element),
+                fullname: Some(fullname),
+                ..Fields::default()
+            },
+            Observation::EventDispatched { event: e, fullname } => {
+                event("event_dispatched", e, fullname)
+            }
+            Observation::TransitionSelected {
+                transition,
+                fullname,
+            } => Fields {
+                kind: "transition_selected",
+                transition: Some(transition),
+                fullname: Some(fullname),
+                ..Fields::default()
+            },
+            Observation::StateExited { state: s, fullname } => state("state_exited", s, fullname),
+            Observation::StateEntered { state: s, fullname } => state("state_entered", s, fullname),
+            Observation::GuardEvaluated {
+                transition,
+                guard,
+                result,
+            } => Fields {
+                kind: "guard_evaluated",
+                transition: Some(transition),
+                guard: Some(guard),
+                result: Some(result),
+                ..Fields::default()
+            },
+            Observation::EffectRun {
+                transition,
+                behavior,
+            } => Fields {
+                kind: "effect_run",
+                transition: Some(transition),
+                behavior: Some(behavior),
+                ..Fields::default()
+            },
+            Observation::EventDeferred { event: e, fullname } => {
+                event("event_deferred", e, fullname)
+            }
+            Observation::EventDiscarded { event: e, fullname } => {
+                event("event_discarded", e, fullname)
+            }
+            Observation::ActivityStarted { state: s, fullname } => {
+                state("activity_started", s, fullname)
+            }
+            Observation::ActivityAborted { state: s, fullname } => {
+                state("activity_aborted", s, fullname)
+            }
+            Observation::ActivityDone { state: s, fullname } => state("activity_done", s, fullname),
+        }
+    }
+}
+
+/// Only the fields which apply are key-values.
+#[cfg(feature = "log")]
+impl log::kv::Source for Fields<'_> {
+    fn visit<'kvs>(
+        &'kvs self,
+        visitor: &mut dyn log::kv::VisitSource<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        visitor.visit_pair("kind".into(), self.kind.into())?;
+        let ids = [
+            ("state", self.state),
+            ("event", self.event),
+            ("transition", self.transition),
+            ("element", self.element),
+        ];
+        for (key, id) in ids {
+            if let Some(id) = id {
+                visitor.visit_pair(key.into(), id.into())?;
+            }
+        }
+        let names = [
+            ("fullname", self.fullname),
+            ("guard", self.guard),
+            ("behavior", self.behavior),
+        ];
+        for (key, name) in names {
+            if let Some(name) = name {
+                visitor.visit_pair(key.into(), name.into())?;
+            }
+        }
+        if let Some(result) = self.result {
+            visitor.visit_pair("result".into(), result.into())?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the observations to the `log` crate.
+#[cfg(feature = "log")]
+#[derive(Debug, Default)]
+pub struct LogObserver;
+
+#[cfg(feature = "log")]
+impl Observer for LogObserver {
+    fn observe(&mut self, observation: &Observation<'_>) {
+        if !log::log_enabled!(target: "rust_uml_sm", log::Level::Debug) {
+            return;
+        }
+        log::logger().log(
+            &log::Record::builder()
+                .args(format_args!("{}", observation))
+                .level(log::Level::Debug)
+                .target("rust_uml_sm")
+                .module_path_static(Some(module_path!()))
+                .file_static(Some(file!()))
+                .line(Some(line!()))
+                .key_values(&Fields::of(observation))
+                .build(),
+        );
+    }
+}
+
+/// Emits the observations as `tracing` events.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Default)]
+pub struct TracingObserver;
+
+#[cfg(feature = "tracing")]
+impl Observer for TracingObserver {
+    fn observe(&mut self, observation: &Observation<'_>) {
+        let f = Fields::of(observation);
+        tracing::debug!(
+            target: "rust_uml_sm",
+            kind = f.kind,
+            state = f.state,
+            event = f.event,
+            transition = f.transition,
+            element = f.element,
+            fullname = f.fullname,
+            guard = f.guard,
+            behavior = f.behavior,
+            result = f.result,
+            "{}",
+            observation
+        );
+    }
+}
+
+impl Db {
+    /// Set the observer told about the elements added from now on and
+    /// about the execution, replacing any previous one.
+    pub fn set_observer(&mut self, observer: impl Observer + 'static) {
+        self.observer.0 = Some(Box::new(observer));
+    }
+
+    /// Remove the observer and return it.
+    pub fn take_observer(&mut self) -> Option<Box<dyn Observer>> {
+        self.observer.0.take()
+    }
+
+    /// Hand an observation built from the definition to the observer.
+    pub(crate) fn notify(&mut self, observation: impl FnOnce(&Db) -> Observation<'_>) {
+        if let Some(mut observer) = self.observer.0.take() {
+            observer.observe(&observation(self));
+            self.observer.0 = Some(observer);
+        }
+    }
+}
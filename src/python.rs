@@ -0,0 +1,328 @@
+//! The Python module, with the `python` feature.
+//!
+//! ```text
+//! import rust_uml_sm as sm
+//!
+//! door = sm.Definition(open("door.sm").read())
+//! door.events                           # ['open', 'close']
+//! inst = door.instance()
+//! inst.guards = {"unlocked": False}
+//! inst.behaviors = lambda name: print(name)
+//! inst.record()
+//! inst.start()
+//! assert not inst.dispatch("open")
+//! inst.active_states                    # ['door::region_1::closed']
+//! inst.trace                            # the recorded trace as text
+//! sm.validate(text)                     # the problems of a model
+//! ```
+//!
+//! The extension is built with
+//! `cargo rustc --release --lib --features python --crate-type cdylib`,
+//! the library is imported as `rust_uml_sm.so`.
+//!
+//! A [`Definition`] is loaded from the textual format and checked with
+//! `validate`, each [`Instance`] of it runs on its own. Behaviors and
+//! guards are named in the model. `behaviors` is called with the name of
+//! each behavior performed, or is a dict of callables by name. `guards`
+//! is called with the name of each guard evaluated, or is a dict of
+//! bools or callables by name; a guard is true without them. An
+//! exception raised by them makes the guard false, the step runs to
+//! completion and the exception is raised by the method performing it.
+//!
+//! Failures raise `StateMachineError` with the message of the Rust
+//! error.
+
+use crate::trace::Trace;
+use crate::*;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+create_exception!(rust_uml_sm, StateMachineError, PyException);
+
+fn py_error(err: crate::StateMachineError) -> PyErr {
+    StateMachineError::new_err(err.to_string())
+}
+
+/// A model, from which instances are created.
+#[pyclass(unsendable)]
+pub struct Definition {
+    model: String,
+    def: StateMachineDef,
+}
+
+#[pymethods]
+impl Definition {
+    /// Parse and check a model in the textual format.
+    #[new]
+    fn new(model: &str) -> PyResult<Definition> {
+        let def = dsl::parse(model).map_err(py_error)?;
+        def.validate().map_err(py_error)?;
+        Ok(Definition {
+            model: model.to_string(),
+            def,
+        })
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        self.def._name(0)
+    }
+
+    /// The names of the event types dispatched by name, time and change
+    /// events aside.
+    #[getter]
+    fn events(&self) -> Vec<&str> {
+        self.def
+            .iter_signal_events()
+            .map(|e| self.def._name(e))
+            .collect()
+    }
+
+    /// The fullname of the element at a path, see `find`, None if there
+    /// is none.
+    fn fullname(&self, path: &str) -> Option<String> {
+        self.def.find(path).map(|v| self.def._fullname(v).clone())
+    }
+
+    /// A new instance, not started.
+    fn instance(&self) -> Instance {
+        Instance {
+            def: dsl::parse(&self.model).expect("internal_error:907101"),
+            behaviors: None,
+            guards: None,
+        }
+    }
+
+    /// The model in the textual format, as written by `dsl::write`.
+    fn to_text(&self) -> String {
+        dsl::write(&self.def)
+    }
+
+    fn to_json(&self) -> String {
+        render::json(&self.def)
+    }
+
+    fn to_plantuml(&self) -> String {
+        render::plantuml(&self.def)
+    }
+
+    fn to_svg(&self) -> String {
+        render::svg(&self.def)
+    }
+}
+
+/// Calls the Python callables of an instance, and keeps the first
+/// exception they raise.
+struct PyContext<'a, 'py> {
+    py: Python<'py>,
+    behaviors: Option<&'a Py<PyAny>>,
+    guards: Option<&'a Py<PyAny>>,
+    error: Option<PyErr>,
+}
+
+impl PyContext<'_, '_> {
+    /// The callable for name in callables, called with name if
+    /// callables is callable itself.
+    fn call(&mut self, callables: &Py<PyAny>, name: &str) -> Option<Bound<'_, PyAny>> {
+        let callables = callables.bind(self.py);
+        let result = if let Ok(dict) = callables.cast::<PyDict>() {
+            match dict.get_item(name) {
+                Ok(Some(item)) if item.is_callable() => item.call0(),
+                Ok(Some(item)) => Ok(item),
+                Ok(None) => return None,
+                Err(err) => Err(err),
+            }
+        } else {
+            callables.call1((name,))
+        };
+        match result {
+            Ok(result) => Some(result),
+            Err(err) => {
+                self.error.get_or_insert(err);
+                Some(false.into_pyobject(self.py).unwrap().to_owned().into_any())
+            }
+        }
+    }
+
+    /// The result of a step, or the exception kept.
+    fn result<T>(self, result: StateMachineResult<T>) -> PyResult<T> {
+        let result = result.map_err(py_error)?;
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(result),
+        }
+    }
+}
+
+impl Context for PyContext<'_, '_> {
+    fn behavior(&mut self, name: &str) {
+        if let Some(behaviors) = self.behaviors {
+            self.call(behaviors, name);
+        }
+    }
+
+    fn guard(&mut self, name: &str) -> bool {
+        let guards = match self.guards {
+            Some(guards) => guards,
+            None => return true,
+        };
+        match self.call(guards, name).map(|result| result.is_truthy()) {
+            Some(Ok(result)) => result,
+            Some(Err(err)) => {
+                self.error.get_or_insert(err);
+                false
+            }
+            None => true,
+        }
+    }
+}
+
+/// A running copy of a definition.
+#[pyclass(unsendable)]
+pub struct Instance {
+    def: StateMachineDef,
+    #[pyo3(get, set)]
+    behaviors: Option<Py<PyAny>>,
+    #[pyo3(get, set)]
+    guards: Option<Py<PyAny>>,
+}
+
+impl Instance {
+    /// Run f with the context calling the callables set.
+    fn with_context<T>(
+        &mut self,
+        py: Python<'_>,
+        f: impl FnOnce(&mut StateMachineDef, &mut dyn Context) -> StateMachineResult<T>,
+    ) -> PyResult<T> {
+        let mut ctx = PyContext {
+            py,
+            behaviors: self.behaviors.as_ref(),
+            guards: self.guards.as_ref(),
+            error: None,
+        };
+        let result = f(&mut self.def, &mut ctx);
+        ctx.result(result)
+    }
+}
+
+#[pymethods]
+impl Instance {
+    fn start(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.with_context(py, |def, ctx| def.start_with(ctx))
+    }
+
+    fn stop(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.with_context(py, |def, ctx| def.stop_with(ctx))
+    }
+
+    /// Dispatch an event by name and run to completion, return True if
+    /// it fired a transition.
+    fn dispatch(&mut self, py: Python<'_>, event: &str) -> PyResult<bool> {
+        let event = self
+            .def
+            .event_type(event)
+            .ok_or_else(|| py_error(crate::StateMachineError::UnknownEvent(event.to_string())))?;
+        self.with_context(py, |def, ctx| def.dispatch_with(event, ctx))
+    }
+
+    /// The fullnames of the innermost active states.
+    #[getter]
+    fn active_states(&self) -> Vec<String> {
+        self.def
+            .active_states()
+            .into_iter()
+            .map(|s| self.def._fullname(s).clone())
+            .collect()
+    }
+
+    /// Whether the vertex at a path, see `find`, is active.
+    fn is_active(&self, path: &str) -> PyResult<bool> {
+        match self.def.find(path) {
+            Some(v) => Ok(self.def.is_active(v)),
+            None => Err(py_error(crate::StateMachineError::UnknownPath(
+                path.to_string(),
+            ))),
+        }
+    }
+
+    /// `Idle`, `Running` or `Terminated`.
+    #[getter]
+    fn status(&self) -> String {
+        format!("{:?}", self.def.status())
+    }
+
+    /// The names of the deferred events, oldest first.
+    #[getter]
+    fn deferred_events(&self) -> Vec<&str> {
+        self.def
+            .deferred_events()
+            .iter()
+            .map(|e| self.def._name(*e))
+            .collect()
+    }
+
+    /// Start recording a trace, see `Db::record`.
+    fn record(&mut self) {
+        self.def.record();
+    }
+
+    /// The trace recorded so far as text, None if not recording.
+    #[getter]
+    fn trace(&self) -> Option<String> {
+        self.def.trace().map(|t| t.to_string())
+    }
+
+    /// Replay a trace on this instance, which must not have been started,
+    /// with the guard outcomes recorded. Return None if it replayed as
+    /// recorded, or where it diverged.
+    fn replay(&mut self, py: Python<'_>, trace: &str) -> PyResult<Option<String>> {
+        let trace: Trace = trace.parse().map_err(py_error)?;
+        let divergence = self.with_context(py, |def, ctx| def.replay_with(&trace, ctx))?;
+        Ok(divergence.map(|d| d.to_string()))
+    }
+
+    /// The execution state as text, see `Db::snapshot`.
+    fn snapshot(&self) -> String {
+        self.def.snapshot().to_string()
+    }
+
+    /// Restore a snapshot, the instance must not be running.
+    fn restore(&mut self, snapshot: &str) -> PyResult<()> {
+        let snapshot = snapshot.parse().map_err(py_error)?;
+        self.def.restore(&snapshot).map_err(py_error)
+    }
+}
+
+/// The problems of a model in the textual format, as reported by
+/// `sm validate`, empty if there are none.
+#[pyfunction]
+fn validate(model: &str) -> Vec<String> {
+    let def = match dsl::parse(model) {
+        Ok(def) => def,
+        Err(err) => return vec![err.to_string()],
+    };
+    def.problems()
+        .into_iter()
+        .map(|problem| match problem {
+            crate::StateMachineError::NoInitialState(region) => format!(
+                "region {} has no initial pseudostate",
+                def._fullname(region)
+            ),
+            crate::StateMachineError::NoEnabledTransition(vertex) => {
+                format!("{} has no outgoing transition", def._fullname(vertex))
+            }
+            err => err.to_string(),
+        })
+        .collect()
+}
+
+#[pymodule]
+fn rust_uml_sm(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Definition>()?;
+    m.add_class::<Instance>()?;
+    m.add_function(wrap_pyfunction!(validate, m)?)?;
+    m.add("StateMachineError", m.py().get_type::<StateMachineError>())?;
+    Ok(())
+}
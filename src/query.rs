@@ -0,0 +1,171 @@
+//! Looking up elements by name and reading the properties of a
+//! definition.
+//!
+//! [`Db::find`] returns the element at a path of names from the state
+//! machine, see `builder` for how paths are looked up, or with the path
+//! as fullname. [`Db::glob`] returns the elements whose fullname matches
+//! a pattern, one name per `::` separated segment of the pattern:
+//!
+//! * `*` matches any part of a name and `?` any single character
+//! * `**` as a segment matches any number of names, none included
+//!
+//! ```text
+//! sm1::**::Error*     elements named Error... at any depth
+//! sm1::region_1::*    the vertices and transitions of region_1
+//! ```
+//!
+//! The `iter_` methods go over all the elements of a kind in the order
+//! they were added, the other methods read what a vertex, state or
+//! transition refers to and return an error for a dbid of another kind.
+
+use crate::*;
+
+/// Match a name against a segment of a pattern.
+fn matches_name(pattern: &str, name: &str) -> bool {
+    let mut chars = pattern.chars();
+    match chars.next() {
+        None => name.is_empty(),
+        Some('*') => {
+            let rest = chars.as_str();
+            name.char_indices()
+                .map(|(i, _)| i)
+                .chain(core::iter::once(name.len()))
+                .any(|i| matches_name(rest, &name[i..]))
+        }
+        Some(c) => {
+            let mut names = name.chars();
+            match names.next() {
+                Some(n) if c == '?' || c == n => matches_name(chars.as_str(), names.as_str()),
+                _ => false,
+            }
+        }
+    }
+}
+
+/// Match the names of a fullname against the segments of a pattern.
+fn matches_path(pattern: &[&str], names: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => names.is_empty(),
+        Some((&"**", rest)) => (0..=names.len()).any(|i| matches_path(rest, &names[i..])),
+        Some((segment, rest)) => match names.split_first() {
+            Some((name, names)) => matches_name(segment, name) && matches_path(rest, names),
+            None => false,
+        },
+    }
+}
+
+impl Db {
+    /// The element at a path of names from the state machine, or with
+    /// the path as fullname.
+    pub fn find(&self, path: &str) -> Option<DbId> {
+        self.lookup(0, path)
+    }
+
+    /// The elements whose fullname matches the pattern, see the module
+    /// documentation.
+    pub fn glob(&self, pattern: &str) -> Vec<DbId> {
+        let pattern: Vec<&str> = pattern.split("::").collect();
+        self.fullnames
+            .iter()
+            .enumerate()
+            .filter(|(_, fullname)| {
+                let names: Vec<&str> = fullname.split("::").collect();
+                matches_path(&pattern, &names)
+            })
+            .map(|(dbid, _)| dbid)
+            .collect()
+    }
+
+    /// All the states, simple, composite and orthogonal.
+    pub fn iter_states(&self) -> impl Iterator<Item = DbId> + '_ {
+        self.states.iter().map(|s| s.dbid)
+    }
+
+    /// All the vertices, states, pseudostates and final states.
+    pub fn iter_vertices(&self) -> impl Iterator<Item = DbId> + '_ {
+        self.vertices.iter().map(|v| v.dbid)
+    }
+
+    pub fn iter_regions(&self) -> impl Iterator<Item = DbId> + '_ {
+        self.regions.iter().map(|r| r.dbid)
+    }
+
+    pub fn iter_transitions(&self) -> impl Iterator<Item = DbId> + '_ {
+        self.transitions.iter().map(|t| t.dbid)
+    }
+
+    pub fn iter_event_types(&self) -> impl Iterator<Item = DbId> + '_ {
+        self.event_types.iter().map(|e| e.dbid)
+    }
+
+    /// The event types dispatched by name, time and change events
+    /// aside.
+    pub fn iter_signal_events(&self) -> impl Iterator<Item = DbId> + '_ {
+        self.event_types
+            .iter()
+            .filter(|e| e.is_signal())
+            .map(|e| e.dbid)
+    }
+
+    /// The transitions leaving a vertex.
+    pub fn outgoing(&self, vertex: DbId) -> StateMachineResult<Vec<DbId>> {
+        Ok(self.vertex_def(vertex)?.outgoing.clone())
+    }
+
+    /// The transitions entering a vertex.
+    pub fn incoming(&self, vertex: DbId) -> StateMachineResult<Vec<DbId>> {
+        Ok(self.vertex_def(vertex)?.incoming.clone())
+    }
+
+    /// The event type triggering a transition, none for a completion
+    /// transition.
+    pub fn trigger(&self, transition: DbId) -> StateMachineResult<Option<DbId>> {
+        Ok(self.transitions[self.transition(transition)?].trigger)
+    }
+
+    pub fn source(&self, transition: DbId) -> StateMachineResult<DbId> {
+        Ok(self.transitions[self.transition(transition)?].source)
+    }
+
+    pub fn target(&self, transition: DbId) -> StateMachineResult<DbId> {
+        Ok(self.transitions[self.transition(transition)?].target)
+    }
+
+    pub fn guard(&self, transition: DbId) -> StateMachineResult<Option<Guard>> {
+        Ok(match self.transitions[self.transition(transition)?].guard {
+            OptGuard::Guard(guard) => Some(guard),
+            OptGuard::None => None,
+        })
+    }
+
+    pub fn effect(&self, transition: DbId) -> StateMachineResult<Option<Behavior>> {
+        Ok(
+            match self.transitions[self.transition(transition)?].effect {
+                OptBehavior::Behavior(effect) => Some(effect),
+                OptBehavior::None => None,
+            },
+        )
+    }
+
+    pub fn transition_kind(&self, transition: DbId) -> StateMachineResult<TransitionKind> {
+        Ok(self.transitions[self.transition(transition)?].kind)
+    }
+
+    pub fn entry(&self, state: DbId) -> StateMachineResult<Option<Behavior>> {
+        Ok(self.states[self.state(state)?].entry)
+    }
+
+    pub fn exit(&self, state: DbId) -> StateMachineResult<Option<Behavior>> {
+        Ok(self.states[self.state(state)?].exit)
+    }
+
+    /// The doActivity behavior of a state.
+    pub fn do_activity(&self, state: DbId) -> StateMachineResult<Option<Behavior>> {
+        Ok(self.states[self.state(state)?].do_while)
+    }
+
+    /// The event types deferred while a state is active.
+    pub fn deferrable_events(&self, state: DbId) -> StateMachineResult<Vec<DbId>> {
+        Ok(self.states[self.state(state)?].deferrable.clone())
+    }
+}
@@ -0,0 +1,1237 @@
+//! Diagrams and exports of a definition as text.
+//!
+//! * [`plantuml`] and [`mermaid`] write state diagrams for these tools
+//! * [`dot`] writes a Graphviz graph, composite states are clusters
+//! * [`json`] writes the structure of the definition for other tools,
+//!   [`from_json`] reads it back
+//! * [`svg`] draws the definition itself, see `layout`, and [`html`]
+//!   wraps the drawing in a page which plays a trace back
+//!
+//! Vertices are identified by their dbid, `n12`, and labelled with their
+//! name. Transitions are labelled `trigger [guard] / effect`, internal
+//! transitions and the behaviors of states are listed in the state where
+//! the format allows it.
+
+use crate::layout::{self, Layout};
+use crate::trace::{self, Trace};
+use crate::*;
+use alloc::vec;
+use core::fmt::Write;
+
+/// The label of a transition, `trigger [guard] / effect`.
+pub(crate) fn label(def: &StateMachineDef, t: DbId) -> String {
+    let tr = def._transition(t);
+    let mut parts = Vec::new();
+    if let Some(event) = tr.trigger {
+        parts.push(def._name(event).to_string());
+    }
+    if let OptGuard::Guard(guard) = tr.guard {
+        parts.push(format!("[{}]", guard.name));
+    }
+    if let OptBehavior::Behavior(effect) = tr.effect {
+        parts.push(format!("/ {}", effect.name));
+    }
+    parts.join(" ")
+}
+
+/// The entry, exit and do behaviors, deferred events and internal
+/// transitions of a state, one per line.
+pub(crate) fn state_lines(def: &StateMachineDef, state: DbId) -> Vec<String> {
+    let s = def._state(state);
+    let mut lines = Vec::new();
+    for (word, behavior) in [("entry", s.entry), ("exit", s.exit), ("do", s.do_while)] {
+        if let Some(b) = behavior {
+            lines.push(format!("{} / {}", word, b.name));
+        }
+    }
+    if !s.deferrable.is_empty() {
+        let events: Vec<&str> = s.deferrable.iter().map(|e| def._name(*e)).collect();
+        lines.push(format!("defer / {}", events.join(", ")));
+    }
+    for t in def.outgoing(state).unwrap_or_default() {
+        if def._transition(t).kind == TransitionKind::Internal {
+            lines.push(label(def, t));
+        }
+    }
+    lines
+}
+
+/// A state with vertices in its regions.
+fn is_composite(def: &StateMachineDef, v: DbId) -> bool {
+    def.elements[v].element_type == ElementType::Vertex(VertexType::State)
+        && def
+            ._state(v)
+            .regions
+            .iter()
+            .any(|r| !def.subvertices(*r).unwrap_or_default().is_empty())
+}
+
+fn vertex_type(def: &StateMachineDef, v: DbId) -> VertexType {
+    match def.elements[v].element_type {
+        ElementType::Vertex(t) => t,
+        _ => VertexType::State,
+    }
+}
+
+fn indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push_str("    ");
+    }
+}
+
+//----------------------------------------------------------------
+// PlantUML and Mermaid
+//----------------------------------------------------------------
+
+#[derive(Clone, Copy, PartialEq)]
+enum Diagram {
+    PlantUml,
+    Mermaid,
+}
+
+/// A PlantUML state diagram, the state machine is the outermost state.
+pub fn plantuml(def: &StateMachineDef) -> String {
+    let mut out = String::from("@startuml\n");
+    let _ = writeln!(out, "state \"{}\" as n0 {{", def._name(0));
+    diagram_regions(def, Diagram::PlantUml, 0, 1, &mut out);
+    out.push_str("}\n");
+    diagram_transitions(def, Diagram::PlantUml, &mut out);
+    out.push_str("@enduml\n");
+    out
+}
+
+/// A Mermaid `stateDiagram-v2`, the state machine is the outermost
+/// state.
+pub fn mermaid(def: &StateMachineDef) -> String {
+    let mut out = String::from("stateDiagram-v2\n");
+    let _ = writeln!(out, "    state \"{}\" as n0", def._name(0));
+    out.push_str("    state n0 {\n");
+    diagram_regions(def, Diagram::Mermaid, 0, 2, &mut out);
+    out.push_str("    }\n");
+    diagram_transitions(def, Diagram::Mermaid, &mut out);
+    out
+}
+
+fn diagram_regions(
+    def: &StateMachineDef,
+    diagram: Diagram,
+    owner: DbId,
+    depth: usize,
+    out: &mut String,
+) {
+    for (i, r) in def
+        .regions(owner)
+        .unwrap_or_default()
+        .into_iter()
+        .enumerate()
+    {
+        if i > 0 {
+            indent(out, depth);
+            out.push_str("--\n");
+        }
+        let vertices = def.subvertices(r).unwrap_or_default();
+        for v in vertices.iter().copied() {
+            let name = def._name(v);
+            let stereotype = match vertex_type(def, v) {
+                VertexType::InitialState | VertexType::FinalState => continue,
+                VertexType::State => "",
+                VertexType::Pseudostate(kind) => match (kind, diagram) {
+                    (PseudostateKind::Choice | PseudostateKind::Junction, _) => " <<choice>>",
+                    (PseudostateKind::Fork, _) => " <<fork>>",
+                    (PseudostateKind::Join, _) => " <<join>>",
+                    (PseudostateKind::ShallowHistory, Diagram::PlantUml) => " <<history>>",
+                    (PseudostateKind::DeepHistory, Diagram::PlantUml) => " <<history*>>",
+                    (PseudostateKind::EntryPoint, Diagram::PlantUml) => " <<entryPoint>>",
+                    (PseudostateKind::ExitPoint, Diagram::PlantUml) => " <<exitPoint>>",
+                    (PseudostateKind::Terminate, Diagram::PlantUml) => " <<end>>",
+                    _ => "",
+                },
+            };
+            indent(out, depth);
+            match diagram {
+                Diagram::PlantUml => {
+                    let _ = write!(out, "state \"{}\" as n{}{}", name, v, stereotype);
+                    if is_composite(def, v) {
+                        out.push_str(" {\n");
+                        diagram_regions(def, diagram, v, depth + 1, out);
+                        indent(out, depth);
+                        out.push('}');
+                    }
+                    out.push('\n');
+                    if vertex_type(def, v) == VertexType::State {
+                        for line in state_lines(def, v) {
+                            indent(out, depth);
+                            let _ = writeln!(out, "n{} : {}", v, line);
+                        }
+                    }
+                }
+                Diagram::Mermaid => {
+                    // mermaid only labels states which are declared
+                    // with a description, stereotyped ones cannot be
+                    if stereotype.is_empty() {
+                        let _ = writeln!(out, "state \"{}\" as n{}", name, v);
+                    } else {
+                        let _ = writeln!(out, "state n{}{}", v, stereotype);
+                    }
+                    if is_composite(def, v) {
+                        indent(out, depth);
+                        let _ = writeln!(out, "state n{} {{", v);
+                        diagram_regions(def, diagram, v, depth + 1, out);
+                        indent(out, depth);
+                        out.push_str("}\n");
+                    }
+                }
+            }
+        }
+        // initial transitions and transitions into final states are
+        // drawn from and to the [*] of their region
+        for v in vertices.iter().copied() {
+            let transitions = match vertex_type(def, v) {
+                VertexType::InitialState => def.outgoing(v),
+                VertexType::FinalState => def.incoming(v),
+                _ => continue,
+            };
+            for t in transitions.unwrap_or_default() {
+                let tr = def._transition(t);
+                let (source, target) = match vertex_type(def, v) {
+                    VertexType::InitialState => (String::from("[*]"), format!("n{}", tr.target)),
+                    _ => (format!("n{}", tr.source), String::from("[*]")),
+                };
+                indent(out, depth);
+                transition_line(def, t, &source, &target, out);
+            }
+        }
+    }
+}
+
+/// The transitions which are not drawn in a region.
+fn diagram_transitions(def: &StateMachineDef, diagram: Diagram, out: &mut String) {
+    for tr in &def.transitions {
+        if vertex_type(def, tr.source) == VertexType::InitialState
+            || vertex_type(def, tr.target) == VertexType::FinalState
+            || tr.kind == TransitionKind::Internal
+        {
+            continue;
+        }
+        if diagram == Diagram::Mermaid {
+            out.push_str("    ");
+        }
+        let (source, target) = (format!("n{}", tr.source), format!("n{}", tr.target));
+        transition_line(def, tr.dbid, &source, &target, out);
+    }
+}
+
+fn transition_line(def: &StateMachineDef, t: DbId, source: &str, target: &str, out: &mut String) {
+    let label = label(def, t);
+    match label.is_empty() {
+        true => {
+            let _ = writeln!(out, "{} --> {}", source, target);
+        }
+        false => {
+            let _ = writeln!(out, "{} --> {} : {}", source, target, label);
+        }
+    }
+}
+
+//----------------------------------------------------------------
+// Graphviz
+//----------------------------------------------------------------
+
+/// A Graphviz digraph. Composite states are clusters holding an
+/// invisible node their transitions are drawn from and to, the regions
+/// of orthogonal states are dashed clusters.
+pub fn dot(def: &StateMachineDef) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "digraph \"{}\" {{", def._name(0));
+    out.push_str("    compound=true;\n");
+    out.push_str("    node [shape=box, style=rounded];\n");
+    dot_regions(def, 0, 1, &mut out);
+    for tr in &def.transitions {
+        if tr.kind == TransitionKind::Internal {
+            continue;
+        }
+        let mut attrs = Vec::new();
+        let label = label(def, tr.dbid);
+        if !label.is_empty() {
+            attrs.push(format!("label={}", quote(&label)));
+        }
+        if is_composite(def, tr.source) {
+            attrs.push(format!("ltail=cluster_n{}", tr.source));
+        }
+        if is_composite(def, tr.target) {
+            attrs.push(format!("lhead=cluster_n{}", tr.target));
+        }
+        let _ = write!(out, "    n{} -> n{}", tr.source, tr.target);
+        if !attrs.is_empty() {
+            let _ = write!(out, " [{}]", attrs.join(", "));
+        }
+        out.push_str(";\n");
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+fn dot_regions(def: &StateMachineDef, owner: DbId, depth: usize, out: &mut String) {
+    let regions = def.regions(owner).unwrap_or_default();
+    let orthogonal = regions.len() > 1;
+    for r in regions {
+        let mut depth = depth;
+        if orthogonal {
+            indent(out, depth);
+            let _ = writeln!(out, "subgraph cluster_n{} {{", r);
+            indent(out, depth + 1);
+            let _ = writeln!(out, "label={}; style=dashed;", quote(def._name(r)));
+            depth += 1;
+        }
+        for v in def.subvertices(r).unwrap_or_default() {
+            let name = def._name(v);
+            indent(out, depth);
+            if is_composite(def, v) {
+                let mut lines = vec![name.to_string()];
+                lines.extend(state_lines(def, v));
+                let _ = writeln!(out, "subgraph cluster_n{} {{", v);
+                indent(out, depth + 1);
+                let _ = writeln!(out, "label={}; style=rounded;", quote(&lines.join("\n")));
+                indent(out, depth + 1);
+                let _ = writeln!(out, "n{} [shape=point, style=invis];", v);
+                dot_regions(def, v, depth + 1, out);
+                indent(out, depth);
+                out.push_str("}\n");
+                continue;
+            }
+            let attrs = match vertex_type(def, v) {
+                VertexType::State => {
+                    let mut lines = vec![name.to_string()];
+                    lines.extend(state_lines(def, v));
+                    format!("label={}", quote(&lines.join("\n")))
+                }
+                VertexType::InitialState => {
+                    String::from("shape=circle, style=filled, fillcolor=black, label=\"\", width=0.2")
+                }
+                VertexType::FinalState => String::from("shape=doublecircle, label=\"\", width=0.2"),
+                VertexType::Pseudostate(kind) => match kind {
+                    PseudostateKind::Choice => String::from("shape=diamond, label=\"\""),
+                    PseudostateKind::Fork | PseudostateKind::Join => String::from(
+                        "shape=rect, style=filled, fillcolor=black, label=\"\", height=0.1",
+                    ),
+                    PseudostateKind::ShallowHistory => String::from("shape=circle, label=\"H\""),
+                    PseudostateKind::DeepHistory => String::from("shape=circle, label=\"H*\""),
+                    PseudostateKind::Terminate => String::from("shape=plaintext, label=\"X\""),
+                    _ => format!(
+                        "shape=circle, style=filled, fillcolor=black, width=0.1, xlabel={}, label=\"\"",
+                        quote(name)
+                    ),
+                },
+            };
+            let _ = writeln!(out, "n{} [{}];", v, attrs);
+        }
+        if orthogonal {
+            indent(out, depth - 1);
+            out.push_str("}\n");
+        }
+    }
+}
+
+//----------------------------------------------------------------
+// JSON
+//----------------------------------------------------------------
+
+/// Quote a string for JSON.
+pub fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_name<T>(value: Option<T>, name: impl Fn(T) -> Name) -> String {
+    value.map_or(String::from("null"), |v| json_string(name(v)))
+}
+
+/// The structure of the definition as JSON: its name, event types,
+/// regions, vertices and transitions, which refer to each other by
+/// dbid.
+pub fn json(def: &StateMachineDef) -> String {
+    let mut out = String::from("{\n");
+    let _ = writeln!(out, "  \"name\": {},", json_string(def._name(0)));
+    let events: Vec<String> = def
+        .event_types
+        .iter()
+        .map(|e| {
+            format!(
+                "    {{\"id\": {}, \"name\": {}}}",
+                e.dbid,
+                json_string(e.name)
+            )
+        })
+        .collect();
+    let _ = writeln!(out, "  \"events\": [\n{}\n  ],", events.join(",\n"));
+    let regions: Vec<String> = def
+        .regions
+        .iter()
+        .map(|r| {
+            format!(
+                "    {{\"id\": {}, \"name\": {}, \"owner\": {}}}",
+                r.dbid,
+                json_string(r.name),
+                def.parents[r.dbid]
+            )
+        })
+        .collect();
+    let _ = writeln!(out, "  \"regions\": [\n{}\n  ],", regions.join(",\n"));
+    let vertices: Vec<String> = def
+        .vertices
+        .iter()
+        .map(|v| {
+            let mut text = format!(
+                "    {{\"id\": {}, \"kind\": {}, \"name\": {}, \"region\": {}",
+                v.dbid,
+                json_string(diff::kind(def.elements[v.dbid].element_type)),
+                json_string(v.name),
+                v.container
+            );
+            if v.vertex_type == VertexType::State {
+                let s = def._state(v.dbid);
+                let defer: Vec<String> = s.deferrable.iter().map(|e| e.to_string()).collect();
+                let _ = write!(
+                    text,
+                    ", \"entry\": {}, \"exit\": {}, \"do\": {}, \"defer\": [{}]",
+                    json_name(s.entry, |b| b.name),
+                    json_name(s.exit, |b| b.name),
+                    json_name(s.do_while, |b| b.name),
+                    defer.join(", ")
+                );
+            }
+            text.push('}');
+            text
+        })
+        .collect();
+    let _ = writeln!(out, "  \"vertices\": [\n{}\n  ],", vertices.join(",\n"));
+    let transitions: Vec<String> = def
+        .transitions
+        .iter()
+        .map(|t| {
+            let guard = match t.guard {
+                OptGuard::Guard(g) => Some(g),
+                OptGuard::None => None,
+            };
+            let effect = match t.effect {
+                OptBehavior::Behavior(b) => Some(b),
+                OptBehavior::None => None,
+            };
+            format!(
+                "    {{\"id\": {}, \"name\": {}, \"source\": {}, \"target\": {}, \"trigger\": {}, \"guard\": {}, \"effect\": {}, \"kind\": \"{}\"}}",
+                t.dbid,
+                json_string(t.name),
+                t.source,
+                t.target,
+                t.trigger.map_or(String::from("null"), |e| e.to_string()),
+                json_name(guard, |g| g.name),
+                json_name(effect, |b| b.name),
+                format!("{:?}", t.kind).to_lowercase()
+            )
+        })
+        .collect();
+    let _ = writeln!(
+        out,
+        "  \"transitions\": [\n{}\n  ]",
+        transitions.join(",\n")
+    );
+    out.push_str("}\n");
+    out
+}
+
+/// A value of a JSON document and the byte offset it starts at.
+struct Json {
+    value: Value,
+    at: usize,
+}
+
+enum Value {
+    Null,
+    /// true and false, no member of the format has them
+    Bool,
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+fn json_error<T>(src: &str, message: String, at: usize) -> Result<T, dsl::ParseError> {
+    let before = &src[..at];
+    let line = before.matches('\n').count() + 1;
+    let column = at - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    let span = dsl::Span {
+        start: at,
+        end: at + 1,
+        line,
+        column,
+    };
+    Err(dsl::ParseError { message, span })
+}
+
+struct JsonReader<'s> {
+    src: &'s str,
+    pos: usize,
+}
+
+impl JsonReader<'_> {
+    fn document(&mut self) -> Result<Json, dsl::ParseError> {
+        let value = self.value()?;
+        self.skip_space();
+        if self.pos < self.src.len() {
+            return json_error(self.src, "expected the end of the text".into(), self.pos);
+        }
+        Ok(value)
+    }
+
+    fn skip_space(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        self.skip_space();
+        let found = self.src.as_bytes().get(self.pos) == Some(&c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), dsl::ParseError> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => json_error(self.src, format!("expected `{}`", c as char), self.pos),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, dsl::ParseError> {
+        self.skip_space();
+        let at = self.pos;
+        let rest = &self.src[at..];
+        let value = match rest.as_bytes().first() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        let key = self.value()?;
+                        let key = match key.value {
+                            Value::String(key) => key,
+                            _ => return json_error(self.src, "expected a name".into(), key.at),
+                        };
+                        self.expect(b':')?;
+                        members.push((key, self.value()?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Value::Object(members)
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value()?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Value::Array(items)
+            }
+            Some(b'"') => Value::String(self.string()?),
+            Some(b'-' | b'0'..=b'9') => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                Value::Number(rest[..len].to_string())
+            }
+            _ if rest.starts_with("null") => {
+                self.pos += 4;
+                Value::Null
+            }
+            _ if rest.starts_with("true") => {
+                self.pos += 4;
+                Value::Bool
+            }
+            _ if rest.starts_with("false") => {
+                self.pos += 5;
+                Value::Bool
+            }
+            _ => return json_error(self.src, "expected a value".into(), at),
+        };
+        Ok(Json { value, at })
+    }
+
+    fn string(&mut self) -> Result<String, dsl::ParseError> {
+        let start = self.pos;
+        let mut out = String::new();
+        let mut chars = self.src[start + 1..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos = start + 1 + i + 1;
+                    return Ok(out);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, e)| e) {
+                        Some('"') => Some('"'),
+                        Some('\\') => Some('\\'),
+                        Some('/') => Some('/'),
+                        Some('n') => Some('\n'),
+                        Some('t') => Some('\t'),
+                        Some('r') => Some('\r'),
+                        Some('b') => Some('\u{8}'),
+                        Some('f') => Some('\u{c}'),
+                        Some('u') => {
+                            let hex: String = chars.by_ref().take(4).map(|(_, h)| h).collect();
+                            u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+                        }
+                        _ => None,
+                    };
+                    match escaped {
+                        Some(e) => out.push(e),
+                        None => {
+                            return json_error(self.src, "invalid escape".into(), start + 1 + i)
+                        }
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+        json_error(self.src, "unterminated string".into(), start)
+    }
+}
+
+/// Reads the elements of a definition out of a document written by
+/// [`json`].
+struct JsonModel<'s> {
+    src: &'s str,
+}
+
+impl JsonModel<'_> {
+    fn error<T>(&self, message: String, json: &Json) -> Result<T, dsl::ParseError> {
+        json_error(self.src, message, json.at)
+    }
+
+    fn check<T>(&self, result: StateMachineResult<T>, json: &Json) -> Result<T, dsl::ParseError> {
+        result.or_else(|e| self.error(e.to_string(), json))
+    }
+
+    /// A member of an object, None if it is missing or null.
+    fn member<'j>(
+        &self,
+        object: &'j Json,
+        name: &str,
+    ) -> Result<Option<&'j Json>, dsl::ParseError> {
+        match &object.value {
+            Value::Object(members) => Ok(members
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value)
+                .filter(|value| !matches!(value.value, Value::Null))),
+            _ => self.error("expected an object".into(), object),
+        }
+    }
+
+    fn field<'j>(&self, object: &'j Json, name: &str) -> Result<&'j Json, dsl::ParseError> {
+        match self.member(object, name)? {
+            Some(value) => Ok(value),
+            None => self.error(format!("missing `{}`", name), object),
+        }
+    }
+
+    fn array<'j>(&self, json: &'j Json) -> Result<&'j [Json], dsl::ParseError> {
+        match &json.value {
+            Value::Array(items) => Ok(items),
+            _ => self.error("expected an array".into(), json),
+        }
+    }
+
+    fn text<'j>(&self, json: &'j Json) -> Result<&'j str, dsl::ParseError> {
+        match &json.value {
+            Value::String(text) => Ok(text),
+            _ => self.error("expected a string".into(), json),
+        }
+    }
+
+    fn id(&self, json: &Json) -> Result<DbId, dsl::ParseError> {
+        match &json.value {
+            Value::Number(n) => match n.parse() {
+                Ok(id) => Ok(id),
+                Err(_) => self.error(format!("`{}` is not a dbid", n), json),
+            },
+            _ => self.error("expected a dbid".into(), json),
+        }
+    }
+
+    fn opt_text<'j>(
+        &self,
+        object: &'j Json,
+        name: &str,
+    ) -> Result<Option<&'j str>, dsl::ParseError> {
+        self.member(object, name)?.map(|v| self.text(v)).transpose()
+    }
+
+    fn build(&self, document: &Json) -> Result<StateMachineDef, dsl::ParseError> {
+        let mut interned = Interned::default();
+        let name = self.text(self.field(document, "name")?)?;
+        let mut db = StateMachineDef::new(interned.intern(name));
+        db.interned = interned;
+        let transitions = self.array(self.field(document, "transitions")?)?;
+        let mut elements = Vec::new();
+        for section in ["events", "regions", "vertices", "transitions"] {
+            for element in self.array(self.field(document, section)?)? {
+                elements.push((self.id(self.field(element, "id")?)?, section, element));
+            }
+        }
+        // each element only refers to elements added before it
+        elements.sort_by_key(|(id, _, _)| *id);
+        for (id, section, element) in &elements {
+            let name = db.intern(self.text(self.field(element, "name")?)?);
+            let added = match *section {
+                "events" => self.event(&mut db, *id, name, transitions)?,
+                "regions" => match self.id(self.field(element, "owner")?)? {
+                    0 => db.add_sm_region(name),
+                    owner => db.add_region(name, owner),
+                },
+                "vertices" => self.vertex(&mut db, name, element)?,
+                _ => self.transition(&mut db, name, element)?,
+            };
+            if self.check(added, element)? != *id {
+                return self.error(
+                    format!("dbid {} out of sequence, ids follow each other from 1", id),
+                    element,
+                );
+            }
+        }
+        for (id, _, element) in elements.iter().filter(|(_, s, _)| *s == "vertices") {
+            if let Some(defer) = self.member(element, "defer")? {
+                for event in self.array(defer)? {
+                    let r = db.add_deferrable_event(*id, self.id(event)?);
+                    self.check(r, event)?;
+                }
+            }
+        }
+        Ok(db)
+    }
+
+    /// Time and change events are told apart from signals by their
+    /// names, `after(30s)`, `at(1h)` and `when(condition)`.
+    fn event(
+        &self,
+        db: &mut StateMachineDef,
+        id: DbId,
+        name: Name,
+        transitions: &[Json],
+    ) -> Result<StateMachineResult<DbId>, dsl::ParseError> {
+        if let Some(condition) = name.strip_prefix("when(").and_then(|c| c.strip_suffix(')')) {
+            return Ok(db.add_change_event(condition));
+        }
+        let when = match (name.strip_prefix("after("), name.strip_prefix("at(")) {
+            (Some(d), _) => d
+                .strip_suffix(')')
+                .and_then(timer::parse_duration)
+                .map(TimeEvent::After),
+            (_, Some(d)) => d
+                .strip_suffix(')')
+                .and_then(timer::parse_duration)
+                .map(TimeEvent::At),
+            _ => None,
+        };
+        let when = match when {
+            Some(when) => when,
+            None => return Ok(db.add_event_type(name)),
+        };
+        for t in transitions {
+            if let Some(trigger) = self.member(t, "trigger")? {
+                if self.id(trigger)? == id {
+                    return Ok(db.add_time_event(self.id(self.field(t, "source")?)?, when));
+                }
+            }
+        }
+        Ok(Err(StateMachineError::InvalidEventType(id)))
+    }
+
+    fn vertex(
+        &self,
+        db: &mut StateMachineDef,
+        name: Name,
+        element: &Json,
+    ) -> Result<StateMachineResult<DbId>, dsl::ParseError> {
+        let kind = self.field(element, "kind")?;
+        let vertex_type = match self.text(kind)? {
+            "state" => VertexType::State,
+            "initial" => VertexType::InitialState,
+            "final" => VertexType::FinalState,
+            "entrypoint" => VertexType::Pseudostate(PseudostateKind::EntryPoint),
+            "exitpoint" => VertexType::Pseudostate(PseudostateKind::ExitPoint),
+            "deephistory" => VertexType::Pseudostate(PseudostateKind::DeepHistory),
+            "history" => VertexType::Pseudostate(PseudostateKind::ShallowHistory),
+            "join" => VertexType::Pseudostate(PseudostateKind::Join),
+            "fork" => VertexType::Pseudostate(PseudostateKind::Fork),
+            "junction" => VertexType::Pseudostate(PseudostateKind::Junction),
+            "terminate" => VertexType::Pseudostate(PseudostateKind::Terminate),
+            "choice" => VertexType::Pseudostate(PseudostateKind::Choice),
+            other => return self.error(format!("unknown vertex kind `{}`", other), kind),
+        };
+        let region = self.id(self.field(element, "region")?)?;
+        let dbid = match db.add_vertex(name, region, vertex_type) {
+            Ok(dbid) => dbid,
+            e => return Ok(e),
+        };
+        if vertex_type == VertexType::State {
+            if let Some(entry) = self.opt_text(element, "entry")? {
+                let behavior = Behavior::named(db.intern(entry));
+                self.check(db.set_entry(dbid, behavior), element)?;
+            }
+            if let Some(exit) = self.opt_text(element, "exit")? {
+                let behavior = Behavior::named(db.intern(exit));
+                self.check(db.set_exit(dbid, behavior), element)?;
+            }
+            if let Some(activity) = self.opt_text(element, "do")? {
+                let behavior = Behavior::named(db.intern(activity));
+                self.check(db.set_do(dbid, behavior), element)?;
+            }
+        }
+        Ok(Ok(dbid))
+    }
+
+    fn transition(
+        &self,
+        db: &mut StateMachineDef,
+        name: Name,
+        element: &Json,
+    ) -> Result<StateMachineResult<DbId>, dsl::ParseError> {
+        let kind = match self.opt_text(element, "kind")? {
+            None | Some("external") => TransitionKind::External,
+            Some("internal") => TransitionKind::Internal,
+            Some("local") => TransitionKind::Local,
+            Some(other) => {
+                return self.error(format!("unknown transition kind `{}`", other), element)
+            }
+        };
+        let trigger = self
+            .member(element, "trigger")?
+            .map(|t| self.id(t))
+            .transpose()?;
+        let guard = match self.opt_text(element, "guard")? {
+            Some(guard) => OptGuard::Guard(Guard::named(db.intern(guard))),
+            None => OptGuard::None,
+        };
+        let effect = match self.opt_text(element, "effect")? {
+            Some(effect) => OptBehavior::Behavior(Behavior::named(db.intern(effect))),
+            None => OptBehavior::None,
+        };
+        let source = self.id(self.field(element, "source")?)?;
+        let target = self.id(self.field(element, "target")?)?;
+        let dbid = match db.add_transition(name, trigger, source, target, effect, guard) {
+            Ok(dbid) => dbid,
+            e => return Ok(e),
+        };
+        if kind != TransitionKind::External {
+            self.check(db.set_transition_kind(dbid, kind), element)?;
+        }
+        Ok(Ok(dbid))
+    }
+}
+
+/// Read a definition back from the JSON written by [`json`]. The dbids
+/// are kept, elements are added in the order of their ids.
+pub fn from_json(src: &str) -> StateMachineResult<StateMachineDef> {
+    let document = JsonReader { src, pos: 0 }.document()?;
+    Ok(JsonModel { src }.build(&document)?)
+}
+
+//----------------------------------------------------------------
+// SVG
+//----------------------------------------------------------------
+
+/// Margin around the state machine.
+const MARGIN: i32 = 10;
+
+/// Escape text for XML.
+fn xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// An SVG drawing of the definition, laid out by [`Layout`] without
+/// external tools. Each vertex and transition is a group with the id
+/// `n{dbid}` and the classes `vertex`, `state` or `pseudostate`, and
+/// `transition`, so it can be styled.
+pub fn svg(def: &StateMachineDef) -> String {
+    let layout = Layout::new(def);
+    let mut out = String::new();
+    let (width, height) = (layout.width() + 2 * MARGIN, layout.height() + 2 * MARGIN);
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"12\">",
+        w = width,
+        h = height
+    );
+    out.push_str(
+        "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"8\" markerHeight=\"8\" orient=\"auto\"><path d=\"M0,0 L10,5 L0,10 z\"/></marker></defs>\n",
+    );
+    let _ = writeln!(out, "<g transform=\"translate({m},{m})\">", m = MARGIN);
+    svg_vertex(def, &layout, 0, &mut out);
+    for tr in &def.transitions {
+        if tr.kind != TransitionKind::Internal {
+            svg_transition(def, &layout, tr.dbid, &mut out);
+        }
+    }
+    out.push_str("</g>\n</svg>\n");
+    out
+}
+
+fn svg_vertex(def: &StateMachineDef, layout: &Layout, v: DbId, out: &mut String) {
+    let Some(r) = layout.rect(v) else {
+        return;
+    };
+    let (cx, cy) = r.center();
+    let kind = match def.elements[v].element_type {
+        ElementType::Vertex(VertexType::Pseudostate(_))
+        | ElementType::Vertex(VertexType::InitialState)
+        | ElementType::Vertex(VertexType::FinalState) => "pseudostate",
+        _ => "state",
+    };
+    let _ = writeln!(out, "<g class=\"vertex {}\" id=\"n{}\">", kind, v);
+    let black = "fill=\"black\" stroke=\"black\"";
+    let white = "fill=\"white\" stroke=\"black\"";
+    match def.elements[v].element_type {
+        ElementType::Vertex(VertexType::InitialState) => {
+            let _ = writeln!(
+                out,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {}/>",
+                cx,
+                cy,
+                r.width / 2,
+                black
+            );
+        }
+        ElementType::Vertex(VertexType::FinalState) => {
+            let _ = writeln!(
+                out,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {}/>",
+                cx,
+                cy,
+                r.width / 2,
+                white
+            );
+            let _ = writeln!(
+                out,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {}/>",
+                cx,
+                cy,
+                r.width / 2 - 4,
+                black
+            );
+        }
+        ElementType::Vertex(VertexType::Pseudostate(kind)) => match kind {
+            PseudostateKind::Fork | PseudostateKind::Join => {
+                let _ = writeln!(
+                    out,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>",
+                    r.x, r.y, r.width, r.height, black
+                );
+            }
+            PseudostateKind::Choice => {
+                let _ = writeln!(
+                    out,
+                    "<polygon points=\"{},{} {},{} {},{} {},{}\" {}/>",
+                    cx,
+                    r.y,
+                    r.right(),
+                    cy,
+                    cx,
+                    r.bottom(),
+                    r.x,
+                    cy,
+                    white
+                );
+            }
+            PseudostateKind::Junction => {
+                let _ = writeln!(
+                    out,
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"6\" {}/>",
+                    cx, cy, black
+                );
+            }
+            PseudostateKind::Terminate => {
+                let _ =
+                    writeln!(
+                    out,
+                    "<path d=\"M{},{} L{},{} M{},{} L{},{}\" stroke=\"black\" stroke-width=\"2\"/>",
+                    r.x, r.y, r.right(), r.bottom(), r.right(), r.y, r.x, r.bottom()
+                );
+            }
+            _ => {
+                let _ = writeln!(
+                    out,
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {}/>",
+                    cx,
+                    cy,
+                    r.width / 2,
+                    white
+                );
+                let mark = match kind {
+                    PseudostateKind::ShallowHistory => "H",
+                    PseudostateKind::DeepHistory => "H*",
+                    _ => "",
+                };
+                if !mark.is_empty() {
+                    let _ = writeln!(
+                        out,
+                        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                        cx,
+                        cy + 4,
+                        mark
+                    );
+                }
+                if kind == PseudostateKind::ExitPoint {
+                    let d = r.width / 3;
+                    let _ = writeln!(
+                        out,
+                        "<path d=\"M{},{} L{},{} M{},{} L{},{}\" stroke=\"black\"/>",
+                        cx - d,
+                        cy - d,
+                        cx + d,
+                        cy + d,
+                        cx + d,
+                        cy - d,
+                        cx - d,
+                        cy + d
+                    );
+                }
+                if matches!(
+                    kind,
+                    PseudostateKind::EntryPoint | PseudostateKind::ExitPoint
+                ) {
+                    let _ = writeln!(
+                        out,
+                        "<text x=\"{}\" y=\"{}\">{}</text>",
+                        r.right() + 4,
+                        r.y - 2,
+                        xml(def._name(v))
+                    );
+                }
+            }
+        },
+        _ => {
+            let _ = writeln!(
+                out,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"10\" {}/>",
+                r.x, r.y, r.width, r.height, white
+            );
+            for (i, line) in layout::title(def, v).iter().enumerate() {
+                let y = r.y + layout::LINE_HEIGHT * (i as i32 + 1);
+                match i {
+                    0 => {
+                        let _ = writeln!(
+                            out,
+                            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-weight=\"bold\">{}</text>",
+                            cx,
+                            y,
+                            xml(line)
+                        );
+                    }
+                    _ => {
+                        let _ = writeln!(
+                            out,
+                            "<text x=\"{}\" y=\"{}\">{}</text>",
+                            r.x + layout::CHAR_WIDTH,
+                            y,
+                            xml(line)
+                        );
+                    }
+                }
+            }
+            for (i, region) in def.regions(v).unwrap_or_default().into_iter().enumerate() {
+                if let Some(rr) = layout.rect(region) {
+                    let dash = if i > 0 {
+                        " stroke-dasharray=\"6,4\""
+                    } else {
+                        ""
+                    };
+                    let _ = writeln!(
+                        out,
+                        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\"{}/>",
+                        rr.x,
+                        rr.y,
+                        rr.right(),
+                        rr.y,
+                        dash
+                    );
+                }
+                for sub in def.subvertices(region).unwrap_or_default() {
+                    svg_vertex(def, layout, sub, out);
+                }
+            }
+        }
+    }
+    out.push_str("</g>\n");
+}
+
+fn svg_transition(def: &StateMachineDef, layout: &Layout, t: DbId, out: &mut String) {
+    let Some(edge) = layout.edge(t) else {
+        return;
+    };
+    let mut path = format!("M{},{}", edge.start.0, edge.start.1);
+    match edge.controls[..] {
+        [] => path.push_str(" L"),
+        [c] => {
+            let _ = write!(path, " Q{},{}", c.0, c.1);
+        }
+        [c1, c2, ..] => {
+            let _ = write!(path, " C{},{} {},{}", c1.0, c1.1, c2.0, c2.1);
+        }
+    }
+    let _ = write!(path, " {},{}", edge.end.0, edge.end.1);
+    let _ = writeln!(out, "<g class=\"transition\" id=\"n{}\">", t);
+    let _ = writeln!(
+        out,
+        "<path d=\"{}\" fill=\"none\" stroke=\"black\" marker-end=\"url(#arrow)\"/>",
+        path
+    );
+    let label = label(def, t);
+    if !label.is_empty() {
+        let _ = writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" stroke=\"white\" stroke-width=\"3\" paint-order=\"stroke\">{}</text>",
+            edge.label.center().0,
+            edge.label.bottom() - 4,
+            xml(&label)
+        );
+    }
+    out.push_str("</g>\n");
+}
+
+//----------------------------------------------------------------
+// HTML
+//----------------------------------------------------------------
+
+const STEPPER: &str = r#"<script>
+const svg = document.querySelector("svg");
+const label = document.getElementById("step");
+let current = 0, timer = null;
+function show(i) {
+    current = Math.max(0, Math.min(steps.length - 1, i));
+    svg.querySelectorAll(".active").forEach(e => e.classList.remove("active"));
+    if (steps.length == 0) { label.textContent = "no steps"; return; }
+    const step = steps[current];
+    step.active.forEach(id => {
+        const e = document.getElementById("n" + id);
+        if (e) e.classList.add("active");
+    });
+    label.textContent = (current + 1) + "/" + steps.length + ": " + step.input;
+}
+function play() {
+    if (timer) { clearInterval(timer); timer = null; return; }
+    if (current == steps.length - 1) show(0);
+    timer = setInterval(() => {
+        if (current == steps.length - 1) { clearInterval(timer); timer = null; } else { show(current + 1); }
+    }, 1000);
+}
+document.getElementById("first").onclick = () => show(0);
+document.getElementById("prev").onclick = () => show(current - 1);
+document.getElementById("next").onclick = () => show(current + 1);
+document.getElementById("last").onclick = () => show(steps.length - 1);
+document.getElementById("play").onclick = play;
+document.addEventListener("keydown", e => {
+    if (e.key == "ArrowLeft") show(current - 1);
+    if (e.key == "ArrowRight") show(current + 1);
+});
+show(0);
+</script>
+"#;
+
+/// A single HTML page holding the [`svg`] drawing of the definition
+/// and a stepper which plays a trace back: the states active after each
+/// step, and the states enclosing them, are highlighted. The page needs
+/// nothing else, it can be attached to a bug report.
+pub fn html(def: &StateMachineDef, trace: &Trace) -> String {
+    let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(out, "<title>{}</title>", xml(def._name(0)));
+    out.push_str(
+        "<style>
+body { font-family: sans-serif; }
+.vertex.state.active > rect { fill: #ffe08a; stroke-width: 2; }
+.vertex.pseudostate.active > circle { stroke: #d08000; stroke-width: 3; }
+#controls { margin: 8px 0; }
+#step { margin-left: 8px; }
+</style>
+</head>
+<body>
+<div id=\"controls\">
+<button id=\"first\">&#x23EE;</button><button id=\"prev\">&#x25C0;</button><button id=\"play\">&#x23EF;</button><button id=\"next\">&#x25B6;</button><button id=\"last\">&#x23ED;</button><span id=\"step\"></span>
+</div>
+",
+    );
+    out.push_str(&svg(def));
+    out.push_str("<script>\nconst steps = [\n");
+    for step in &trace.steps {
+        let input = match step.input {
+            trace::Input::Start => String::from("start"),
+            trace::Input::Stop => String::from("stop"),
+            trace::Input::ActivityDone(state) => {
+                format!("{} done", def.name(state).unwrap_or("?"))
+            }
+            trace::Input::Dispatch(event) => {
+                let name = def.name(event).unwrap_or("?");
+                match step.consumed {
+                    true => String::from(name),
+                    false => format!("{} (not consumed)", name),
+                }
+            }
+        };
+        // the enclosing states of the active vertices are active too
+        let mut active: Vec<DbId> = Vec::new();
+        for v in &step.configuration {
+            let mut v = *v;
+            while v != 0 && v < def.elements.len() && !active.contains(&v) {
+                active.push(v);
+                v = def.parents[def.parents[v]];
+            }
+        }
+        active.sort_unstable();
+        let ids: Vec<String> = active.iter().map(|v| v.to_string()).collect();
+        let _ = writeln!(
+            out,
+            "    {{\"input\": {}, \"active\": [{}]}},",
+            json_string(&input),
+            ids.join(", ")
+        );
+    }
+    out.push_str("];\n</script>\n");
+    out.push_str(STEPPER);
+    out.push_str("</body>\n</html>\n");
+    out
+}
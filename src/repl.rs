@@ -0,0 +1,334 @@
+//! An interactive session with a model, behind `sm repl`.
+//!
+//! The model is parsed and started, then each line read is a command:
+//!
+//! ```text
+//! show                     the active configuration, status and deferred events
+//! events                   the events which would fire a transition, and how
+//! send <event>, <event>    dispatch an event
+//! guard <name> [on|off]    set or toggle the outcome of a named guard
+//! step on|off, next        show the micro-steps one at a time
+//! undo                     go back before the last start, dispatch or stop
+//! start, stop
+//! save <file>              write the session as a trace
+//! load <file>              replay a trace from the start
+//! help, quit
+//! ```
+//!
+//! Named guards are true unless turned off with `guard`. A session is
+//! recorded as a [`Trace`], undoing replays it on a fresh definition
+//! without the last step, with the guard outcomes recorded. A saved
+//! session replays the same way with `Db::replay` or `load`.
+//!
+//! Dispatching runs to completion, the micro-steps are the observations
+//! made on the way: transitions selected, guards evaluated, states
+//! exited and entered, behaviors performed. With `step on` they are
+//! shown one per `next`, or per empty line, and the configuration once
+//! they are all shown.
+
+use crate::trace::Trace;
+use crate::*;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+/// The guard outcomes set by the user, the behaviors and observations
+/// are logged as micro-steps.
+struct Session {
+    guards: Vec<(String, bool)>,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl Context for Session {
+    fn behavior(&mut self, name: &str) {
+        self.log.lock().unwrap().push(format!("behavior {}", name));
+    }
+
+    fn guard(&mut self, name: &str) -> bool {
+        guard_outcome(&self.guards, name)
+    }
+}
+
+fn guard_outcome(guards: &[(String, bool)], name: &str) -> bool {
+    guards
+        .iter()
+        .find(|(g, _)| g == name)
+        .is_none_or(|(_, result)| *result)
+}
+
+pub struct Repl {
+    model: String,
+    def: StateMachineDef,
+    session: Session,
+    /// Micro-steps not shown yet.
+    pending: VecDeque<String>,
+    stepping: bool,
+}
+
+const HELP: &str = "\
+show                     the active configuration, status and deferred events
+events                   the events which would fire a transition, and how
+send <event>, <event>    dispatch an event
+guard <name> [on|off]    set or toggle the outcome of a named guard
+step on|off, next        show the micro-steps one at a time
+undo                     go back before the last start, dispatch or stop
+start, stop
+save <file>              write the session as a trace
+load <file>              replay a trace from the start
+help, quit
+";
+
+impl Repl {
+    /// Parse the model and start it, the start is the first step of the
+    /// session and its micro-steps are pending.
+    pub fn new(model: &str) -> StateMachineResult<Repl> {
+        let mut repl = Repl {
+            model: model.to_string(),
+            def: dsl::parse(model)?,
+            session: Session {
+                guards: Vec::new(),
+                log: Arc::new(Mutex::new(Vec::new())),
+            },
+            pending: VecDeque::new(),
+            stepping: false,
+        };
+        repl.observe();
+        repl.def.record();
+        repl.def.start_with(&mut repl.session)?;
+        repl.pending = repl.session.log.lock().unwrap().drain(..).collect();
+        Ok(repl)
+    }
+
+    pub fn def(&self) -> &StateMachineDef {
+        &self.def
+    }
+
+    /// The session so far.
+    pub fn trace(&self) -> &Trace {
+        self.def.trace().expect("internal_error:502117")
+    }
+
+    fn observe(&mut self) {
+        let log = self.session.log.clone();
+        self.def.set_observer(move |o: &Observation<'_>| {
+            // effects are logged by the session with the other behaviors
+            if !matches!(
+                o,
+                Observation::ElementAdded { .. } | Observation::EffectRun { .. }
+            ) {
+                log.lock().unwrap().push(o.to_string());
+            }
+        });
+    }
+
+    /// Read commands until the end of the input or `quit`.
+    pub fn run(&mut self, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+        self.flush(&mut out)?;
+        write!(out, "> ")?;
+        out.flush()?;
+        for line in input.lines() {
+            if !self.command(&line?, &mut out)? {
+                break;
+            }
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        writeln!(out)
+    }
+
+    /// Perform one command, return false on `quit`.
+    pub fn command(&mut self, line: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let result = match words[..] {
+            [] | ["next"] => self.next(out),
+            ["quit" | "exit" | "q"] => return Ok(false),
+            ["help" | "?"] => out.write_all(HELP.as_bytes()),
+            ["show"] => self.show(out),
+            ["events"] => self.events(out),
+            ["send", event] | [event] if self.def.event_type(event).is_some() => {
+                let event = self.def.event_type(event).unwrap();
+                self.input(out, |def, ctx| def.dispatch_with(event, ctx).map(|_| ()))
+            }
+            ["send", event] => writeln!(out, "unknown event {}", event),
+            ["start"] => self.input(out, |def, ctx| def.start_with(ctx)),
+            ["stop"] => self.input(out, |def, ctx| def.stop_with(ctx)),
+            ["guard", name] => {
+                let result = !guard_outcome(&self.session.guards, name);
+                self.set_guard(name, result, out)
+            }
+            ["guard", name, "on" | "true"] => self.set_guard(name, true, out),
+            ["guard", name, "off" | "false"] => self.set_guard(name, false, out),
+            ["step", "on"] => {
+                self.stepping = true;
+                Ok(())
+            }
+            ["step", "off"] => {
+                self.stepping = false;
+                self.flush(out)
+            }
+            ["undo"] => self.undo(out),
+            ["save", path] => match std::fs::write(path, self.trace().to_string()) {
+                Ok(()) => writeln!(out, "saved {} steps to {}", self.trace().steps.len(), path),
+                Err(err) => writeln!(out, "{}: {}", path, err),
+            },
+            ["load", path] => match std::fs::read_to_string(path) {
+                Ok(text) => match text.parse::<Trace>() {
+                    Ok(trace) => self.replay(&trace, out),
+                    Err(err) => writeln!(out, "{}: {}", path, err),
+                },
+                Err(err) => writeln!(out, "{}: {}", path, err),
+            },
+            _ => writeln!(out, "unknown command `{}`, try help", line.trim()),
+        };
+        result.map(|_| true)
+    }
+
+    /// Perform a start, dispatch or stop and show its micro-steps.
+    fn input(
+        &mut self,
+        out: &mut dyn Write,
+        input: impl FnOnce(&mut StateMachineDef, &mut Session) -> StateMachineResult<()>,
+    ) -> io::Result<()> {
+        self.pending.clear();
+        if let Err(err) = input(&mut self.def, &mut self.session) {
+            self.session.log.lock().unwrap().clear();
+            return writeln!(out, "error: {}", err);
+        }
+        self.pending = self.session.log.lock().unwrap().drain(..).collect();
+        match self.stepping {
+            true => self.next(out),
+            false => self.flush(out),
+        }
+    }
+
+    /// Show the next micro-step, and the configuration after the last.
+    fn next(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        match self.pending.pop_front() {
+            Some(step) if self.pending.is_empty() => {
+                writeln!(out, "  {}", step)?;
+                self.show(out)
+            }
+            Some(step) => writeln!(out, "  {}  ({} more)", step, self.pending.len()),
+            None => Ok(()),
+        }
+    }
+
+    /// Show the remaining micro-steps and the configuration.
+    fn flush(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        for step in self.pending.drain(..) {
+            writeln!(out, "  {}", step)?;
+        }
+        self.show(out)
+    }
+
+    /// The active configuration, see `write_active_states`.
+    fn show(&self, out: &mut dyn Write) -> io::Result<()> {
+        if let Err(err) = self.def.write_active_states(out) {
+            return match err {
+                StateMachineError::IOError(err) => Err(err),
+                err => writeln!(out, "error: {}", err),
+            };
+        }
+        write!(out, "[status] {:?}", self.def.status())?;
+        for event in self.def.deferred_events() {
+            write!(out, " deferred {}", self.def._name(*event))?;
+        }
+        writeln!(out)
+    }
+
+    /// The transitions the events would fire, with the outcome of their
+    /// guard. Only the innermost transitions for an event are listed,
+    /// like `dispatch` selects them.
+    fn events(&self, out: &mut dyn Write) -> io::Result<()> {
+        if !self.def.is_running() {
+            return writeln!(out, "not running, try start");
+        }
+        let mut enabled = Vec::new();
+        for event in self.def.iter_signal_events() {
+            let mut transitions = Vec::new();
+            for leaf in self.def.active_states() {
+                let mut v = leaf;
+                while v != 0 {
+                    let mut found = false;
+                    for t in self.def.outgoing(v).unwrap_or_default() {
+                        let tr = self.def._transition(t);
+                        if tr.trigger != Some(event) {
+                            continue;
+                        }
+                        let pass = match tr.guard {
+                            OptGuard::Guard(g) => guard_outcome(&self.session.guards, g.name),
+                            OptGuard::None => true,
+                        };
+                        if !transitions.iter().any(|(d, _)| *d == t) {
+                            transitions.push((t, pass));
+                        }
+                        found |= pass;
+                    }
+                    if found {
+                        break;
+                    }
+                    v = self.def.parents[self.def.parents[v]];
+                }
+            }
+            if transitions.iter().any(|(_, pass)| *pass) {
+                enabled.push(event);
+            }
+            for (t, pass) in transitions {
+                let tr = self.def._transition(t);
+                let guard = match tr.guard {
+                    OptGuard::Guard(g) => format!(" [{}]", g.name),
+                    OptGuard::None => String::new(),
+                };
+                writeln!(
+                    out,
+                    "{} {}{}: {} -> {}{}",
+                    if pass { '+' } else { '-' },
+                    self.def._name(event),
+                    guard,
+                    self.def._name(tr.source),
+                    self.def._name(tr.target),
+                    match tr.kind {
+                        TransitionKind::Internal => " (internal)",
+                        _ => "",
+                    }
+                )?;
+            }
+        }
+        let names: Vec<&str> = enabled.iter().map(|e| self.def._name(*e)).collect();
+        writeln!(out, "enabled: {}", names.join(", "))
+    }
+
+    fn set_guard(&mut self, name: &str, result: bool, out: &mut dyn Write) -> io::Result<()> {
+        self.session.guards.retain(|(g, _)| g != name);
+        self.session.guards.push((name.to_string(), result));
+        writeln!(out, "guard [{}] is {}", name, result)
+    }
+
+    fn undo(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        let mut trace = self.trace().clone();
+        if trace.steps.pop().is_none() {
+            return writeln!(out, "nothing to undo");
+        }
+        self.replay(&trace, out)
+    }
+
+    /// Replay a trace on a fresh definition, which becomes the session.
+    fn replay(&mut self, trace: &Trace, out: &mut dyn Write) -> io::Result<()> {
+        let mut def = dsl::parse(&self.model).expect("internal_error:502118");
+        def.record();
+        let mut quiet = Session {
+            guards: self.session.guards.clone(),
+            log: Arc::new(Mutex::new(Vec::new())),
+        };
+        match def.replay_with(trace, &mut quiet) {
+            Ok(None) => (),
+            Ok(Some(divergence)) => return writeln!(out, "{}", divergence),
+            Err(err) => return writeln!(out, "error: {}", err),
+        }
+        self.def = def;
+        self.observe();
+        self.pending.clear();
+        writeln!(out, "at step {}", trace.steps.len())?;
+        self.show(out)
+    }
+}
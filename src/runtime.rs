@@ -0,0 +1,903 @@
+//! Execution of a state machine definition.
+//!
+//! A definition keeps its own active state configuration: each region
+//! records its active vertex and, when it is exited, that vertex as its
+//! history. Events are processed one at a time, to completion:
+//!
+//! * the enabled transitions are selected, a transition whose source is
+//!   nested deeper has priority over a conflicting transition of an
+//!   enclosing state
+//! * each selected transition exits the states within the least common
+//!   ancestor region of its source and target, innermost first, performs
+//!   its effect, and enters its target together with the states which
+//!   enclose it, outermost first
+//! * completion transitions of states which completed meanwhile are
+//!   taken before the event is done with
+//! * an event which enables no transition but is deferred by an active
+//!   state is kept, and dispatched again once another event has been
+//!   consumed
+//!
+//! Behaviors and guards created from a function call it, those which
+//! are only known by name are resolved by a [`Context`].
+//!
+//! A do-activity is performed once its state is entered, unless the
+//! context keeps it going in the background, see
+//! [`Context::start_activity`]. The state then completes once
+//! [`Db::activity_done_with`] is called, and the activity is aborted
+//! before the state is exited.
+//!
+//! The time events triggering the transitions from a state are armed
+//! when it is entered and cancelled when it is exited, see `timer`. The
+//! change events whose condition became true are dispatched once an
+//! input was processed, see `change`.
+
+use crate::trace::Input;
+use crate::*;
+
+/// Upper bound on the completion transitions taken for a single event,
+/// only exceeded by completion transitions looping forever.
+pub(crate) const MAX_COMPLETIONS: usize = 1000;
+
+/// Performs named behaviors and evaluates named guards on behalf of a
+/// running state machine, and is told about the states entered and
+/// exited.
+pub trait Context {
+    /// Perform the behavior with the given name.
+    fn behavior(&mut self, name: &str) {
+        let _ = name;
+    }
+
+    /// Evaluate the guard with the given name. Guards nobody knows
+    /// about are true, in the contexts of the crate and the ones
+    /// `#[behaviors]` generates alike.
+    fn guard(&mut self, name: &str) -> bool {
+        let _ = name;
+        true
+    }
+
+    /// Called with the name of a state once its entry behavior has
+    /// been performed.
+    fn on_entry(&mut self, state: &str) {
+        let _ = state;
+    }
+
+    /// Called with the name of a state before its exit behavior is
+    /// performed.
+    fn on_exit(&mut self, state: &str) {
+        let _ = state;
+    }
+
+    /// Start the named do-activity of a state once it is entered. By
+    /// default it is performed like any behavior and is over when it
+    /// returns. Return true if it goes on in the background instead:
+    /// the state does not complete before `Db::activity_done_with` is
+    /// called, and `abort_activity` is called if it is exited first.
+    fn start_activity(&mut self, state: DbId, name: &str) -> bool {
+        let _ = state;
+        self.behavior(name);
+        false
+    }
+
+    /// Abort the do-activity of a state going on in the background,
+    /// the state is being exited and its exit behavior is performed
+    /// next.
+    fn abort_activity(&mut self, state: DbId) {
+        let _ = state;
+    }
+
+    /// Evaluate the condition of a change event, see `change`,
+    /// conditions nobody knows about are false.
+    fn condition(&mut self, name: &str) -> bool {
+        let _ = name;
+        false
+    }
+}
+
+/// Named behaviors do nothing and named guards are true.
+impl Context for () {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Status {
+    /// Not started, or stopped.
+    Idle,
+    Running,
+    /// A terminate pseudostate, or a final state in every region of the
+    /// state machine, was reached.
+    Terminated,
+}
+
+impl Db {
+    /// Enter the initial configuration of the state machine.
+    pub fn start(&mut self) -> StateMachineResult<()> {
+        self.start_with(&mut ())
+    }
+
+    /// Enter the initial configuration of the state machine, named
+    /// behaviors and guards are resolved by ctx.
+    pub fn start_with(&mut self, ctx: &mut dyn Context) -> StateMachineResult<()> {
+        if self.status == Status::Running {
+            return Err(StateMachineError::AlreadyRunning);
+        }
+        for region in self.regions.iter_mut() {
+            region.active_state = 0;
+            region.history = 0;
+        }
+        self.completions.clear();
+        self.deferred.clear();
+        self.activities.clear();
+        self.timers.clear();
+        self.conditions.clear();
+        self.status = Status::Running;
+        self.begin_step(Input::Start);
+        self.enter_regions(ctx, 0, &[], false)?;
+        self.complete(ctx)?;
+        self.end_step(false);
+        self.changed(ctx)?;
+        Ok(())
+    }
+
+    /// Process an event to completion.
+    /// Return true if it fired a transition and false if it was
+    /// deferred or discarded.
+    pub fn dispatch(&mut self, event: DbId) -> StateMachineResult<bool> {
+        self.dispatch_with(event, &mut ())
+    }
+
+    /// Process an event to completion, named behaviors and guards are
+    /// resolved by ctx.
+    pub fn dispatch_with(
+        &mut self,
+        event: DbId,
+        ctx: &mut dyn Context,
+    ) -> StateMachineResult<bool> {
+        let consumed = self.dispatch_event(event, ctx)?;
+        self.changed(ctx)?;
+        Ok(consumed)
+    }
+
+    /// Process an event to completion, the change events it causes
+    /// aside.
+    pub(crate) fn dispatch_event(
+        &mut self,
+        event: DbId,
+        ctx: &mut dyn Context,
+    ) -> StateMachineResult<bool> {
+        match self.element(event)?.element_type {
+            ElementType::EventType => (),
+            _ => return Err(StateMachineError::InvalidEventType(event)),
+        }
+        if self.status != Status::Running {
+            return Err(StateMachineError::NotRunning);
+        }
+        self.begin_step(Input::Dispatch(event));
+        let consumed = self.step(ctx, event)?;
+        if consumed {
+            self.recall(ctx)?;
+        }
+        self.end_step(consumed);
+        Ok(consumed)
+    }
+
+    /// Exit all active states, innermost first. The state machine may
+    /// then be started again.
+    pub fn stop(&mut self) -> StateMachineResult<()> {
+        self.stop_with(&mut ())
+    }
+
+    /// Exit all active states, named behaviors are resolved by ctx.
+    pub fn stop_with(&mut self, ctx: &mut dyn Context) -> StateMachineResult<()> {
+        self.begin_step(Input::Stop);
+        if self.status == Status::Running {
+            for r in self.sm_regions().into_iter().rev() {
+                self.exit_region(ctx, r);
+            }
+        }
+        self.status = Status::Idle;
+        self.completions.clear();
+        self.deferred.clear();
+        self.activities.clear();
+        self.timers.clear();
+        self.conditions.clear();
+        self.end_step(false);
+        Ok(())
+    }
+
+    /// The do-activity of a state, going on in the background, is over.
+    /// The state completes once its regions, if any, reached a final
+    /// state, and its completion transition is taken. Return false if
+    /// the activity was not going on, the state was exited meanwhile.
+    pub fn activity_done(&mut self, state: DbId) -> StateMachineResult<bool> {
+        self.activity_done_with(state, &mut ())
+    }
+
+    /// The do-activity of a state is over, named behaviors and guards
+    /// are resolved by ctx.
+    pub fn activity_done_with(
+        &mut self,
+        state: DbId,
+        ctx: &mut dyn Context,
+    ) -> StateMachineResult<bool> {
+        if self.status != Status::Running {
+            return Err(StateMachineError::NotRunning);
+        }
+        let i = match self.activities.iter().position(|s| *s == state) {
+            Some(i) => i,
+            None => return Ok(false),
+        };
+        self.activities.remove(i);
+        self.begin_step(Input::ActivityDone(state));
+        self.notify(|db| Observation::ActivityDone {
+            state,
+            fullname: &db.fullnames[state],
+        });
+        let completed = self._state(state).regions.iter().all(|r| {
+            let v = self.active_vertex(*r);
+            v != 0 && self.is_final(v)
+        });
+        if completed {
+            self.completions.push_back(state);
+            self.complete(ctx)?;
+            self.recall(ctx)?;
+        }
+        self.end_step(completed);
+        self.changed(ctx)?;
+        Ok(true)
+    }
+
+    /// The states whose do-activity goes on in the background.
+    pub fn running_activities(&self) -> &[DbId] {
+        &self.activities
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status == Status::Running
+    }
+
+    /// Return the innermost active vertices, in the order of their
+    /// regions: simple states and final states once the state machine
+    /// has settled.
+    pub fn active_states(&self) -> Vec<VertexDbId> {
+        let mut active = Vec::new();
+        if self.status != Status::Idle {
+            for r in &self.state_machine.regions {
+                self.collect_active(*r, &mut active);
+            }
+        }
+        active
+    }
+
+    fn collect_active(&self, region: RegionDbId, active: &mut Vec<VertexDbId>) {
+        let v = self.active_vertex(region);
+        if v == 0 {
+            return;
+        }
+        match self.elements[v].element_type {
+            ElementType::Vertex(VertexType::State) if self._state(v).is_composite() => {
+                for r in &self._state(v).regions {
+                    self.collect_active(*r, active);
+                }
+            }
+            _ => active.push(v),
+        }
+    }
+
+    /// Return true if the vertex or region is part of the active state
+    /// configuration.
+    pub fn is_active(&self, dbid: DbId) -> bool {
+        if dbid == 0 {
+            return self.status != Status::Idle;
+        }
+        match self.elements.get(dbid).map(|e| e.element_type) {
+            Some(ElementType::Vertex(_)) => {
+                let region = self.parents[dbid];
+                self.active_vertex(region) == dbid && self.is_active(self.parents[region])
+            }
+            Some(ElementType::Region) => self.is_active(self.parents[dbid]),
+            _ => false,
+        }
+    }
+
+    /// Return the deferred events waiting to be dispatched again.
+    pub fn deferred_events(&self) -> &[DbId] {
+        &self.deferred
+    }
+
+    fn active_vertex(&self, region: RegionDbId) -> VertexDbId {
+        self.regions[self.elements[region].idx].active_state
+    }
+
+    fn set_active(&mut self, region: RegionDbId, vertex: VertexDbId) {
+        let r_idx = self.elements[region].idx;
+        self.regions[r_idx].active_state = vertex;
+    }
+
+    fn _outgoing(&self, vertex: VertexDbId) -> Vec<DbId> {
+        self.vertices[self.elements[vertex].idx].outgoing.clone()
+    }
+
+    pub(crate) fn _transition(&self, dbid: DbId) -> &Transition {
+        &self.transitions[self.elements[dbid].idx]
+    }
+
+    fn is_final(&self, dbid: DbId) -> bool {
+        self.elements[dbid].element_type == ElementType::Vertex(VertexType::FinalState)
+    }
+
+    /// True if dbid is ancestor or is contained in it.
+    fn within(&self, dbid: DbId, ancestor: DbId) -> bool {
+        let mut d = dbid;
+        loop {
+            if d == ancestor {
+                return true;
+            }
+            if d == 0 {
+                return false;
+            }
+            d = self.parents[d];
+        }
+    }
+
+    /// The element directly contained in ancestor which contains dbid
+    fn child_of(&self, ancestor: DbId, dbid: DbId) -> Option<DbId> {
+        let mut d = dbid;
+        while d != 0 {
+            if self.parents[d] == ancestor {
+                return Some(d);
+            }
+            d = self.parents[d];
+        }
+        None
+    }
+
+    fn depth(&self, dbid: DbId) -> usize {
+        let mut n = 0;
+        let mut d = dbid;
+        while d != 0 {
+            n += 1;
+            d = self.parents[d];
+        }
+        n
+    }
+
+    /// The region in which a transition exits the active vertex and
+    /// enters the target, or 0 when the transition crosses regions of
+    /// the state machine itself.
+    fn scope(&self, transition: DbId) -> DbId {
+        let t = self._transition(transition);
+        if t.kind == TransitionKind::Local
+            && t.source != t.target
+            && self.within(t.target, t.source)
+        {
+            if let Some(r) = self.child_of(t.source, t.target) {
+                return r;
+            }
+        }
+        let mut r = self.parents[t.source];
+        while r != 0 {
+            if self.elements[r].element_type == ElementType::Region && self.within(t.target, r) {
+                return r;
+            }
+            r = self.parents[r];
+        }
+        0
+    }
+
+    /// The outermost vertex exited by a transition
+    fn exit_root(&self, transition: DbId) -> Option<DbId> {
+        let t = self._transition(transition);
+        match t.kind {
+            TransitionKind::Internal => None,
+            _ => match self.scope(transition) {
+                0 => Some(0),
+                r => self.child_of(r, t.source),
+            },
+        }
+    }
+
+    fn conflict(&self, t1: DbId, t2: DbId) -> bool {
+        match (self.exit_root(t1), self.exit_root(t2)) {
+            (Some(a), Some(b)) => self.within(a, b) || self.within(b, a),
+            _ => false,
+        }
+    }
+
+    /// Start the do-activity of a state, return true if it goes on in
+    /// the background.
+    fn start_activity(&mut self, ctx: &mut dyn Context, state: DbId) -> bool {
+        let running = match self._state(state).do_while {
+            Some(Behavior {
+                func: Some(func), ..
+            }) => {
+                func();
+                false
+            }
+            Some(behavior) => ctx.start_activity(state, behavior.name),
+            None => false,
+        };
+        if running {
+            self.activities.push(state);
+            self.notify(|db| Observation::ActivityStarted {
+                state,
+                fullname: &db.fullnames[state],
+            });
+        }
+        running
+    }
+
+    fn perform(&self, ctx: &mut dyn Context, behavior: Option<Behavior>) {
+        if let Some(behavior) = behavior {
+            match behavior.func {
+                Some(func) => func(),
+                None => ctx.behavior(behavior.name),
+            }
+        }
+    }
+
+    fn test(&mut self, ctx: &mut dyn Context, transition: DbId) -> bool {
+        match self._transition(transition).guard {
+            OptGuard::None => true,
+            OptGuard::Guard(guard) => {
+                let result = match self.recorded_guard(transition) {
+                    Some(result) => result,
+                    None => match guard.func {
+                        Some(func) => func(),
+                        None => ctx.guard(guard.name),
+                    },
+                };
+                self.guard_evaluated(transition, result);
+                self.notify(|_| Observation::GuardEvaluated {
+                    transition,
+                    guard: guard.name,
+                    result,
+                });
+                result
+            }
+        }
+    }
+
+    /// Perform the effect of a transition.
+    fn run_effect(&mut self, ctx: &mut dyn Context, transition: DbId) {
+        if let OptBehavior::Behavior(effect) = self._transition(transition).effect {
+            self.notify(|_| Observation::EffectRun {
+                transition,
+                behavior: effect.name,
+            });
+            self.perform(ctx, Some(effect));
+        }
+    }
+
+    /// A transition into a join is only enabled while the sources of
+    /// all the transitions into the join are active.
+    fn join_ready(&self, transition: DbId) -> bool {
+        let target = self._transition(transition).target;
+        match self.elements[target].element_type {
+            ElementType::Vertex(VertexType::Pseudostate(PseudostateKind::Join)) => self.vertices
+                [self.elements[target].idx]
+                .incoming
+                .iter()
+                .all(|t| self.is_active(self._transition(*t).source)),
+            _ => true,
+        }
+    }
+
+    /// The first outgoing transition of vertex with the given trigger
+    /// whose guard is true. Guards are evaluated once per event.
+    fn enabled(
+        &mut self,
+        ctx: &mut dyn Context,
+        vertex: VertexDbId,
+        trigger: Option<DbId>,
+        tested: &mut Vec<(DbId, bool)>,
+    ) -> Option<DbId> {
+        for t in self._outgoing(vertex) {
+            if self._transition(t).trigger != trigger || !self.join_ready(t) {
+                continue;
+            }
+            let pass = match tested.iter().find(|(dbid, _)| *dbid == t) {
+                Some((_, pass)) => *pass,
+                None => {
+                    let pass = self.test(ctx, t);
+                    tested.push((t, pass));
+                    pass
+                }
+            };
+            if pass {
+                return Some(t);
+            }
+        }
+        None
+    }
+
+    /// The outgoing transition taken from a choice or junction: the
+    /// first with a true guard, or the one guarded by `else`.
+    fn branch(&mut self, ctx: &mut dyn Context, vertex: VertexDbId) -> Option<DbId> {
+        let mut otherwise = None;
+        for t in self._outgoing(vertex) {
+            match self._transition(t).guard {
+                OptGuard::Guard(guard) if guard.func.is_none() && guard.name == "else" => {
+                    otherwise = otherwise.or(Some(t))
+                }
+                _ => {
+                    if self.test(ctx, t) {
+                        return Some(t);
+                    }
+                }
+            }
+        }
+        otherwise
+    }
+
+    fn select(&mut self, ctx: &mut dyn Context, event: DbId) -> Vec<DbId> {
+        let mut tested = Vec::new();
+        let mut candidates = Vec::new();
+        for leaf in self.active_states() {
+            let mut v = leaf;
+            while v != 0 {
+                if let Some(t) = self.enabled(ctx, v, Some(event), &mut tested) {
+                    if !candidates.contains(&t) {
+                        candidates.push(t);
+                    }
+                    break;
+                }
+                v = self.parents[self.parents[v]];
+            }
+        }
+        // innermost sources first
+        candidates.sort_by_key(|t| core::cmp::Reverse(self.depth(self._transition(*t).source)));
+        let mut selected: Vec<DbId> = Vec::new();
+        for t in candidates {
+            if !selected.iter().any(|s| self.conflict(*s, t)) {
+                selected.push(t);
+            }
+        }
+        selected
+    }
+
+    fn defers(&self, event: DbId) -> bool {
+        self.active_states().into_iter().any(|leaf| {
+            let mut v = leaf;
+            while v != 0 {
+                if self.elements[v].element_type == ElementType::Vertex(VertexType::State)
+                    && self._state(v).deferrable.contains(&event)
+                {
+                    return true;
+                }
+                v = self.parents[self.parents[v]];
+            }
+            false
+        })
+    }
+
+    fn step(&mut self, ctx: &mut dyn Context, event: DbId) -> StateMachineResult<bool> {
+        self.notify(|db| Observation::EventDispatched {
+            event,
+            fullname: &db.fullnames[event],
+        });
+        let selected = self.select(ctx, event);
+        if selected.is_empty() {
+            if self.defers(event) {
+                self.deferred.push(event);
+                self.notify(|db| Observation::EventDeferred {
+                    event,
+                    fullname: &db.fullnames[event],
+                });
+            } else {
+                self.notify(|db| Observation::EventDiscarded {
+                    event,
+                    fullname: &db.fullnames[event],
+                });
+            }
+            return Ok(false);
+        }
+        for t in selected {
+            if self.status != Status::Running {
+                break;
+            }
+            if self.is_active(self._transition(t).source) {
+                self.fire(ctx, t)?;
+            }
+        }
+        self.complete(ctx)?;
+        Ok(true)
+    }
+
+    /// Dispatch the deferred events again until none of them is
+    /// consumed.
+    fn recall(&mut self, ctx: &mut dyn Context) -> StateMachineResult<()> {
+        while !self.deferred.is_empty() && self.status == Status::Running {
+            let pending = core::mem::take(&mut self.deferred);
+            let mut consumed = false;
+            for (i, event) in pending.iter().enumerate() {
+                if self.status != Status::Running {
+                    self.deferred.extend_from_slice(&pending[i..]);
+                    break;
+                }
+                consumed |= self.step(ctx, *event)?;
+            }
+            if !consumed {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Take the completion transitions of the states which completed.
+    fn complete(&mut self, ctx: &mut dyn Context) -> StateMachineResult<()> {
+        let mut fired = 0;
+        while let Some(state) = self.completions.pop_front() {
+            if self.status != Status::Running {
+                self.completions.clear();
+                break;
+            }
+            if !self.is_active(state) {
+                continue;
+            }
+            if let Some(t) = self.enabled(ctx, state, None, &mut Vec::new()) {
+                fired += 1;
+                if fired > MAX_COMPLETIONS {
+                    return Err(StateMachineError::CompletionLoop(state));
+                }
+                self.fire(ctx, t)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fire(&mut self, ctx: &mut dyn Context, transition: DbId) -> StateMachineResult<()> {
+        self.notify(|db| Observation::TransitionSelected {
+            transition,
+            fullname: &db.fullnames[transition],
+        });
+        let t = self._transition(transition);
+        let (target, kind) = (t.target, t.kind);
+        if kind == TransitionKind::Internal {
+            self.run_effect(ctx, transition);
+            return Ok(());
+        }
+        let scope = self.scope(transition);
+        self.exit_scope(ctx, scope);
+        self.run_effect(ctx, transition);
+        self.enter_scope(ctx, scope, &[target])
+    }
+
+    fn exit_scope(&mut self, ctx: &mut dyn Context, scope: DbId) {
+        if scope == 0 {
+            for r in self.sm_regions().into_iter().rev() {
+                self.exit_region(ctx, r);
+            }
+            return;
+        }
+        let v = self.active_vertex(scope);
+        if v != 0 {
+            self.exit_vertex(ctx, v);
+            self.set_active(scope, 0);
+        }
+    }
+
+    fn enter_scope(
+        &mut self,
+        ctx: &mut dyn Context,
+        scope: DbId,
+        targets: &[DbId],
+    ) -> StateMachineResult<()> {
+        match scope {
+            0 => self.enter_regions(ctx, 0, targets, false),
+            r => self.enter_region(ctx, r, targets, false),
+        }
+    }
+
+    fn exit_region(&mut self, ctx: &mut dyn Context, region: RegionDbId) {
+        let v = self.active_vertex(region);
+        if v != 0 {
+            self.exit_vertex(ctx, v);
+            let r_idx = self.elements[region].idx;
+            self.regions[r_idx].history = v;
+            self.regions[r_idx].active_state = 0;
+        }
+    }
+
+    fn exit_vertex(&mut self, ctx: &mut dyn Context, vertex: VertexDbId) {
+        if self.elements[vertex].element_type != ElementType::Vertex(VertexType::State) {
+            return;
+        }
+        for r in self._state(vertex).regions.clone().into_iter().rev() {
+            self.exit_region(ctx, r);
+        }
+        if let Some(i) = self.activities.iter().position(|s| *s == vertex) {
+            self.activities.remove(i);
+            self.notify(|db| Observation::ActivityAborted {
+                state: vertex,
+                fullname: &db.fullnames[vertex],
+            });
+            ctx.abort_activity(vertex);
+        }
+        self.cancel_timers(vertex);
+        self.notify(|db| Observation::StateExited {
+            state: vertex,
+            fullname: &db.fullnames[vertex],
+        });
+        ctx.on_exit(self.names[vertex]);
+        let exit = self._state(vertex).exit;
+        self.perform(ctx, exit);
+    }
+
+    /// Enter the regions of the state machine (owner 0) or of a state,
+    /// each with the targets it contains.
+    fn enter_regions(
+        &mut self,
+        ctx: &mut dyn Context,
+        owner: DbId,
+        targets: &[DbId],
+        deep: bool,
+    ) -> StateMachineResult<()> {
+        for r in self.regions(owner)? {
+            let explicit: Vec<DbId> = targets
+                .iter()
+                .copied()
+                .filter(|t| *t != owner && self.within(*t, r))
+                .collect();
+            self.enter_region(ctx, r, &explicit, deep)?;
+        }
+        Ok(())
+    }
+
+    /// Enter a region towards the targets, or by default if there are
+    /// none: through the most recent active vertex when restoring deep
+    /// history, otherwise through the initial pseudostate. A region
+    /// without one is left inactive.
+    fn enter_region(
+        &mut self,
+        ctx: &mut dyn Context,
+        region: RegionDbId,
+        targets: &[DbId],
+        deep: bool,
+    ) -> StateMachineResult<()> {
+        if let Some(target) = targets.first() {
+            let child = self
+                .child_of(region, *target)
+                .ok_or(StateMachineError::InvalidVertex(*target))?;
+            return self.enter_vertex(ctx, child, targets, false);
+        }
+        let history = self.regions[self.elements[region].idx].history;
+        if deep && history != 0 && !self.is_final(history) {
+            return self.enter_vertex(ctx, history, &[], true);
+        }
+        let initial = self.regions[self.elements[region].idx]
+            .subvertex
+            .iter()
+            .copied()
+            .find(|v| {
+                self.elements[*v].element_type == ElementType::Vertex(VertexType::InitialState)
+            });
+        match initial {
+            Some(initial) => {
+                let t = *self
+                    ._outgoing(initial)
+                    .first()
+                    .ok_or(StateMachineError::NoEnabledTransition(initial))?;
+                self.set_active(region, initial);
+                self.fire(ctx, t)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn enter_vertex(
+        &mut self,
+        ctx: &mut dyn Context,
+        vertex: VertexDbId,
+        targets: &[DbId],
+        deep: bool,
+    ) -> StateMachineResult<()> {
+        let region = self.parents[vertex];
+        self.set_active(region, vertex);
+        match self.elements[vertex].element_type {
+            ElementType::Vertex(VertexType::State) => {
+                let s = self._state(vertex);
+                let (entry, simple) = (s.entry, s.is_simple());
+                self.perform(ctx, entry);
+                ctx.on_entry(self.names[vertex]);
+                self.notify(|db| Observation::StateEntered {
+                    state: vertex,
+                    fullname: &db.fullnames[vertex],
+                });
+                self.arm_timers(vertex);
+                let running = self.start_activity(ctx, vertex);
+                if simple && !running {
+                    self.completions.push_back(vertex);
+                }
+                self.enter_regions(ctx, vertex, targets, deep)
+            }
+            ElementType::Vertex(VertexType::FinalState) => {
+                self.final_reached(region);
+                Ok(())
+            }
+            ElementType::Vertex(VertexType::InitialState) => {
+                self.enter_region(ctx, region, &[], false)
+            }
+            ElementType::Vertex(VertexType::Pseudostate(kind)) => {
+                self.enter_pseudostate(ctx, vertex, kind)
+            }
+            _ => Err(StateMachineError::InvalidVertex(vertex)),
+        }
+    }
+
+    fn enter_pseudostate(
+        &mut self,
+        ctx: &mut dyn Context,
+        vertex: VertexDbId,
+        kind: PseudostateKind,
+    ) -> StateMachineResult<()> {
+        let region = self.parents[vertex];
+        match kind {
+            PseudostateKind::Terminate => {
+                self.status = Status::Terminated;
+                Ok(())
+            }
+            PseudostateKind::Fork => {
+                let outgoing = self._outgoing(vertex);
+                let first = *outgoing
+                    .first()
+                    .ok_or(StateMachineError::NoEnabledTransition(vertex))?;
+                let scope = self.scope(first);
+                self.exit_scope(ctx, scope);
+                let mut targets = Vec::new();
+                for t in outgoing {
+                    self.notify(|db| Observation::TransitionSelected {
+                        transition: t,
+                        fullname: &db.fullnames[t],
+                    });
+                    self.run_effect(ctx, t);
+                    targets.push(self._transition(t).target);
+                }
+                self.enter_scope(ctx, scope, &targets)
+            }
+            PseudostateKind::ShallowHistory | PseudostateKind::DeepHistory => {
+                let history = self.regions[self.elements[region].idx].history;
+                if history != 0 && !self.is_final(history) {
+                    let deep = kind == PseudostateKind::DeepHistory;
+                    return self.enter_vertex(ctx, history, &[], deep);
+                }
+                match self._outgoing(vertex).first() {
+                    Some(t) => self.fire(ctx, *t),
+                    None => {
+                        self.set_active(region, 0);
+                        self.enter_region(ctx, region, &[], false)
+                    }
+                }
+            }
+            _ => {
+                let t = self
+                    .branch(ctx, vertex)
+                    .ok_or(StateMachineError::NoEnabledTransition(vertex))?;
+                self.fire(ctx, t)
+            }
+        }
+    }
+
+    /// The state owning the region completes once all its regions
+    /// reached a final state and its do-activity is over, the state
+    /// machine terminates.
+    fn final_reached(&mut self, region: RegionDbId) {
+        let owner = self.parents[region];
+        let regions = match owner {
+            0 => self.state_machine.regions.clone(),
+            _ => self._state(owner).regions.clone(),
+        };
+        if regions.iter().all(|r| {
+            let v = self.active_vertex(*r);
+            v != 0 && self.is_final(v)
+        }) {
+            match owner {
+                0 => self.status = Status::Terminated,
+                _ if self.activities.contains(&owner) => (),
+                _ => self.completions.push_back(owner),
+            }
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! Snapshot of a running state machine and its restoration.
+//!
+//! A [`Snapshot`] records the execution state of a definition between
+//! two events: its status, the active vertex of each region, the
+//! history of each region, the deferred events and the change events
+//! whose condition was true. Elements are named
+//! by fullname so a snapshot may be restored in another process, into
+//! the definition built again from the same model, which the
+//! fingerprint of the definition checks, see `migrate`.
+//!
+//! A snapshot is written as text, one item per line, and with the
+//! `serde` feature it may be serialized with serde as well:
+//!
+//! ```text
+//! running
+//! fingerprint 5c0ab2e1d06f3b9a
+//! active door::region_1::opened
+//! history door::region_1::closed
+//! deferred door::knock
+//! condition door::when(locked)
+//! ```
+//!
+//! [`Db::restore`] checks the snapshot against the definition and makes
+//! it the execution state, no behavior is performed. The timers of the
+//! active states are armed again from the time it is restored.
+
+use crate::*;
+use core::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    pub status: Status,
+    /// The fingerprint of the definition, not checked if missing.
+    pub fingerprint: Option<u64>,
+    /// The active vertex of every region which has one, outermost
+    /// first.
+    pub active: Vec<String>,
+    /// The vertex each region was last in, for the regions which were
+    /// exited.
+    pub history: Vec<String>,
+    /// The deferred events in the order they are dispatched again.
+    pub deferred: Vec<String>,
+    /// The change events whose condition was true, they fire once it
+    /// becomes false and true again.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub conditions: Vec<String>,
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.status {
+            Status::Idle => writeln!(f, "idle")?,
+            Status::Running => writeln!(f, "running")?,
+            Status::Terminated => writeln!(f, "terminated")?,
+        }
+        if let Some(fingerprint) = self.fingerprint {
+            writeln!(f, "fingerprint {:016x}", fingerprint)?;
+        }
+        for vertex in &self.active {
+            writeln!(f, "active {}", vertex)?;
+        }
+        for vertex in &self.history {
+            writeln!(f, "history {}", vertex)?;
+        }
+        for event in &self.deferred {
+            writeln!(f, "deferred {}", event)?;
+        }
+        for event in &self.conditions {
+            writeln!(f, "condition {}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Snapshot {
+    type Err = StateMachineError;
+
+    /// Read a snapshot written by `to_string`.
+    fn from_str(text: &str) -> StateMachineResult<Snapshot> {
+        let invalid = |line: &str| StateMachineError::InvalidSnapshot(line.to_string());
+        let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+        let status = match lines.next() {
+            Some("idle") => Status::Idle,
+            Some("running") => Status::Running,
+            Some("terminated") => Status::Terminated,
+            line => return Err(invalid(line.unwrap_or_default())),
+        };
+        let mut snapshot = Snapshot {
+            status,
+            fingerprint: None,
+            active: Vec::new(),
+            history: Vec::new(),
+            deferred: Vec::new(),
+            conditions: Vec::new(),
+        };
+        for line in lines {
+            let (kind, name) = line.split_once(' ').ok_or_else(|| invalid(line))?;
+            let name = name.trim().to_string();
+            match kind {
+                "fingerprint" if snapshot.fingerprint.is_none() => {
+                    let fingerprint = u64::from_str_radix(&name, 16).map_err(|_| invalid(line))?;
+                    snapshot.fingerprint = Some(fingerprint);
+                }
+                "active" => snapshot.active.push(name),
+                "history" => snapshot.history.push(name),
+                "deferred" => snapshot.deferred.push(name),
+                "condition" => snapshot.conditions.push(name),
+                _ => return Err(invalid(line)),
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+impl Db {
+    /// Record the execution state, between events.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut active = Vec::new();
+        let mut history = Vec::new();
+        for region in &self.regions {
+            // before start the regions point at their initial vertex
+            if region.active_state != 0 && self.status != Status::Idle {
+                active.push(self.fullnames[region.active_state].clone());
+            }
+            if region.history != 0 {
+                history.push(self.fullnames[region.history].clone());
+            }
+        }
+        Snapshot {
+            status: self.status,
+            fingerprint: Some(self.fingerprint()),
+            active,
+            history,
+            deferred: self
+                .deferred
+                .iter()
+                .map(|event| self.fullnames[*event].clone())
+                .collect(),
+            conditions: self
+                .conditions
+                .iter()
+                .map(|event| self.fullnames[*event].clone())
+                .collect(),
+        }
+    }
+
+    /// Make the snapshot the execution state of the state machine,
+    /// which must not be running. No entry behavior is performed. The
+    /// snapshot must have been taken with the same definition, see
+    /// `check_snapshot`.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> StateMachineResult<()> {
+        if self.status == Status::Running {
+            return Err(StateMachineError::AlreadyRunning);
+        }
+        let (active, history, deferred, conditions) = self.check_snapshot(snapshot)?;
+        for region in self.regions.iter_mut() {
+            region.active_state = 0;
+            region.history = 0;
+        }
+        for vertex in active {
+            let r_idx = self.elements[self.parents[vertex]].idx;
+            self.regions[r_idx].active_state = vertex;
+        }
+        for vertex in history {
+            let r_idx = self.elements[self.parents[vertex]].idx;
+            self.regions[r_idx].history = vertex;
+        }
+        self.completions.clear();
+        self.deferred = deferred;
+        self.conditions = conditions;
+        self.activities.clear();
+        self.status = snapshot.status;
+        self.timers.clear();
+        if self.status == Status::Running {
+            let states: Vec<DbId> = self.iter_states().filter(|s| self.is_active(*s)).collect();
+            for state in states {
+                self.arm_timers(state);
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the active vertices, history, deferred events and true
+    /// conditions of a snapshot if it has the fingerprint of the definition, names
+    /// vertices and events of it, has at most one active vertex per
+    /// region, and has an active vertex in every region with an initial
+    /// pseudostate of the active states, and of the state machine while
+    /// running.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn check_snapshot(
+        &self,
+        snapshot: &Snapshot,
+    ) -> StateMachineResult<(Vec<VertexDbId>, Vec<VertexDbId>, Vec<DbId>, Vec<DbId>)> {
+        if let Some(fingerprint) = snapshot.fingerprint {
+            if fingerprint != self.fingerprint() {
+                return Err(StateMachineError::FingerprintMismatch(fingerprint));
+            }
+        }
+        let active = self.vertices_named(&snapshot.active)?;
+        let history = self.vertices_named(&snapshot.history)?;
+        let deferred = self.events_named(&snapshot.deferred)?;
+        let conditions = self.events_named(&snapshot.conditions)?;
+        if let Some(i) = conditions
+            .iter()
+            .position(|e| self.event_types[self.elements[*e].idx].condition.is_none())
+        {
+            return Err(StateMachineError::InvalidSnapshot(
+                snapshot.conditions[i].clone(),
+            ));
+        }
+        for (i, vertex) in active.iter().enumerate() {
+            let region = self.parents[*vertex];
+            let owner = self.parents[region];
+            let nested = owner == 0
+                || (self.elements[owner].element_type == ElementType::Vertex(VertexType::State)
+                    && active.contains(&owner));
+            if !nested || active[..i].iter().any(|v| self.parents[*v] == region) {
+                return Err(StateMachineError::InvalidSnapshot(
+                    self.fullnames[*vertex].clone(),
+                ));
+            }
+        }
+        let mut owners: Vec<DbId> = active
+            .iter()
+            .copied()
+            .filter(|v| self.elements[*v].element_type == ElementType::Vertex(VertexType::State))
+            .collect();
+        if snapshot.status == Status::Running {
+            owners.push(0);
+        }
+        for owner in owners {
+            for region in self.regions(owner)? {
+                let entered = self.regions[self.elements[region].idx]
+                    .subvertex
+                    .iter()
+                    .any(|v| {
+                        self.elements[*v].element_type
+                            == ElementType::Vertex(VertexType::InitialState)
+                    });
+                if entered && !active.iter().any(|v| self.parents[*v] == region) {
+                    return Err(StateMachineError::InvalidSnapshot(
+                        self.fullnames[region].clone(),
+                    ));
+                }
+            }
+        }
+        Ok((active, history, deferred, conditions))
+    }
+
+    pub(crate) fn dbid_named(&self, fullname: &str) -> Option<DbId> {
+        self.fullnames.iter().position(|name| name == fullname)
+    }
+
+    fn events_named(&self, fullnames: &[String]) -> StateMachineResult<Vec<DbId>> {
+        fullnames
+            .iter()
+            .map(|name| match self.dbid_named(name) {
+                Some(dbid) if self.elements[dbid].element_type == ElementType::EventType => {
+                    Ok(dbid)
+                }
+                _ => Err(StateMachineError::InvalidSnapshot(name.clone())),
+            })
+            .collect()
+    }
+
+    fn vertices_named(&self, fullnames: &[String]) -> StateMachineResult<Vec<VertexDbId>> {
+        fullnames
+            .iter()
+            .map(|name| match self.dbid_named(name) {
+                Some(dbid)
+                    if matches!(self.elements[dbid].element_type, ElementType::Vertex(_)) =>
+                {
+                    Ok(dbid)
+                }
+                _ => Err(StateMachineError::InvalidSnapshot(name.clone())),
+            })
+            .collect()
+    }
+}
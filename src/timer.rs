@@ -0,0 +1,283 @@
+//! Time events and the clock which drives them.
+//!
+//! A transition triggered by a time event fires once its source state
+//! has been active for a duration, `after(30s)`, or once the clock
+//! reaches a point in time, `at(3600s)`, a duration since the epoch of
+//! the clock. Durations are written with one of the units `ns`, `us`,
+//! `ms`, `s`, `min` and `h`:
+//!
+//! ```text
+//! state connecting {
+//!     on connected -> online;
+//!     on after(30s) -> retry;
+//! }
+//! ```
+//!
+//! Time events belong to the state whose outgoing transitions they
+//! trigger, see [`Db::add_time_event`], and are named after when they
+//! occur, `sm::region_1::connecting::after(30s)`. They are not looked up
+//! by name with `event_type`.
+//!
+//! The timers of a state are armed when it is entered and cancelled when
+//! it is exited. Time goes by according to the [`Clock`] set with
+//! [`Db::set_clock`], without one it stands still at zero. Nothing
+//! fires by itself: [`Db::fire_timers`] dispatches the time events whose
+//! deadline has passed, and [`Db::next_deadline`] tells when to call it
+//! next. With a [`VirtualClock`] tests advance time at will.
+//!
+//! ```text
+//! let clock = VirtualClock::new();
+//! def.set_clock(clock.clone());
+//! def.start()?;
+//! clock.advance(Duration::from_secs(30));
+//! def.fire_timers()?;
+//! ```
+//!
+//! The timers are not part of a snapshot, the timers of the active
+//! states are armed again when it is restored. The `compiled` and
+//! `frozen` engines have no timers, `freeze` and
+//! `codegen::static_module` reject definitions with time events.
+
+use crate::*;
+use core::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeEvent {
+    /// Once the source state has been active for the duration.
+    After(Duration),
+    /// Once the clock reaches the time, a duration since its epoch.
+    At(Duration),
+}
+
+/// Write a duration with the largest unit which divides it.
+fn write_duration(f: &mut fmt::Formatter, d: Duration) -> fmt::Result {
+    let (secs, nanos) = (d.as_secs(), d.subsec_nanos());
+    if nanos == 0 {
+        match secs {
+            0 => write!(f, "0s"),
+            _ if secs % 3600 == 0 => write!(f, "{}h", secs / 3600),
+            _ if secs % 60 == 0 => write!(f, "{}min", secs / 60),
+            _ => write!(f, "{}s", secs),
+        }
+    } else if nanos % 1_000_000 == 0 {
+        write!(f, "{}ms", d.as_millis())
+    } else if nanos % 1_000 == 0 {
+        write!(f, "{}us", d.as_micros())
+    } else {
+        write!(f, "{}ns", d.as_nanos())
+    }
+}
+
+impl fmt::Display for TimeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (word, d) = match self {
+            TimeEvent::After(d) => ("after", *d),
+            TimeEvent::At(d) => ("at", *d),
+        };
+        write!(f, "{}(", word)?;
+        write_duration(f, d)?;
+        write!(f, ")")
+    }
+}
+
+pub(crate) use rust_uml_sm_dsl::parse_duration;
+
+/// Tells the time to the timers of the definition it is set on.
+pub trait Clock: Send {
+    /// The time elapsed since the epoch of the clock.
+    fn now(&self) -> Duration;
+}
+
+/// A clock which only moves when told to, clones share the time.
+#[cfg(target_has_atomic = "64")]
+#[derive(Debug, Clone, Default)]
+pub struct VirtualClock(alloc::sync::Arc<core::sync::atomic::AtomicU64>);
+
+#[cfg(target_has_atomic = "64")]
+impl VirtualClock {
+    /// A clock at zero.
+    pub fn new() -> VirtualClock {
+        VirtualClock::default()
+    }
+
+    /// Move the time forward.
+    pub fn advance(&self, d: Duration) {
+        let nanos = d.as_nanos() as u64;
+        self.0
+            .fetch_add(nanos, core::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Set the time, which may go backwards.
+    pub fn set(&self, now: Duration) {
+        self.0
+            .store(now.as_nanos() as u64, core::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[cfg(target_has_atomic = "64")]
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.0.load(core::sync::atomic::Ordering::SeqCst))
+    }
+}
+
+/// The system time, since the UNIX epoch.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+/// Holds the clock of a definition, if any.
+#[derive(Default)]
+pub(crate) struct Slot(Option<Box<dyn Clock>>);
+
+impl fmt::Debug for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(_) => f.write_str("Some(Clock)"),
+            None => f.write_str("None"),
+        }
+    }
+}
+
+/// A time event of an active state waiting for its deadline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timer {
+    pub state: DbId,
+    pub event: DbId,
+    pub deadline: Duration,
+}
+
+impl Db {
+    /// Set the clock the timers go by, replacing any previous one. The
+    /// timers already armed keep their deadline.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock.0 = Some(Box::new(clock));
+    }
+
+    /// Remove the clock and return it, time stands still.
+    pub fn take_clock(&mut self) -> Option<Box<dyn Clock>> {
+        self.clock.0.take()
+    }
+
+    /// The time of the clock, zero without one.
+    pub fn now(&self) -> Duration {
+        match &self.clock.0 {
+            Some(clock) => clock.now(),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Register a time event triggering transitions from a state. The
+    /// event is shared by the transitions of the state with the same
+    /// time event.
+    pub fn add_time_event(&mut self, state: DbId, when: TimeEvent) -> StateMachineResult<DbId> {
+        self.state(state)?;
+        let existing = self
+            .event_types
+            .iter()
+            .find(|ev| ev.when == Some(when) && self.parents[ev.dbid] == state);
+        if let Some(ev) = existing {
+            return Ok(ev.dbid);
+        }
+        let e_idx = self.event_types.len();
+        let name = self.intern(&when.to_string());
+        let dbid = self.new_element(name, state, e_idx, ElementType::EventType);
+        self.event_types.push(EventType {
+            when: Some(when),
+            ..EventType::new(name, dbid)
+        });
+        Ok(dbid)
+    }
+
+    /// When an event type occurs, None if it is not a time event.
+    pub fn time_event(&self, event: DbId) -> StateMachineResult<Option<TimeEvent>> {
+        match self.element(event)?.element_type {
+            ElementType::EventType => Ok(self.event_types[self.elements[event].idx].when),
+            _ => Err(StateMachineError::InvalidEventType(event)),
+        }
+    }
+
+    /// The timers armed, in the order their states were entered.
+    pub fn timers(&self) -> &[Timer] {
+        &self.timers
+    }
+
+    /// The earliest deadline of the timers armed.
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.timers.iter().map(|t| t.deadline).min()
+    }
+
+    /// Dispatch the time events whose deadline has passed, earliest
+    /// first, each to completion. A timer cancelled meanwhile, its state
+    /// exited by an earlier one, does not fire. Return how many were
+    /// dispatched.
+    pub fn fire_timers(&mut self) -> StateMachineResult<usize> {
+        self.fire_timers_with(&mut ())
+    }
+
+    /// Dispatch the time events whose deadline has passed, named
+    /// behaviors and guards are resolved by ctx.
+    pub fn fire_timers_with(&mut self, ctx: &mut dyn Context) -> StateMachineResult<usize> {
+        if self.status != Status::Running {
+            return Err(StateMachineError::NotRunning);
+        }
+        let now = self.now();
+        let mut due: Vec<Timer> = self
+            .timers
+            .iter()
+            .copied()
+            .filter(|t| t.deadline <= now)
+            .collect();
+        due.sort_by_key(|t| t.deadline);
+        let mut fired = 0;
+        for timer in due {
+            if self.status != Status::Running {
+                break;
+            }
+            if let Some(i) = self.timers.iter().position(|t| *t == timer) {
+                self.timers.remove(i);
+                self.dispatch_with(timer.event, ctx)?;
+                fired += 1;
+            }
+        }
+        Ok(fired)
+    }
+
+    /// Arm the timers of the time events triggering the transitions
+    /// from a state which was entered.
+    pub(crate) fn arm_timers(&mut self, state: DbId) {
+        let now = self.now();
+        for t in self.vertices[self.elements[state].idx].outgoing.clone() {
+            let event = match self._transition(t).trigger {
+                Some(event) => event,
+                None => continue,
+            };
+            let deadline = match self.event_types[self.elements[event].idx].when {
+                Some(TimeEvent::After(d)) => now.checked_add(d).unwrap_or(Duration::MAX),
+                Some(TimeEvent::At(at)) => at,
+                None => continue,
+            };
+            if !self.timers.iter().any(|t| t.event == event) {
+                self.timers.push(Timer {
+                    state,
+                    event,
+                    deadline,
+                });
+            }
+        }
+    }
+
+    /// Cancel the timers of a state which is exited.
+    pub(crate) fn cancel_timers(&mut self, state: DbId) {
+        self.timers.retain(|t| t.state != state);
+    }
+}
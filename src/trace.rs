@@ -0,0 +1,304 @@
+//! Recording of an execution and its deterministic replay.
+//!
+//! Once [`Db::record`] was called, each start, dispatch and stop of the
+//! definition appends a [`Step`] to its [`Trace`]: the input, the guards
+//! evaluated with their results, whether the event was consumed and the
+//! resulting active state configuration.
+//!
+//! [`Db::replay_with`] feeds a trace to a definition which is not
+//! running, built from the same model. Guards evaluated in the order
+//! they were recorded are not evaluated again, the recorded outcome is
+//! used instead, so the replay does not depend on the world the guards
+//! looked at. Behaviors are still performed. The replay stops at the
+//! first step which differs from the recording and reports it as a
+//! [`Divergence`].
+//!
+//! A trace is written one step per line, the dbids of the event, of the
+//! guarded transitions and of the active states:
+//!
+//! ```text
+//! start / 3
+//! 12 - / 3
+//! 13 + 9:1 / 5
+//! done 5 + / 7
+//! stop /
+//! ```
+//!
+//! `done` is the end of a do-activity going on in the background, see
+//! [`Db::activity_done`]. Those do-activities must go on in the
+//! background in the replay too.
+
+use crate::*;
+use core::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    Start,
+    Dispatch(DbId),
+    Stop,
+    /// The do-activity of the state is over, see `Db::activity_done`.
+    ActivityDone(DbId),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub input: Input,
+    /// The guarded transitions evaluated, in order, with their result.
+    pub guards: Vec<(DbId, bool)>,
+    /// Whether the dispatched event fired a transition.
+    pub consumed: bool,
+    /// The active states once the input was processed.
+    pub configuration: Vec<VertexDbId>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+}
+
+/// The first step of a replay which did not go as recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// Index of the step in the trace.
+    pub step: usize,
+    pub expected: Step,
+    pub actual: Step,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.input {
+            Input::Start => write!(f, "start")?,
+            Input::Stop => write!(f, "stop")?,
+            Input::Dispatch(event) => {
+                write!(f, "{} {}", event, if self.consumed { '+' } else { '-' })?
+            }
+            Input::ActivityDone(state) => write!(
+                f,
+                "done {} {}",
+                state,
+                if self.consumed { '+' } else { '-' }
+            )?,
+        }
+        for (transition, result) in &self.guards {
+            write!(f, " {}:{}", transition, *result as u8)?;
+        }
+        write!(f, " /")?;
+        for state in &self.configuration {
+            write!(f, " {}", state)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "step {} diverged: recorded `{}`, replayed `{}`",
+            self.step, self.expected, self.actual
+        )
+    }
+}
+
+impl FromStr for Step {
+    type Err = ();
+
+    fn from_str(line: &str) -> Result<Step, ()> {
+        let mut words = line.split_whitespace();
+        let number = |word: Option<&str>| word.ok_or(())?.parse::<DbId>().map_err(|_| ());
+        let (input, consumed) = match words.next() {
+            Some("start") => (Input::Start, false),
+            Some("stop") => (Input::Stop, false),
+            word => {
+                let input = match word {
+                    Some("done") => Input::ActivityDone(number(words.next())?),
+                    word => Input::Dispatch(number(word)?),
+                };
+                match words.next() {
+                    Some("+") => (input, true),
+                    Some("-") => (input, false),
+                    _ => return Err(()),
+                }
+            }
+        };
+        let mut guards = Vec::new();
+        loop {
+            match words.next() {
+                Some("/") => break,
+                Some(guard) => {
+                    let mut parts = guard.splitn(2, ':');
+                    let transition = number(parts.next())?;
+                    let result = match parts.next() {
+                        Some("1") => true,
+                        Some("0") => false,
+                        _ => return Err(()),
+                    };
+                    guards.push((transition, result));
+                }
+                None => return Err(()),
+            }
+        }
+        let configuration = words
+            .map(|word| number(Some(word)))
+            .collect::<Result<Vec<_>, ()>>()?;
+        Ok(Step {
+            input,
+            guards,
+            consumed,
+            configuration,
+        })
+    }
+}
+
+impl FromStr for Trace {
+    type Err = StateMachineError;
+
+    /// Read a trace written by `to_string`, blank lines are skipped.
+    fn from_str(text: &str) -> StateMachineResult<Trace> {
+        let mut steps = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            steps.push(
+                line.parse()
+                    .map_err(|_| StateMachineError::InvalidTrace(n + 1))?,
+            );
+        }
+        Ok(Trace { steps })
+    }
+}
+
+/// Recording and replaying state of a definition.
+#[derive(Debug, Default)]
+pub(crate) struct Tracer {
+    recording: Option<Trace>,
+    /// The step being processed.
+    step: Option<Step>,
+    /// Set while replaying.
+    replay: bool,
+    /// Recorded guard outcomes still to be used by the replayed step.
+    replaying: VecDeque<(DbId, bool)>,
+}
+
+impl Db {
+    /// Start recording a new trace, dropping the one recorded so far.
+    pub fn record(&mut self) {
+        self.tracer.recording = Some(Trace::default());
+    }
+
+    /// The trace recorded so far, if recording.
+    pub fn trace(&self) -> Option<&Trace> {
+        self.tracer.recording.as_ref()
+    }
+
+    /// Stop recording and return the trace.
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.tracer.recording.take()
+    }
+
+    /// Replay a trace from the start with recorded guard outcomes.
+    /// The state machine must not be running.
+    pub fn replay(&mut self, trace: &Trace) -> StateMachineResult<Option<Divergence>> {
+        self.replay_with(trace, &mut ())
+    }
+
+    /// Replay a trace from the start, named behaviors are resolved by
+    /// ctx and guards which were not recorded too. Return the first
+    /// step which diverged from the recording, if any.
+    pub fn replay_with(
+        &mut self,
+        trace: &Trace,
+        ctx: &mut dyn Context,
+    ) -> StateMachineResult<Option<Divergence>> {
+        if self.status == Status::Running {
+            return Err(StateMachineError::AlreadyRunning);
+        }
+        let mut divergence = None;
+        self.tracer.replay = true;
+        for (i, expected) in trace.steps.iter().enumerate() {
+            self.tracer.replaying = expected.guards.iter().copied().collect();
+            let result = match expected.input {
+                Input::Start => self.start_with(ctx),
+                Input::Dispatch(event) => self.dispatch_with(event, ctx).map(|_| ()),
+                Input::Stop => self.stop_with(ctx),
+                Input::ActivityDone(state) => self.activity_done_with(state, ctx).map(|_| ()),
+            };
+            self.tracer.replaying.clear();
+            if let Err(err) = result {
+                self.tracer.replay = false;
+                return Err(err);
+            }
+            let actual = self.tracer.step.take().expect("internal_error:733104");
+            if actual != *expected {
+                divergence = Some(Divergence {
+                    step: i,
+                    expected: expected.clone(),
+                    actual,
+                });
+                break;
+            }
+        }
+        self.tracer.replay = false;
+        Ok(divergence)
+    }
+
+    pub(crate) fn begin_step(&mut self, input: Input) {
+        self.tracer.step = match self.tracer.recording.is_some() || self.tracer.replay {
+            true => Some(Step {
+                input,
+                guards: Vec::new(),
+                consumed: false,
+                configuration: Vec::new(),
+            }),
+            false => None,
+        };
+    }
+
+    pub(crate) fn end_step(&mut self, consumed: bool) {
+        if self.tracer.step.is_none() {
+            return;
+        }
+        let configuration = self.active_states();
+        if let Some(step) = self.tracer.step.as_mut() {
+            step.consumed = consumed;
+            step.configuration = configuration;
+            if let Some(trace) = self.tracer.recording.as_mut() {
+                trace.steps.push(step.clone());
+            }
+        }
+    }
+
+    /// True while replaying a trace.
+    pub(crate) fn replaying(&self) -> bool {
+        self.tracer.replay
+    }
+
+    /// The recorded outcome of the guard when replaying it.
+    pub(crate) fn recorded_guard(&mut self, transition: DbId) -> Option<bool> {
+        match self.tracer.replaying.front() {
+            Some((t, result)) if *t == transition => {
+                let result = *result;
+                self.tracer.replaying.pop_front();
+                Some(result)
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn guard_evaluated(&mut self, transition: DbId, result: bool) {
+        if let Some(step) = self.tracer.step.as_mut() {
+            step.guards.push((transition, result));
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! JavaScript bindings, with the `wasm-bindgen` feature.
+//!
+//! ```text
+//! import { Definition } from "./rust_uml_sm.js";
+//!
+//! const door = new Definition(modelText);     // the textual format
+//! const sm = door.instance();
+//! sm.setBehaviors(name => console.log(name));
+//! sm.setGuards(name => name != "locked");
+//! sm.start();
+//! sm.dispatch("open");
+//! sm.activeStates();                          // ["door::region_1::opened", ...]
+//! document.body.innerHTML = door.toSvg();
+//! ```
+//!
+//! A [`Definition`] is loaded from the textual format and checked with
+//! `validate`, each [`Instance`] of it runs on its own. Behaviors and
+//! guards are named in the model, the functions set on an instance are
+//! called with their name; without them behaviors do nothing and guards
+//! are true. Guards are true if their function returns a truthy value,
+//! and false if it throws.
+//!
+//! Errors are thrown as JavaScript `Error`s with the message of the
+//! `StateMachineError`.
+
+use crate::*;
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+fn js_error(err: StateMachineError) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+/// A model, from which instances are created.
+#[wasm_bindgen]
+pub struct Definition {
+    model: String,
+    def: StateMachineDef,
+}
+
+#[wasm_bindgen]
+impl Definition {
+    /// Parse and check a model in the textual format.
+    #[wasm_bindgen(constructor)]
+    pub fn new(model: &str) -> Result<Definition, JsError> {
+        let def = dsl::parse(model).map_err(js_error)?;
+        def.validate().map_err(js_error)?;
+        Ok(Definition {
+            model: model.to_string(),
+            def,
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.def._name(0).to_string()
+    }
+
+    /// The names of the event types dispatched by name, time and change
+    /// events aside.
+    pub fn events(&self) -> Vec<String> {
+        self.def
+            .iter_signal_events()
+            .map(|e| self.def._name(e).to_string())
+            .collect()
+    }
+
+    /// A new instance, not started.
+    pub fn instance(&self) -> Instance {
+        Instance {
+            def: dsl::parse(&self.model).expect("internal_error:904411"),
+            behaviors: None,
+            guards: None,
+        }
+    }
+
+    #[wasm_bindgen(js_name = toSvg)]
+    pub fn to_svg(&self) -> String {
+        render::svg(&self.def)
+    }
+
+    #[wasm_bindgen(js_name = toPlantuml)]
+    pub fn to_plantuml(&self) -> String {
+        render::plantuml(&self.def)
+    }
+
+    #[wasm_bindgen(js_name = toMermaid)]
+    pub fn to_mermaid(&self) -> String {
+        render::mermaid(&self.def)
+    }
+
+    #[wasm_bindgen(js_name = toDot)]
+    pub fn to_dot(&self) -> String {
+        render::dot(&self.def)
+    }
+
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> String {
+        render::json(&self.def)
+    }
+
+    /// The model in the textual format, as written by `dsl::write`.
+    #[wasm_bindgen(js_name = toText)]
+    pub fn to_text(&self) -> String {
+        dsl::write(&self.def)
+    }
+}
+
+/// Calls the JavaScript functions of an instance.
+struct JsContext<'a> {
+    behaviors: Option<&'a Function>,
+    guards: Option<&'a Function>,
+}
+
+impl Context for JsContext<'_> {
+    fn behavior(&mut self, name: &str) {
+        if let Some(f) = self.behaviors {
+            let _ = f.call1(&JsValue::NULL, &JsValue::from_str(name));
+        }
+    }
+
+    fn guard(&mut self, name: &str) -> bool {
+        match self.guards {
+            Some(f) => f
+                .call1(&JsValue::NULL, &JsValue::from_str(name))
+                .is_ok_and(|result| result.is_truthy()),
+            None => true,
+        }
+    }
+}
+
+/// A running copy of a definition.
+#[wasm_bindgen]
+pub struct Instance {
+    def: StateMachineDef,
+    behaviors: Option<Function>,
+    guards: Option<Function>,
+}
+
+impl Instance {
+    /// Run f with the context calling the functions set.
+    fn with_context<T>(
+        &mut self,
+        f: impl FnOnce(&mut StateMachineDef, &mut dyn Context) -> StateMachineResult<T>,
+    ) -> Result<T, JsError> {
+        let mut ctx = JsContext {
+            behaviors: self.behaviors.as_ref(),
+            guards: self.guards.as_ref(),
+        };
+        f(&mut self.def, &mut ctx).map_err(js_error)
+    }
+}
+
+#[wasm_bindgen]
+impl Instance {
+    /// Set the function performing the behaviors, called with their
+    /// name.
+    #[wasm_bindgen(js_name = setBehaviors)]
+    pub fn set_behaviors(&mut self, f: Function) {
+        self.behaviors = Some(f);
+    }
+
+    /// Set the function evaluating the guards, called with their name.
+    #[wasm_bindgen(js_name = setGuards)]
+    pub fn set_guards(&mut self, f: Function) {
+        self.guards = Some(f);
+    }
+
+    pub fn start(&mut self) -> Result<(), JsError> {
+        self.with_context(|def, ctx| def.start_with(ctx))
+    }
+
+    pub fn stop(&mut self) -> Result<(), JsError> {
+        self.with_context(|def, ctx| def.stop_with(ctx))
+    }
+
+    /// Dispatch an event by name, return true if it fired a transition.
+    pub fn dispatch(&mut self, event: &str) -> Result<bool, JsError> {
+        let event = self
+            .def
+            .event_type(event)
+            .ok_or_else(|| js_error(StateMachineError::UnknownEvent(event.to_string())))?;
+        self.with_context(|def, ctx| def.dispatch_with(event, ctx))
+    }
+
+    /// Dispatch an event by dbid, see `toJson` for the dbids.
+    #[wasm_bindgen(js_name = dispatchId)]
+    pub fn dispatch_id(&mut self, event: usize) -> Result<bool, JsError> {
+        self.with_context(|def, ctx| def.dispatch_with(event, ctx))
+    }
+
+    /// The fullnames of the innermost active states.
+    #[wasm_bindgen(js_name = activeStates)]
+    pub fn active_states(&self) -> Vec<String> {
+        self.def
+            .active_states()
+            .into_iter()
+            .map(|s| self.def._fullname(s).clone())
+            .collect()
+    }
+
+    /// The dbids of the innermost active states.
+    #[wasm_bindgen(js_name = activeIds)]
+    pub fn active_ids(&self) -> Vec<usize> {
+        self.def.active_states()
+    }
+
+    /// Whether the vertex at a path, see `find`, is active.
+    #[wasm_bindgen(js_name = isActive)]
+    pub fn is_active(&self, path: &str) -> bool {
+        self.def.find(path).is_some_and(|v| self.def.is_active(v))
+    }
+
+    /// `Idle`, `Running` or `Terminated`.
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> String {
+        format!("{:?}", self.def.status())
+    }
+
+    /// The execution state as text, see `Db::snapshot`.
+    pub fn snapshot(&self) -> String {
+        self.def.snapshot().to_string()
+    }
+
+    /// Restore a snapshot, the instance must not be running.
+    pub fn restore(&mut self, snapshot: &str) -> Result<(), JsError> {
+        let snapshot = snapshot.parse().map_err(js_error)?;
+        self.def.restore(&snapshot).map_err(js_error)
+    }
+
+    /// Start recording a trace, see `Db::record`.
+    pub fn record(&mut self) {
+        self.def.record();
+    }
+
+    /// The trace recorded so far as text, empty if not recording.
+    pub fn trace(&self) -> String {
+        self.def.trace().map(|t| t.to_string()).unwrap_or_default()
+    }
+}
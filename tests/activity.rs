@@ -0,0 +1,173 @@
+#![cfg(feature = "async")]
+extern crate rust_uml_sm;
+mod common;
+use common::active;
+use rust_uml_sm::activity::{Activities, Activity, AsyncInstance};
+use rust_uml_sm::{dsl, Context};
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{self, Poll, Wake, Waker};
+
+struct Noop;
+
+impl Wake for Noop {
+    fn wake(self: Arc<Self>) {}
+}
+
+/// Poll f until it is ready.
+fn block_on<F: Future>(f: F) -> F::Output {
+    let waker = Waker::from(Arc::new(Noop));
+    let mut cx = task::Context::from_waker(&waker);
+    let mut f = pin!(f);
+    loop {
+        if let Poll::Ready(output) = f.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// Pending the given number of times, sets its flag when dropped.
+struct Countdown {
+    polls: usize,
+    dropped: Arc<AtomicBool>,
+}
+
+impl Future for Countdown {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<()> {
+        match self.polls {
+            0 => Poll::Ready(()),
+            _ => {
+                self.polls -= 1;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Countdown {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Logs the behaviors performed, the do-activities are countdowns.
+#[derive(Default, Clone)]
+struct Downloads {
+    log: Arc<Mutex<Vec<String>>>,
+    dropped: Arc<AtomicBool>,
+}
+
+impl Context for Downloads {
+    fn behavior(&mut self, name: &str) {
+        let dropped = self.dropped.load(Ordering::SeqCst);
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("{} (dropped: {})", name, dropped));
+    }
+}
+
+impl Activities for Downloads {
+    fn activity(&mut self, name: &str) -> Option<Activity> {
+        let polls = match name {
+            "download" => 3,
+            "blink" => 1000,
+            _ => return None,
+        };
+        self.dropped.store(false, Ordering::SeqCst);
+        Some(Box::pin(Countdown {
+            polls,
+            dropped: self.dropped.clone(),
+        }))
+    }
+}
+
+const DOWNLOAD: &str = "statemachine dl {
+    event cancel, finish;
+    initial -> fetching;
+    state fetching {
+        do download;
+        exit close;
+        on cancel -> idle;
+        -> busy;
+    }
+    state idle { do wait; }
+    state busy {
+        do blink;
+        initial -> work;
+        state work { on finish -> over; }
+        final over;
+        -> idle;
+    }
+}";
+
+#[test]
+fn test_activity_done() {
+    let def = dsl::parse(DOWNLOAD).unwrap();
+    let fetching = def.find("fetching").unwrap();
+    let busy = def.find("busy").unwrap();
+    let finish = def.event_type("finish").unwrap();
+    let mut sm = AsyncInstance::new(def, Downloads::default());
+    sm.start().unwrap();
+    assert_eq!(sm.running().collect::<Vec<_>>(), vec![fetching]);
+
+    // the completion transition is taken once the download is over
+    assert_eq!(block_on(sm.next_done()).unwrap(), fetching);
+    assert_eq!(active(sm.def()), vec!["work"]);
+
+    // and busy completes once its region is final and blink is over
+    assert!(sm.dispatch(finish).unwrap());
+    assert_eq!(active(sm.def()), vec!["over"]);
+    assert_eq!(block_on(sm.next_done()).unwrap(), busy);
+    assert_eq!(active(sm.def()), vec!["idle"]);
+    assert_eq!(sm.running().count(), 0);
+    assert_eq!(
+        *sm.ctx().log.lock().unwrap(),
+        vec!["close (dropped: true)", "wait (dropped: true)"]
+    );
+}
+
+#[test]
+fn test_activity_aborted() {
+    let def = dsl::parse(DOWNLOAD).unwrap();
+    let cancel = def.event_type("cancel").unwrap();
+    let mut sm = AsyncInstance::new(def, Downloads::default());
+    sm.start().unwrap();
+    let waker = Waker::from(Arc::new(Noop));
+    assert!(sm
+        .poll_done(&mut task::Context::from_waker(&waker))
+        .is_pending());
+
+    // dropped before the exit behavior
+    assert!(sm.dispatch(cancel).unwrap());
+    assert_eq!(
+        *sm.ctx().log.lock().unwrap(),
+        vec!["close (dropped: true)", "wait (dropped: true)"]
+    );
+    assert_eq!(active(sm.def()), vec!["idle"]);
+    assert_eq!(sm.running().count(), 0);
+    assert!(sm
+        .poll_done(&mut task::Context::from_waker(&waker))
+        .is_pending());
+}
+
+#[test]
+fn test_activity_replay() {
+    let mut def = dsl::parse(DOWNLOAD).unwrap();
+    def.record();
+    let mut sm = AsyncInstance::new(def, Downloads::default());
+    sm.start().unwrap();
+    block_on(sm.next_done()).unwrap();
+    sm.stop().unwrap();
+    let (mut def, _) = sm.into_inner();
+    let trace = def.take_trace().unwrap();
+    assert_eq!(trace.steps.len(), 3);
+
+    let mut again = AsyncInstance::new(dsl::parse(DOWNLOAD).unwrap(), Downloads::default());
+    assert_eq!(again.replay(&trace).unwrap(), None);
+}
@@ -0,0 +1,114 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::builder::Builder;
+use rust_uml_sm::diff::diff;
+use rust_uml_sm::dsl;
+use rust_uml_sm::{PseudostateKind, StateMachineError, TransitionKind, VertexType};
+
+const DOOR: &str = include_str!("diff/door_v1.sm");
+
+#[test]
+fn test_build_door() {
+    let mut sm = Builder::new("door");
+    sm.event("open").event("close").event("push").event("lock");
+    sm.initial("closed");
+    sm.state("closed").defer("push");
+    sm.state("opened").entry("light_on").initial("ajar");
+    sm.state("opened::ajar");
+    sm.state("opened").region("region_1").state("wide");
+    sm.transition("closed", "opened").on("open").effect("creak");
+    sm.transition("opened::ajar", "opened::region_1::wide")
+        .on("push");
+    sm.transition("opened", "closed").on("close");
+    let door = sm.build().unwrap();
+    let d = diff(&door, &dsl::parse(DOOR).unwrap());
+    assert!(d.is_empty(), "{}", d);
+}
+
+#[test]
+fn test_paths() {
+    let mut sm = Builder::new("sm1");
+    let s4 = sm.state("s2").region("r3").state("s4").dbid();
+    assert!(s4.is_some());
+    assert_eq!(sm.state("s2::r3::s4").dbid(), s4);
+    assert_eq!(sm.state("s2::s4").dbid(), s4);
+    assert_eq!(sm.find("s2::r3::s4"), s4);
+    assert_eq!(sm.find("sm1::region_1::s2::r3::s4"), s4);
+    assert_eq!(sm.find("s2::s5"), None);
+
+    // the states on the way are created
+    let s7 = sm.state("s5::s6::s7").dbid();
+    assert_eq!(sm.find("s5::region_1::s6::region_1::s7"), s7);
+    sm.initial("s2");
+    sm.state("s2").region("r3").initial("s4");
+    let def = sm.build().unwrap();
+    assert_eq!(
+        def.fullname(s7.unwrap()).unwrap(),
+        "sm1::region_1::s5::region_1::s6::region_1::s7"
+    );
+}
+
+#[test]
+fn test_transitions() {
+    let mut sm = Builder::new("sm1");
+    sm.initial("s1");
+    sm.vertex("c", VertexType::Pseudostate(PseudostateKind::Choice));
+    sm.transition("c", "s2").guard("ready");
+    sm.transition("c", "s1");
+    sm.transition("s1", "c").on("ev1").named("go");
+    sm.transition("s2", "s2")
+        .on("ev2")
+        .effect("count")
+        .kind(TransitionKind::Internal);
+    sm.state("s1");
+    let s2 = sm.state("s2").dbid().unwrap();
+    let mut def = sm.build().unwrap();
+    def.start().unwrap();
+    def.dispatch(def.event_type("ev1").unwrap()).unwrap();
+    assert_eq!(def.active_states(), vec![s2]);
+    let expected = "statemachine sm1 {
+        initial -> s1;
+        choice c { [ready] -> s2; -> s1; }
+        state s1 { go: on ev1 -> c; }
+        state s2 { on ev2 / count; }
+    }";
+    let d = diff(&def, &dsl::parse(expected).unwrap());
+    assert!(d.is_empty(), "{}", d);
+}
+
+#[test]
+fn test_errors() {
+    let mut sm = Builder::new("sm1");
+    sm.initial("s1");
+    sm.transition("s1", "s3");
+    sm.state("s1");
+    assert!(matches!(
+        sm.build(),
+        Err(StateMachineError::UnknownPath(path)) if path == "s3"
+    ));
+
+    // the first error is kept, the chain goes on without effect
+    let mut sm = Builder::new("sm1");
+    sm.state("s1").region("r1");
+    sm.state("s1").region("r2");
+    sm.state("s1").state("s2").entry("never");
+    sm.state("s3");
+    assert!(matches!(
+        sm.build(),
+        Err(StateMachineError::ContainsMultipleRegions(_))
+    ));
+
+    let mut sm = Builder::new("sm1");
+    sm.vertex("s1", VertexType::FinalState);
+    sm.state("s1");
+    assert!(matches!(
+        sm.build(),
+        Err(StateMachineError::Duplicate("s1"))
+    ));
+
+    let mut sm = Builder::new("sm1");
+    sm.state("s1");
+    assert!(matches!(
+        sm.build(),
+        Err(StateMachineError::NoInitialState(_))
+    ));
+}
@@ -0,0 +1,293 @@
+extern crate rust_uml_sm;
+mod common;
+use common::active;
+use rust_uml_sm::snapshot::Snapshot;
+use rust_uml_sm::{dsl, Context, StateMachineError, Status};
+
+const BUFFER: &str = "statemachine buffer {
+    event push;
+    initial -> filling;
+    state filling {
+        on push / store;
+        on when(full) -> flushing;
+    }
+    state flushing {
+        entry drain;
+        -> filling;
+    }
+}";
+
+/// A buffer which is full with 3 items, logs the behaviors.
+#[derive(Default)]
+struct Buffer {
+    len: usize,
+    log: Vec<String>,
+}
+
+impl Context for Buffer {
+    fn behavior(&mut self, name: &str) {
+        match name {
+            "store" => self.len += 1,
+            "drain" => self.len = 0,
+            _ => (),
+        }
+        self.log.push(format!("{} {}", name, self.len));
+    }
+
+    fn condition(&mut self, name: &str) -> bool {
+        name == "full" && self.len >= 3
+    }
+}
+
+#[test]
+fn test_change_after_step() {
+    let mut def = dsl::parse(BUFFER).unwrap();
+    let push = def.event_type("push").unwrap();
+    let mut buffer = Buffer::default();
+    def.start_with(&mut buffer).unwrap();
+    for _ in 0..4 {
+        assert!(def.dispatch_with(push, &mut buffer).unwrap());
+    }
+    assert_eq!(active(&def), vec!["filling"]);
+    assert_eq!(
+        buffer.log,
+        vec!["store 1", "store 2", "store 3", "drain 0", "store 1"]
+    );
+}
+
+#[test]
+fn test_notify_changed() {
+    let mut def = dsl::parse(BUFFER).unwrap();
+    let mut buffer = Buffer::default();
+    def.start_with(&mut buffer).unwrap();
+    assert_eq!(def.notify_changed_with(&mut buffer).unwrap(), 0);
+
+    // once per rising edge
+    buffer.len = 5;
+    assert_eq!(def.notify_changed_with(&mut buffer).unwrap(), 1);
+    assert_eq!(buffer.log, vec!["drain 0"]);
+    assert_eq!(def.notify_changed_with(&mut buffer).unwrap(), 0);
+
+    def.stop_with(&mut buffer).unwrap();
+    assert!(matches!(
+        def.notify_changed_with(&mut buffer),
+        Err(StateMachineError::NotRunning)
+    ));
+}
+
+#[test]
+fn test_true_at_start() {
+    let mut def = dsl::parse(BUFFER).unwrap();
+    let mut buffer = Buffer {
+        len: 3,
+        ..Buffer::default()
+    };
+    def.start_with(&mut buffer).unwrap();
+    assert_eq!(buffer.log, vec!["drain 0"]);
+    assert_eq!(active(&def), vec!["filling"]);
+}
+
+#[test]
+fn test_discarded() {
+    // the condition becomes true while waiting, nothing is triggered
+    // later while it stays true
+    let mut def = dsl::parse(
+        "statemachine m {
+            event go;
+            initial -> waiting;
+            state waiting { on go -> watching; }
+            state watching { on when(full) -> done; }
+            final done;
+        }",
+    )
+    .unwrap();
+    let go = def.event_type("go").unwrap();
+    let mut buffer = Buffer::default();
+    def.start_with(&mut buffer).unwrap();
+    buffer.len = 3;
+    assert_eq!(def.notify_changed_with(&mut buffer).unwrap(), 1);
+    def.dispatch_with(go, &mut buffer).unwrap();
+    assert_eq!(active(&def), vec!["watching"]);
+
+    buffer.len = 0;
+    def.notify_changed_with(&mut buffer).unwrap();
+    buffer.len = 3;
+    def.notify_changed_with(&mut buffer).unwrap();
+    assert_eq!(active(&def), vec!["done"]);
+}
+
+/// Counts the entries, the conditions are the parity of the count.
+struct Parity(usize);
+
+impl Context for Parity {
+    fn behavior(&mut self, _name: &str) {
+        self.0 += 1;
+    }
+
+    fn condition(&mut self, name: &str) -> bool {
+        (self.0 % 2 == 1) == (name == "odd")
+    }
+}
+
+#[test]
+fn test_change_loop() {
+    let mut def = dsl::parse(
+        "statemachine m {
+            initial -> a;
+            state a {
+                entry count;
+                on when(odd) -> a;
+                on when(even) -> a;
+            }
+        }",
+    )
+    .unwrap();
+    assert!(matches!(
+        def.start_with(&mut Parity(0)),
+        Err(StateMachineError::CompletionLoop(_))
+    ));
+}
+
+#[test]
+fn test_change_events() {
+    let mut def = dsl::parse(BUFFER).unwrap();
+    let full = def.find("buffer::when(full)").unwrap();
+    assert_eq!(def.change_event(full).unwrap(), Some("full"));
+    assert_eq!(def.add_change_event("full").unwrap(), full);
+    assert_eq!(def.event_type("when(full)"), None);
+    assert_eq!(
+        def.iter_signal_events().collect::<Vec<_>>(),
+        vec![def.event_type("push").unwrap()]
+    );
+
+    let text = dsl::write(&def);
+    assert!(text.contains("    event push;\n"));
+    assert!(text.contains("on when(full) -> flushing;"));
+    assert_eq!(dsl::write(&dsl::parse(&text).unwrap()), text);
+}
+
+#[test]
+fn test_change_replay() {
+    let mut def = dsl::parse(BUFFER).unwrap();
+    let push = def.event_type("push").unwrap();
+    def.record();
+    let mut buffer = Buffer::default();
+    def.start_with(&mut buffer).unwrap();
+    for _ in 0..3 {
+        def.dispatch_with(push, &mut buffer).unwrap();
+    }
+    let trace = def.take_trace().unwrap();
+    assert_eq!(trace.steps.len(), 5);
+
+    // the change event is in the trace, conditions are not evaluated
+    let mut again = dsl::parse(BUFFER).unwrap();
+    let mut buffer = Buffer::default();
+    assert_eq!(again.replay_with(&trace, &mut buffer).unwrap(), None);
+    assert_eq!(buffer.log, vec!["store 1", "store 2", "store 3", "drain 0"]);
+}
+
+const PLANT: &str = "statemachine plant {
+    event reset, kill;
+    initial -> on;
+    state on {
+        region motor {
+            initial -> working;
+            state working { do work; }
+        }
+        region heat {
+            initial -> idle;
+            state idle { on when(hot) -> alarm; }
+            state alarm {
+                on reset -> idle;
+                on kill -> k;
+            }
+            terminate k;
+        }
+    }
+}";
+
+/// A plant which may be hot, its motor works in the background.
+#[derive(Default)]
+struct Plant {
+    hot: bool,
+}
+
+impl Context for Plant {
+    fn condition(&mut self, name: &str) -> bool {
+        name == "hot" && self.hot
+    }
+
+    fn start_activity(&mut self, _state: usize, _name: &str) -> bool {
+        true
+    }
+}
+
+#[test]
+fn test_change_snapshot() {
+    let mut def = dsl::parse(PLANT).unwrap();
+    let mut plant = Plant { hot: true };
+    def.start_with(&mut plant).unwrap();
+    assert!(active(&def).contains(&"alarm"));
+    let snapshot = def.snapshot();
+    assert_eq!(snapshot.conditions, vec!["plant::when(hot)"]);
+    let text = snapshot.to_string();
+    assert!(text.contains("\ncondition plant::when(hot)\n"));
+    assert_eq!(text.parse::<Snapshot>().unwrap(), snapshot);
+
+    // still hot, the alarm is not raised again once reset
+    let mut again = dsl::parse(PLANT).unwrap();
+    again.restore(&snapshot).unwrap();
+    let reset = again.event_type("reset").unwrap();
+    assert!(again.dispatch_with(reset, &mut plant).unwrap());
+    assert!(active(&again).contains(&"idle"));
+    plant.hot = false;
+    assert_eq!(again.notify_changed_with(&mut plant).unwrap(), 0);
+    plant.hot = true;
+    assert_eq!(again.notify_changed_with(&mut plant).unwrap(), 1);
+    assert!(active(&again).contains(&"alarm"));
+
+    let mut invalid = snapshot.clone();
+    invalid.conditions = vec!["plant::reset".to_string()];
+    assert!(matches!(
+        dsl::parse(PLANT).unwrap().restore(&invalid),
+        Err(StateMachineError::InvalidSnapshot(name)) if name == "plant::reset"
+    ));
+}
+
+#[test]
+fn test_restore_resets_conditions() {
+    let mut cold = dsl::parse(PLANT).unwrap();
+    cold.start_with(&mut Plant::default()).unwrap();
+    let snapshot = cold.snapshot();
+    assert!(snapshot.conditions.is_empty());
+
+    // terminated while hot, with the motor working
+    let mut def = dsl::parse(PLANT).unwrap();
+    let mut plant = Plant { hot: true };
+    def.start_with(&mut plant).unwrap();
+    def.dispatch_with(def.event_type("kill").unwrap(), &mut plant)
+        .unwrap();
+    assert_eq!(def.status(), Status::Terminated);
+    assert_eq!(def.running_activities().len(), 1);
+
+    def.restore(&snapshot).unwrap();
+    assert!(def.running_activities().is_empty());
+    assert_eq!(def.notify_changed_with(&mut plant).unwrap(), 1);
+    assert!(active(&def).contains(&"alarm"));
+}
+
+#[test]
+fn test_change_replay_conditions() {
+    let mut def = dsl::parse(PLANT).unwrap();
+    let mut plant = Plant { hot: true };
+    def.record();
+    def.start_with(&mut plant).unwrap();
+    let trace = def.take_trace().unwrap();
+
+    let mut again = dsl::parse(PLANT).unwrap();
+    assert_eq!(again.replay_with(&trace, &mut plant).unwrap(), None);
+    assert_eq!(again.snapshot().conditions, vec!["plant::when(hot)"]);
+    let reset = again.event_type("reset").unwrap();
+    assert!(again.dispatch_with(reset, &mut plant).unwrap());
+    assert!(active(&again).contains(&"idle"));
+}
@@ -0,0 +1,224 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/");
+
+fn sm(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sm"))
+        .current_dir(DIR)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn model(name: &str, text: &str) -> String {
+    let path = std::env::temp_dir().join(format!("sm_cli_{}_{}.sm", std::process::id(), name));
+    std::fs::write(&path, text).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn test_usage() {
+    let none = sm(&[], "");
+    assert_eq!(none.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&none.stderr).contains("usage: sm"));
+    assert_eq!(sm(&["frobnicate"], "").status.code(), Some(2));
+    assert_eq!(
+        sm(&["render", "diff/door_v1.sm"], "").status.code(),
+        Some(2)
+    );
+    assert_eq!(sm(&["stats", "missing.sm"], "").status.code(), Some(2));
+    assert_eq!(sm(&["help"], "").status.code(), Some(0));
+}
+
+#[test]
+fn test_validate() {
+    let ok = sm(&["validate", "codegen/pump.sm"], "");
+    assert_eq!(ok.status.code(), Some(0));
+
+    let file = model("validate", "statemachine x { state a; choice c; }");
+    let bad = sm(&["validate", &file, "--json"], "");
+    assert_eq!(bad.status.code(), Some(1));
+    let found: serde_json::Value = serde_json::from_slice(&bad.stdout).unwrap();
+    assert_eq!(found[0]["code"], "no-initial");
+    assert_eq!(found[1]["code"], "no-outgoing");
+    assert_eq!(found[1]["message"], "c has no outgoing transition");
+
+    let file = model("parse", "statemachine x {\n    state a\n}");
+    let bad = sm(&["validate", &file, "--json"], "");
+    assert_eq!(bad.status.code(), Some(1));
+    let found: serde_json::Value = serde_json::from_slice(&bad.stdout).unwrap();
+    assert_eq!(found[0]["code"], "parse");
+    assert_eq!(found[0]["line"], 3);
+    let bad = sm(&["validate", &file], "");
+    assert!(String::from_utf8_lossy(&bad.stderr).contains(&format!("{}:3:1: error:", file)));
+}
+
+#[test]
+fn test_render_and_convert() {
+    let dot = sm(&["render", "diff/door_v1.sm", "--format", "dot"], "");
+    assert_eq!(dot.status.code(), Some(0));
+    assert!(stdout(&dot).starts_with("digraph \"door\""));
+    let mermaid = sm(&["render", "diff/door_v1.sm", "--format", "mermaid"], "");
+    assert!(stdout(&mermaid).starts_with("stateDiagram-v2"));
+    // drawn without plantuml
+    let svg = sm(&["render", "diff/door_v1.sm", "--format", "svg"], "");
+    assert_eq!(svg.status.code(), Some(0));
+    assert!(stdout(&svg).starts_with("<svg "));
+
+    let trace = std::env::temp_dir().join(format!("sm_cli_{}.trace", std::process::id()));
+    std::fs::write(&trace, "start / 3\n9 + / 7\n").unwrap();
+    let html = sm(
+        &[
+            "render",
+            "diff/door_v1.sm",
+            "-f",
+            "html",
+            "--trace",
+            trace.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(html.status.code(), Some(0));
+    assert!(stdout(&html).contains("{\"input\": \"open\", \"active\": [4, 7]}"));
+
+    let written = sm(&["convert", "diff/door_v1.sm", "--to", "sm"], "");
+    let round = model("convert", &stdout(&written));
+    let same = sm(&["diff", "diff/door_v1.sm", &round], "");
+    assert_eq!(same.status.code(), Some(0), "{}", stdout(&same));
+
+    let json = sm(&["convert", "diff/door_v1.sm", "--to", "json"], "");
+    let value: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(value["name"], "door");
+    let rust = sm(&["convert", "diff/door_v1.sm", "--to", "rust"], "");
+    assert!(stdout(&rust).contains("pub mod door {"));
+    let rust = sm(
+        &["convert", "-", "--to", "rust"],
+        include_str!("diff/door_v1.sm"),
+    );
+    assert_eq!(rust.status.code(), Some(0));
+    assert!(stdout(&rust).contains("pub mod door {"));
+
+    // json is read back
+    let again = sm(&["convert", "-", "--to", "sm"], &stdout(&json));
+    assert_eq!(again.status.code(), Some(0));
+    assert_eq!(stdout(&again), stdout(&written));
+    let rust = sm(&["convert", "-", "--to", "rust"], &stdout(&json));
+    assert!(stdout(&rust).contains("pub mod door {"));
+    let broken = sm(&["convert", "-", "--to", "sm"], "{\"name\": \"door\"}");
+    assert_eq!(broken.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&broken.stderr).contains("missing `transitions`"));
+}
+
+#[test]
+fn test_simulate() {
+    let run = sm(
+        &["simulate", "diff/door_v1.sm"],
+        "open\n# a comment\npush\n\nclose\nlock\n",
+    );
+    assert_eq!(run.status.code(), Some(0));
+    assert_eq!(
+        stdout(&run),
+        "[start]: closed\n\
+         open: opened::ajar  [creak, light_on]\n\
+         push: opened::wide\n\
+         close: closed\n\
+         lock (not fired): closed\n"
+    );
+
+    let run = sm(
+        &[
+            "simulate",
+            "codegen/pump.sm",
+            "--json",
+            "--guard",
+            "primed=false",
+        ],
+        "start\n",
+    );
+    let steps: Vec<serde_json::Value> = stdout(&run)
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(steps[1]["event"], "start");
+    assert_eq!(steps[1]["active"][0], "running::low");
+    assert!(!steps[1]["behaviors"]
+        .as_array()
+        .unwrap()
+        .contains(&"beep".into()));
+
+    let unknown = sm(&["simulate", "diff/door_v1.sm"], "open\nfly\n");
+    assert_eq!(unknown.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&unknown.stderr).contains("line 2: unknown event fly"));
+}
+
+#[test]
+fn test_json_failures() {
+    let failure =
+        |output: &Output| -> serde_json::Value { serde_json::from_slice(&output.stderr).unwrap() };
+    let unknown = sm(&["simulate", "diff/door_v1.sm", "--json"], "open\nfly\n");
+    assert_eq!(unknown.status.code(), Some(1));
+    let value = failure(&unknown);
+    assert_eq!(value["severity"], "error");
+    assert_eq!(value["code"], "unknown-event");
+    assert_eq!(value["message"], "unknown event fly");
+    assert_eq!(value["line"], 2);
+    assert!(value["file"].is_null());
+
+    let bad = model(
+        "json_failure",
+        "statemachine s {\n    state a;\n    oops\n}\n",
+    );
+    let value = failure(&sm(&["stats", &bad, "--json"], ""));
+    assert_eq!(value["code"], "parse");
+    assert_eq!(value["file"], bad.as_str());
+    assert_eq!(
+        (value["line"].clone(), value["column"].clone()),
+        (3.into(), 5.into())
+    );
+
+    let missing = sm(&["validate", "missing.sm", "--json"], "");
+    assert_eq!(missing.status.code(), Some(2));
+    let value = failure(&missing);
+    assert_eq!(value["code"], "io");
+    assert_eq!(value["file"], "missing.sm");
+
+    let value = failure(&sm(&["stats", "--json"], ""));
+    assert_eq!(value["code"], "usage");
+    assert_eq!(value["message"], "missing model file");
+}
+
+#[test]
+fn test_stats() {
+    let stats = sm(&["stats", "diff/door_v1.sm", "--json"], "");
+    let value: serde_json::Value = serde_json::from_slice(&stats.stdout).unwrap();
+    assert_eq!(value["states"], 4);
+    assert_eq!(value["pseudostates"], 2);
+    assert_eq!(value["transitions"], 5);
+    assert_eq!(value["depth"], 2);
+}
+
+#[test]
+fn test_repl() {
+    let session = sm(&["repl", "diff/door_v1.sm"], "open\nundo\nquit\n");
+    assert_eq!(session.status.code(), Some(0));
+    let out = stdout(&session);
+    assert!(out.contains("[active_state] door::region_1::opened::region_1::ajar"));
+    assert!(
+        out.ends_with("at step 1\n[active_state] door::region_1::closed\n[status] Running\n> \n")
+    );
+}
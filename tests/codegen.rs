@@ -0,0 +1,116 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::codegen;
+use rust_uml_sm::StateMachineError;
+use std::convert::TryFrom;
+
+include!("codegen/sm1.rs");
+include!("codegen/match.rs");
+
+use sm1::{Busy, BusyBlink, BusyFetch, BusyFetchReading, Event, State};
+
+#[test]
+fn test_codegen_up_to_date() {
+    let model = include_str!("codegen/sm1.sm");
+    assert_eq!(
+        codegen::module(model).unwrap(),
+        include_str!("codegen/sm1.rs"),
+        "regenerate tests/codegen/sm1.rs with codegen::generate_to"
+    );
+}
+
+#[test]
+fn test_codegen_types() {
+    let mut sm = sm1::definition();
+    sm.start().unwrap();
+    assert_eq!(State::current(&sm), Some(State::Idle));
+    assert_eq!(State::Idle.leaves(), sm.active_states());
+
+    sm.dispatch(Event::Go.into()).unwrap();
+    let busy = Busy {
+        fetch: BusyFetch::Waiting,
+        blink: BusyBlink::OnLed,
+    };
+    assert_eq!(State::current(&sm), Some(State::Busy(busy)));
+    assert_eq!(sm.name(State::Busy(busy).dbid()).unwrap(), "busy");
+
+    sm.dispatch(Event::Next.dbid()).unwrap();
+    sm.dispatch(Event::Next.dbid()).unwrap();
+    match State::current(&sm) {
+        Some(State::Busy(Busy {
+            fetch: BusyFetch::Reading(BusyFetchReading::Body),
+            blink: BusyBlink::OffLed,
+        })) => (),
+        other => panic!("unexpected {:?}", other),
+    }
+    let leaves = State::current(&sm).unwrap().leaves();
+    assert_eq!(leaves, sm.active_states());
+
+    sm.dispatch(Event::Stop.dbid()).unwrap();
+    assert_eq!(State::current(&sm), Some(State::Done));
+
+    assert_eq!(
+        Event::from_dbid(sm.event_type("next").unwrap()),
+        Some(Event::Next)
+    );
+    assert_eq!(Event::try_from(0), Err(0));
+    assert_eq!(
+        Event::ALL.iter().map(|e| e.name()).collect::<Vec<_>>(),
+        vec!["go", "next", "stop"]
+    );
+}
+
+#[test]
+fn test_codegen_keywords() {
+    let model = include_str!("codegen/match.sm");
+    assert_eq!(
+        codegen::module(model).unwrap(),
+        include_str!("codegen/match.rs"),
+        "regenerate tests/codegen/match.rs with codegen::generate_to"
+    );
+    let mut sm = r#match::definition();
+    sm.start().unwrap();
+    let state = r#match::State::current(&sm).unwrap();
+    let r#match::State::Impl(inner) = state;
+    assert_eq!(inner.r#type, r#match::ImplType::Move);
+    assert_eq!(inner.r#where, r#match::ImplWhere::Ref);
+    sm.dispatch(r#match::Event::Loop.into()).unwrap();
+    assert_eq!(
+        r#match::State::current(&sm),
+        Some(r#match::State::Impl(r#match::Impl {
+            r#type: r#match::ImplType::Yield,
+            r#where: r#match::ImplWhere::Ref,
+        }))
+    );
+    assert!(codegen::static_module(model, 1)
+        .unwrap()
+        .contains("pub mod r#match {"));
+}
+
+#[test]
+fn test_codegen_invalid_names() {
+    let invalid = |model: &str| match codegen::module(model) {
+        Err(StateMachineError::InvalidIdentifier(name)) => name,
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    };
+    let model = |name: &str| format!("statemachine sm {{ initial -> {0}; state {0}; }}", name);
+    assert_eq!(invalid(&model("1st")), "1st");
+    // camel cased to Self
+    assert_eq!(invalid(&model("self")), "self");
+    assert_eq!(invalid(&model("_")), "_");
+    assert_eq!(
+        invalid("statemachine crate { initial -> a; state a; }"),
+        "crate"
+    );
+    assert_eq!(
+        invalid("statemachine super { initial -> a; state a; }"),
+        "super"
+    );
+    assert_eq!(
+        invalid("statemachine sm { event self; initial -> a; state a; }"),
+        "self"
+    );
+    assert!(matches!(
+        codegen::static_module(&model("2nd"), 1),
+        Err(StateMachineError::InvalidIdentifier(name)) if name == "2nd"
+    ));
+}
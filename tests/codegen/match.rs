@@ -0,0 +1,174 @@
+// Generated by rust_uml_sm::codegen, do not edit.
+#[allow(dead_code)]
+pub mod r#match {
+pub const MODEL: &str = "// names which are Rust keywords, escaped as raw identifiers\nstatemachine match {\n    event loop, type;\n    initial -> impl;\n    state impl {\n        region type {\n            initial -> move;\n            state move { on loop -> yield; }\n            state yield;\n        }\n        region where {\n            initial -> ref;\n            state ref;\n        }\n        on type -> impl;\n    }\n}\n";
+
+/// Build the definition, the dbids below refer to it.
+pub fn definition() -> ::rust_uml_sm::StateMachineDef {
+    ::rust_uml_sm::dsl::parse(MODEL).expect("checked by codegen")
+}
+
+/// The active state configuration of the state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum State {
+    Impl(Impl),
+}
+
+impl State {
+    /// The dbid of the state
+    pub fn dbid(&self) -> usize {
+        match self {
+            State::Impl(_) => 3,
+        }
+    }
+
+    /// The dbids of the innermost states
+    pub fn leaves(&self) -> Vec<usize> {
+        match self {
+            State::Impl(s) => s.leaves(),
+        }
+    }
+
+    pub fn from_dbids(active: &[usize]) -> Option<State> {
+        if let Some(s) = Impl::from_dbids(active) {
+            return Some(State::Impl(s));
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Impl {
+    pub r#type: ImplType,
+    pub r#where: ImplWhere,
+}
+
+impl Impl {
+    /// The dbids of the innermost states
+    pub fn leaves(&self) -> Vec<usize> {
+        let mut leaves = Vec::new();
+        leaves.extend(self.r#type.leaves());
+        leaves.extend(self.r#where.leaves());
+        leaves
+    }
+
+    pub fn from_dbids(active: &[usize]) -> Option<Impl> {
+        Some(Impl {
+            r#type: ImplType::from_dbids(active)?,
+            r#where: ImplWhere::from_dbids(active)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImplType {
+    Move,
+    Yield,
+}
+
+impl ImplType {
+    /// The dbid of the state
+    pub fn dbid(&self) -> usize {
+        match self {
+            ImplType::Move => 6,
+            ImplType::Yield => 7,
+        }
+    }
+
+    /// The dbids of the innermost states
+    pub fn leaves(&self) -> Vec<usize> {
+        match self {
+            ImplType::Move => vec![6],
+            ImplType::Yield => vec![7],
+        }
+    }
+
+    pub fn from_dbids(active: &[usize]) -> Option<ImplType> {
+        if active.contains(&6) {
+            return Some(ImplType::Move);
+        }
+        if active.contains(&7) {
+            return Some(ImplType::Yield);
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImplWhere {
+    Ref,
+}
+
+impl ImplWhere {
+    /// The dbid of the state
+    pub fn dbid(&self) -> usize {
+        match self {
+            ImplWhere::Ref => 10,
+        }
+    }
+
+    /// The dbids of the innermost states
+    pub fn leaves(&self) -> Vec<usize> {
+        match self {
+            ImplWhere::Ref => vec![10],
+        }
+    }
+
+    pub fn from_dbids(active: &[usize]) -> Option<ImplWhere> {
+        if active.contains(&10) {
+            return Some(ImplWhere::Ref);
+        }
+        None
+    }
+}
+
+impl State {
+    pub fn current(def: &::rust_uml_sm::StateMachineDef) -> Option<State> {
+        State::from_dbids(&def.active_states())
+    }
+}
+
+/// The event types of the state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    Loop,
+    Type,
+}
+
+impl Event {
+    pub const ALL: [Event; 2] = [Event::Loop, Event::Type];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Loop => "loop",
+            Event::Type => "type",
+        }
+    }
+
+    /// The dbid of the event type
+    pub fn dbid(self) -> usize {
+        match self {
+            Event::Loop => 11,
+            Event::Type => 12,
+        }
+    }
+
+    pub fn from_dbid(dbid: usize) -> Option<Event> {
+        Event::ALL.iter().copied().find(|e| e.dbid() == dbid)
+    }
+}
+
+impl From<Event> for usize {
+    fn from(event: Event) -> usize {
+        event.dbid()
+    }
+}
+
+impl ::core::convert::TryFrom<usize> for Event {
+    type Error = usize;
+
+    fn try_from(dbid: usize) -> Result<Event, usize> {
+        Event::from_dbid(dbid).ok_or(dbid)
+    }
+}
+}
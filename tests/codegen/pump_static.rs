@@ -0,0 +1,284 @@
+// Generated by rust_uml_sm::codegen, do not edit.
+#[allow(dead_code)]
+pub mod pump {
+use ::rust_uml_sm::compiled::{Element, Id, Kind, Tables};
+
+pub type Machine = ::rust_uml_sm::compiled::Machine<'static, Tables, 25, 5, 28, 64>;
+
+pub const fn machine() -> Machine {
+    Machine::new(&TABLES)
+}
+
+pub static TABLES: Tables = Tables {
+    name: "pump",
+    elements: &ELEMENTS,
+    triggered,
+    candidates,
+    scope,
+    defers,
+};
+
+static ELEMENTS: [Element; 67] = [
+    Element { kind: Kind::StateMachine, parent: 0, name: "pump", regions: &[1], ..Element::NONE },
+    Element { kind: Kind::Region, parent: 0, name: "region_1", initial: 2, ..Element::NONE },
+    Element { kind: Kind::InitialState, parent: 1, name: "initial", outgoing: &[39], ..Element::NONE },
+    Element { kind: Kind::State, parent: 1, name: "off", entry: Some(::rust_uml_sm::Behavior::named("lamp_off")), defer: &[37], outgoing: &[40, 41, 42], incoming: &[39, 42, 44, 62, 64], ..Element::NONE },
+    Element { kind: Kind::Pseudostate(::rust_uml_sm::PseudostateKind::Choice), parent: 1, name: "check", outgoing: &[43, 44], incoming: &[40], ..Element::NONE },
+    Element { kind: Kind::State, parent: 1, name: "running", regions: &[6], entry: Some(::rust_uml_sm::Behavior::named("motor_on")), exit: Some(::rust_uml_sm::Behavior::named("motor_off")), activity: Some(::rust_uml_sm::Behavior::named("spin")), outgoing: &[62, 63], incoming: &[43], ..Element::NONE },
+    Element { kind: Kind::Region, parent: 5, name: "region_1", index: 1, initial: 8, ..Element::NONE },
+    Element { kind: Kind::Pseudostate(::rust_uml_sm::PseudostateKind::DeepHistory), parent: 6, name: "h", incoming: &[41], ..Element::NONE },
+    Element { kind: Kind::InitialState, parent: 6, name: "initial", outgoing: &[45], ..Element::NONE },
+    Element { kind: Kind::State, parent: 6, name: "low", outgoing: &[46, 47, 48], incoming: &[45, 47, 52, 60, 61], ..Element::NONE },
+    Element { kind: Kind::State, parent: 6, name: "high", regions: &[11], outgoing: &[52], incoming: &[46], ..Element::NONE },
+    Element { kind: Kind::Region, parent: 10, name: "region_1", index: 2, initial: 12, ..Element::NONE },
+    Element { kind: Kind::InitialState, parent: 11, name: "initial", outgoing: &[49], ..Element::NONE },
+    Element { kind: Kind::State, parent: 11, name: "warm", outgoing: &[50], incoming: &[49, 51], ..Element::NONE },
+    Element { kind: Kind::State, parent: 11, name: "hot_spot", exit: Some(::rust_uml_sm::Behavior::named("cool")), outgoing: &[51], incoming: &[50], ..Element::NONE },
+    Element { kind: Kind::Pseudostate(::rust_uml_sm::PseudostateKind::Fork), parent: 6, name: "split", outgoing: &[53, 54], incoming: &[48], ..Element::NONE },
+    Element { kind: Kind::State, parent: 6, name: "draining", regions: &[17, 22], outgoing: &[60], ..Element::NONE },
+    Element { kind: Kind::Region, parent: 16, name: "valve", index: 3, initial: 18, ..Element::NONE },
+    Element { kind: Kind::InitialState, parent: 17, name: "initial", outgoing: &[55], ..Element::NONE },
+    Element { kind: Kind::State, parent: 17, name: "closed_valve", incoming: &[55], ..Element::NONE },
+    Element { kind: Kind::State, parent: 17, name: "opening", outgoing: &[56], incoming: &[53], ..Element::NONE },
+    Element { kind: Kind::FinalState, parent: 17, name: "opened", incoming: &[56], ..Element::NONE },
+    Element { kind: Kind::Region, parent: 16, name: "pump", index: 4, initial: 23, ..Element::NONE },
+    Element { kind: Kind::InitialState, parent: 22, name: "initial", outgoing: &[57], ..Element::NONE },
+    Element { kind: Kind::State, parent: 22, name: "idle_pump", incoming: &[57], ..Element::NONE },
+    Element { kind: Kind::State, parent: 22, name: "slowing", outgoing: &[58, 59], incoming: &[54], ..Element::NONE },
+    Element { kind: Kind::FinalState, parent: 22, name: "stopped", incoming: &[58], ..Element::NONE },
+    Element { kind: Kind::Pseudostate(::rust_uml_sm::PseudostateKind::Join), parent: 6, name: "j", outgoing: &[61], incoming: &[59], ..Element::NONE },
+    Element { kind: Kind::State, parent: 1, name: "error", defer: &[31], outgoing: &[64, 65, 66], incoming: &[63], ..Element::NONE },
+    Element { kind: Kind::FinalState, parent: 1, name: "done", incoming: &[65], ..Element::NONE },
+    Element { kind: Kind::Pseudostate(::rust_uml_sm::PseudostateKind::Terminate), parent: 1, name: "k", incoming: &[66], ..Element::NONE },
+    Element { kind: Kind::EventType, parent: 0, name: "start", ..Element::NONE },
+    Element { kind: Kind::EventType, parent: 0, name: "stop", ..Element::NONE },
+    Element { kind: Kind::EventType, parent: 0, name: "tick", ..Element::NONE },
+    Element { kind: Kind::EventType, parent: 0, name: "fault", ..Element::NONE },
+    Element { kind: Kind::EventType, parent: 0, name: "reset", ..Element::NONE },
+    Element { kind: Kind::EventType, parent: 0, name: "prime", ..Element::NONE },
+    Element { kind: Kind::EventType, parent: 0, name: "drain", ..Element::NONE },
+    Element { kind: Kind::EventType, parent: 0, name: "kill", ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "initial", source: 2, target: 3, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t2", index: 1, source: 3, target: 4, trigger: 31, guard: Some(::rust_uml_sm::Guard::named("primed")), effect: Some(::rust_uml_sm::Behavior::named("beep")), ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t3", index: 2, source: 3, target: 7, trigger: 31, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t4", index: 3, source: 3, target: 3, trigger: 36, effect: Some(::rust_uml_sm::Behavior::named("fill")), transition: ::rust_uml_sm::TransitionKind::Internal, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t5", index: 4, source: 4, target: 5, guard: Some(::rust_uml_sm::Guard::named("pressure_ok")), ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t6", index: 5, source: 4, target: 3, guard: Some(::rust_uml_sm::Guard::named("else")), effect: Some(::rust_uml_sm::Behavior::named("complain")), ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 6, name: "initial", index: 6, source: 8, target: 9, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 6, name: "t8", index: 7, source: 9, target: 10, trigger: 33, guard: Some(::rust_uml_sm::Guard::named("hot")), ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 6, name: "t9", index: 8, source: 9, target: 9, trigger: 33, effect: Some(::rust_uml_sm::Behavior::named("count")), transition: ::rust_uml_sm::TransitionKind::Internal, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 6, name: "t10", index: 9, source: 9, target: 15, trigger: 37, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 11, name: "initial", index: 10, source: 12, target: 13, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 11, name: "t12", index: 11, source: 13, target: 14, trigger: 33, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 11, name: "t13", index: 12, source: 14, target: 13, trigger: 33, guard: Some(::rust_uml_sm::Guard::named("hot")), ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 6, name: "t14", index: 13, source: 10, target: 9, trigger: 33, guard: Some(::rust_uml_sm::Guard::named("cold")), ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 6, name: "t15", index: 14, source: 15, target: 20, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 6, name: "t16", index: 15, source: 15, target: 25, effect: Some(::rust_uml_sm::Behavior::named("slow")), ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 17, name: "initial", index: 16, source: 18, target: 19, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 17, name: "t18", index: 17, source: 20, target: 21, trigger: 33, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 22, name: "initial", index: 18, source: 23, target: 24, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 22, name: "t20", index: 19, source: 25, target: 26, trigger: 33, guard: Some(::rust_uml_sm::Guard::named("hot")), ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 22, name: "t21", index: 20, source: 25, target: 27, trigger: 33, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 6, name: "t22", index: 21, source: 16, target: 9, effect: Some(::rust_uml_sm::Behavior::named("drained")), ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 6, name: "t23", index: 22, source: 27, target: 9, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t24", index: 23, source: 5, target: 3, trigger: 32, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t25", index: 24, source: 5, target: 28, trigger: 34, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t26", index: 25, source: 28, target: 3, trigger: 35, guard: Some(::rust_uml_sm::Guard::named("recoverable")), ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t27", index: 26, source: 28, target: 29, trigger: 35, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t28", index: 27, source: 28, target: 30, trigger: 38, ..Element::NONE },
+];
+
+/// The outgoing transitions of a vertex for an event, 0 for completion.
+fn triggered(vertex: Id, event: Id) -> &'static [Id] {
+    match (vertex, event) {
+        (2, 0) => &[39],
+        (3, 31) => &[40, 41],
+        (3, 36) => &[42],
+        (4, 0) => &[43, 44],
+        (5, 32) => &[62],
+        (5, 34) => &[63],
+        (8, 0) => &[45],
+        (9, 33) => &[46, 47],
+        (9, 37) => &[48],
+        (10, 33) => &[52],
+        (12, 0) => &[49],
+        (13, 33) => &[50],
+        (14, 33) => &[51],
+        (15, 0) => &[53, 54],
+        (16, 0) => &[60],
+        (18, 0) => &[55],
+        (20, 33) => &[56],
+        (23, 0) => &[57],
+        (25, 33) => &[58, 59],
+        (27, 0) => &[61],
+        (28, 35) => &[64, 65],
+        (28, 38) => &[66],
+        _ => &[],
+    }
+}
+
+/// The transitions an event may fire from a vertex and the states containing it.
+fn candidates(vertex: Id, event: Id) -> &'static [Id] {
+    match (vertex, event) {
+        (3, 31) => &[40, 41],
+        (3, 36) => &[42],
+        (5, 32) => &[62],
+        (5, 34) => &[63],
+        (7, 32) => &[62],
+        (7, 34) => &[63],
+        (8, 32) => &[62],
+        (8, 34) => &[63],
+        (9, 32) => &[62],
+        (9, 33) => &[46, 47],
+        (9, 34) => &[63],
+        (9, 37) => &[48],
+        (10, 32) => &[62],
+        (10, 33) => &[52],
+        (10, 34) => &[63],
+        (12, 32) => &[62],
+        (12, 33) => &[52],
+        (12, 34) => &[63],
+        (13, 32) => &[62],
+        (13, 33) => &[50, 52],
+        (13, 34) => &[63],
+        (14, 32) => &[62],
+        (14, 33) => &[51, 52],
+        (14, 34) => &[63],
+        (15, 32) => &[62],
+        (15, 34) => &[63],
+        (16, 32) => &[62],
+        (16, 34) => &[63],
+        (18, 32) => &[62],
+        (18, 34) => &[63],
+        (19, 32) => &[62],
+        (19, 34) => &[63],
+        (20, 32) => &[62],
+        (20, 33) => &[56],
+        (20, 34) => &[63],
+        (21, 32) => &[62],
+        (21, 34) => &[63],
+        (23, 32) => &[62],
+        (23, 34) => &[63],
+        (24, 32) => &[62],
+        (24, 34) => &[63],
+        (25, 32) => &[62],
+        (25, 33) => &[58, 59],
+        (25, 34) => &[63],
+        (26, 32) => &[62],
+        (26, 34) => &[63],
+        (27, 32) => &[62],
+        (27, 34) => &[63],
+        (28, 35) => &[64, 65],
+        (28, 38) => &[66],
+        _ => &[],
+    }
+}
+
+/// Whether a vertex or a state containing it defers an event.
+fn defers(vertex: Id, event: Id) -> bool {
+    matches!((vertex, event), (3, 37) | (28, 31))
+}
+
+/// The region a transition exits and enters, 0 for the state machine.
+fn scope(transition: Id) -> Id {
+    match transition {
+        39 => 1,
+        40 => 1,
+        41 => 1,
+        42 => 1,
+        43 => 1,
+        44 => 1,
+        45 => 6,
+        46 => 6,
+        47 => 6,
+        48 => 6,
+        49 => 11,
+        50 => 11,
+        51 => 11,
+        52 => 6,
+        53 => 6,
+        54 => 6,
+        55 => 17,
+        56 => 17,
+        57 => 22,
+        58 => 22,
+        59 => 6,
+        60 => 6,
+        61 => 6,
+        62 => 1,
+        63 => 1,
+        64 => 1,
+        65 => 1,
+        66 => 1,
+        _ => 0,
+    }
+}
+
+/// The event types of the state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    Start,
+    Stop,
+    Tick,
+    Fault,
+    Reset,
+    Prime,
+    Drain,
+    Kill,
+}
+
+impl Event {
+    pub const ALL: [Event; 8] = [Event::Start, Event::Stop, Event::Tick, Event::Fault, Event::Reset, Event::Prime, Event::Drain, Event::Kill];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Start => "start",
+            Event::Stop => "stop",
+            Event::Tick => "tick",
+            Event::Fault => "fault",
+            Event::Reset => "reset",
+            Event::Prime => "prime",
+            Event::Drain => "drain",
+            Event::Kill => "kill",
+        }
+    }
+
+    /// The dbid of the event type
+    pub fn dbid(self) -> usize {
+        match self {
+            Event::Start => 31,
+            Event::Stop => 32,
+            Event::Tick => 33,
+            Event::Fault => 34,
+            Event::Reset => 35,
+            Event::Prime => 36,
+            Event::Drain => 37,
+            Event::Kill => 38,
+        }
+    }
+
+    pub fn from_dbid(dbid: usize) -> Option<Event> {
+        Event::ALL.iter().copied().find(|e| e.dbid() == dbid)
+    }
+}
+
+impl From<Event> for usize {
+    fn from(event: Event) -> usize {
+        event.dbid()
+    }
+}
+
+impl ::core::convert::TryFrom<usize> for Event {
+    type Error = usize;
+
+    fn try_from(dbid: usize) -> Result<Event, usize> {
+        Event::from_dbid(dbid).ok_or(dbid)
+    }
+}
+
+impl From<Event> for Id {
+    fn from(event: Event) -> Id {
+        event.dbid() as Id
+    }
+}
+}
@@ -0,0 +1,229 @@
+// Generated by rust_uml_sm::codegen, do not edit.
+#[allow(dead_code)]
+pub mod sm1 {
+pub const MODEL: &str = "statemachine sm1 {\n    event go, next, stop;\n    initial -> idle;\n    state idle { on go -> busy; }\n    state busy {\n        region fetch {\n            initial -> waiting;\n            state waiting { on next -> reading; }\n            state reading {\n                initial -> header;\n                state header { on next -> body; }\n                state body;\n            }\n        }\n        region blink {\n            initial -> on_led;\n            state on_led { on next -> off_led; }\n            state off_led;\n        }\n        on stop -> done;\n    }\n    final done;\n}\n";
+
+/// Build the definition, the dbids below refer to it.
+pub fn definition() -> ::rust_uml_sm::StateMachineDef {
+    ::rust_uml_sm::dsl::parse(MODEL).expect("checked by codegen")
+}
+
+/// The active state configuration of the state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum State {
+    Idle,
+    Busy(Busy),
+    Done,
+}
+
+impl State {
+    /// The dbid of the state
+    pub fn dbid(&self) -> usize {
+        match self {
+            State::Idle => 3,
+            State::Busy(_) => 4,
+            State::Done => 17,
+        }
+    }
+
+    /// The dbids of the innermost states
+    pub fn leaves(&self) -> Vec<usize> {
+        match self {
+            State::Idle => vec![3],
+            State::Busy(s) => s.leaves(),
+            State::Done => vec![17],
+        }
+    }
+
+    pub fn from_dbids(active: &[usize]) -> Option<State> {
+        if active.contains(&3) {
+            return Some(State::Idle);
+        }
+        if let Some(s) = Busy::from_dbids(active) {
+            return Some(State::Busy(s));
+        }
+        if active.contains(&17) {
+            return Some(State::Done);
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Busy {
+    pub fetch: BusyFetch,
+    pub blink: BusyBlink,
+}
+
+impl Busy {
+    /// The dbids of the innermost states
+    pub fn leaves(&self) -> Vec<usize> {
+        let mut leaves = Vec::new();
+        leaves.extend(self.fetch.leaves());
+        leaves.extend(self.blink.leaves());
+        leaves
+    }
+
+    pub fn from_dbids(active: &[usize]) -> Option<Busy> {
+        Some(Busy {
+            fetch: BusyFetch::from_dbids(active)?,
+            blink: BusyBlink::from_dbids(active)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BusyFetch {
+    Waiting,
+    Reading(BusyFetchReading),
+}
+
+impl BusyFetch {
+    /// The dbid of the state
+    pub fn dbid(&self) -> usize {
+        match self {
+            BusyFetch::Waiting => 7,
+            BusyFetch::Reading(_) => 8,
+        }
+    }
+
+    /// The dbids of the innermost states
+    pub fn leaves(&self) -> Vec<usize> {
+        match self {
+            BusyFetch::Waiting => vec![7],
+            BusyFetch::Reading(s) => s.leaves(),
+        }
+    }
+
+    pub fn from_dbids(active: &[usize]) -> Option<BusyFetch> {
+        if active.contains(&7) {
+            return Some(BusyFetch::Waiting);
+        }
+        if let Some(s) = BusyFetchReading::from_dbids(active) {
+            return Some(BusyFetch::Reading(s));
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BusyFetchReading {
+    Header,
+    Body,
+}
+
+impl BusyFetchReading {
+    /// The dbid of the state
+    pub fn dbid(&self) -> usize {
+        match self {
+            BusyFetchReading::Header => 11,
+            BusyFetchReading::Body => 12,
+        }
+    }
+
+    /// The dbids of the innermost states
+    pub fn leaves(&self) -> Vec<usize> {
+        match self {
+            BusyFetchReading::Header => vec![11],
+            BusyFetchReading::Body => vec![12],
+        }
+    }
+
+    pub fn from_dbids(active: &[usize]) -> Option<BusyFetchReading> {
+        if active.contains(&11) {
+            return Some(BusyFetchReading::Header);
+        }
+        if active.contains(&12) {
+            return Some(BusyFetchReading::Body);
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BusyBlink {
+    OnLed,
+    OffLed,
+}
+
+impl BusyBlink {
+    /// The dbid of the state
+    pub fn dbid(&self) -> usize {
+        match self {
+            BusyBlink::OnLed => 15,
+            BusyBlink::OffLed => 16,
+        }
+    }
+
+    /// The dbids of the innermost states
+    pub fn leaves(&self) -> Vec<usize> {
+        match self {
+            BusyBlink::OnLed => vec![15],
+            BusyBlink::OffLed => vec![16],
+        }
+    }
+
+    pub fn from_dbids(active: &[usize]) -> Option<BusyBlink> {
+        if active.contains(&15) {
+            return Some(BusyBlink::OnLed);
+        }
+        if active.contains(&16) {
+            return Some(BusyBlink::OffLed);
+        }
+        None
+    }
+}
+
+impl State {
+    pub fn current(def: &::rust_uml_sm::StateMachineDef) -> Option<State> {
+        State::from_dbids(&def.active_states())
+    }
+}
+
+/// The event types of the state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    Go,
+    Next,
+    Stop,
+}
+
+impl Event {
+    pub const ALL: [Event; 3] = [Event::Go, Event::Next, Event::Stop];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Go => "go",
+            Event::Next => "next",
+            Event::Stop => "stop",
+        }
+    }
+
+    /// The dbid of the event type
+    pub fn dbid(self) -> usize {
+        match self {
+            Event::Go => 18,
+            Event::Next => 19,
+            Event::Stop => 20,
+        }
+    }
+
+    pub fn from_dbid(dbid: usize) -> Option<Event> {
+        Event::ALL.iter().copied().find(|e| e.dbid() == dbid)
+    }
+}
+
+impl From<Event> for usize {
+    fn from(event: Event) -> usize {
+        event.dbid()
+    }
+}
+
+impl ::core::convert::TryFrom<usize> for Event {
+    type Error = usize;
+
+    fn try_from(dbid: usize) -> Result<Event, usize> {
+        Event::from_dbid(dbid).ok_or(dbid)
+    }
+}
+}
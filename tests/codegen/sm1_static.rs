@@ -0,0 +1,164 @@
+// Generated by rust_uml_sm::codegen, do not edit.
+#[allow(dead_code)]
+pub mod sm1 {
+use ::rust_uml_sm::compiled::{Element, Id, Kind, Tables};
+
+pub type Machine = ::rust_uml_sm::compiled::Machine<'static, Tables, 13, 4, 9, 4>;
+
+pub const fn machine() -> Machine {
+    Machine::new(&TABLES)
+}
+
+pub static TABLES: Tables = Tables {
+    name: "sm1",
+    elements: &ELEMENTS,
+    triggered,
+    candidates,
+    scope,
+    defers,
+};
+
+static ELEMENTS: [Element; 30] = [
+    Element { kind: Kind::StateMachine, parent: 0, name: "sm1", regions: &[1], ..Element::NONE },
+    Element { kind: Kind::Region, parent: 0, name: "region_1", initial: 2, ..Element::NONE },
+    Element { kind: Kind::InitialState, parent: 1, name: "initial", outgoing: &[21], ..Element::NONE },
+    Element { kind: Kind::State, parent: 1, name: "idle", outgoing: &[22], incoming: &[21], ..Element::NONE },
+    Element { kind: Kind::State, parent: 1, name: "busy", regions: &[5, 13], outgoing: &[29], incoming: &[22], ..Element::NONE },
+    Element { kind: Kind::Region, parent: 4, name: "fetch", index: 1, initial: 6, ..Element::NONE },
+    Element { kind: Kind::InitialState, parent: 5, name: "initial", outgoing: &[23], ..Element::NONE },
+    Element { kind: Kind::State, parent: 5, name: "waiting", outgoing: &[24], incoming: &[23], ..Element::NONE },
+    Element { kind: Kind::State, parent: 5, name: "reading", regions: &[9], incoming: &[24], ..Element::NONE },
+    Element { kind: Kind::Region, parent: 8, name: "region_1", index: 2, initial: 10, ..Element::NONE },
+    Element { kind: Kind::InitialState, parent: 9, name: "initial", outgoing: &[25], ..Element::NONE },
+    Element { kind: Kind::State, parent: 9, name: "header", outgoing: &[26], incoming: &[25], ..Element::NONE },
+    Element { kind: Kind::State, parent: 9, name: "body", incoming: &[26], ..Element::NONE },
+    Element { kind: Kind::Region, parent: 4, name: "blink", index: 3, initial: 14, ..Element::NONE },
+    Element { kind: Kind::InitialState, parent: 13, name: "initial", outgoing: &[27], ..Element::NONE },
+    Element { kind: Kind::State, parent: 13, name: "on_led", outgoing: &[28], incoming: &[27], ..Element::NONE },
+    Element { kind: Kind::State, parent: 13, name: "off_led", incoming: &[28], ..Element::NONE },
+    Element { kind: Kind::FinalState, parent: 1, name: "done", incoming: &[29], ..Element::NONE },
+    Element { kind: Kind::EventType, parent: 0, name: "go", ..Element::NONE },
+    Element { kind: Kind::EventType, parent: 0, name: "next", ..Element::NONE },
+    Element { kind: Kind::EventType, parent: 0, name: "stop", ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "initial", source: 2, target: 3, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t2", index: 1, source: 3, target: 4, trigger: 18, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 5, name: "initial", index: 2, source: 6, target: 7, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 5, name: "t4", index: 3, source: 7, target: 8, trigger: 19, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 9, name: "initial", index: 4, source: 10, target: 11, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 9, name: "t6", index: 5, source: 11, target: 12, trigger: 19, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 13, name: "initial", index: 6, source: 14, target: 15, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 13, name: "t8", index: 7, source: 15, target: 16, trigger: 19, ..Element::NONE },
+    Element { kind: Kind::Transition, parent: 1, name: "t9", index: 8, source: 4, target: 17, trigger: 20, ..Element::NONE },
+];
+
+/// The outgoing transitions of a vertex for an event, 0 for completion.
+fn triggered(vertex: Id, event: Id) -> &'static [Id] {
+    match (vertex, event) {
+        (2, 0) => &[21],
+        (3, 18) => &[22],
+        (4, 20) => &[29],
+        (6, 0) => &[23],
+        (7, 19) => &[24],
+        (10, 0) => &[25],
+        (11, 19) => &[26],
+        (14, 0) => &[27],
+        (15, 19) => &[28],
+        _ => &[],
+    }
+}
+
+/// The transitions an event may fire from a vertex and the states containing it.
+fn candidates(vertex: Id, event: Id) -> &'static [Id] {
+    match (vertex, event) {
+        (3, 18) => &[22],
+        (4, 20) => &[29],
+        (6, 20) => &[29],
+        (7, 19) => &[24],
+        (7, 20) => &[29],
+        (8, 20) => &[29],
+        (10, 20) => &[29],
+        (11, 19) => &[26],
+        (11, 20) => &[29],
+        (12, 20) => &[29],
+        (14, 20) => &[29],
+        (15, 19) => &[28],
+        (15, 20) => &[29],
+        (16, 20) => &[29],
+        _ => &[],
+    }
+}
+
+/// Whether a vertex or a state containing it defers an event.
+fn defers(_vertex: Id, _event: Id) -> bool {
+    false
+}
+
+/// The region a transition exits and enters, 0 for the state machine.
+fn scope(transition: Id) -> Id {
+    match transition {
+        21 => 1,
+        22 => 1,
+        23 => 5,
+        24 => 5,
+        25 => 9,
+        26 => 9,
+        27 => 13,
+        28 => 13,
+        29 => 1,
+        _ => 0,
+    }
+}
+
+/// The event types of the state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    Go,
+    Next,
+    Stop,
+}
+
+impl Event {
+    pub const ALL: [Event; 3] = [Event::Go, Event::Next, Event::Stop];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Go => "go",
+            Event::Next => "next",
+            Event::Stop => "stop",
+        }
+    }
+
+    /// The dbid of the event type
+    pub fn dbid(self) -> usize {
+        match self {
+            Event::Go => 18,
+            Event::Next => 19,
+            Event::Stop => 20,
+        }
+    }
+
+    pub fn from_dbid(dbid: usize) -> Option<Event> {
+        Event::ALL.iter().copied().find(|e| e.dbid() == dbid)
+    }
+}
+
+impl From<Event> for usize {
+    fn from(event: Event) -> usize {
+        event.dbid()
+    }
+}
+
+impl ::core::convert::TryFrom<usize> for Event {
+    type Error = usize;
+
+    fn try_from(dbid: usize) -> Result<Event, usize> {
+        Event::from_dbid(dbid).ok_or(dbid)
+    }
+}
+
+impl From<Event> for Id {
+    fn from(event: Event) -> Id {
+        event.dbid() as Id
+    }
+}
+}
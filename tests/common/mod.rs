@@ -0,0 +1,65 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use rust_uml_sm::{Context, StateMachineDef};
+
+/// A linear congruential generator, the same sequence for the same seed.
+pub fn next(seed: &mut u64) -> u64 {
+    *seed = seed
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+    *seed >> 33
+}
+
+/// Records everything the engine does, guards are drawn from a seeded
+/// generator so two runs see the same values as long as they evaluate
+/// the same guards in the same order.
+#[derive(Clone)]
+pub struct Recorder {
+    pub seed: u64,
+    pub log: Vec<String>,
+    /// the do-activities going on in the background
+    pub background: &'static [&'static str],
+}
+
+impl Recorder {
+    pub fn new(seed: u64) -> Recorder {
+        Recorder {
+            seed,
+            log: Vec::new(),
+            background: &[],
+        }
+    }
+}
+
+impl Context for Recorder {
+    fn behavior(&mut self, name: &str) {
+        self.log.push(name.to_string());
+    }
+    fn guard(&mut self, name: &str) -> bool {
+        let pass = next(&mut self.seed) & 1 == 0;
+        self.log.push(format!("[{}] {}", name, pass));
+        pass
+    }
+    fn on_entry(&mut self, state: &str) {
+        self.log.push(format!("+{}", state));
+    }
+    fn on_exit(&mut self, state: &str) {
+        self.log.push(format!("-{}", state));
+    }
+    fn start_activity(&mut self, state: usize, name: &str) -> bool {
+        self.log.push(format!("{} {}", name, state));
+        self.background.contains(&name)
+    }
+    fn abort_activity(&mut self, state: usize) {
+        self.log.push(format!("abort {}", state));
+    }
+}
+
+/// The names of the active states.
+pub fn active(def: &StateMachineDef) -> Vec<&str> {
+    def.active_states()
+        .into_iter()
+        .map(|s| def.name(s).unwrap())
+        .collect()
+}
@@ -0,0 +1,333 @@
+extern crate rust_uml_sm;
+mod common;
+use common::{next, Recorder};
+use rust_uml_sm::codegen;
+use rust_uml_sm::dsl;
+use rust_uml_sm::frozen::Frozen;
+use rust_uml_sm::StateMachineError;
+use rust_uml_sm::Status;
+
+include!("codegen/pump_static.rs");
+
+fn outcome(result: Result<bool, StateMachineError>) -> String {
+    match result {
+        Ok(consumed) => consumed.to_string(),
+        Err(err) => format!("{:?}", err),
+    }
+}
+
+#[test]
+fn test_compiled_up_to_date() {
+    let model = include_str!("codegen/pump.sm");
+    assert_eq!(
+        codegen::static_module(model, 64).unwrap(),
+        include_str!("codegen/pump_static.rs"),
+        "regenerate tests/codegen/pump_static.rs with codegen::generate_static_to"
+    );
+    assert_eq!(
+        codegen::static_module(include_str!("codegen/sm1.sm"), 4).unwrap(),
+        include_str!("codegen/sm1_static.rs")
+    );
+    // the same module from the JSON export of the model
+    let json = rust_uml_sm::render::json(&dsl::parse(model).unwrap());
+    assert_eq!(
+        codegen::static_module(&json, 64).unwrap(),
+        include_str!("codegen/pump_static.rs")
+    );
+}
+
+#[test]
+fn test_compiled_matches_interpreted() {
+    let model = include_str!("codegen/pump.sm");
+    let mut seen = Vec::new();
+    for trace in 0..50u64 {
+        let mut sm = dsl::parse(model).unwrap();
+        let mut machine = pump::machine();
+        let mut ctx1 = Recorder::new(trace);
+        let mut ctx2 = Recorder::new(trace);
+        sm.start_with(&mut ctx1).unwrap();
+        machine.start(&mut ctx2).unwrap();
+
+        let mut events = trace + 1000;
+        for i in 0..60 {
+            let event = pump::Event::ALL[next(&mut events) as usize % pump::Event::ALL.len()];
+            let r1 = outcome(sm.dispatch_with(event.dbid(), &mut ctx1));
+            let r2 = outcome(machine.dispatch(event.into(), &mut ctx2));
+            let active: Vec<usize> = machine
+                .active_states()
+                .iter()
+                .map(|id| *id as usize)
+                .collect();
+            let at = format!("trace {} event {} {:?}", trace, i, event);
+            assert_eq!(r1, r2, "{}", at);
+            assert_eq!(ctx1.log, ctx2.log, "{}", at);
+            assert_eq!(sm.active_states(), active, "{}", at);
+            assert_eq!(sm.status(), machine.status(), "{}", at);
+            let deferred: Vec<usize> = machine
+                .deferred_events()
+                .iter()
+                .map(|id| *id as usize)
+                .collect();
+            assert_eq!(sm.deferred_events(), &deferred[..], "{}", at);
+            if sm.status() == Status::Terminated {
+                break;
+            }
+        }
+        sm.stop_with(&mut ctx1).unwrap();
+        machine.stop(&mut ctx2).unwrap();
+        assert_eq!(ctx1.log, ctx2.log);
+        seen.extend(ctx1.log);
+    }
+    // the traces went through the choice, the fork, the completion of
+    // draining and the internal transitions
+    for name in &[
+        "complain", "slow", "+low", "drained", "cool", "+error", "fill",
+    ] {
+        assert!(seen.iter().any(|s| s == name), "{} not reached", name);
+    }
+}
+
+#[test]
+fn test_frozen_matches_interpreted() {
+    let model = include_str!("codegen/pump.sm");
+    let frozen: Frozen<25, 5, 28, 8> = dsl::parse(model).unwrap().freeze().unwrap();
+    for trace in 0..50u64 {
+        let mut sm = dsl::parse(model).unwrap();
+        let mut machine = frozen.machine::<64>();
+        let mut ctx1 = Recorder::new(trace);
+        let mut ctx2 = Recorder::new(trace);
+        sm.start_with(&mut ctx1).unwrap();
+        machine.start(&mut ctx2).unwrap();
+
+        let mut events = trace + 2000;
+        for i in 0..60 {
+            let event = pump::Event::ALL[next(&mut events) as usize % pump::Event::ALL.len()];
+            let id = frozen.event(event.name()).unwrap();
+            let r1 = outcome(sm.dispatch_with(event.dbid(), &mut ctx1));
+            let r2 = outcome(machine.dispatch(id, &mut ctx2));
+            let active: Vec<usize> = machine
+                .active_states()
+                .iter()
+                .map(|id| *id as usize)
+                .collect();
+            let at = format!("trace {} event {} {:?}", trace, i, event);
+            assert_eq!(r1, r2, "{}", at);
+            assert_eq!(ctx1.log, ctx2.log, "{}", at);
+            assert_eq!(sm.active_states(), active, "{}", at);
+            assert_eq!(sm.status(), machine.status(), "{}", at);
+            if sm.status() == Status::Terminated {
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_frozen_activities_match_interpreted() {
+    let model = "statemachine sm {
+        event go, finish;
+        initial -> idle;
+        state idle { on go -> busy; }
+        state busy {
+            do blink;
+            initial -> fetching;
+            state fetching { do fetch; -> work; }
+            state work { on finish -> over; }
+            final over;
+            on go -> idle;
+            -> idle / done;
+        }
+    }";
+    let def = dsl::parse(model).unwrap();
+    let frozen: Frozen<7, 2, 7, 2> = def.freeze().unwrap();
+    let [busy, fetching] = [
+        def.find("busy").unwrap(),
+        def.find("busy::fetching").unwrap(),
+    ];
+    let [go, finish] = [
+        def.event_type("go").unwrap(),
+        def.event_type("finish").unwrap(),
+    ];
+    let inputs: &[(&str, usize)] = &[
+        ("dispatch", go),
+        ("done", fetching),
+        ("dispatch", go),
+        ("dispatch", go),
+        ("done", fetching),
+        ("dispatch", finish),
+        ("done", busy),
+        ("done", busy),
+    ];
+    let mut sm = dsl::parse(model).unwrap();
+    let mut machine = frozen.machine::<0>();
+    let mut ctx1 = Recorder::new(0);
+    let mut ctx2 = Recorder::new(0);
+    ctx1.background = &["blink", "fetch"];
+    ctx2.background = &["blink", "fetch"];
+    sm.start_with(&mut ctx1).unwrap();
+    machine.start(&mut ctx2).unwrap();
+    for (i, (input, id)) in inputs.iter().enumerate() {
+        let (r1, r2) = match *input {
+            "dispatch" => (
+                sm.dispatch_with(*id, &mut ctx1),
+                machine.dispatch(*id as u16, &mut ctx2),
+            ),
+            _ => (
+                sm.activity_done_with(*id, &mut ctx1),
+                machine.activity_done(*id as u16, &mut ctx2),
+            ),
+        };
+        let at = format!("input {} {} {}", i, input, id);
+        assert_eq!(outcome(r1), outcome(r2), "{}", at);
+        assert_eq!(ctx1.log, ctx2.log, "{}", at);
+        let active: Vec<usize> = machine
+            .active_states()
+            .iter()
+            .map(|id| *id as usize)
+            .collect();
+        assert_eq!(sm.active_states(), active, "{}", at);
+        let running: Vec<usize> = machine
+            .running_activities()
+            .iter()
+            .map(|id| *id as usize)
+            .collect();
+        assert_eq!(sm.running_activities(), &running[..], "{}", at);
+    }
+    // busy completed once its region was final and blink was over
+    assert!(ctx1.log.iter().any(|s| s == "done"));
+    assert!(ctx1.log.iter().any(|s| s.starts_with("abort")));
+}
+
+#[test]
+fn test_freeze_capacities() {
+    let sm = dsl::parse(include_str!("codegen/pump.sm")).unwrap();
+    let exceeded = |result: Result<(), StateMachineError>| match result {
+        Err(StateMachineError::CapacityExceeded(what)) => what,
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(exceeded(sm.freeze::<24, 5, 28, 8>().map(|_| ())), "states");
+    assert_eq!(exceeded(sm.freeze::<25, 4, 28, 8>().map(|_| ())), "regions");
+    assert_eq!(
+        exceeded(sm.freeze::<25, 5, 27, 8>().map(|_| ())),
+        "transitions"
+    );
+    assert_eq!(
+        exceeded(sm.freeze::<25, 5, 28, 7>().map(|_| ())),
+        "event types"
+    );
+
+    // the queue of deferred events is bounded when running
+    let frozen = sm.freeze::<25, 5, 28, 8>().unwrap();
+    let mut machine = frozen.machine::<1>();
+    let drain = frozen.event("drain").unwrap();
+    machine.start(&mut ()).unwrap();
+    assert!(!machine.dispatch(drain, &mut ()).unwrap());
+    assert!(matches!(
+        machine.dispatch(drain, &mut ()),
+        Err(StateMachineError::CapacityExceeded("deferred events"))
+    ));
+
+    // generated tables are checked against the capacities on start
+    type Small<const S: usize, const R: usize, const T: usize> =
+        rust_uml_sm::compiled::Machine<'static, rust_uml_sm::compiled::Tables, S, R, T, 8>;
+    let mut small = Small::<25, 4, 28>::new(&pump::TABLES);
+    assert!(!small.is_active(9));
+    assert_eq!(exceeded(small.start(&mut ())), "regions");
+    assert!(!small.is_running());
+    let mut small = Small::<24, 5, 28>::new(&pump::TABLES);
+    assert_eq!(exceeded(small.start(&mut ())), "states");
+    let mut small = Small::<25, 5, 27>::new(&pump::TABLES);
+    assert_eq!(exceeded(small.start(&mut ())), "transitions");
+}
+
+#[test]
+fn test_unsupported_events() {
+    // the pump with a timeout, then a change event, while running low
+    for (trigger, path) in &[
+        (
+            "after(10s)",
+            "pump::region_1::running::region_1::low::after(10s)",
+        ),
+        ("when(overheated)", "pump::when(overheated)"),
+    ] {
+        let model = include_str!("codegen/pump.sm").replacen(
+            "state low {",
+            &format!("state low {{\n            on {} -> high;", trigger),
+            1,
+        );
+        let sm = dsl::parse(&model).unwrap();
+        let event = sm.find(path);
+        assert!(event.is_some());
+        assert!(matches!(
+            sm.freeze::<25, 5, 29, 9>(),
+            Err(StateMachineError::Unsupported(dbid)) if Some(dbid) == event
+        ));
+        assert!(matches!(
+            codegen::static_module(&model, 64),
+            Err(StateMachineError::Unsupported(dbid)) if Some(dbid) == event
+        ));
+    }
+}
+
+#[test]
+fn test_frozen_functions() {
+    use rust_uml_sm::{Behavior, OptBehavior, OptGuard, StateMachineDef};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static ENTERED: AtomicUsize = AtomicUsize::new(0);
+    fn entered() {
+        ENTERED.fetch_add(1, Ordering::SeqCst);
+    }
+    let mut sm = StateMachineDef::new("sm1");
+    let s1 = sm.add_state("s1").unwrap();
+    let s2 = sm.add_state("s2").unwrap();
+    let r = sm.get_only_region(0).unwrap();
+    sm.initial_transition(r, s1, OptBehavior::None).unwrap();
+    sm.set_entry(s2, Behavior::new(entered)).unwrap();
+    let ev = sm.add_event_type("ev1").unwrap();
+    sm.add_transition("t1", Some(ev), s1, s2, OptBehavior::None, OptGuard::None)
+        .unwrap();
+
+    let frozen: Frozen<3, 1, 2, 1> = sm.freeze().unwrap();
+    let mut machine = frozen.machine::<0>();
+    machine.start(&mut ()).unwrap();
+    assert!(machine.is_active(s1 as u16));
+    assert!(machine
+        .dispatch(frozen.event("ev1").unwrap(), &mut ())
+        .unwrap());
+    assert!(machine.is_active(s2 as u16));
+    assert_eq!(ENTERED.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_compiled_static() {
+    use pump::Event;
+    static TABLES: &rust_uml_sm::compiled::Tables = &pump::TABLES;
+    let mut machine = pump::machine();
+    let mut ctx = ();
+    assert!(matches!(
+        machine.dispatch(Event::Start.into(), &mut ctx),
+        Err(StateMachineError::NotRunning)
+    ));
+    machine.start(&mut ctx).unwrap();
+    let name = |m: &pump::Machine| {
+        m.active_states()
+            .iter()
+            .map(|id| TABLES.elements[*id as usize].name)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(name(&machine), vec!["off"]);
+    // guards are true with the unit context
+    assert!(machine.dispatch(Event::Start.into(), &mut ctx).unwrap());
+    assert_eq!(name(&machine), vec!["low"]);
+    assert!(machine.dispatch(Event::Drain.into(), &mut ctx).unwrap());
+    assert_eq!(name(&machine), vec!["opening", "slowing"]);
+    assert!(machine.dispatch(Event::Tick.into(), &mut ctx).unwrap());
+    assert_eq!(name(&machine), vec!["low"]);
+    assert!(machine.dispatch(Event::Fault.into(), &mut ctx).unwrap());
+    assert!(machine.dispatch(Event::Kill.into(), &mut ctx).unwrap());
+    assert_eq!(machine.status(), Status::Terminated);
+    assert!(matches!(
+        machine.dispatch(0, &mut ctx),
+        Err(StateMachineError::InvalidEventType(0))
+    ));
+}
@@ -1,4 +1,5 @@
 extern crate rust_uml_sm;
+use rust_uml_sm::behaviors;
 use rust_uml_sm::Effect;
 use rust_uml_sm::Entry;
 use rust_uml_sm::Exit;
@@ -110,7 +111,7 @@ fn test_query_regions() {
     let _ = sm.add_substate("s4", s3).unwrap();
     println!("{:#?}", sm);
     assert_eq!(sm.sm_regions(), vec![r1, r2]);
-    assert_eq!(sm.regions(s1).unwrap(), vec![]);
+    assert_eq!(sm.regions(s1).unwrap(), Vec::<usize>::new());
     assert_eq!(sm.regions(s2).unwrap(), vec![5]);
     assert_eq!(sm.regions(s3).unwrap(), vec![7]);
 }
@@ -329,40 +330,62 @@ enum Emotion {
     Angry,
 }
 #[derive(StateMachine)]
+#[statemachine(constructor = new)]
+#[state(s1, initial)]
+#[state(a, parent = s1, initial)]
+#[state(b, parent = s1)]
+#[state(c, parent = s1)]
+#[transition(a -> b, on = event1, guard = is_happy)]
+#[transition(b -> c, on = event1, effect = sulk)]
 struct Foo {
     emotion: Emotion,
+    entered: Vec<&'static str>,
 }
 impl Foo {
     fn new() -> Foo {
         Foo {
             emotion: Emotion::Happy,
+            entered: Vec::new(),
         }
     }
 }
+#[behaviors]
+impl Foo {
+    #[guard]
+    fn is_happy(&self) -> bool {
+        self.emotion == Emotion::Happy
+    }
+    #[behavior]
+    fn sulk(&mut self) {
+        self.emotion = match self.emotion {
+            Emotion::Happy => Emotion::Sad,
+            _ => Emotion::Angry,
+        };
+    }
+    #[on_entry(a)]
+    fn entered_a(&mut self) {
+        self.entered.push("a");
+    }
+    #[on_entry(b, c)]
+    fn entered_b_or_c(&mut self) {
+        self.entered.push("b|c");
+    }
+}
 
 #[test]
 fn test_macro() {
-    let foo = Foo::new_statemachine();
+    let mut foo = Foo::new_statemachine();
     assert_eq!(foo.state.emotion, Emotion::Happy);
-    /*
-    let s1 = foo.define.state("s1", None);
-    let s1_a = foo.define.state("a", s1);
-    let s1_b = foo.define.state("b", s1);
-    let s1_c = foo.define.state("c", s1);
-    let s1_c = foo.start();
-    let s1_c = foo.send("event1");
-    let s1_c = foo.stop();
-
-    let ev1 = sm.add_event_type("ev1");
-    let guard_false = Guard::some(false_guard);
-    let guard_true = Guard::some(true_guard);
-    let trans_effect = Effect::some(print_transition);
-
-    let t1 = sm
-        .add_transition("t1", ev1, s1, s2, trans_effect, guard_false)
-        .unwrap();
-    let t2 = sm
-        .add_transition("t2", ev1, s1, s2, trans_effect, guard_true)
-        .unwrap();
-    */
+    foo.start().unwrap();
+    assert_eq!(foo.active_states(), vec!["Foo::region_1::s1::region_1::a"]);
+    assert!(foo.send("event1").unwrap());
+    assert!(foo.is_in("b") && foo.is_in("s1"));
+    assert!(foo.send("event1").unwrap());
+    assert!(foo.is_in("c"));
+    assert_eq!(foo.state.emotion, Emotion::Sad);
+    assert!(!foo.send("event1").unwrap());
+    assert!(foo.send("event2").is_err());
+    foo.stop().unwrap();
+    assert!(foo.active_states().is_empty());
+    assert_eq!(foo.state.entered, vec!["a", "b|c", "b|c"]);
 }
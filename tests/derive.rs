@@ -0,0 +1,114 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::behaviors;
+use rust_uml_sm::StateMachine;
+
+#[derive(StateMachine, Debug, PartialEq)]
+enum Door {
+    #[state(initial)]
+    #[transition(-> Opened, on = open, guard = unlocked)]
+    #[transition(on = knock, effect = answer)]
+    Closed,
+    #[state(entry = light_on, exit = light_off, defer = lock)]
+    #[transition(-> Closed, on = close)]
+    Opened,
+    #[state(final)]
+    Gone,
+}
+
+#[derive(StateMachine)]
+#[statemachine(states = Door)]
+struct House {
+    locked: bool,
+    log: Vec<String>,
+}
+
+#[behaviors]
+impl House {
+    #[guard(unlocked)]
+    fn is_unlocked(&self) -> bool {
+        !self.locked
+    }
+    #[behavior]
+    fn answer(&mut self) {
+        self.log.push("answer".to_string());
+    }
+    #[behavior]
+    fn light_on(&mut self) {
+        self.log.push("on".to_string());
+    }
+    #[behavior]
+    fn light_off(&mut self) {
+        self.log.push("off".to_string());
+    }
+    #[on_exit(Opened)]
+    fn left(&mut self) {
+        self.log.push("left".to_string());
+    }
+}
+
+#[test]
+fn test_derive_enum() {
+    let sm = Door::definition();
+    assert_eq!(Door::ALL.len(), 3);
+    assert_eq!(Door::Opened.name(), "Opened");
+    assert_eq!(Door::Opened.fullname(), "Door::region_1::Opened");
+    assert_eq!(
+        Door::from_fullname("Door::region_1::Closed"),
+        Some(Door::Closed)
+    );
+    let r = sm.get_only_region(0).unwrap();
+    let names: Vec<_> = sm
+        .states(r)
+        .unwrap()
+        .iter()
+        .map(|s| sm.fullname(*s).unwrap().clone())
+        .collect();
+    assert_eq!(
+        names,
+        vec![Door::Closed.fullname(), Door::Opened.fullname()]
+    );
+    assert!(sm.event_type("lock").is_some());
+}
+
+#[test]
+fn test_derive_states_from_enum() {
+    let mut house = House {
+        locked: true,
+        log: Vec::new(),
+    }
+    .into_statemachine();
+    house.start().unwrap();
+    assert_eq!(house.active_states(), vec![Door::Closed.fullname()]);
+    assert!(!house.send("open").unwrap());
+    assert!(house.send("knock").unwrap());
+    house.state.locked = false;
+    assert!(house.send("open").unwrap());
+    assert!(house.is_in("Opened"));
+    // deferred while opened, then discarded once closed
+    assert!(!house.send("lock").unwrap());
+    assert_eq!(house.define.deferred_events().len(), 1);
+    assert!(house.send("close").unwrap());
+    assert!(house.define.deferred_events().is_empty());
+    assert_eq!(house.state.log, vec!["answer", "on", "left", "off"]);
+}
+
+#[derive(StateMachine)]
+#[state(idle, initial)]
+#[state(busy)]
+#[transition(idle -> busy, on = go, guard = is_ready)]
+struct Motor {}
+
+#[behaviors]
+impl Motor {
+    #[behavior]
+    fn unused(&mut self) {}
+}
+
+#[test]
+fn test_derive_unbound_guard() {
+    // is_ready has no method, it is true like any unknown guard
+    let mut motor = Motor {}.into_statemachine();
+    motor.start().unwrap();
+    assert!(motor.send("go").unwrap());
+    assert!(motor.is_in("busy"));
+}
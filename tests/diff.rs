@@ -0,0 +1,96 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::diff::{diff, Change};
+use rust_uml_sm::dsl;
+use std::process::Command;
+
+const V1: &str = include_str!("diff/door_v1.sm");
+const V2: &str = include_str!("diff/door_v2.sm");
+
+#[test]
+fn test_diff() {
+    let d = diff(&dsl::parse(V1).unwrap(), &dsl::parse(V2).unwrap());
+    assert!(d.changes.contains(&Change::Moved {
+        from: "door::region_1::opened::region_1::wide".to_string(),
+        to: "door::region_1::wide".to_string(),
+        kind: "state",
+    }));
+    assert!(d.changes.contains(&Change::Added {
+        fullname: "door::region_1::locked".to_string(),
+        kind: "state",
+    }));
+    assert!(d.changes.contains(&Change::Changed {
+        fullname: "door::region_1::t2".to_string(),
+        property: "guard",
+        old: "".to_string(),
+        new: "unlocked".to_string(),
+    }));
+    assert_eq!(
+        d.to_string(),
+        "> door::region_1::opened::region_1::wide -> door::region_1::wide (state)\n\
+         - door::region_1::opened::region_1::t4 (transition)\n\
+         + door::region_1::locked (state)\n\
+         + door::region_1::t3 (transition)\n\
+         + door::region_1::opened::region_1::t5 (transition)\n\
+         + door::region_1::t7 (transition)\n\
+         ~ door::region_1::closed defer: push -> \n\
+         ~ door::region_1::opened entry: light_on -> lamp_on\n\
+         ~ door::region_1::t2 guard:  -> unlocked\n\
+         ~ door::region_1::t2 effect: creak -> \n"
+    );
+}
+
+#[test]
+fn test_diff_same() {
+    let v1 = dsl::parse(V1).unwrap();
+    assert!(diff(&v1, &dsl::parse(V1).unwrap()).is_empty());
+    // only the name of the state machine differs
+    let renamed = dsl::parse(&V1.replace("statemachine door", "statemachine gate")).unwrap();
+    assert_eq!(
+        diff(&v1, &renamed).changes,
+        vec![Change::Changed {
+            fullname: "door".to_string(),
+            property: "name",
+            old: "door".to_string(),
+            new: "gate".to_string(),
+        }]
+    );
+    // transitions only renumbered are the same
+    let reordered = V1.replace("state wide;", "state wide { on push -> ajar; }");
+    let d = diff(&v1, &dsl::parse(&reordered).unwrap());
+    assert_eq!(d.changes.len(), 1, "{}", d);
+}
+
+#[test]
+fn test_diff_kind() {
+    let choice = V1.replace("state wide;", "choice wide { -> ajar; }");
+    let d = diff(&dsl::parse(V1).unwrap(), &dsl::parse(&choice).unwrap());
+    assert_eq!(
+        d.to_string(),
+        "+ door::region_1::opened::region_1::t5 (transition)\n\
+         ~ door::region_1::opened::region_1::wide kind: state -> choice\n"
+    );
+}
+
+#[test]
+fn test_sm_diff() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/diff/");
+    let sm = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_sm"))
+            .arg("diff")
+            .args(args.iter().map(|a| format!("{}{}", dir, a)))
+            .output()
+            .unwrap()
+    };
+    let same = sm(&["door_v1.sm", "door_v1.sm"]);
+    assert_eq!(same.status.code(), Some(0));
+    assert!(same.stdout.is_empty());
+
+    let changed = sm(&["door_v1.sm", "door_v2.sm"]);
+    assert_eq!(changed.status.code(), Some(1));
+    let expected = diff(&dsl::parse(V1).unwrap(), &dsl::parse(V2).unwrap()).to_string();
+    assert_eq!(String::from_utf8_lossy(&changed.stdout), expected);
+
+    let missing = sm(&["door_v1.sm", "missing.sm"]);
+    assert_eq!(missing.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("missing.sm"));
+}
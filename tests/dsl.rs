@@ -0,0 +1,201 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::dsl;
+use rust_uml_sm::PseudostateKind;
+use rust_uml_sm::StateMachineError;
+use rust_uml_sm::VertexType;
+
+const DOOR: &str = r#"
+statemachine door {
+    event open, close;
+    initial -> closed;
+    state closed {
+        on open [unlocked] -> opened / creak;
+        on lock / click;
+    }
+    state opened {
+        entry light_on;
+        exit light_off;
+        on close -> closed;
+    }
+}
+"#;
+
+fn parse_error(src: &str) -> dsl::ParseError {
+    match dsl::parse(src) {
+        Err(StateMachineError::ParseError(e)) => e,
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("expected a parse error"),
+    }
+}
+
+#[test]
+fn test_parse_simple() {
+    let sm = dsl::parse(DOOR).unwrap();
+    assert_eq!(sm.name(0).unwrap(), "door");
+    let r1 = sm.get_only_region(0).unwrap();
+    let states = sm.states(r1).unwrap();
+    let names: Vec<_> = states.iter().map(|s| sm.name(*s).unwrap()).collect();
+    assert_eq!(names, vec!["closed", "opened"]);
+    assert!(sm.event_type("open").is_some());
+    assert!(sm.event_type("lock").is_some());
+    // closed is entered by the initial transition and from opened
+    assert_eq!(sm.transitions(states[0]).unwrap().len(), 3);
+    assert_eq!(sm.transitions(states[1]).unwrap().len(), 1);
+
+    // the names read are leaked once, not once per definition
+    let again = dsl::parse(DOOR).unwrap();
+    assert!(std::ptr::eq(
+        sm.name(states[0]).unwrap(),
+        again.name(states[0]).unwrap()
+    ));
+}
+
+#[test]
+fn test_parse_regions_and_paths() {
+    let sm = dsl::parse(
+        r#"
+        statemachine sm1 {
+            region r1 {
+                initial -> s1;
+                state s1 { on ev1 -> s2::r3::s4; }
+                state s2 {
+                    region r3 { initial -> s4; state s4; }
+                    region r4 { initial -> s5; state s5 { on ev2 -> sm1::r2::s91; } }
+                }
+            }
+            region r2 {
+                initial -> s91;
+                state s91 { state s92; }
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(sm.sm_regions().len(), 2);
+    let r1 = sm.sm_regions()[0];
+    assert_eq!(sm.fullname(r1).unwrap(), "sm1::r1");
+    let s2 = sm.states(r1).unwrap()[1];
+    assert_eq!(sm.is_orthogonal(s2).unwrap(), true);
+    let r3 = sm.regions(s2).unwrap()[0];
+    let s4 = sm.states(r3).unwrap()[0];
+    assert_eq!(sm.fullname(s4).unwrap(), "sm1::r1::s2::r3::s4");
+    // initial transition and the transition from s1
+    assert_eq!(sm.transitions(s4).unwrap().len(), 2);
+    let s91 = sm.states(sm.sm_regions()[1]).unwrap()[0];
+    assert_eq!(sm.transitions(s91).unwrap().len(), 2);
+    let s92 = sm.states(sm.get_only_region(s91).unwrap()).unwrap()[0];
+    assert_eq!(sm.fullname(s92).unwrap(), "sm1::r2::s91::region_1::s92");
+}
+
+#[test]
+fn test_parse_pseudostates() {
+    let sm = dsl::parse(
+        r#"
+        statemachine sm1 {
+            initial -> idle;
+            state idle { on go -> c1; }
+            choice c1 {
+                [small] -> busy;
+                [else] -> idle / complain;
+            }
+            state busy {
+                history h;
+                deephistory dh;
+                initial -> a;
+                state a { on done -> end; }
+            }
+            final end;
+            terminate kill;
+        }
+        "#,
+    )
+    .unwrap();
+    let r = sm.get_only_region(0).unwrap();
+    let vertices = sm.subvertices(r).unwrap();
+    let types: Vec<_> = vertices
+        .iter()
+        .map(|v| sm.vertex_type(*v).unwrap())
+        .collect();
+    assert_eq!(
+        types,
+        vec![
+            VertexType::InitialState,
+            VertexType::State,
+            VertexType::Pseudostate(PseudostateKind::Choice),
+            VertexType::State,
+            VertexType::FinalState,
+            VertexType::Pseudostate(PseudostateKind::Terminate),
+        ]
+    );
+    let busy = vertices[3];
+    let inner = sm.subvertices(sm.get_only_region(busy).unwrap()).unwrap();
+    assert_eq!(
+        sm.vertex_type(inner[0]).unwrap(),
+        VertexType::Pseudostate(PseudostateKind::ShallowHistory)
+    );
+    assert_eq!(
+        sm.vertex_type(inner[1]).unwrap(),
+        VertexType::Pseudostate(PseudostateKind::DeepHistory)
+    );
+    // the final state is reached from busy::a, idle from initial and c1
+    assert_eq!(sm.transitions(vertices[4]).unwrap().len(), 1);
+    assert_eq!(sm.transitions(sm.states(r).unwrap()[0]).unwrap().len(), 2);
+}
+
+#[test]
+fn test_parse_unknown_target() {
+    let src = "statemachine sm1 {\n    state s1 { on ev1 -> s3; }\n    state s2;\n}\n";
+    let e = parse_error(src);
+    assert_eq!(e.message, "unknown state `s3`");
+    assert_eq!((e.span.line, e.span.column), (2, 26));
+    assert_eq!(e.to_string(), "2:26: unknown state `s3`");
+    assert!(e.render(src).contains("                         ^^"));
+}
+
+#[test]
+fn test_parse_errors() {
+    let e = parse_error("statemachine sm1 { state s1; state s1; }");
+    assert_eq!(e.message, "duplicate name `s1`");
+    assert_eq!(e.span.column, 36);
+
+    let e = parse_error("statemachine sm1 { state s1 { on ev1 -> s1 } }");
+    assert_eq!(e.message, "expected `;`, found `}`");
+
+    let e = parse_error("statemachine sm1 { state s1 { entry; } }");
+    assert_eq!(e.message, "expected a behavior name, found `;`");
+
+    let e = parse_error("statemachine sm1 { on ev1 -> s1; state s1; }");
+    assert_eq!(
+        e.message,
+        "a transition must be declared in its source state or pseudostate"
+    );
+
+    let e = parse_error("statemachine sm1 {\n  state s1 { region a { state x; } region b { state y; } state z; }\n}");
+    assert_eq!(
+        e.message,
+        "`s1` has several regions, declare `z` inside one of them"
+    );
+    assert_eq!(e.span.line, 2);
+
+    let e = parse_error("statemachine sm1 { state s1 $ }");
+    assert_eq!(e.message, "unexpected character `$`");
+}
+
+/// Writing a definition and reading it back gives the same definition.
+#[test]
+fn test_write() {
+    for model in [
+        DOOR,
+        include_str!("codegen/pump.sm"),
+        include_str!("codegen/sm1.sm"),
+        include_str!("diff/door_v1.sm"),
+        include_str!("diff/door_v2.sm"),
+    ] {
+        let def = dsl::parse(model).unwrap();
+        let text = dsl::write(&def);
+        let again = dsl::parse(&text).unwrap_or_else(|e| panic!("{}\n{}", text, e));
+        let d = rust_uml_sm::diff::diff(&def, &again);
+        assert!(d.is_empty(), "{}\n{}", text, d);
+        assert_eq!(dsl::write(&again), text);
+    }
+}
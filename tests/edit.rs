@@ -0,0 +1,145 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::diff::diff;
+use rust_uml_sm::dsl;
+use rust_uml_sm::StateMachineDef;
+use rust_uml_sm::StateMachineError;
+
+const DOOR: &str = include_str!("diff/door_v1.sm");
+
+fn named(sm: &StateMachineDef, fullname: &str) -> usize {
+    (0..)
+        .map_while(|d| sm.fullname(d).ok().map(|name| (d, name)))
+        .find(|(_, name)| *name == fullname)
+        .map(|(d, _)| d)
+        .unwrap_or_else(|| panic!("no {}", fullname))
+}
+
+/// Check the links of the definition agree with each other.
+fn check(sm: &StateMachineDef) {
+    let mut d = 1;
+    while let Ok(fullname) = sm.fullname(d) {
+        let parent = sm.parent(d).unwrap();
+        let expected = format!("{}::{}", sm.fullname(parent).unwrap(), sm.name(d).unwrap());
+        assert_eq!(*fullname, expected);
+        d += 1;
+    }
+    for t in sm.transitions(0).unwrap() {
+        assert!(sm.fullname(t).is_ok());
+    }
+}
+
+#[test]
+fn test_rename() {
+    let mut sm = dsl::parse(DOOR).unwrap();
+    let opened = named(&sm, "door::region_1::opened");
+    sm.rename_element(opened, "open").unwrap();
+    check(&sm);
+    named(&sm, "door::region_1::open::region_1::ajar");
+    let closed = named(&sm, "door::region_1::closed");
+    assert!(matches!(
+        sm.rename_element(closed, "open"),
+        Err(StateMachineError::Duplicate("open"))
+    ));
+    sm.rename_element(0, "gate").unwrap();
+    named(&sm, "gate::region_1::open::region_1::wide");
+}
+
+#[test]
+fn test_move() {
+    let mut sm = dsl::parse(DOOR).unwrap();
+    let wide = named(&sm, "door::region_1::opened::region_1::wide");
+    let top = named(&sm, "door::region_1");
+    let inner = named(&sm, "door::region_1::opened::region_1");
+    let opened = named(&sm, "door::region_1::opened");
+    assert!(matches!(
+        sm.move_vertex(opened, inner),
+        Err(StateMachineError::InvalidRegion(r)) if r == inner
+    ));
+    let push = sm
+        .outgoing(named(&sm, "door::region_1::opened::region_1::ajar"))
+        .unwrap()[0];
+    sm.move_vertex(wide, top).unwrap();
+    check(&sm);
+    // push, incoming, stays with its source ajar
+    assert_eq!(sm.parent(push).unwrap(), inner);
+    let expected = DOOR
+        .replace("state wide;\n", "")
+        .replace("state closed", "state wide;\n    state closed");
+    assert!(diff(&sm, &dsl::parse(&expected).unwrap()).is_empty());
+}
+
+#[test]
+fn test_retarget() {
+    let mut sm = dsl::parse(DOOR).unwrap();
+    let push = sm
+        .transitions(named(&sm, "door::region_1::opened::region_1::wide"))
+        .unwrap()[0];
+    let closed = named(&sm, "door::region_1::closed");
+    sm.retarget_transition(push, closed).unwrap();
+    check(&sm);
+    assert_eq!(sm.transitions(closed).unwrap().len(), 3);
+    let expected = DOOR.replace("on push -> wide", "on push -> closed");
+    assert!(diff(&sm, &dsl::parse(&expected).unwrap()).is_empty());
+
+    // an internal transition only ends in its source
+    let mut sm =
+        dsl::parse(&DOOR.replace("state wide;", "state wide { on lock / beep; }")).unwrap();
+    let wide = named(&sm, "door::region_1::opened::region_1::wide");
+    let beep = sm.outgoing(wide).unwrap()[0];
+    let closed = named(&sm, "door::region_1::closed");
+    assert!(matches!(
+        sm.retarget_transition(beep, closed),
+        Err(StateMachineError::InvalidTransitionKind(t)) if t == beep
+    ));
+    sm.retarget_transition(beep, wide).unwrap();
+    assert_eq!(sm.target(beep).unwrap(), wide);
+}
+
+#[test]
+fn test_remove() {
+    let mut sm = dsl::parse(DOOR).unwrap();
+    sm.remove_element(named(&sm, "door::region_1::opened::region_1::wide"))
+        .unwrap();
+    check(&sm);
+    let expected = DOOR
+        .replace("state wide;\n", "")
+        .replace(" { on push -> wide; }", ";");
+    assert!(diff(&sm, &dsl::parse(&expected).unwrap()).is_empty());
+
+    sm.remove_element(sm.event_type("push").unwrap()).unwrap();
+    check(&sm);
+    let expected = expected
+        .replace("open, close, push,", "open, close,")
+        .replace(" defer push;", "");
+    assert!(diff(&sm, &dsl::parse(&expected).unwrap()).is_empty());
+
+    // the edited definition runs
+    sm.start().unwrap();
+    sm.dispatch(sm.event_type("open").unwrap()).unwrap();
+    assert_eq!(
+        sm.active_states(),
+        vec![named(&sm, "door::region_1::opened::region_1::ajar")]
+    );
+    assert!(matches!(
+        sm.remove_element(named(&sm, "door::region_1::closed")),
+        Err(StateMachineError::AlreadyRunning)
+    ));
+}
+
+#[test]
+fn test_remove_composite() {
+    let mut sm = dsl::parse(DOOR).unwrap();
+    sm.remove_element(named(&sm, "door::region_1::opened"))
+        .unwrap();
+    check(&sm);
+    let expected = "statemachine door {
+        event open, close, push, lock;
+        initial -> closed;
+        state closed { defer push; }
+    }";
+    assert!(diff(&sm, &dsl::parse(expected).unwrap()).is_empty());
+    assert!(matches!(
+        sm.remove_element(0),
+        Err(StateMachineError::InvalidDbId(0))
+    ));
+}
@@ -0,0 +1,86 @@
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_header_up_to_date() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(root.join("cbindgen.toml")).unwrap();
+    let mut header = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(root.join("src/ffi.rs"))
+        .generate()
+        .unwrap()
+        .write(&mut header);
+    assert_eq!(
+        String::from_utf8(header).unwrap(),
+        include_str!("../include/rust_uml_sm.h"),
+        "regenerate include/rust_uml_sm.h with `cbindgen --config cbindgen.toml src/ffi.rs -o include/rust_uml_sm.h`"
+    );
+}
+
+/// Build the crate as a static library with the `ffi` feature, and
+/// tests/ffi/harness.c with it. Skipped if there is no C compiler.
+#[test]
+fn test_c_harness() {
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    if Command::new(&cc).arg("--version").output().is_err() {
+        eprintln!("skipped: no C compiler {}", cc);
+        return;
+    }
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target = root.join("target/ffi");
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = Command::new(cargo)
+        .args([
+            "rustc",
+            "--lib",
+            "--features",
+            "ffi",
+            "--crate-type",
+            "staticlib",
+        ])
+        .arg("--manifest-path")
+        .arg(root.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target)
+        .output()
+        .expect("cargo");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let harness = target.join("harness");
+    let output = Command::new(&cc)
+        .args(["-Wall", "-Werror", "-std=c99"])
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(root.join("tests/ffi/harness.c"))
+        .arg(target.join("debug/librust_uml_sm.a"))
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&harness)
+        .output()
+        .expect("cc");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = Command::new(&harness).output().expect("harness");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert_eq!(
+        stdout,
+        "error: 1:14: expected a state machine name, found `{`
+error: Unknown event push
+error: State machine is not running
+[active_state] door::region_1::closed
+[active_state] door::region_1::opened
+creaks 1, lights 1
+creaks 2, lights 1
+"
+    );
+}
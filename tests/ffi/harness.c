@@ -0,0 +1,92 @@
+/* Uses the C API, built and run by tests/ffi.rs. Prints what happens,
+ * which the test compares, and exits with 1 on an unexpected result. */
+#include <stdio.h>
+#include <string.h>
+
+#include "rust_uml_sm.h"
+
+#define CHECK(cond)                                                   \
+    if (!(cond)) {                                                    \
+        printf("failed: %s, line %d: %s\n", #cond, __LINE__,          \
+               sm_last_error());                                      \
+        return 1;                                                     \
+    }
+
+static const char *MODEL =
+    "statemachine door {\n"
+    "    event open, close, lock;\n"
+    "    initial -> closed;\n"
+    "    state closed { on open [unlocked] -> opened / creak; }\n"
+    "    state opened { entry light_on; on close -> closed; }\n"
+    "}\n";
+
+struct door {
+    int creaks;
+    int lights;
+    bool locked;
+};
+
+static void creak(void *user_data) { ((struct door *)user_data)->creaks++; }
+
+static void light_on(void *user_data) { ((struct door *)user_data)->lights++; }
+
+static bool unlocked(void *user_data) { return !((struct door *)user_data)->locked; }
+
+static void print_active(const SmDefinition *def, const SmInstance *sm) {
+    size_t ids[4];
+    size_t n = sm_instance_active_states(sm, ids, 4);
+    for (size_t i = 0; i < n && i < 4; i++) {
+        printf("[active_state] %s\n", sm_definition_fullname(def, ids[i]));
+    }
+}
+
+int main(void) {
+    CHECK(sm_definition_load("statemachine {") == NULL);
+    printf("error: %s\n", sm_last_error());
+
+    SmDefinition *def = sm_definition_load(MODEL);
+    CHECK(def != NULL);
+    ptrdiff_t open = sm_definition_event(def, "open");
+    ptrdiff_t close = sm_definition_event(def, "close");
+    ptrdiff_t opened = sm_definition_find(def, "opened");
+    CHECK(open >= 0 && close >= 0 && opened >= 0);
+    CHECK(sm_definition_event(def, "push") == -1);
+    printf("error: %s\n", sm_last_error());
+    CHECK(strcmp(sm_definition_name(def, (size_t)open), "open") == 0);
+
+    struct door door = {0, 0, true};
+    SmInstance *sm = sm_instance_new(def);
+    /* instances do not refer to their definition */
+    SmInstance *other = sm_instance_new(def);
+    CHECK(sm_instance_on_behavior(sm, "creak", creak, &door) == 0);
+    CHECK(sm_instance_on_behavior(sm, "light_on", light_on, &door) == 0);
+    CHECK(sm_instance_on_guard(sm, "unlocked", unlocked, &door) == 0);
+    CHECK(sm_instance_status(sm) == SM_STATUS_IDLE);
+    CHECK(sm_instance_dispatch(sm, (size_t)open) == -1);
+    printf("error: %s\n", sm_last_error());
+
+    CHECK(sm_instance_start(sm) == 0);
+    CHECK(sm_instance_status(sm) == SM_STATUS_RUNNING);
+    print_active(def, sm);
+    CHECK(sm_instance_dispatch(sm, (size_t)open) == 0);
+    door.locked = false;
+    CHECK(sm_instance_dispatch(sm, (size_t)open) == 1);
+    CHECK(sm_instance_is_active(sm, (size_t)opened));
+    print_active(def, sm);
+    printf("creaks %d, lights %d\n", door.creaks, door.lights);
+
+    /* without callbacks behaviors do nothing and guards are true */
+    CHECK(sm_instance_start(other) == 0);
+    CHECK(sm_instance_dispatch(other, (size_t)open) == 1);
+    CHECK(sm_instance_on_behavior(sm, "light_on", NULL, NULL) == 0);
+    CHECK(sm_instance_dispatch(sm, (size_t)close) == 1);
+    CHECK(sm_instance_dispatch(sm, (size_t)open) == 1);
+    printf("creaks %d, lights %d\n", door.creaks, door.lights);
+
+    CHECK(sm_instance_stop(sm) == 0);
+    CHECK(sm_instance_active_states(sm, NULL, 0) == 0);
+    sm_instance_free(sm);
+    sm_instance_free(other);
+    sm_definition_free(def);
+    return 0;
+}
@@ -0,0 +1,125 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::dsl;
+use rust_uml_sm::layout::{Layout, Rect};
+use rust_uml_sm::{StateMachineDef, TransitionKind};
+
+const MODELS: [&str; 4] = [
+    include_str!("diff/door_v1.sm"),
+    include_str!("diff/door_v2.sm"),
+    include_str!("codegen/pump.sm"),
+    include_str!("codegen/sm1.sm"),
+];
+
+/// The vertices of a region lie within it and do not overlap, the
+/// regions lie within their state.
+fn check(def: &StateMachineDef, layout: &Layout, owner: usize) {
+    let outer = layout.rect(owner).unwrap();
+    let regions = def.regions(owner).unwrap_or_default();
+    for r in &regions {
+        let region = layout.rect(*r).unwrap();
+        assert!(outer.contains(&region), "region {} outside {}", r, owner);
+        let vertices = def.subvertices(*r).unwrap();
+        for (i, v) in vertices.iter().enumerate() {
+            let rect = layout.rect(*v).unwrap();
+            assert!(region.contains(&rect), "{} outside region {}", v, r);
+            for w in &vertices[i + 1..] {
+                assert!(
+                    !rect.overlaps(&layout.rect(*w).unwrap()),
+                    "{} over {}",
+                    v,
+                    w
+                );
+            }
+            check(def, layout, *v);
+        }
+    }
+    for (i, r) in regions.iter().enumerate() {
+        for s in &regions[i + 1..] {
+            let (a, b) = (layout.rect(*r).unwrap(), layout.rect(*s).unwrap());
+            assert!(!a.overlaps(&b), "region {} over {}", r, s);
+        }
+    }
+}
+
+#[test]
+fn test_layout() {
+    for model in MODELS {
+        let def = dsl::parse(model).unwrap();
+        let layout = Layout::new(&def);
+        let machine = layout.rect(0).unwrap();
+        assert_eq!((machine.x, machine.y), (0, 0));
+        assert_eq!(
+            (machine.width, machine.height),
+            (layout.width(), layout.height())
+        );
+        check(&def, &layout, 0);
+        assert!(layout
+            .rect(def.iter_transitions().next().unwrap())
+            .is_none());
+    }
+}
+
+#[test]
+fn test_edges() {
+    for model in MODELS {
+        let def = dsl::parse(model).unwrap();
+        let layout = Layout::new(&def);
+        let mut labels = Vec::new();
+        for t in def.iter_transitions() {
+            let internal = def.transition_kind(t).unwrap() == TransitionKind::Internal;
+            let Some(edge) = layout.edge(t) else {
+                assert!(internal, "no edge for {}", t);
+                continue;
+            };
+            // the ends are on the border of the source and target
+            let on_border = |rect: Rect, (x, y): (i32, i32)| {
+                rect.contains(&Rect {
+                    x,
+                    y,
+                    width: 0,
+                    height: 0,
+                }) && (x == rect.x || x == rect.right() || y == rect.y || y == rect.bottom())
+            };
+            let source = layout.rect(def.source(t).unwrap()).unwrap();
+            let target = layout.rect(def.target(t).unwrap()).unwrap();
+            assert!(on_border(source, edge.start), "{} start {:?}", t, edge);
+            assert!(on_border(target, edge.end), "{} end {:?}", t, edge);
+            if edge.label.width > 0 {
+                labels.push(edge.label);
+            }
+        }
+        for (i, a) in labels.iter().enumerate() {
+            for b in &labels[i + 1..] {
+                assert!(!a.overlaps(b), "labels {:?} {:?}", a, b);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_routing() {
+    // both ways between the same states, and a loop
+    let def = dsl::parse(
+        "statemachine m {
+            initial -> a;
+            state a { on go -> b; on again -> a; }
+            state b { on back -> a; }
+            state c;
+        }",
+    )
+    .unwrap();
+    let layout = Layout::new(&def);
+    let (a, b) = (def.find("a").unwrap(), def.find("b").unwrap());
+    let edge = |state: usize, i: usize| {
+        let t = def.outgoing(state).unwrap()[i];
+        layout.edge(t).unwrap().clone()
+    };
+    let (go, again, back) = (edge(a, 0), edge(a, 1), edge(b, 0));
+    assert_eq!(go.controls.len(), 1);
+    assert_eq!(back.controls.len(), 1);
+    assert_ne!(go.at(8), back.at(8));
+    assert_eq!(again.controls.len(), 2);
+    let a = layout.rect(a).unwrap();
+    assert_eq!(again.start.0, a.right());
+    assert!(again.at(8).0 > a.right());
+}
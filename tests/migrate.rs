@@ -0,0 +1,155 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::dsl;
+use rust_uml_sm::migrate::Migration;
+use rust_uml_sm::StateMachineDef;
+use rust_uml_sm::StateMachineError;
+
+const V1: &str = r#"
+statemachine door {
+    initial -> closed;
+    state closed { on open -> opened; on lock -> locked; defer knock; }
+    state opened {
+        initial -> ajar;
+        state ajar { on push -> wide; }
+        state wide;
+        on close -> closed;
+    }
+    state locked { on unlock -> closed; }
+}
+"#;
+
+const V2: &str = r#"
+statemachine door {
+    initial -> closed;
+    state closed { on open -> open; defer knock; }
+    state open {
+        initial -> ajar;
+        state ajar { on push -> wide; }
+        state wide;
+        on close -> closed;
+    }
+}
+"#;
+
+fn run(model: &str, events: &[&str]) -> StateMachineDef {
+    let mut sm = dsl::parse(model).unwrap();
+    sm.start().unwrap();
+    for name in events {
+        sm.dispatch(sm.event_type(name).unwrap()).unwrap();
+    }
+    sm
+}
+
+#[test]
+fn test_fingerprint() {
+    let v1 = dsl::parse(V1).unwrap();
+    assert_eq!(v1.fingerprint(), dsl::parse(V1).unwrap().fingerprint());
+    assert_ne!(v1.fingerprint(), dsl::parse(V2).unwrap().fingerprint());
+    // a guard is enough to change it
+    let guarded = V1.replace("on unlock ->", "on unlock [key] ->");
+    assert_ne!(
+        v1.fingerprint(),
+        dsl::parse(&guarded).unwrap().fingerprint()
+    );
+
+    let snapshot = run(V1, &["knock"]).snapshot();
+    assert_eq!(snapshot.fingerprint, Some(v1.fingerprint()));
+    let mut v2 = dsl::parse(V2).unwrap();
+    assert!(matches!(
+        v2.restore(&snapshot),
+        Err(StateMachineError::FingerprintMismatch(f)) if f == v1.fingerprint()
+    ));
+}
+
+#[test]
+fn test_rename() {
+    let mut migration = Migration::new();
+    migration
+        .rename("door::region_1::opened", "door::region_1::open")
+        .rename(
+            "door::region_1::opened::region_1::ajar",
+            "door::region_1::open::region_1::half",
+        );
+    assert_eq!(
+        migration.apply("door::region_1::opened::region_1::wide"),
+        "door::region_1::open::region_1::wide"
+    );
+    // the longest rename applies
+    assert_eq!(
+        migration.apply("door::region_1::opened::region_1::ajar"),
+        "door::region_1::open::region_1::half"
+    );
+    // only whole names are renamed
+    assert_eq!(
+        migration.apply("door::region_1::openedx"),
+        "door::region_1::openedx"
+    );
+    assert_eq!(migration.apply("door::knock"), "door::knock");
+}
+
+#[test]
+fn test_migrate() {
+    let snapshots = vec![
+        run(V1, &["knock"]).snapshot(),
+        run(V1, &["open", "push"]).snapshot(),
+        run(V1, &["lock"]).snapshot(),
+        run(V2, &["open"]).snapshot(),
+    ];
+    let mut v2 = dsl::parse(V2).unwrap();
+    let mut migration = Migration::new();
+    migration.rename("door::region_1::opened", "door::region_1::open");
+    let report = v2.migrate(&snapshots, &migration);
+
+    let migrated: Vec<usize> = report.migrated.iter().map(|(i, _)| *i).collect();
+    assert_eq!(migrated, vec![0, 1, 3]);
+    assert_eq!(report.migrated[0].1.deferred, vec!["door::knock"]);
+    assert_eq!(
+        report.migrated[1].1.active,
+        vec![
+            "door::region_1::open",
+            "door::region_1::open::region_1::wide"
+        ]
+    );
+    assert_eq!(report.migrated[2].1, snapshots[3]);
+
+    assert_eq!(report.unmigrated.len(), 1);
+    assert_eq!(report.unmigrated[0].index, 2);
+    assert_eq!(report.unmigrated[0].missing, vec!["door::region_1::locked"]);
+    assert_eq!(
+        report.to_string(),
+        "3 migrated, 1 not migrated\nsnapshot 2: no door::region_1::locked\n"
+    );
+
+    v2.restore(&report.migrated[1].1).unwrap();
+    v2.dispatch(v2.event_type("close").unwrap()).unwrap();
+    let active: Vec<&str> = v2
+        .active_states()
+        .iter()
+        .map(|s| v2.name(*s).unwrap())
+        .collect();
+    assert_eq!(active, vec!["closed"]);
+}
+
+#[test]
+fn test_migrate_invalid_configuration() {
+    // the open state gains a region which would be left inactive
+    let v3 = V2.replace(
+        "initial -> ajar;\n        state ajar { on push -> wide; }\n        state wide;",
+        "region leaf { initial -> ajar; state ajar { on push -> wide; } state wide; }\n        \
+         region latch { initial -> up; state up; }",
+    );
+    assert_ne!(v3, V2);
+    let v3 = dsl::parse(&v3).unwrap();
+    let mut migration = Migration::new();
+    migration.rename(
+        "door::region_1::open::region_1",
+        "door::region_1::open::leaf",
+    );
+    let report = v3.migrate(&[run(V2, &["open"]).snapshot()], &migration);
+    assert!(report.migrated.is_empty());
+    assert!(report.unmigrated[0].missing.is_empty());
+    assert!(matches!(
+        &report.unmigrated[0].error,
+        Some(StateMachineError::InvalidSnapshot(region)) if region == "door::region_1::open::latch"
+    ));
+}
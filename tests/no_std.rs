@@ -0,0 +1,37 @@
+use std::path::Path;
+use std::process::Command;
+
+const TARGET: &str = "thumbv7em-none-eabihf";
+
+/// Build tests/no_std, which uses the crate without its `std` feature,
+/// for a bare-metal target. Skipped if the standard library of the
+/// target is not installed (`rustup target add thumbv7em-none-eabihf`).
+#[test]
+fn test_build_no_std() {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let libdir = Command::new(rustc)
+        .args(["--print", "target-libdir", "--target", TARGET])
+        .output()
+        .expect("rustc");
+    let libdir = String::from_utf8_lossy(&libdir.stdout).trim().to_string();
+    if libdir.is_empty() || !Path::new(&libdir).exists() {
+        eprintln!("skipped: target {} is not installed", TARGET);
+        return;
+    }
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = Command::new(cargo)
+        .arg("build")
+        .arg("--manifest-path")
+        .arg(root.join("tests/no_std/Cargo.toml"))
+        .arg("--target-dir")
+        .arg(root.join("target/no_std"))
+        .args(["--target", TARGET])
+        .output()
+        .expect("cargo");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
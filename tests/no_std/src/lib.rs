@@ -0,0 +1,54 @@
+//! Uses the definition, the runtime and the macros without `std`.
+#![no_std]
+extern crate alloc;
+
+use rust_uml_sm::{behaviors, statemachine, StateMachine, StateMachineResult};
+
+#[derive(StateMachine)]
+#[statemachine(constructor = new)]
+#[state(closed, initial)]
+#[state(opened, entry = light_on)]
+#[transition(closed -> opened, on = open)]
+#[transition(opened -> closed, on = close)]
+pub struct Door {
+    pub lights: u32,
+}
+
+#[behaviors]
+impl Door {
+    fn new() -> Door {
+        Door { lights: 0 }
+    }
+
+    #[behavior]
+    fn light_on(&mut self) {
+        self.lights += 1;
+    }
+}
+
+statemachine! {
+    statemachine valve {
+        initial -> shut;
+        state shut { on open -> flowing; }
+        state flowing { on close -> shut; }
+    }
+}
+
+pub fn run() -> StateMachineResult<u32> {
+    let mut door = Door::new_statemachine();
+    door.start()?;
+    door.send("open")?;
+    door.send("close")?;
+
+    let mut sm = rust_uml_sm::dsl::parse("statemachine lamp { initial -> off; state off; }")?;
+    sm.start()?;
+
+    let mut valve = valve::definition();
+    valve.start()?;
+
+    let frozen: rust_uml_sm::frozen::Frozen<4, 1, 3, 2> = valve.freeze()?;
+    let mut machine = frozen.machine::<2>();
+    machine.start(&mut ())?;
+    machine.dispatch(frozen.event("open").unwrap_or(0), &mut ())?;
+    Ok(door.state.lights)
+}
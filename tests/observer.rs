@@ -0,0 +1,145 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::dsl;
+use rust_uml_sm::Observation;
+use rust_uml_sm::StateMachineDef;
+use std::sync::{Arc, Mutex};
+
+/// Set an observer on sm which keeps the observations as text.
+fn observe(sm: &mut StateMachineDef) -> Arc<Mutex<Vec<String>>> {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let observed = log.clone();
+    sm.set_observer(move |o: &Observation| observed.lock().unwrap().push(o.to_string()));
+    log
+}
+
+fn send(sm: &mut StateMachineDef, event: &str) -> bool {
+    let ev = sm.event_type(event).unwrap();
+    sm.dispatch(ev).unwrap()
+}
+
+#[test]
+fn test_observe_elements() {
+    let mut sm = StateMachineDef::new("sm1");
+    let log = observe(&mut sm);
+    let s1 = sm.add_state("s1").unwrap();
+    sm.add_substate("s11", s1).unwrap();
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![
+            "added sm1::region_1::s1",
+            "added sm1::region_1::s1::region_1",
+            "added sm1::region_1::s1::region_1::s11",
+        ]
+    );
+    assert!(sm.take_observer().is_some());
+    sm.add_state("s2").unwrap();
+    assert_eq!(log.lock().unwrap().len(), 3);
+}
+
+#[test]
+fn test_observe_run() {
+    let mut sm = dsl::parse(
+        r#"
+        statemachine door {
+            initial -> closed;
+            state closed { defer knock; on open [unlocked] -> opened / creak; }
+            state opened { on close -> closed; }
+        }
+        "#,
+    )
+    .unwrap();
+    let log = observe(&mut sm);
+    sm.start().unwrap();
+    assert!(!send(&mut sm, "close"));
+    assert!(!send(&mut sm, "knock"));
+    assert!(send(&mut sm, "open"));
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![
+            "selected door::region_1::initial",
+            "entered door::region_1::closed",
+            "dispatched door::close",
+            "discarded door::close",
+            "dispatched door::knock",
+            "deferred door::knock",
+            "dispatched door::open",
+            "guard [unlocked] is true",
+            "selected door::region_1::t2",
+            "exited door::region_1::closed",
+            "effect creak",
+            "entered door::region_1::opened",
+            // dispatched again once open was consumed
+            "dispatched door::knock",
+            "discarded door::knock",
+        ]
+    );
+}
+
+/// Keeps the key-values of the records, `kind=value` joined by spaces.
+#[cfg(feature = "log")]
+struct KeyValues(Mutex<Vec<String>>);
+
+#[cfg(feature = "log")]
+impl log::Log for KeyValues {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        struct Join(Vec<String>);
+        impl<'kvs> log::kv::VisitSource<'kvs> for Join {
+            fn visit_pair(
+                &mut self,
+                key: log::kv::Key<'kvs>,
+                value: log::kv::Value<'kvs>,
+            ) -> Result<(), log::kv::Error> {
+                self.0.push(format!("{}={}", key, value));
+                Ok(())
+            }
+        }
+        let mut join = Join(Vec::new());
+        record.key_values().visit(&mut join).unwrap();
+        self.0.lock().unwrap().push(join.0.join(" "));
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn test_log_fields() {
+    static LOGGER: KeyValues = KeyValues(Mutex::new(Vec::new()));
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+    let mut sm = dsl::parse(
+        "statemachine door {
+            initial -> closed;
+            state closed { on open [unlocked] -> closed; }
+        }",
+    )
+    .unwrap();
+    sm.set_observer(rust_uml_sm::observer::LogObserver);
+    sm.start().unwrap();
+    send(&mut sm, "open");
+    let closed = sm.find("closed").unwrap();
+    let open = sm.event_type("open").unwrap();
+    let t2 = sm.find("door::region_1::t2").unwrap();
+    let logged = LOGGER.0.lock().unwrap();
+    for fields in [
+        format!(
+            "kind=state_entered state={} fullname=door::region_1::closed",
+            closed
+        ),
+        format!("kind=event_dispatched event={} fullname=door::open", open),
+        format!(
+            "kind=guard_evaluated transition={} guard=unlocked result=true",
+            t2
+        ),
+        format!(
+            "kind=state_exited state={} fullname=door::region_1::closed",
+            closed
+        ),
+    ] {
+        assert!(logged.contains(&fields), "{} not in {:?}", fields, logged);
+    }
+}
@@ -0,0 +1,62 @@
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::path::Path;
+use std::process::Command;
+
+/// Build the Python extension with the `python` feature and run
+/// tests/python/test_rust_uml_sm.py with it. Skipped if there is no
+/// `python3`, the extension is built for the one found by PyO3.
+#[test]
+fn test_python_module() {
+    let python = std::env::var("PYO3_PYTHON").unwrap_or_else(|_| "python3".to_string());
+    if Command::new(&python).arg("--version").output().is_err() {
+        eprintln!("skipped: no {}", python);
+        return;
+    }
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target = root.join("target/python");
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = Command::new(cargo)
+        .args([
+            "rustc",
+            "--lib",
+            "--features",
+            "python",
+            "--crate-type",
+            "cdylib",
+        ])
+        .arg("--manifest-path")
+        .arg(root.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target)
+        .output()
+        .expect("cargo");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // Python looks for rust_uml_sm.pyd on Windows and rust_uml_sm.so
+    // elsewhere, macOS included
+    let module = match cfg!(windows) {
+        true => "rust_uml_sm.pyd",
+        false => "rust_uml_sm.so",
+    };
+    std::fs::copy(
+        target
+            .join("debug")
+            .join(format!("{}rust_uml_sm{}", DLL_PREFIX, DLL_SUFFIX)),
+        target.join(module),
+    )
+    .unwrap();
+
+    let output = Command::new(&python)
+        .arg(root.join("tests/python/test_rust_uml_sm.py"))
+        .env("PYTHONPATH", &target)
+        .output()
+        .expect("python");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
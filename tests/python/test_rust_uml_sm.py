@@ -0,0 +1,124 @@
+"""The Python module, run by tests/python.rs with the extension built
+into target/python."""
+
+import os
+import unittest
+
+import rust_uml_sm as sm
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+with open(os.path.join(HERE, "..", "diff", "door_v1.sm")) as f:
+    DOOR = f.read()
+
+LOCK = """statemachine lock {
+    event turn, open;
+    initial -> unlocked;
+    state unlocked { on turn [has_key] -> locked / click; }
+    state locked { on turn [has_key] -> unlocked / click; defer open; }
+}"""
+
+
+class DefinitionTest(unittest.TestCase):
+    def test_load(self):
+        door = sm.Definition(DOOR)
+        self.assertEqual(door.name, "door")
+        self.assertEqual(door.events, ["open", "close", "push", "lock"])
+        self.assertEqual(door.fullname("opened::ajar"), "door::region_1::opened::region_1::ajar")
+        self.assertIsNone(door.fullname("nope"))
+        self.assertEqual(sm.Definition(door.to_text()).to_text(), door.to_text())
+        self.assertIn("open / creak", door.to_plantuml())
+        self.assertTrue(door.to_svg().startswith("<svg"))
+
+    def test_errors(self):
+        with self.assertRaises(sm.StateMachineError) as raised:
+            sm.Definition("statemachine {")
+        self.assertIn("expected a state machine name", str(raised.exception))
+        self.assertEqual(sm.validate(DOOR), [])
+        self.assertEqual(len(sm.validate("statemachine {")), 1)
+        self.assertEqual(
+            sm.validate("statemachine s { state a; }"),
+            ["region s::region_1 has no initial pseudostate"],
+        )
+
+
+class InstanceTest(unittest.TestCase):
+    def test_dispatch(self):
+        door = sm.Definition(DOOR)
+        inst = door.instance()
+        self.assertEqual(inst.status, "Idle")
+        inst.start()
+        self.assertEqual(inst.active_states, ["door::region_1::closed"])
+        self.assertFalse(inst.dispatch("push"))
+        self.assertEqual(inst.deferred_events, ["push"])
+        self.assertTrue(inst.dispatch("open"))
+        self.assertTrue(inst.is_active("opened"))
+        self.assertEqual(inst.active_states, ["door::region_1::opened::region_1::wide"])
+        self.assertEqual(inst.deferred_events, [])
+        with self.assertRaises(sm.StateMachineError):
+            inst.dispatch("nope")
+        inst.stop()
+        self.assertEqual(inst.active_states, [])
+
+    def test_callables(self):
+        lock = sm.Definition(LOCK)
+        inst = lock.instance()
+        performed = []
+        inst.behaviors = performed.append
+        inst.guards = {"has_key": False}
+        inst.start()
+        self.assertFalse(inst.dispatch("turn"))
+        inst.guards["has_key"] = True
+        self.assertTrue(inst.dispatch("turn"))
+        self.assertEqual(performed, ["click"])
+
+        inst.behaviors = {}
+        inst.guards = lambda name: name == "has_key"
+        self.assertTrue(inst.dispatch("turn"))
+        self.assertTrue(inst.is_active("unlocked"))
+        self.assertEqual(performed, ["click"])
+
+    def test_exceptions(self):
+        inst = sm.Definition(LOCK).instance()
+
+        def guard(name):
+            raise KeyError(name)
+
+        inst.guards = guard
+        inst.start()
+        with self.assertRaises(KeyError):
+            inst.dispatch("turn")
+        self.assertTrue(inst.is_active("unlocked"))
+
+    def test_trace(self):
+        lock = sm.Definition(LOCK)
+        inst = lock.instance()
+        self.assertIsNone(inst.trace)
+        inst.record()
+        inst.start()
+        inst.dispatch("turn")
+        inst.dispatch("open")
+        trace = inst.trace
+        self.assertEqual(len(trace.splitlines()), 3)
+
+        again = lock.instance()
+        self.assertIsNone(again.replay(trace))
+        self.assertEqual(again.active_states, inst.active_states)
+        self.assertEqual(again.snapshot(), inst.snapshot())
+
+        # the guard outcomes recorded are replayed
+        other = lock.instance()
+        self.assertIn("diverged", other.replay(trace.replace(":1", ":0")))
+
+    def test_snapshot(self):
+        door = sm.Definition(DOOR)
+        inst = door.instance()
+        inst.start()
+        inst.dispatch("open")
+        copy = door.instance()
+        copy.restore(inst.snapshot())
+        self.assertTrue(copy.is_active("opened::ajar"))
+
+
+if __name__ == "__main__":
+    unittest.main()
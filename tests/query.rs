@@ -0,0 +1,99 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::dsl;
+use rust_uml_sm::{StateMachineDef, TransitionKind};
+
+const DOOR: &str = include_str!("diff/door_v1.sm");
+
+fn names(sm: &StateMachineDef, dbids: impl IntoIterator<Item = usize>) -> Vec<String> {
+    dbids
+        .into_iter()
+        .map(|d| sm.fullname(d).unwrap().clone())
+        .collect()
+}
+
+#[test]
+fn test_find() {
+    let sm = dsl::parse(DOOR).unwrap();
+    let ajar = sm.find("opened::ajar").unwrap();
+    assert_eq!(sm.find("opened::region_1::ajar"), Some(ajar));
+    assert_eq!(
+        sm.find("door::region_1::opened::region_1::ajar"),
+        Some(ajar)
+    );
+    assert_eq!(
+        sm.fullname(ajar).unwrap(),
+        "door::region_1::opened::region_1::ajar"
+    );
+    assert_eq!(sm.find("region_1"), Some(1));
+    assert_eq!(sm.find("opened::locked"), None);
+    assert_eq!(sm.find("closed::ajar"), None);
+}
+
+#[test]
+fn test_glob() {
+    let sm = dsl::parse(DOOR).unwrap();
+    assert_eq!(
+        names(&sm, sm.glob("door::**::?ide")),
+        ["door::region_1::opened::region_1::wide"]
+    );
+    assert_eq!(
+        names(&sm, sm.glob("door::**::*pen*")),
+        ["door::region_1::opened", "door::open"]
+    );
+    assert_eq!(
+        names(&sm, sm.glob("door::region_1::opened::*")),
+        ["door::region_1::opened::region_1"]
+    );
+    // the state machine, 2 regions, 6 vertices, 5 transitions and 4 events
+    assert_eq!(sm.glob("**").len(), 18);
+    assert!(sm.glob("door::*::ajar").is_empty());
+    assert_eq!(sm.glob("door::**::ajar::**").len(), 1);
+}
+
+#[test]
+fn test_iterate() {
+    let sm = dsl::parse(DOOR).unwrap();
+    assert_eq!(
+        names(&sm, sm.iter_states()),
+        [
+            "door::region_1::closed",
+            "door::region_1::opened",
+            "door::region_1::opened::region_1::ajar",
+            "door::region_1::opened::region_1::wide",
+        ]
+    );
+    assert_eq!(sm.iter_vertices().count(), 6);
+    assert_eq!(sm.iter_regions().count(), 2);
+    assert_eq!(
+        names(&sm, sm.iter_event_types()),
+        ["door::open", "door::close", "door::push", "door::lock"]
+    );
+    assert_eq!(sm.iter_transitions().count(), 5);
+}
+
+#[test]
+fn test_accessors() {
+    let sm = dsl::parse(DOOR).unwrap();
+    let closed = sm.find("closed").unwrap();
+    let opened = sm.find("opened").unwrap();
+    let [t] = sm.outgoing(closed).unwrap()[..] else {
+        panic!("one transition expected");
+    };
+    assert_eq!(sm.source(t).unwrap(), closed);
+    assert_eq!(sm.target(t).unwrap(), opened);
+    assert_eq!(sm.trigger(t).unwrap(), sm.event_type("open"));
+    assert_eq!(sm.effect(t).unwrap().unwrap().name(), "creak");
+    assert!(sm.guard(t).unwrap().is_none());
+    assert_eq!(sm.transition_kind(t).unwrap(), TransitionKind::External);
+    assert!(sm.incoming(opened).unwrap().contains(&t));
+    assert_eq!(sm.entry(opened).unwrap().unwrap().name(), "light_on");
+    assert!(sm.exit(opened).unwrap().is_none());
+    assert!(sm.do_activity(opened).unwrap().is_none());
+    assert_eq!(
+        sm.deferrable_events(closed).unwrap(),
+        vec![sm.event_type("push").unwrap()]
+    );
+    assert!(sm.source(closed).is_err());
+    assert!(sm.outgoing(t).is_err());
+    assert!(sm.entry(t).is_err());
+}
@@ -0,0 +1,169 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::dsl;
+use rust_uml_sm::render;
+use rust_uml_sm::StateMachineError;
+
+const DOOR: &str = include_str!("diff/door_v1.sm");
+const PUMP: &str = include_str!("codegen/pump.sm");
+const TIMED: &str = "statemachine timed {
+    initial -> idle;
+    state idle {
+        on after(30s) -> busy;
+        on when(ready) -> busy;
+    }
+    state busy;
+}";
+
+#[test]
+fn test_plantuml() {
+    let door = dsl::parse(DOOR).unwrap();
+    assert_eq!(
+        render::plantuml(&door),
+        "@startuml
+state \"door\" as n0 {
+    state \"closed\" as n3
+    n3 : defer / push
+    state \"opened\" as n4 {
+        state \"ajar\" as n7
+        state \"wide\" as n8
+        [*] --> n7
+    }
+    n4 : entry / light_on
+    [*] --> n3
+}
+n3 --> n4 : open / creak
+n7 --> n8 : push
+n4 --> n3 : close
+@enduml
+"
+    );
+    let pump = render::plantuml(&dsl::parse(PUMP).unwrap());
+    for line in [
+        "state \"check\" as n4 <<choice>>",
+        "state \"h\" as n7 <<history*>>",
+        "n20 --> [*] : tick",
+        "            --\n",
+        "n4 --> n3 : [else] / complain",
+        "n9 : tick / count",
+    ] {
+        assert!(pump.contains(line), "{} not in\n{}", line, pump);
+    }
+}
+
+#[test]
+fn test_mermaid() {
+    let door = render::mermaid(&dsl::parse(DOOR).unwrap());
+    assert!(door.starts_with("stateDiagram-v2\n"));
+    assert!(door.contains("        state n4 {\n            state \"ajar\" as n7\n"));
+    assert!(door.contains("    n3 --> n4 : open / creak\n"));
+    let pump = render::mermaid(&dsl::parse(PUMP).unwrap());
+    assert!(pump.contains("state n15 <<fork>>"));
+}
+
+#[test]
+fn test_dot() {
+    let door = render::dot(&dsl::parse(DOOR).unwrap());
+    assert!(door.starts_with("digraph \"door\" {\n    compound=true;\n"));
+    assert!(door.contains("    n3 [label=\"closed\\ndefer / push\"];\n"));
+    assert!(door.contains("    subgraph cluster_n4 {\n"));
+    assert!(door.contains("    n3 -> n4 [label=\"open / creak\", lhead=cluster_n4];\n"));
+    assert!(door.contains("    n4 -> n3 [label=\"close\", ltail=cluster_n4];\n"));
+    // orthogonal regions are dashed clusters
+    let pump = render::dot(&dsl::parse(PUMP).unwrap());
+    assert!(pump.contains("label=\"valve\"; style=dashed;"));
+}
+
+#[test]
+fn test_json() {
+    let door = render::json(&dsl::parse(DOOR).unwrap());
+    let value: serde_json::Value = serde_json::from_str(&door).unwrap();
+    assert_eq!(value["name"], "door");
+    assert_eq!(value["events"].as_array().unwrap().len(), 4);
+    assert_eq!(value["vertices"][2]["entry"], "light_on");
+    assert_eq!(value["vertices"][1]["defer"][0], value["events"][2]["id"]);
+    assert_eq!(value["transitions"][1]["effect"], "creak");
+    assert_eq!(value["transitions"][1]["trigger"], value["events"][0]["id"]);
+    // every format is valid JSON, whatever the names
+    let pump = render::json(&dsl::parse(PUMP).unwrap());
+    assert!(serde_json::from_str::<serde_json::Value>(&pump).is_ok());
+    assert_eq!(render::json_string("a\"b\\\n"), "\"a\\\"b\\\\\\n\"");
+}
+
+#[test]
+fn test_from_json() {
+    for model in [DOOR, PUMP, TIMED] {
+        let def = dsl::parse(model).unwrap();
+        let read = render::from_json(&render::json(&def)).unwrap();
+        assert_eq!(render::json(&read), render::json(&def));
+        assert_eq!(dsl::write(&read), dsl::write(&def));
+    }
+    let read = render::from_json(&render::json(&dsl::parse(TIMED).unwrap())).unwrap();
+    assert!(read.time_event(6).unwrap().is_some());
+
+    let err = |json: &str| match render::from_json(json) {
+        Err(StateMachineError::ParseError(e)) => (e.message, e.span.line, e.span.column),
+        other => panic!("not a parse error, ok: {}", other.is_ok()),
+    };
+    assert_eq!(err("{\"name\": 1}"), ("expected a string".into(), 1, 10));
+    assert_eq!(
+        err("{\n  \"name\" \"door\"}"),
+        ("expected `:`".into(), 2, 10)
+    );
+    assert_eq!(
+        err("{\"name\": \"door\"}"),
+        ("missing `transitions`".into(), 1, 1)
+    );
+    let skipped = render::json(&dsl::parse(DOOR).unwrap()).replace("\"id\": 2,", "\"id\": 99,");
+    assert!(err(&skipped).0.contains("out of sequence"));
+}
+
+#[test]
+fn test_svg() {
+    let door = dsl::parse(DOOR).unwrap();
+    let svg = render::svg(&door);
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches("<g").count(), svg.matches("</g>").count());
+    // a group per vertex, the state machine included, and per transition
+    for v in door
+        .iter_vertices()
+        .chain([0])
+        .chain(door.iter_transitions())
+    {
+        assert!(
+            svg.contains(&format!(" id=\"n{}\"", v)),
+            "n{} in\n{}",
+            v,
+            svg
+        );
+    }
+    assert!(svg.contains(">entry / light_on</text>"));
+    assert!(svg.contains(">open / creak</text>"));
+    // orthogonal regions are separated by dashed lines
+    let pump = render::svg(&dsl::parse(PUMP).unwrap());
+    assert_eq!(pump.matches("stroke-dasharray").count(), 1);
+}
+
+#[test]
+fn test_html() {
+    let mut door = dsl::parse(DOOR).unwrap();
+    door.record();
+    door.start().unwrap();
+    door.dispatch(door.event_type("open").unwrap()).unwrap();
+    door.dispatch(door.event_type("lock").unwrap()).unwrap();
+    let trace = door.take_trace().unwrap();
+    let html = render::html(&door, &trace);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains(&render::svg(&door)));
+    // the composite state is highlighted with its active substate
+    let opened = door.find("opened").unwrap();
+    let ajar = door.find("opened::ajar").unwrap();
+    assert!(html.contains(&format!(
+        "{{\"input\": \"open\", \"active\": [{}, {}]}}",
+        opened, ajar
+    )));
+    assert!(html.contains("{\"input\": \"lock (not consumed)\""));
+    // nothing is loaded from elsewhere
+    assert!(!html.contains("src="));
+    assert!(!html.contains("href="));
+}
@@ -0,0 +1,140 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::dsl;
+use rust_uml_sm::repl::Repl;
+
+const DOOR: &str = include_str!("diff/door_v1.sm");
+
+/// Perform the commands and return what they wrote.
+fn run(repl: &mut Repl, commands: &[&str]) -> String {
+    let mut out = Vec::new();
+    for command in commands {
+        assert!(repl.command(command, &mut out).unwrap());
+    }
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_send_and_show() {
+    let mut repl = Repl::new(DOOR).unwrap();
+    assert_eq!(
+        run(&mut repl, &["next"]),
+        "  selected door::region_1::initial  (1 more)\n"
+    );
+    assert_eq!(
+        run(&mut repl, &["show"]),
+        "[active_state] door::region_1::closed\n[status] Running\n"
+    );
+    assert_eq!(
+        run(&mut repl, &["events"]),
+        "+ open: closed -> opened\nenabled: open\n"
+    );
+    assert_eq!(
+        run(&mut repl, &["send open"]),
+        "  dispatched door::open
+  selected door::region_1::t2
+  exited door::region_1::closed
+  behavior creak
+  behavior light_on
+  entered door::region_1::opened
+  selected door::region_1::opened::region_1::initial
+  entered door::region_1::opened::region_1::ajar
+[active_state] door::region_1::opened::region_1::ajar
+[status] Running
+"
+    );
+    // an event name alone is sent too
+    assert!(
+        run(&mut repl, &["push"]).contains("[active_state] door::region_1::opened::region_1::wide")
+    );
+    assert_eq!(run(&mut repl, &["send fly"]), "unknown event fly\n");
+    assert!(!repl.command("quit", &mut Vec::new()).unwrap());
+}
+
+#[test]
+fn test_guards() {
+    let model = DOOR.replace("on open ->", "on open [unlocked] ->");
+    let mut repl = Repl::new(&model).unwrap();
+    assert_eq!(
+        run(&mut repl, &["guard unlocked"]),
+        "guard [unlocked] is false\n"
+    );
+    assert_eq!(
+        run(&mut repl, &["events"]),
+        "- open [unlocked]: closed -> opened\nenabled: \n"
+    );
+    let out = run(&mut repl, &["open"]);
+    assert!(out.contains("guard [unlocked] is false"));
+    assert!(out.ends_with("[active_state] door::region_1::closed\n[status] Running\n"));
+    run(&mut repl, &["guard unlocked on", "open"]);
+    assert!(repl
+        .def()
+        .is_active(repl.def().find("opened::ajar").unwrap()));
+}
+
+#[test]
+fn test_events_are_signals() {
+    let model = DOOR.replace(
+        "on open -> opened / creak;",
+        "on open -> opened / creak; on after(5s) -> opened; on when(windy) -> opened;",
+    );
+    let mut repl = Repl::new(&model).unwrap();
+    run(&mut repl, &["start"]);
+    assert_eq!(
+        run(&mut repl, &["events"]),
+        "+ open: closed -> opened\nenabled: open\n"
+    );
+}
+
+#[test]
+fn test_step() {
+    let mut repl = Repl::new(DOOR).unwrap();
+    run(&mut repl, &["open", "step on"]);
+    assert_eq!(
+        run(&mut repl, &["push"]),
+        "  dispatched door::push  (3 more)\n"
+    );
+    assert_eq!(
+        run(&mut repl, &["next", ""]),
+        "  selected door::region_1::opened::region_1::t4  (2 more)\n  \
+         exited door::region_1::opened::region_1::ajar  (1 more)\n"
+    );
+    assert_eq!(
+        run(&mut repl, &["", ""]),
+        "  entered door::region_1::opened::region_1::wide\n\
+         [active_state] door::region_1::opened::region_1::wide\n[status] Running\n"
+    );
+}
+
+#[test]
+fn test_undo_and_save() {
+    let mut repl = Repl::new(DOOR).unwrap();
+    run(&mut repl, &["open", "push"]);
+    assert_eq!(
+        run(&mut repl, &["undo"]),
+        "at step 2\n[active_state] door::region_1::opened::region_1::ajar\n[status] Running\n"
+    );
+    assert_eq!(repl.trace().steps.len(), 2);
+    // the session goes on from there
+    run(&mut repl, &["close"]);
+    assert!(repl.def().is_active(repl.def().find("closed").unwrap()));
+
+    let path = std::env::temp_dir().join(format!("sm_repl_{}.trace", std::process::id()));
+    let path = path.to_str().unwrap();
+    assert_eq!(
+        run(&mut repl, &[&format!("save {}", path)]),
+        format!("saved 3 steps to {}\n", path)
+    );
+    let mut fresh = dsl::parse(DOOR).unwrap();
+    let trace = std::fs::read_to_string(path).unwrap().parse().unwrap();
+    assert!(fresh.replay(&trace).unwrap().is_none());
+    assert_eq!(fresh.active_states(), repl.def().active_states());
+
+    let mut other = Repl::new(DOOR).unwrap();
+    assert!(run(&mut other, &[&format!("load {}", path)]).starts_with("at step 3\n"));
+    assert_eq!(other.def().active_states(), repl.def().active_states());
+    std::fs::remove_file(path).unwrap();
+
+    run(&mut repl, &["undo", "undo", "undo"]);
+    assert!(!repl.def().is_running());
+    assert_eq!(run(&mut repl, &["undo"]), "nothing to undo\n");
+}
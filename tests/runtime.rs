@@ -0,0 +1,406 @@
+extern crate rust_uml_sm;
+mod common;
+use common::active;
+use rust_uml_sm::dsl;
+use rust_uml_sm::Context;
+use rust_uml_sm::StateMachineDef;
+use rust_uml_sm::StateMachineError;
+use rust_uml_sm::Status;
+
+/// Records behaviors, guards are looked up in a list of true ones.
+#[derive(Default)]
+struct Recorder {
+    log: Vec<String>,
+    true_guards: Vec<&'static str>,
+}
+
+impl Context for Recorder {
+    fn behavior(&mut self, name: &str) {
+        self.log.push(name.to_string());
+    }
+    fn guard(&mut self, name: &str) -> bool {
+        self.true_guards.contains(&name)
+    }
+    fn on_entry(&mut self, state: &str) {
+        self.log.push(format!("+{}", state));
+    }
+    fn on_exit(&mut self, state: &str) {
+        self.log.push(format!("-{}", state));
+    }
+}
+
+fn send(sm: &mut StateMachineDef, ctx: &mut Recorder, event: &str) -> bool {
+    let ev = sm.event_type(event).unwrap();
+    sm.dispatch_with(ev, ctx).unwrap()
+}
+
+#[test]
+fn test_run_simple() {
+    let mut sm = dsl::parse(
+        r#"
+        statemachine door {
+            initial -> closed;
+            state closed {
+                on open [unlocked] -> opened / creak;
+                on knock / answer;
+            }
+            state opened {
+                entry light_on;
+                exit light_off;
+                on close -> closed;
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let mut ctx = Recorder::default();
+    assert!(matches!(
+        sm.dispatch_with(sm.event_type("open").unwrap(), &mut ctx),
+        Err(StateMachineError::NotRunning)
+    ));
+    sm.start_with(&mut ctx).unwrap();
+    assert_eq!(sm.status(), Status::Running);
+    assert_eq!(active(&sm), vec!["closed"]);
+
+    // guard is false
+    assert!(!send(&mut sm, &mut ctx, "open"));
+    assert_eq!(active(&sm), vec!["closed"]);
+
+    // internal transition
+    assert!(send(&mut sm, &mut ctx, "knock"));
+    ctx.true_guards.push("unlocked");
+    assert!(send(&mut sm, &mut ctx, "open"));
+    assert_eq!(active(&sm), vec!["opened"]);
+    assert!(!send(&mut sm, &mut ctx, "open"));
+    assert!(send(&mut sm, &mut ctx, "close"));
+    sm.stop_with(&mut ctx).unwrap();
+    assert_eq!(sm.status(), Status::Idle);
+    assert!(sm.active_states().is_empty());
+    assert_eq!(
+        ctx.log,
+        vec![
+            "+closed",
+            "answer",
+            "-closed",
+            "creak",
+            "light_on",
+            "+opened",
+            "-opened",
+            "light_off",
+            "+closed",
+            "-closed"
+        ]
+    );
+}
+
+#[test]
+fn test_run_hierarchy() {
+    let mut sm = dsl::parse(
+        r#"
+        statemachine sm1 {
+            initial -> s1;
+            state s1 {
+                history h;
+                initial -> a;
+                state a { on next -> b; }
+                state b { on next -> c; }
+                state c;
+                on leave -> s2;
+                on next -> s2;
+            }
+            state s2 {
+                on back -> s1;
+                on resume -> s1::h;
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let mut ctx = Recorder::default();
+    sm.start_with(&mut ctx).unwrap();
+    assert_eq!(active(&sm), vec!["a"]);
+    // the inner transition has priority over the one of s1
+    assert!(send(&mut sm, &mut ctx, "next"));
+    assert_eq!(active(&sm), vec!["b"]);
+    assert!(send(&mut sm, &mut ctx, "next"));
+    assert_eq!(active(&sm), vec!["c"]);
+    // c has no transition for next, s1 has
+    assert!(send(&mut sm, &mut ctx, "next"));
+    assert_eq!(active(&sm), vec!["s2"]);
+    assert!(send(&mut sm, &mut ctx, "resume"));
+    assert_eq!(active(&sm), vec!["c"]);
+    assert!(send(&mut sm, &mut ctx, "leave"));
+    assert!(send(&mut sm, &mut ctx, "back"));
+    assert_eq!(active(&sm), vec!["a"]);
+    assert_eq!(
+        ctx.log[..6].to_vec(),
+        vec!["+s1", "+a", "-a", "+b", "-b", "+c"]
+    );
+    assert_eq!(ctx.log[6..9].to_vec(), vec!["-c", "-s1", "+s2"]);
+}
+
+#[test]
+fn test_run_orthogonal_and_final() {
+    let mut sm = dsl::parse(
+        r#"
+        statemachine sm1 {
+            initial -> work;
+            state work {
+                region r1 {
+                    initial -> a1;
+                    state a1 { on go -> done1; }
+                    final done1;
+                }
+                region r2 {
+                    initial -> b1;
+                    state b1 { defer later; on go -> b2; }
+                    state b2 { on later -> done2; }
+                    final done2;
+                }
+                -> finished / wrap_up;
+            }
+            state finished { on later -> end; }
+            final end;
+        }
+        "#,
+    )
+    .unwrap();
+    let mut ctx = Recorder::default();
+    sm.start_with(&mut ctx).unwrap();
+    assert_eq!(active(&sm), vec!["a1", "b1"]);
+    // deferred by b1
+    assert!(!send(&mut sm, &mut ctx, "later"));
+    assert_eq!(sm.deferred_events().len(), 1);
+    // go fires in both regions, later is dispatched again and fires in
+    // b2, both regions are final and work completes
+    assert!(send(&mut sm, &mut ctx, "go"));
+    assert!(sm.deferred_events().is_empty());
+    assert_eq!(active(&sm), vec!["finished"]);
+    assert!(ctx.log.contains(&"wrap_up".to_string()));
+    assert!(send(&mut sm, &mut ctx, "later"));
+    assert_eq!(sm.status(), Status::Terminated);
+    assert_eq!(active(&sm), vec!["end"]);
+}
+
+#[test]
+fn test_run_pseudostates() {
+    let mut sm = dsl::parse(
+        r#"
+        statemachine sm1 {
+            initial -> idle;
+            state idle { on go -> c1; on fork -> f; on kill -> k; }
+            choice c1 {
+                [small] -> busy;
+                [else] -> idle / complain;
+            }
+            state busy {
+                deephistory dh;
+                initial -> a;
+                state a {
+                    initial -> a1;
+                    state a1 { on next -> a2; }
+                    state a2;
+                }
+                on pause -> idle;
+            }
+            fork f {
+                -> par::p1::x;
+                -> par::p2::y;
+            }
+            state par {
+                region p1 { initial -> w; state w; state x; }
+                region p2 { initial -> z; state y; state z; }
+                on pause -> idle;
+            }
+            terminate k;
+        }
+        "#,
+    )
+    .unwrap();
+    let mut ctx = Recorder::default();
+    sm.start_with(&mut ctx).unwrap();
+    assert!(send(&mut sm, &mut ctx, "go"));
+    assert_eq!(active(&sm), vec!["idle"]);
+    assert_eq!(ctx.log.last().unwrap(), "+idle");
+    assert!(ctx.log.contains(&"complain".to_string()));
+
+    ctx.true_guards.push("small");
+    assert!(send(&mut sm, &mut ctx, "go"));
+    assert_eq!(active(&sm), vec!["a1"]);
+    assert!(send(&mut sm, &mut ctx, "next"));
+    assert!(send(&mut sm, &mut ctx, "pause"));
+    assert_eq!(active(&sm), vec!["idle"]);
+
+    // entering busy through its deep history pseudostate
+    let mut sm2 = dsl::parse(
+        r#"
+        statemachine sm2 {
+            initial -> busy;
+            state busy {
+                deephistory dh;
+                initial -> a;
+                state a {
+                    initial -> a1;
+                    state a1 { on next -> a2; }
+                    state a2;
+                }
+                on pause -> idle;
+            }
+            state idle { on resume -> busy::dh; on restart -> busy; }
+        }
+        "#,
+    )
+    .unwrap();
+    sm2.start_with(&mut ctx).unwrap();
+    assert!(send(&mut sm2, &mut ctx, "next"));
+    assert!(send(&mut sm2, &mut ctx, "pause"));
+    assert!(send(&mut sm2, &mut ctx, "resume"));
+    assert_eq!(active(&sm2), vec!["a2"]);
+    assert!(send(&mut sm2, &mut ctx, "pause"));
+    assert!(send(&mut sm2, &mut ctx, "restart"));
+    assert_eq!(active(&sm2), vec!["a1"]);
+
+    // fork enters both regions of par explicitly
+    assert!(send(&mut sm, &mut ctx, "fork"));
+    assert_eq!(active(&sm), vec!["x", "y"]);
+    assert!(send(&mut sm, &mut ctx, "pause"));
+    assert!(send(&mut sm, &mut ctx, "kill"));
+    assert_eq!(sm.status(), Status::Terminated);
+    assert!(matches!(
+        sm.dispatch_with(sm.event_type("go").unwrap(), &mut ctx),
+        Err(StateMachineError::NotRunning)
+    ));
+}
+
+#[test]
+fn test_run_functions() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static ENTERED: AtomicUsize = AtomicUsize::new(0);
+    fn entered() {
+        ENTERED.fetch_add(1, Ordering::SeqCst);
+    }
+    let mut sm = StateMachineDef::new("sm1");
+    let s1 = sm.add_state("s1").unwrap();
+    let s2 = sm.add_state("s2").unwrap();
+    let r = sm.get_only_region(0).unwrap();
+    sm.initial_transition(r, s1, rust_uml_sm::OptBehavior::None)
+        .unwrap();
+    sm.set_entry(s2, rust_uml_sm::Behavior::new(entered))
+        .unwrap();
+    let ev = sm.add_event_type("ev1").unwrap();
+    sm.add_transition(
+        "t1",
+        Some(ev),
+        s1,
+        s2,
+        rust_uml_sm::OptBehavior::None,
+        rust_uml_sm::OptGuard::None,
+    )
+    .unwrap();
+    sm.start().unwrap();
+    assert!(sm.is_active(s1));
+    assert!(sm.dispatch(ev).unwrap());
+    assert!(sm.is_active(s2) && !sm.is_active(s1));
+    assert_eq!(ENTERED.load(Ordering::SeqCst), 1);
+    assert!(matches!(sm.start(), Err(StateMachineError::AlreadyRunning)));
+}
+
+/// Do-activities named in `background` go on until `activity_done`.
+#[derive(Default)]
+struct Background {
+    recorder: Recorder,
+    background: Vec<&'static str>,
+}
+
+impl Context for Background {
+    fn behavior(&mut self, name: &str) {
+        self.recorder.behavior(name)
+    }
+    fn on_exit(&mut self, state: &str) {
+        self.recorder.on_exit(state)
+    }
+    fn start_activity(&mut self, _state: usize, name: &str) -> bool {
+        self.recorder.log.push(format!("start {}", name));
+        if self.background.contains(&name) {
+            return true;
+        }
+        self.behavior(name);
+        false
+    }
+    fn abort_activity(&mut self, state: usize) {
+        self.recorder.log.push(format!("abort {}", state));
+    }
+}
+
+#[test]
+fn test_run_activities() {
+    let mut sm = dsl::parse(
+        r#"
+        statemachine dl {
+            event cancel;
+            initial -> fetching;
+            state fetching {
+                do download;
+                exit close;
+                on cancel -> idle;
+                -> done;
+            }
+            state idle { do wait; -> fetching; }
+            state done;
+        }
+        "#,
+    )
+    .unwrap();
+    let fetching = sm.find("fetching").unwrap();
+    let idle = sm.find("idle").unwrap();
+    let mut ctx = Background {
+        background: vec!["download"],
+        ..Default::default()
+    };
+    sm.record();
+    sm.start_with(&mut ctx).unwrap();
+    assert_eq!(active(&sm), vec!["fetching"]);
+    assert_eq!(sm.running_activities(), &[fetching]);
+
+    // aborted before the exit behavior, `wait` is over once performed
+    let cancel = sm.event_type("cancel").unwrap();
+    assert!(sm.dispatch_with(cancel, &mut ctx).unwrap());
+    assert_eq!(
+        ctx.recorder.log,
+        vec![
+            "start download",
+            format!("abort {}", fetching).as_str(),
+            "-fetching",
+            "close",
+            "start wait",
+            "wait",
+            "-idle",
+            "start download"
+        ]
+    );
+    assert!(!sm.activity_done_with(idle, &mut ctx).unwrap());
+
+    assert!(sm.activity_done_with(fetching, &mut ctx).unwrap());
+    assert_eq!(active(&sm), vec!["done"]);
+    assert!(sm.running_activities().is_empty());
+    assert!(!sm.activity_done(fetching).unwrap());
+
+    let trace = sm.take_trace().unwrap();
+    assert_eq!(
+        trace.steps[2].to_string(),
+        format!("done {} + / {}", fetching, sm.find("done").unwrap())
+    );
+    assert_eq!(
+        trace
+            .to_string()
+            .parse::<rust_uml_sm::trace::Trace>()
+            .unwrap(),
+        trace
+    );
+    let mut again = dsl::parse(&dsl::write(&sm)).unwrap();
+    let mut ctx = Background {
+        background: vec!["download"],
+        ..Default::default()
+    };
+    assert_eq!(again.replay_with(&trace, &mut ctx).unwrap(), None);
+}
@@ -0,0 +1,144 @@
+extern crate rust_uml_sm;
+mod common;
+use common::{next, Recorder};
+use rust_uml_sm::dsl;
+use rust_uml_sm::snapshot::Snapshot;
+use rust_uml_sm::StateMachineError;
+use rust_uml_sm::Status;
+
+const PUMP: &str = include_str!("codegen/pump.sm");
+const EVENTS: [&str; 7] = ["start", "stop", "tick", "fault", "reset", "prime", "drain"];
+
+/// Run a pump for some events, restore its snapshot into a new one and
+/// check both go on the same way.
+#[test]
+fn test_restore_resumes() {
+    for trace in 0..30u64 {
+        let mut sm = dsl::parse(PUMP).unwrap();
+        let mut ctx = Recorder::new(trace);
+        let mut events = trace + 500;
+        sm.start_with(&mut ctx).unwrap();
+        for _ in 0..(trace % 12) {
+            let name = EVENTS[next(&mut events) as usize % EVENTS.len()];
+            if sm.status() == Status::Running {
+                sm.dispatch_with(sm.event_type(name).unwrap(), &mut ctx)
+                    .unwrap();
+            }
+        }
+        let snapshot = sm.snapshot();
+        let text = snapshot.to_string();
+
+        let mut restored = dsl::parse(PUMP).unwrap();
+        restored.restore(&text.parse().unwrap()).unwrap();
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.active_states(), sm.active_states());
+        assert_eq!(restored.status(), sm.status());
+
+        ctx.log.clear();
+        let mut ctx2 = ctx.clone();
+        for _ in 0..30 {
+            if sm.status() != Status::Running {
+                break;
+            }
+            let event = sm
+                .event_type(EVENTS[next(&mut events) as usize % EVENTS.len()])
+                .unwrap();
+            let r1 = sm.dispatch_with(event, &mut ctx).unwrap();
+            let r2 = restored.dispatch_with(event, &mut ctx2).unwrap();
+            assert_eq!(r1, r2);
+            assert_eq!(restored.active_states(), sm.active_states());
+        }
+        assert_eq!(ctx.log, ctx2.log, "trace {}", trace);
+        assert_eq!(restored.deferred_events(), sm.deferred_events());
+    }
+}
+
+#[test]
+fn test_restore_without_entry() {
+    let mut sm = dsl::parse(PUMP).unwrap();
+    sm.start().unwrap();
+    for name in &["start", "drain"] {
+        sm.dispatch(sm.event_type(name).unwrap()).unwrap();
+    }
+    let snapshot = sm.snapshot();
+    assert_eq!(
+        snapshot.to_string(),
+        format!(
+            "running\n\
+         fingerprint {:016x}\n\
+         active pump::region_1::running\n\
+         active pump::region_1::running::region_1::draining\n\
+         active pump::region_1::running::region_1::draining::valve::opening\n\
+         active pump::region_1::running::region_1::draining::pump::slowing\n",
+            sm.fingerprint()
+        )
+    );
+
+    let mut restored = dsl::parse(PUMP).unwrap();
+    let mut ctx = Recorder::new(0);
+    restored.restore(&snapshot).unwrap();
+    assert!(ctx.log.is_empty());
+    assert!(matches!(
+        restored.restore(&snapshot),
+        Err(StateMachineError::AlreadyRunning)
+    ));
+    restored.stop_with(&mut ctx).unwrap();
+    assert_eq!(
+        ctx.log,
+        vec!["-slowing", "-opening", "-draining", "-running", "motor_off"]
+    );
+}
+
+#[test]
+fn test_restore_invalid() {
+    let invalid = |text: &str| {
+        let mut sm = dsl::parse(PUMP).unwrap();
+        match text.parse::<Snapshot>().and_then(|s| sm.restore(&s)) {
+            Err(StateMachineError::InvalidSnapshot(name)) => name,
+            other => panic!("unexpected {:?}", other),
+        }
+    };
+    assert_eq!(invalid("walking\n"), "walking");
+    assert_eq!(invalid("running\nactive\n"), "active");
+    assert_eq!(
+        invalid("running\nactive pump::region_1::gone\n"),
+        "pump::region_1::gone"
+    );
+    // not a vertex
+    assert_eq!(invalid("running\nactive pump::start\n"), "pump::start");
+    assert_eq!(
+        invalid("running\ndeferred pump::region_1::off\n"),
+        "pump::region_1::off"
+    );
+    // two active vertices in a region
+    assert_eq!(
+        invalid("running\nactive pump::region_1::off\nactive pump::region_1::error\n"),
+        "pump::region_1::error"
+    );
+    // a region of an active state left inactive
+    assert_eq!(
+        invalid("running\nactive pump::region_1::running\n"),
+        "pump::region_1::running::region_1"
+    );
+    // within a state which is not active
+    assert_eq!(
+        invalid(
+            "running\nactive pump::region_1::off\nactive pump::region_1::running::region_1::low\n"
+        ),
+        "pump::region_1::running::region_1::low"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_snapshot_serde() {
+    let mut sm = dsl::parse(PUMP).unwrap();
+    sm.start().unwrap();
+    for name in &["start", "fault", "start"] {
+        sm.dispatch(sm.event_type(name).unwrap()).unwrap();
+    }
+    let snapshot = sm.snapshot();
+    assert_eq!(snapshot.deferred, vec!["pump::start"]);
+    let json = serde_json::to_string(&snapshot).unwrap();
+    assert_eq!(serde_json::from_str::<Snapshot>(&json).unwrap(), snapshot);
+}
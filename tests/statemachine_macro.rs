@@ -0,0 +1,113 @@
+extern crate rust_uml_sm;
+use rust_uml_sm::statemachine;
+
+statemachine! {
+    statemachine door {
+        event open, close;
+        initial -> closed;
+        state closed {
+            on open [unlocked] -> opened / creak;
+            on lock / click;
+        }
+        state opened {
+            entry light_on;
+            exit light_off;
+            on close -> closed;
+        }
+    }
+
+    statemachine player {
+        region transport {
+            initial -> stopped;
+            state stopped { on play -> running::playing; }
+            state running {
+                deephistory h;
+                initial -> playing;
+                state playing { on pause -> paused; }
+                state paused { on play -> playing; }
+                on stop -> stopped;
+            }
+        }
+        region volume {
+            initial -> idle;
+            state idle { defer pause; }
+        }
+    }
+}
+
+#[test]
+fn test_macro_definition() {
+    let sm = door::definition();
+    assert_eq!(sm.name(0).unwrap(), "door");
+    let r = sm.get_only_region(0).unwrap();
+    let states = sm.states(r).unwrap();
+    assert_eq!(sm.fullname(states[1]).unwrap(), "door::region_1::opened");
+    assert_eq!(sm.transitions(states[0]).unwrap().len(), 3);
+    assert!(sm.event_type("lock").is_some());
+}
+
+#[test]
+fn test_macro_matches_dsl() {
+    let dsl = rust_uml_sm::dsl::parse(
+        r#"
+        statemachine player {
+            region transport {
+                initial -> stopped;
+                state stopped { on play -> running::playing; }
+                state running {
+                    deephistory h;
+                    initial -> playing;
+                    state playing { on pause -> paused; }
+                    state paused { on play -> playing; }
+                    on stop -> stopped;
+                }
+            }
+            region volume {
+                initial -> idle;
+                state idle { defer pause; }
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let sm = player::definition();
+    for dbid in 0..40 {
+        assert_eq!(sm.fullname(dbid).ok(), dsl.fullname(dbid).ok());
+    }
+    assert_eq!(
+        sm._plantuml(0, &String::new()).unwrap(),
+        dsl._plantuml(0, &String::new()).unwrap()
+    );
+}
+
+#[test]
+fn test_macro_typed_enums() {
+    assert_eq!(door::State::ALL, [door::State::Closed, door::State::Opened]);
+    assert_eq!(door::State::Opened.name(), "opened");
+    assert_eq!(
+        player::State::from_fullname("player::transport::running::region_1::paused"),
+        Some(player::State::Paused)
+    );
+    let sm = player::definition();
+    for state in player::State::ALL.iter() {
+        let dbid = (0..40)
+            .find(|d| {
+                sm.fullname(*d)
+                    .map(|f| f == state.fullname())
+                    .unwrap_or(false)
+            })
+            .unwrap();
+        assert_eq!(sm.is_state(dbid).unwrap(), true);
+    }
+    assert_eq!(
+        door::Event::ALL,
+        [door::Event::Open, door::Event::Close, door::Event::Lock]
+    );
+    assert_eq!(player::Event::from_name("stop"), Some(player::Event::Stop));
+}
+
+#[test]
+fn test_macro_compile_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
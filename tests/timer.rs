@@ -0,0 +1,178 @@
+extern crate rust_uml_sm;
+mod common;
+use common::active;
+use rust_uml_sm::timer::VirtualClock;
+use rust_uml_sm::{dsl, StateMachineError, TimeEvent, VertexType};
+use std::time::Duration;
+
+const LINK: &str = "statemachine link {
+    event connected, drop;
+    initial -> connecting;
+    state connecting {
+        on connected -> online;
+        on after(30s) -> retry;
+        on at(1h) -> offline;
+    }
+    state retry {
+        on after(1500ms) -> connecting;
+    }
+    state online {
+        on drop -> connecting;
+        on after(2min) / ping;
+    }
+    final offline;
+}";
+
+fn secs(n: u64) -> Duration {
+    Duration::from_secs(n)
+}
+
+#[test]
+fn test_after() {
+    let mut def = dsl::parse(LINK).unwrap();
+    let clock = VirtualClock::new();
+    def.set_clock(clock.clone());
+    def.start().unwrap();
+    assert_eq!(def.timers().len(), 2);
+    assert_eq!(def.next_deadline(), Some(secs(30)));
+
+    clock.advance(secs(29));
+    assert_eq!(def.fire_timers().unwrap(), 0);
+    assert_eq!(active(&def), vec!["connecting"]);
+
+    // armed again each time connecting is entered
+    clock.advance(secs(1));
+    assert_eq!(def.fire_timers().unwrap(), 1);
+    assert_eq!(active(&def), vec!["retry"]);
+    assert_eq!(def.next_deadline(), Some(Duration::from_millis(31500)));
+    clock.advance(secs(2));
+    assert_eq!(def.fire_timers().unwrap(), 1);
+    assert_eq!(active(&def), vec!["connecting"]);
+    assert_eq!(def.next_deadline(), Some(secs(62)));
+}
+
+#[test]
+fn test_cancelled_on_exit() {
+    let mut def = dsl::parse(LINK).unwrap();
+    let clock = VirtualClock::new();
+    def.set_clock(clock.clone());
+    def.start().unwrap();
+    clock.advance(secs(10));
+    assert!(def.dispatch(def.event_type("connected").unwrap()).unwrap());
+    assert_eq!(def.timers().len(), 1);
+    assert_eq!(def.next_deadline(), Some(secs(130)));
+
+    // an internal transition does not arm its timer again
+    clock.advance(secs(600));
+    assert_eq!(def.fire_timers().unwrap(), 1);
+    assert_eq!(active(&def), vec!["online"]);
+    assert!(def.timers().is_empty());
+
+    def.stop().unwrap();
+    assert!(def.timers().is_empty());
+    assert!(matches!(
+        def.fire_timers(),
+        Err(StateMachineError::NotRunning)
+    ));
+}
+
+#[test]
+fn test_earliest_first() {
+    let mut def = dsl::parse(LINK).unwrap();
+    let clock = VirtualClock::new();
+    def.set_clock(clock.clone());
+    def.start().unwrap();
+
+    // connecting times out to retry first, which cancels `at(1h)`
+    clock.set(secs(7200));
+    assert_eq!(def.fire_timers().unwrap(), 1);
+    assert_eq!(active(&def), vec!["retry"]);
+
+    // connecting is entered again after the time of `at(1h)`
+    clock.advance(secs(2));
+    assert_eq!(def.fire_timers().unwrap(), 1);
+    assert_eq!(active(&def), vec!["connecting"]);
+    assert_eq!(def.fire_timers().unwrap(), 1);
+    assert_eq!(active(&def), vec!["offline"]);
+}
+
+#[test]
+fn test_without_clock() {
+    let mut def = dsl::parse(LINK).unwrap();
+    def.start().unwrap();
+    assert_eq!(def.now(), Duration::ZERO);
+    assert_eq!(def.fire_timers().unwrap(), 0);
+    assert_eq!(active(&def), vec!["connecting"]);
+}
+
+#[test]
+fn test_time_events() {
+    let mut def = dsl::parse(LINK).unwrap();
+    let connecting = def.find("connecting").unwrap();
+    let after = def.find("link::region_1::connecting::after(30s)").unwrap();
+    assert_eq!(
+        def.time_event(after).unwrap(),
+        Some(TimeEvent::After(secs(30)))
+    );
+    assert_eq!(def.event_type("after(30s)"), None);
+    assert_eq!(
+        def.add_time_event(connecting, TimeEvent::After(Duration::from_millis(30000)))
+            .unwrap(),
+        after
+    );
+    let retry = def.find("link::region_1::retry::after(1500ms)").unwrap();
+    assert_eq!(
+        def.time_event(retry).unwrap(),
+        Some(TimeEvent::After(Duration::from_millis(1500)))
+    );
+
+    // only states have time events
+    let region = def.get_only_region(0).unwrap();
+    let choice = def
+        .add_vertex(
+            "c",
+            region,
+            VertexType::Pseudostate(rust_uml_sm::PseudostateKind::Choice),
+        )
+        .unwrap();
+    assert!(def.add_time_event(choice, TimeEvent::At(secs(1))).is_err());
+}
+
+#[test]
+fn test_write_and_parse() {
+    let def = dsl::parse(LINK).unwrap();
+    let text = dsl::write(&def);
+    assert!(text.contains("    event connected, drop;\n"));
+    assert!(text.contains("on after(30s) -> retry;"));
+    assert!(text.contains("on at(1h) -> offline;"));
+    assert!(text.contains("on after(2min) / ping;"));
+    assert_eq!(dsl::write(&dsl::parse(&text).unwrap()), text);
+
+    let err = dsl::parse("statemachine m { initial -> a; state a { on after(30 s) -> a; } }")
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1:51: expected a duration such as `30s`, found `30`"
+    );
+    let err = dsl::parse("statemachine m { initial -> a; state a; choice c { on at(1s) -> a; } }")
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1:55: `at(1s)` may only trigger transitions from a state"
+    );
+}
+
+#[test]
+fn test_snapshot_arms_timers() {
+    let mut def = dsl::parse(LINK).unwrap();
+    def.start().unwrap();
+    def.dispatch(def.event_type("connected").unwrap()).unwrap();
+    let snapshot = def.snapshot();
+
+    let mut again = dsl::parse(LINK).unwrap();
+    let clock = VirtualClock::new();
+    clock.set(secs(1000));
+    again.set_clock(clock.clone());
+    again.restore(&snapshot).unwrap();
+    assert_eq!(again.next_deadline(), Some(secs(1120)));
+}
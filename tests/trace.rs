@@ -0,0 +1,157 @@
+extern crate rust_uml_sm;
+mod common;
+use common::{next, Recorder};
+use rust_uml_sm::dsl;
+use rust_uml_sm::trace::{Input, Trace};
+use rust_uml_sm::Context;
+use rust_uml_sm::StateMachineDef;
+use rust_uml_sm::StateMachineError;
+use rust_uml_sm::Status;
+
+/// Records like `Recorder`, guards are never expected to be evaluated.
+struct Replayer(Recorder);
+
+impl Context for Replayer {
+    fn behavior(&mut self, name: &str) {
+        self.0.behavior(name)
+    }
+    fn guard(&mut self, name: &str) -> bool {
+        panic!("guard {} evaluated", name)
+    }
+    fn on_entry(&mut self, state: &str) {
+        self.0.on_entry(state)
+    }
+    fn on_exit(&mut self, state: &str) {
+        self.0.on_exit(state)
+    }
+    fn start_activity(&mut self, state: usize, name: &str) -> bool {
+        self.0.start_activity(state, name)
+    }
+    fn abort_activity(&mut self, state: usize) {
+        self.0.abort_activity(state)
+    }
+}
+
+const PUMP: &str = include_str!("codegen/pump.sm");
+
+fn record(seed: u64, events: &[&str]) -> (Trace, Vec<String>) {
+    let mut sm = dsl::parse(PUMP).unwrap();
+    let mut ctx = Recorder::new(seed);
+    sm.record();
+    sm.start_with(&mut ctx).unwrap();
+    for name in events {
+        if sm.status() != Status::Running {
+            break;
+        }
+        let event = sm.event_type(name).unwrap();
+        sm.dispatch_with(event, &mut ctx).unwrap();
+    }
+    sm.stop_with(&mut ctx).unwrap();
+    // the replay takes the outcome of the guards from the trace
+    ctx.log.retain(|line| !line.starts_with('['));
+    (sm.take_trace().unwrap(), ctx.log)
+}
+
+fn random_events(seed: u64, n: usize) -> Vec<&'static str> {
+    let names = ["start", "stop", "tick", "fault", "reset", "prime", "drain"];
+    let mut pick = seed;
+    (0..n)
+        .map(|_| names[next(&mut pick) as usize % names.len()])
+        .collect()
+}
+
+#[test]
+fn test_record() {
+    let (trace, _) = record(1, &["prime", "start", "tick", "tick"]);
+    assert_eq!(trace.steps.len(), 6);
+    assert_eq!(trace.steps[0].input, Input::Start);
+    assert_eq!(trace.steps[5].input, Input::Stop);
+    assert!(trace.steps[5].configuration.is_empty());
+    // start evaluates primed, and pressure_ok if it was true
+    assert!(!trace.steps[2].guards.is_empty());
+    assert!(trace.steps[1].guards.is_empty());
+    assert!(trace.steps[1].consumed);
+
+    // not recorded unless asked for
+    let mut sm = dsl::parse(PUMP).unwrap();
+    sm.start().unwrap();
+    assert!(sm.trace().is_none());
+}
+
+#[test]
+fn test_replay() {
+    let mut guards = 0;
+    for seed in 0..20 {
+        let (trace, log) = record(seed, &random_events(seed + 100, 40));
+        guards += trace.steps.iter().map(|s| s.guards.len()).sum::<usize>();
+        let mut sm = dsl::parse(PUMP).unwrap();
+        let mut ctx = Replayer(Recorder::new(0));
+        assert_eq!(sm.replay_with(&trace, &mut ctx).unwrap(), None);
+        assert_eq!(ctx.0.log, log, "seed {}", seed);
+    }
+    assert!(guards > 0);
+}
+
+#[test]
+fn test_trace_text() {
+    let (trace, _) = record(3, &random_events(103, 30));
+    let text = trace.to_string();
+    assert_eq!(text.parse::<Trace>().unwrap(), trace);
+    assert_eq!(
+        "start / 3\n\n7 + 9:1 12:0 / 5 6\nstop /\n"
+            .parse::<Trace>()
+            .unwrap()
+            .to_string(),
+        "start / 3\n7 + 9:1 12:0 / 5 6\nstop /\n"
+    );
+    for bad in &["start 3", "7 / 3", "7 + 9:2 /", "7 + x /"] {
+        assert!(matches!(
+            bad.parse::<Trace>(),
+            Err(StateMachineError::InvalidTrace(1))
+        ));
+    }
+}
+
+#[test]
+fn test_replay_divergence() {
+    let door = |target: &str| {
+        dsl::parse(&format!(
+            "statemachine door {{
+                initial -> closed;
+                state closed {{ on open [unlocked] -> {}; }}
+                state opened {{ on close -> closed; }}
+                state ajar {{ on close -> closed; }}
+            }}",
+            target
+        ))
+        .unwrap()
+    };
+    let mut sm = door("opened");
+    sm.record();
+    sm.start().unwrap();
+    let (open, close) = (
+        sm.event_type("open").unwrap(),
+        sm.event_type("close").unwrap(),
+    );
+    sm.dispatch(close).unwrap();
+    sm.dispatch(open).unwrap();
+    sm.dispatch(close).unwrap();
+    let trace = sm.take_trace().unwrap();
+
+    let mut same: StateMachineDef = door("opened");
+    assert_eq!(same.replay(&trace).unwrap(), None);
+    assert!(matches!(
+        same.replay(&trace),
+        Err(StateMachineError::AlreadyRunning)
+    ));
+
+    let mut changed = door("ajar");
+    let divergence = changed.replay(&trace).unwrap().unwrap();
+    assert_eq!(divergence.step, 2);
+    assert_eq!(divergence.expected, trace.steps[2]);
+    assert_ne!(
+        divergence.actual.configuration,
+        trace.steps[2].configuration
+    );
+    assert!(divergence.to_string().starts_with("step 2 diverged"));
+}
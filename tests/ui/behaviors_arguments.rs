@@ -0,0 +1,13 @@
+use rust_uml_sm::behaviors;
+
+struct Foo {}
+
+#[behaviors]
+impl Foo {
+    #[guard]
+    fn ready(&self, strict: bool) -> bool {
+        strict
+    }
+}
+
+fn main() {}
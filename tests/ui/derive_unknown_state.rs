@@ -0,0 +1,9 @@
+use rust_uml_sm::StateMachine;
+
+#[derive(StateMachine)]
+#[state(s1, initial)]
+#[state(s2, parent = s1)]
+#[transition(s1 -> s3, on = ev1)]
+struct Foo {}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use rust_uml_sm::statemachine;
+
+statemachine! {
+    statemachine sm1 {
+        state s1;
+        state s2 {
+            state a;
+            state a;
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use rust_uml_sm::statemachine;
+
+statemachine! {
+    statemachine sm1 {
+        state s1 { on ev1 -> sm2::s2; }
+    }
+    statemachine sm2 {
+        state s2;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use rust_uml_sm::statemachine;
+
+statemachine! {
+    statemachine sm1 {
+        initial -> s1;
+        state s1 { on after(30s) -> s2; }
+        state s2;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use rust_uml_sm::statemachine;
+
+statemachine! {
+    statemachine sm1 {
+        initial -> s1;
+        state s1 { on ev1 -> s3; }
+        state s2;
+    }
+}
+
+fn main() {}
@@ -0,0 +1,143 @@
+//! The JavaScript bindings, run with
+//! `cargo test --target wasm32-unknown-unknown --features wasm-bindgen`
+//! and `wasm-bindgen-test-runner` as the runner, under node. On the
+//! host `test_under_node` does that.
+extern crate rust_uml_sm;
+
+/// Build the tests below for wasm32 with the `wasm-bindgen` feature and
+/// run them under node with `wasm-bindgen-test-runner`, the runner set
+/// in .cargo/config.toml. Skipped if node, the runner or the wasm32
+/// target is missing.
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn test_under_node() {
+    use std::path::Path;
+    use std::process::Command;
+
+    for tool in ["node", "wasm-bindgen-test-runner"] {
+        if Command::new(tool).arg("--version").output().is_err() {
+            eprintln!("skipped: no {}", tool);
+            return;
+        }
+    }
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let libdir = Command::new(rustc)
+        .args([
+            "--print",
+            "target-libdir",
+            "--target",
+            "wasm32-unknown-unknown",
+        ])
+        .output()
+        .expect("rustc");
+    if !Path::new(String::from_utf8_lossy(&libdir.stdout).trim()).exists() {
+        eprintln!("skipped: no wasm32-unknown-unknown target");
+        return;
+    }
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = Command::new(cargo)
+        .current_dir(root)
+        .args([
+            "test",
+            "--target",
+            "wasm32-unknown-unknown",
+            "--features",
+            "wasm-bindgen",
+            "--test",
+            "wasm",
+        ])
+        .arg("--target-dir")
+        .arg(root.join("target/wasm"))
+        .output()
+        .expect("cargo");
+    assert!(
+        output.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+mod bindings {
+    use js_sys::{Array, Function};
+    use rust_uml_sm::wasm::Definition;
+    use wasm_bindgen::JsValue;
+    use wasm_bindgen_test::*;
+
+    const DOOR: &str = include_str!("diff/door_v1.sm");
+
+    const LOCK: &str = "statemachine lock {
+    event turn;
+    initial -> open;
+    state open { on turn [has_key] -> locked / click; }
+    state locked;
+}";
+
+    #[wasm_bindgen_test]
+    fn test_definition() {
+        let door = Definition::new(DOOR).unwrap();
+        assert_eq!(door.name(), "door");
+        assert_eq!(door.events(), vec!["open", "close", "push", "lock"]);
+        assert!(door.to_svg().starts_with("<svg"));
+        assert!(door.to_plantuml().contains("open / creak"));
+        assert!(door.to_json().starts_with('{'));
+        assert_eq!(
+            Definition::new(&door.to_text()).unwrap().to_text(),
+            door.to_text()
+        );
+        assert!(Definition::new("statemachine {").is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_instance() {
+        let door = Definition::new(DOOR).unwrap();
+        let mut sm = door.instance();
+        assert_eq!(sm.status(), "Idle");
+        sm.record();
+        sm.start().unwrap();
+        assert_eq!(sm.active_states(), vec!["door::region_1::closed"]);
+        assert!(sm.dispatch("open").unwrap());
+        assert!(sm.is_active("opened::ajar"));
+        assert!(!sm.dispatch("open").unwrap());
+        assert!(sm.dispatch("nope").is_err());
+        let snapshot = sm.snapshot();
+        assert!(sm.dispatch("close").unwrap());
+        assert!(sm.is_active("closed"));
+        assert_eq!(sm.trace().lines().count(), 4);
+
+        // a second instance runs on its own
+        let mut other = door.instance();
+        other.restore(&snapshot).unwrap();
+        assert!(other.is_active("opened::ajar"));
+        sm.stop().unwrap();
+        assert_eq!(sm.status(), "Idle");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_callbacks() {
+        let lock = Definition::new(LOCK).unwrap();
+        let performed = Array::new();
+        let mut sm = lock.instance();
+        sm.set_behaviors(Function::new_with_args("name", "this.push(name)").bind0(&performed));
+        sm.set_guards(Function::new_with_args(
+            "name",
+            "return globalThis.key === true",
+        ));
+        sm.start().unwrap();
+        assert!(!sm.dispatch("turn").unwrap());
+        assert!(sm.is_active("open"));
+
+        js_sys::Reflect::set(&js_sys::global(), &"key".into(), &JsValue::TRUE).unwrap();
+        assert!(sm.dispatch("turn").unwrap());
+        assert!(sm.is_active("locked"));
+        assert_eq!(performed.join(",").as_string().unwrap(), "click");
+
+        // a guard throwing is false
+        let mut sm = lock.instance();
+        sm.set_guards(Function::new_with_args("name", "throw new Error(name)"));
+        sm.start().unwrap();
+        assert!(!sm.dispatch("turn").unwrap());
+    }
+}